@@ -1,25 +1,148 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use bevy::prelude::*;
-use wrts_messaging::{Client2Match, ClientSharedInfo, Match2Client, Message};
+use wrts_match_shared::{formulas::ballistics::ShellArc, ship_template::ShipTemplateId};
+use wrts_messaging::{Client2Match, ClientId, ClientSharedInfo, Match2Client, Message};
 
 use crate::{
-    AppState, Bullet, DetectionStatus, Health, MoveOrder, PlayerSettings, SmokePuff, Team, Torpedo,
-    Velocity,
-    networking::{ClientInfo, ServerConnection, ThisClient},
+    Aircraft, AppState, Bullet, DetectionStatus, Health, MoveOrder, PlayerSettings, SectionHealth,
+    SmokePuff, Team, Torpedo, Velocity,
+    networking::{ClientInfo, RejoinableMatch, ServerConnection, ThisClient},
     ship::{
-        self, DetectionIndicatorDisplay, Ship, ShipModifiersDisplay, ShipUI, ShipUITrackedShip,
-        TurretState,
+        self, DetectionIndicatorDisplay, GunsIdleIndicator, Ship, ShipModifiersDisplay, ShipUI,
+        ShipUITrackedShip, TurretState,
     },
 };
 
 pub use shared_entity_tracking::SharedEntityTracking;
 
+/// The seed the match was started with, so that it can be reported and reproduced
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct MatchSeed(pub u64);
+
+/// Whether the match's simulation is paused, from `Match2Client::MatchPaused`
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct MatchPaused(pub bool);
+
+/// The time left until the match ends on time, from the latest `Match2Client::MatchTime`
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct MatchTimeRemaining(pub Option<Duration>);
+
+/// Run condition for client-side animation which would otherwise
+/// drift ahead of a paused match
+pub fn match_running(paused: Res<MatchPaused>) -> bool {
+    !paused.0
+}
+
+/// Every message received from the match so far, for the debug overlay
+#[derive(Resource, Debug, Default)]
+pub struct ReceivedMessageCount(pub u64);
+
+/// How long a sent move order can go without the match echoing it back
+/// before it's assumed to have been dropped
+const PENDING_ORDER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Move orders sent to the match which it hasn't echoed back yet, keyed by ship,
+/// so that they can be shown as pending until the match confirms them
+#[derive(Resource, Debug, Default)]
+pub struct PendingMoveOrders(pub HashMap<Entity, PendingMoveOrder>);
+
+#[derive(Debug, Clone)]
+pub struct PendingMoveOrder {
+    pub waypoints: Vec<Vec2>,
+    /// `Time::elapsed` when the order was sent
+    pub sent_at: Duration,
+}
+
+impl PendingMoveOrder {
+    /// Whether the match should have echoed this order back by `now`
+    pub fn is_overdue(&self, now: Duration) -> bool {
+        now.saturating_sub(self.sent_at) > PENDING_ORDER_TIMEOUT
+    }
+}
+
+impl PendingMoveOrders {
+    /// Reconciles a `SetMoveOrder` sent by the match for `ship` with the order pending for it,
+    /// returning whether the match's waypoints should replace the ship's `MoveOrder`
+    ///
+    /// Until the pending order is echoed back, updates to the previous order,
+    /// such as reaching a waypoint, are ignored so they don't overwrite it.
+    /// Once it's overdue, it's assumed dropped and the match's waypoints are taken instead
+    pub fn reconcile(&mut self, ship: Entity, waypoints: &[Vec2], now: Duration) -> bool {
+        let Some(pending) = self.0.get(&ship) else {
+            return true;
+        };
+        if pending.waypoints == waypoints {
+            self.0.remove(&ship);
+            return true;
+        }
+        if pending.is_overdue(now) {
+            warn!("The match never confirmed a move order for {ship}, so it was likely dropped");
+            self.0.remove(&ship);
+            return true;
+        }
+        false
+    }
+}
+
+/// A ship was sunk, from `Match2Client::ShipDestroyed`
+#[derive(Event, Debug, Clone)]
+pub struct ShipSunk {
+    pub victim: String,
+    pub victim_team: Team,
+    pub killer_team: Team,
+    /// `None` if the killing ship was destroyed before the damage landed,
+    /// or this client can't see it
+    pub killer: Option<String>,
+}
+
+/// The outcome of the last match this client played, shown in the lobby
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct LastMatchResult {
+    /// `None` if the match was a draw
+    pub winner: Option<ClientId>,
+}
+
+/// Each player's damage dealt and kills in the last match, from `Match2Client::Scoreboard`
+#[derive(Resource, Debug, Clone, Default)]
+pub struct LastMatchScoreboard(pub Vec<ScoreboardEntry>);
+
+#[derive(Debug, Clone)]
+pub struct ScoreboardEntry {
+    pub client: ClientId,
+    pub user: String,
+    pub damage: f64,
+    pub kills: u32,
+}
+
+/// How many ships of each template every team has been sent,
+/// so that players picking the same template still get distinguishable ship names
+#[derive(Resource, Debug, Default)]
+struct ShipTemplateCounts(HashMap<(ClientId, ShipTemplateId), u32>);
+
+impl ShipTemplateCounts {
+    /// Names the next ship of `template` on `team`, e.g. "Nagato", then "Nagato #2"
+    fn next_name(&mut self, team: ClientId, template: ShipTemplateId) -> String {
+        let count = self.0.entry((team, template)).or_default();
+        *count += 1;
+        match *count {
+            1 => template.to_name().to_string(),
+            count => format!("{} #{count}", template.to_name()),
+        }
+    }
+}
+
 pub struct InMatchPlugin;
 
 impl Plugin for InMatchPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SharedEntityTracking>()
+            .init_resource::<ReceivedMessageCount>()
+            .init_resource::<PendingMoveOrders>()
+            .init_resource::<ShipTemplateCounts>()
+            .init_resource::<MatchPaused>()
+            .init_resource::<MatchTimeRemaining>()
+            .add_event::<ShipSunk>()
             .add_systems(
                 OnEnter(AppState::InMatch),
                 (in_match_startup_networking.pipe(in_match_startup_networking_none_handler)),
@@ -63,6 +186,10 @@ mod shared_entity_tracking {
             self.shared2entity.get(&shared).copied()
         }
 
+        pub fn get_by_local(&self, local: Entity) -> Option<SharedEntityId> {
+            self.entity2shared.get(&local).copied()
+        }
+
         pub fn remove_by_shared(&mut self, shared: SharedEntityId) -> Option<Entity> {
             let Some(local) = self.shared2entity.remove(&shared) else {
                 warn!("Tried removing by shared entity which doesn't exist: {shared:?}");
@@ -104,8 +231,18 @@ mod shared_entity_tracking {
     }
 }
 
-fn clear_shared_entity_tracking_on_match_exit(mut shared_entities: ResMut<SharedEntityTracking>) {
+fn clear_shared_entity_tracking_on_match_exit(
+    mut shared_entities: ResMut<SharedEntityTracking>,
+    mut template_counts: ResMut<ShipTemplateCounts>,
+    mut pending_move_orders: ResMut<PendingMoveOrders>,
+    mut paused: ResMut<MatchPaused>,
+    mut time_remaining: ResMut<MatchTimeRemaining>,
+) {
     shared_entities.clear();
+    template_counts.0.clear();
+    pending_move_orders.0.clear();
+    paused.0 = false;
+    time_remaining.0 = None;
 }
 
 fn in_match_startup_networking(
@@ -126,14 +263,16 @@ fn in_match_startup_networking(
         },
     }))?;
 
-    let Message::Match2Client(Match2Client::InitC { all_clients }) = server.recv_blocking()? else {
+    let Message::Match2Client(Match2Client::InitC { all_clients, seed }) =
+        server.recv_blocking()?
+    else {
         return None;
     };
+    info!("Match seed: {seed}");
+    commands.insert_resource(MatchSeed(seed));
+    // Replaced once this match sends its own
+    commands.remove_resource::<LastMatchScoreboard>();
 
-    assert!(
-        all_clients.len() == 2,
-        "Currently, there should always be two clients per game"
-    );
     for info in all_clients {
         commands.spawn((
             StateScoped(AppState::InMatch),
@@ -163,20 +302,30 @@ fn in_match_networking(
     mut server: ResMut<ServerConnection>,
     mut shared_entities: ResMut<SharedEntityTracking>,
     this_client: Res<ThisClient>,
+    time: Res<Time>,
+    mut received: ResMut<ReceivedMessageCount>,
+    mut template_counts: ResMut<ShipTemplateCounts>,
+    mut pending_move_orders: ResMut<PendingMoveOrders>,
+    mut paused: ResMut<MatchPaused>,
+    mut time_remaining: ResMut<MatchTimeRemaining>,
+    mut next_state: ResMut<NextState<AppState>>,
 ) -> Option<()> {
     // Note: All network actions are queued instead of running of a query,
     // so that previous actions are flushed (i.e. creating a ship then updating that ship's position)
 
     while let Ok(msg) = server.recv_next() {
+        received.0 += 1;
         match msg {
             Message::Match2Client(Match2Client::PrintMsg(s)) => {
                 info!("PrintMsg called: {s}");
             }
-            Message::Match2Client(Match2Client::DestroyEntity(shared)) => {
-                let Some(local) = shared_entities.remove_by_shared(shared) else {
-                    continue;
-                };
-                commands.entity(local).despawn();
+            Message::Match2Client(Match2Client::DestroyEntities(shared)) => {
+                for shared in shared {
+                    let Some(local) = shared_entities.remove_by_shared(shared) else {
+                        continue;
+                    };
+                    commands.entity(local).despawn();
+                }
             }
             Message::Match2Client(Match2Client::SpawnShip {
                 id,
@@ -193,27 +342,36 @@ fn in_match_networking(
                     for turret_idx in 0..turret_instances.len() {
                         turret_states.push(TurretState {
                             dir: turret_rots[turret_idx],
+                            networked_dir: turret_rots[turret_idx],
                         });
                     }
                     turret_states
                 };
+                let name = template_counts.next_name(team, ship_base);
+                let is_own_ship = Team(team).is_this_client(*this_client);
                 // Spawn the ship
                 let local = commands
                     .spawn((
                         StateScoped(AppState::InMatch),
                         Ship {
                             template: ship_base.to_template(),
+                            name: name.clone(),
                             turret_states,
                             reloaded_torp_volleys: 0,
-                            reloading_torp_volleys_remaining_time: vec![
-                                Duration::ZERO;
-                                ship_base
-                                    .to_template()
-                                    .torpedoes
-                                    .as_ref()
-                                    .map(|t| t.volleys)
-                                    .unwrap_or(0)
-                            ],
+                            // Every volley starts the match reloading, the same as on the match
+                            reloading_torp_volleys_remaining_time: ship_base
+                                .to_template()
+                                .torpedoes
+                                .as_ref()
+                                .map_or(vec![], |t| vec![t.reload; t.volleys]),
+                            torp_volley_reload: ship_base
+                                .to_template()
+                                .torpedoes
+                                .as_ref()
+                                .map_or(Duration::ZERO, |t| t.reload),
+                            shell_arc: ShellArc::Low,
+                            guns_idle: false,
+                            gun_range_modifiers: default(),
                         },
                         DetectionStatus::Never,
                         Team(team),
@@ -259,12 +417,35 @@ fn in_match_networking(
                                 ),
                                 (
                                     //
-                                    Text(ship_base.to_name().to_string()),
+                                    Text(name),
+                                    // Mirror matches would otherwise have identical labels on both teams
+                                    TextColor(if is_own_ship {
+                                        Color::WHITE
+                                    } else {
+                                        Color::linear_rgb(1., 0.6, 0.6)
+                                    }),
                                 )
                             ],
                         ));
 
-                        if Team(team).is_this_client(*this_client) {
+                        if is_own_ship {
+                            commands.spawn((
+                                GunsIdleIndicator,
+                                ShipUITrackedShip(local),
+                                Node {
+                                    justify_content: JustifyContent::Center,
+                                    ..default()
+                                },
+                                Visibility::Hidden,
+                                children![(
+                                    Text::new("Guns idle"),
+                                    TextFont {
+                                        font_size: 12.,
+                                        ..default()
+                                    },
+                                    TextColor(Color::linear_rgb(0.8, 0.8, 0.5)),
+                                )],
+                            ));
                             commands.spawn((
                                 ShipModifiersDisplay,
                                 ShipUITrackedShip(local),
@@ -286,6 +467,8 @@ fn in_match_networking(
                 team,
                 owning_ship,
                 damage,
+                caliber,
+                ty,
                 pos,
                 rot,
             }) => {
@@ -295,6 +478,8 @@ fn in_match_networking(
                         Bullet {
                             owning_ship: shared_entities[owning_ship],
                             damage,
+                            caliber,
+                            ty,
                         },
                         Team(team),
                         Transform {
@@ -321,6 +506,7 @@ fn in_match_networking(
                             owning_ship: shared_entities[owning_ship],
                             damage,
                             speed: vel.length(),
+                            launched_at: time.elapsed(),
                         },
                         DetectionStatus::Never,
                         Team(team),
@@ -346,6 +532,24 @@ fn in_match_networking(
                     .id();
                 shared_entities.insert(id, local);
             }
+            Message::Match2Client(Match2Client::SpawnAircraft {
+                id,
+                team,
+                owning_ship,
+                pos,
+            }) => {
+                let local = commands
+                    .spawn((
+                        StateScoped(AppState::InMatch),
+                        Aircraft {
+                            owning_ship: shared_entities[owning_ship],
+                        },
+                        Team(team),
+                        Transform::from_translation(pos.extend(0.)),
+                    ))
+                    .id();
+                shared_entities.insert(id, local);
+            }
             Message::Match2Client(Match2Client::SetSmokeConsumableState { id, state }) => {
                 commands.queue(move |world: &mut World| {
                     let Some(local) = world.resource::<SharedEntityTracking>().get_by_shared(id)
@@ -383,62 +587,130 @@ fn in_match_networking(
                     entity.insert(new_state);
                 });
             }
-            Message::Match2Client(Match2Client::SetReloadedTorps {
-                id,
-                ready_to_fire,
-                still_reloading,
-            }) => {
+            Message::Match2Client(Match2Client::SetTorpReloadBoosterState { id, state }) => {
                 commands.queue(move |world: &mut World| {
                     let Some(local) = world.resource::<SharedEntityTracking>().get_by_shared(id)
                     else {
                         return;
                     };
-                    let mut entity = world.entity_mut(local);
-                    let mut ship = entity.get_mut::<Ship>().unwrap();
-                    ship.reloaded_torp_volleys = ready_to_fire;
-                    ship.reloading_torp_volleys_remaining_time = still_reloading;
+
+                    let new_state = match state {
+                        wrts_messaging::TorpReloadBoosterState::Recharging {
+                            charges_unused,
+                            recharge_time_remaining,
+                        } => ship::TorpReloadBoosterState {
+                            charges_unused,
+                            recharge_time_remaining: Some(recharge_time_remaining),
+                        },
+                        wrts_messaging::TorpReloadBoosterState::Recharged { charges_unused } => {
+                            ship::TorpReloadBoosterState {
+                                charges_unused,
+                                recharge_time_remaining: None,
+                            }
+                        }
+                    };
+                    world.entity_mut(local).insert(new_state);
                 });
             }
-            Message::Match2Client(Match2Client::SetTrans { id, pos, rot }) => {
+            Message::Match2Client(Match2Client::SetSpotterPlaneState { id, state }) => {
                 commands.queue(move |world: &mut World| {
                     let Some(local) = world.resource::<SharedEntityTracking>().get_by_shared(id)
                     else {
                         return;
                     };
+
                     let mut entity = world.entity_mut(local);
-                    let mut trans = entity.get_mut::<Transform>().unwrap();
-                    trans.translation = pos;
-                    trans.rotation = rot;
+                    let new_state = match state {
+                        wrts_messaging::SpotterPlaneState::Flying {
+                            charges_unused,
+                            action_time_remaining,
+                        } => ship::SpotterPlaneState {
+                            charges_unused,
+                            action_state: ship::SpotterPlaneActionState::Flying {
+                                time_remaining: action_time_remaining,
+                            },
+                        },
+                        wrts_messaging::SpotterPlaneState::Recharging {
+                            charges_unused,
+                            recharge_time_remaining,
+                        } => ship::SpotterPlaneState {
+                            charges_unused,
+                            action_state: ship::SpotterPlaneActionState::Recharging {
+                                time_remaining: recharge_time_remaining,
+                            },
+                        },
+                        wrts_messaging::SpotterPlaneState::Recharged { charges_unused } => {
+                            ship::SpotterPlaneState {
+                                charges_unused,
+                                action_state: ship::SpotterPlaneActionState::Recharged,
+                            }
+                        }
+                    };
+                    // Keeps the range rings in step with the match's gun range
+                    if let Some(mut ship) = entity.get_mut::<Ship>() {
+                        let flying = matches!(
+                            new_state.action_state,
+                            ship::SpotterPlaneActionState::Flying { .. }
+                        );
+                        let bonus = ship
+                            .template
+                            .consumables
+                            .spotter_plane()
+                            .filter(|_| flying)
+                            .map(|spotter_plane| spotter_plane.range_bonus);
+                        ship.gun_range_modifiers.spotter_plane_bonus = bonus;
+                    }
+                    entity.insert(new_state);
                 });
             }
-            Message::Match2Client(Match2Client::SetVelocity { id, vel }) => {
+            Message::Match2Client(Match2Client::SetReloadedTorps {
+                id,
+                ready_to_fire,
+                still_reloading,
+                full_reload,
+            }) => {
                 commands.queue(move |world: &mut World| {
                     let Some(local) = world.resource::<SharedEntityTracking>().get_by_shared(id)
                     else {
                         return;
                     };
                     let mut entity = world.entity_mut(local);
-                    entity.insert(Velocity(vel));
-                    //
+                    let mut ship = entity.get_mut::<Ship>().unwrap();
+                    ship.reconcile_torpedo_reloads(ready_to_fire, still_reloading, full_reload);
                 });
             }
-            Message::Match2Client(Match2Client::SetTurretDirs { id, turret_dirs }) => {
+            Message::Match2Client(Match2Client::WorldSnapshot { entities }) => {
                 commands.queue(move |world: &mut World| {
-                    let Some(local) = world.resource::<SharedEntityTracking>().get_by_shared(id)
-                    else {
-                        return;
-                    };
-
-                    let mut entity = world.entity_mut(local);
-                    let mut ship = entity.get_mut::<Ship>().unwrap();
-                    for turret_idx in 0..turret_dirs.len() {
-                        ship.turret_states[turret_idx].dir = turret_dirs[turret_idx];
+                    for update in entities {
+                        let Some(local) = world
+                            .resource::<SharedEntityTracking>()
+                            .get_by_shared(update.id)
+                        else {
+                            continue;
+                        };
+                        let mut entity = world.entity_mut(local);
+                        if let Some((pos, rot)) = update.trans {
+                            let mut trans = entity.get_mut::<Transform>().unwrap();
+                            trans.translation = pos;
+                            trans.rotation = rot;
+                        }
+                        if let Some(vel) = update.vel {
+                            entity.insert(Velocity(vel));
+                        }
+                        if let Some(turret_dirs) = update.turret_dirs {
+                            let mut ship = entity.get_mut::<Ship>().unwrap();
+                            for turret_idx in 0..turret_dirs.len() {
+                                ship.turret_states[turret_idx].networked_dir =
+                                    turret_dirs[turret_idx];
+                            }
+                        }
                     }
                 });
             }
             Message::Match2Client(Match2Client::SetHealth {
                 id,
                 health: new_health,
+                sections,
             }) => {
                 commands.queue(move |world: &mut World| {
                     let Some(local) = world.resource::<SharedEntityTracking>().get_by_shared(id)
@@ -448,12 +720,31 @@ fn in_match_networking(
                     let mut entity = world.entity_mut(local);
                     let mut health = entity.get_mut::<Health>().unwrap();
                     health.0 = new_health;
+                    entity.insert(SectionHealth(sections));
                 });
             }
             Message::Match2Client(Match2Client::SetMoveOrder { id, waypoints }) => {
-                commands
-                    .entity(shared_entities[id])
-                    .insert(MoveOrder { waypoints });
+                let local = shared_entities[id];
+                if pending_move_orders.reconcile(local, &waypoints, time.elapsed()) {
+                    commands.entity(local).insert(MoveOrder { waypoints });
+                }
+            }
+            Message::Match2Client(Match2Client::WaypointReached { id, remaining }) => {
+                let local = shared_entities[id];
+                // Refers to the order the match was following before the pending one
+                if pending_move_orders.0.contains_key(&local) {
+                    continue;
+                }
+                commands.queue(move |world: &mut World| {
+                    let Some(mut move_order) = world.get_mut::<MoveOrder>(local) else {
+                        return;
+                    };
+                    let reached = move_order
+                        .waypoints
+                        .len()
+                        .saturating_sub(remaining as usize);
+                    move_order.waypoints.drain(..reached);
+                });
             }
             Message::Match2Client(Match2Client::SetDetection {
                 id,
@@ -472,6 +763,99 @@ fn in_match_networking(
                     };
                 });
             }
+            Message::Match2Client(Match2Client::DamageDealt {
+                target,
+                attacker_team,
+                attacker,
+                source,
+                damage,
+            }) => {
+                debug!(
+                    "{target:?} took {damage:.0} {source:?} damage from {attacker:?} ({attacker_team})"
+                );
+            }
+            Message::Match2Client(Match2Client::ShipDestroyed {
+                ship,
+                killer_team,
+                killer,
+                source,
+            }) => {
+                info!("{ship:?} was destroyed by {killer:?} ({killer_team}) with {source:?}");
+                // Sent before the `DestroyEntities` including the ship,
+                // so both ships can still be named once this is applied
+                let victim = shared_entities.get_by_shared(ship);
+                let killer = killer.and_then(|killer| shared_entities.get_by_shared(killer));
+                commands.queue(move |world: &mut World| {
+                    let Some(victim) = victim else {
+                        return;
+                    };
+                    let ship_name =
+                        |entity| world.get::<Ship>(entity).map(|ship| ship.name.clone());
+                    let (Some(victim_name), Some(&victim_team)) =
+                        (ship_name(victim), world.get::<Team>(victim))
+                    else {
+                        return;
+                    };
+                    let killer = killer.and_then(ship_name);
+                    world.send_event(ShipSunk {
+                        victim: victim_name,
+                        victim_team,
+                        killer_team: Team(killer_team),
+                        killer,
+                    });
+                });
+            }
+            Message::Match2Client(Match2Client::SetGunsIdle { id, idle }) => {
+                commands.queue(move |world: &mut World| {
+                    let Some(local) = world.resource::<SharedEntityTracking>().get_by_shared(id)
+                    else {
+                        return;
+                    };
+                    if let Some(mut ship) = world.get_mut::<Ship>(local) {
+                        ship.guns_idle = idle;
+                    }
+                });
+            }
+            Message::Match2Client(Match2Client::MatchPaused { paused: new_paused }) => {
+                info!("Match {}", if new_paused { "paused" } else { "resumed" });
+                paused.0 = new_paused;
+            }
+            Message::Match2Client(Match2Client::MatchTime { remaining }) => {
+                time_remaining.0 = Some(remaining);
+            }
+            Message::Match2Client(Match2Client::Scoreboard { entries }) => {
+                // The match's `ClientInfo`s are gone by the time the lobby shows this
+                commands.queue(move |world: &mut World| {
+                    let mut clients = world.query::<&ClientInfo>();
+                    let users = clients
+                        .iter(world)
+                        .map(|cl| (cl.id, cl.user.clone()))
+                        .collect::<HashMap<_, _>>();
+                    let entries = entries
+                        .into_iter()
+                        .map(|(client, damage, kills)| ScoreboardEntry {
+                            client,
+                            user: users
+                                .get(&client)
+                                .cloned()
+                                .unwrap_or_else(|| client.to_string()),
+                            damage,
+                            kills,
+                        })
+                        .collect();
+                    world.insert_resource(LastMatchScoreboard(entries));
+                });
+            }
+            Message::Match2Client(Match2Client::MatchEnded { winner }) => {
+                match winner {
+                    Some(winner) => info!("Match ended, client {winner} won"),
+                    None => info!("Match ended in a draw"),
+                }
+                commands.insert_resource(LastMatchResult { winner });
+                next_state.set(AppState::LobbyMenu);
+                // Anything after this is left over from the match, and is ignored by the lobby
+                break;
+            }
             Message::Match2Client(Match2Client::InitA { .. })
             | Message::Match2Client(Match2Client::InitC { .. })
             | Message::Lobby2Client(_)
@@ -491,10 +875,71 @@ fn in_match_networking(
 
 fn in_match_networking_none_handler(
     In(input): In<Option<()>>,
+    mut commands: Commands,
+    this_client: Res<ThisClient>,
+    server: Res<ServerConnection>,
+    clients: Query<&ClientInfo>,
     mut next_state: ResMut<NextState<AppState>>,
 ) {
     if let None = input {
-        info!("`in_match_networking` disconnected");
+        // Spectators aren't listed among the match's clients, and have no place to rejoin
+        if clients.iter().any(|cl| cl.id == this_client.0) {
+            info!("`in_match_networking` disconnected, the match can be rejoined");
+            commands.insert_resource(RejoinableMatch {
+                client: this_client.0,
+                rejoin_token: server.rejoin_token(),
+            });
+        } else {
+            info!("`in_match_networking` disconnected");
+        }
         next_state.set(AppState::ConnectingToServer);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::prelude::*;
+
+    use crate::in_match::{PENDING_ORDER_TIMEOUT, PendingMoveOrder, PendingMoveOrders};
+
+    #[test]
+    fn test_pending_move_order_reconciled_by_echo() {
+        let ship = Entity::from_raw(1);
+        let sent = vec![vec2(1., 2.), vec2(3., 4.)];
+        let mut pending = PendingMoveOrders::default();
+        pending.0.insert(
+            ship,
+            PendingMoveOrder {
+                waypoints: sent.clone(),
+                sent_at: Duration::ZERO,
+            },
+        );
+
+        // An update to the previous order, sent before the new one arrived
+        let now = Duration::from_millis(100);
+        assert!(!pending.reconcile(ship, &[vec2(5., 6.)], now));
+        assert!(pending.0.contains_key(&ship));
+
+        assert!(pending.reconcile(ship, &sent, now));
+        assert!(pending.0.is_empty());
+    }
+
+    #[test]
+    fn test_overdue_pending_move_order_is_dropped() {
+        let ship = Entity::from_raw(1);
+        let mut pending = PendingMoveOrders::default();
+        pending.0.insert(
+            ship,
+            PendingMoveOrder {
+                waypoints: vec![vec2(1., 2.)],
+                sent_at: Duration::ZERO,
+            },
+        );
+
+        let now = PENDING_ORDER_TIMEOUT * 2;
+        assert!(pending.reconcile(ship, &[vec2(5., 6.)], now));
+        assert!(pending.0.is_empty());
+    }
+}
@@ -1,23 +1,26 @@
-use std::{convert::identity, f32::consts::FRAC_PI_2};
+use std::{collections::HashMap, convert::identity, f32::consts::FRAC_PI_2, fmt};
 
 use bevy::{
     input::{InputSystem, mouse::MouseWheel},
     prelude::*,
+    ui::RelativeCursorPosition,
     window::PrimaryWindow,
 };
 use enum_map::EnumMap;
 use itertools::Itertools;
+use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 use wrts_messaging::{Client2Match, Message};
 
 use crate::{
-    AppState, CursorWorldPos, DetectionStatus, FireTarget, MainCamera, MapZoom, MoveOrder,
-    PlayerSettings, Selected, Team, Velocity,
-    in_match::SharedEntityTracking,
+    AppState, CursorWorldPos, DetectionStatus, FireTarget, HeadingOrder, MainCamera, MapZoom,
+    MoveOrder, PlayerSettings, Selected, Team, Velocity,
+    in_match::{MatchPaused, MatchSeed, PendingMoveOrder, PendingMoveOrders, SharedEntityTracking},
     math_utils,
     networking::{ServerConnection, ThisClient},
-    ship::Ship,
+    ship::{Ship, SmokeConsumableActionState, SmokeConsumableState},
+    ui::in_game::Minimap,
 };
 
 #[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -28,12 +31,18 @@ pub struct InputHandlingPlugin;
 
 impl Plugin for InputHandlingPlugin {
     fn build(&self, app: &mut App) {
-        app
+        app.init_resource::<QueuedOrders>()
+            .init_resource::<FireTargetUnderCursor>()
             //
             .configure_sets(OnEnter(AppState::InMatch), InputHandlingSystem)
             .add_systems(
                 OnEnter(AppState::InMatch),
-                spawn_action_state.in_set(InputHandlingSystem),
+                (
+                    spawn_action_state,
+                    reset_control_groups,
+                    spawn_shell_flight_time_text,
+                )
+                    .in_set(InputHandlingSystem),
             )
             //
             .configure_sets(
@@ -50,6 +59,9 @@ impl Plugin for InputHandlingPlugin {
                     update_hovering
                         .after(update_action_state)
                         .after(update_cursor_world_pos),
+                    update_fire_target_under_cursor
+                        .after(update_action_state)
+                        .after(update_cursor_world_pos),
                     update_map_zoom,
                 )
                     .in_set(InputHandlingSystem),
@@ -63,9 +75,23 @@ impl Plugin for InputHandlingPlugin {
                 Update,
                 (
                     use_consumables,
+                    toggle_shell_arc.after(update_selection),
+                    toggle_enemy_ship_uis,
+                    toggle_rings,
+                    toggle_debug_overlay,
+                    toggle_pause,
+                    copy_match_seed,
+                    adjust_ui_scale,
                     update_selection,
-                    update_selected_ship_orders.after(update_selection),
+                    update_control_groups.after(update_selection),
+                    update_selected_ship_orders.after(update_control_groups),
+                    disengage_selected_ships.after(update_selection),
+                    send_queued_orders
+                        .after(update_selected_ship_orders)
+                        .after(disengage_selected_ships),
+                    set_heading.after(update_selection),
                     fire_torpedoes.after(update_selection),
+                    preview_shell_impact.after(update_selection),
                     update_camera,
                 )
                     .in_set(InputHandlingSystem),
@@ -73,9 +99,57 @@ impl Plugin for InputHandlingPlugin {
     }
 }
 
+/// The controls for each control group, in order, as `(save, select)` pairs
+const CONTROL_GROUP_INPUTS: [(ButtonInputs, ButtonInputs); 9] = [
+    (
+        ButtonInputs::SaveControlGroup1,
+        ButtonInputs::SelectControlGroup1,
+    ),
+    (
+        ButtonInputs::SaveControlGroup2,
+        ButtonInputs::SelectControlGroup2,
+    ),
+    (
+        ButtonInputs::SaveControlGroup3,
+        ButtonInputs::SelectControlGroup3,
+    ),
+    (
+        ButtonInputs::SaveControlGroup4,
+        ButtonInputs::SelectControlGroup4,
+    ),
+    (
+        ButtonInputs::SaveControlGroup5,
+        ButtonInputs::SelectControlGroup5,
+    ),
+    (
+        ButtonInputs::SaveControlGroup6,
+        ButtonInputs::SelectControlGroup6,
+    ),
+    (
+        ButtonInputs::SaveControlGroup7,
+        ButtonInputs::SelectControlGroup7,
+    ),
+    (
+        ButtonInputs::SaveControlGroup8,
+        ButtonInputs::SelectControlGroup8,
+    ),
+    (
+        ButtonInputs::SaveControlGroup9,
+        ButtonInputs::SelectControlGroup9,
+    ),
+];
+
+/// RTS-style control groups, one for each of [CONTROL_GROUP_INPUTS].
+/// Saving a group replaces it with the current selection,
+/// and selecting it selects those ships again
+#[derive(Resource, Debug, Default)]
+struct ControlGroups {
+    groups: [Vec<Entity>; CONTROL_GROUP_INPUTS.len()],
+}
+
 /// Attached to `Ship`s when the cursor is hovering over them
 #[derive(Component, Clone, Copy, PartialEq, Eq, Hash)]
-struct Hovering;
+pub struct Hovering;
 
 #[derive(
     Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, enum_map::Enum, strum::EnumIter,
@@ -90,10 +164,53 @@ pub enum ButtonInputs {
     SetWaypoint,
     PushWaypoint,
     ClearWaypoints,
+    /// Held to preview, and released to stop the selected ships
+    /// and turn them in place to face the cursor
+    SetHeading,
+    /// Clears all waypoints and the fire target at once
+    StopShip,
+    /// Breaks off from the fight, doing whichever of `PlayerSettings::disengage`'s actions are set
+    Disengage,
+    /// Switches the selected ships' main batteries between low and high arc fire
+    ToggleShellArc,
+    ToggleEnemyShipUIs,
+    /// Shows or hides the gun range and detection rings of every ship
+    ToggleRings,
+    ToggleDebugOverlay,
+    /// Pauses or resumes the match, if it allows pausing
+    TogglePause,
+    /// Copies the match's seed to the clipboard, for bug reports
+    CopyMatchSeed,
+    IncreaseUIScale,
+    DecreaseUIScale,
 
     FireTorpVolley,
 
     UseConsumableSmoke,
+    UseConsumableTorpReloadBooster,
+    UseConsumableSpotterPlane,
+    UseConsumableRepairParty,
+
+    /// Replaces the control group with the selected ships
+    SaveControlGroup1,
+    SaveControlGroup2,
+    SaveControlGroup3,
+    SaveControlGroup4,
+    SaveControlGroup5,
+    SaveControlGroup6,
+    SaveControlGroup7,
+    SaveControlGroup8,
+    SaveControlGroup9,
+    /// Selects the ships in the control group, if it isn't empty
+    SelectControlGroup1,
+    SelectControlGroup2,
+    SelectControlGroup3,
+    SelectControlGroup4,
+    SelectControlGroup5,
+    SelectControlGroup6,
+    SelectControlGroup7,
+    SelectControlGroup8,
+    SelectControlGroup9,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
@@ -116,15 +233,75 @@ impl ButtonInputs {
             | ButtonInputs::SetWaypoint
             | ButtonInputs::PushWaypoint
             | ButtonInputs::ClearWaypoints
+            | ButtonInputs::SetHeading
+            | ButtonInputs::StopShip
+            | ButtonInputs::Disengage
+            | ButtonInputs::ToggleShellArc
+            | ButtonInputs::ToggleEnemyShipUIs
+            | ButtonInputs::ToggleRings
+            | ButtonInputs::ToggleDebugOverlay
+            | ButtonInputs::TogglePause
+            | ButtonInputs::CopyMatchSeed
+            | ButtonInputs::IncreaseUIScale
+            | ButtonInputs::DecreaseUIScale
             | ButtonInputs::FireTorpVolley
             | ButtonInputs::UseConsumableSmoke
+            | ButtonInputs::UseConsumableTorpReloadBooster
+            | ButtonInputs::UseConsumableSpotterPlane
+            | ButtonInputs::UseConsumableRepairParty
             | ButtonInputs::SetSelectedShip
             | ButtonInputs::PushSelectedShip
-            | ButtonInputs::ClearSelectedShips => 0,
+            | ButtonInputs::ClearSelectedShips
+            | ButtonInputs::SaveControlGroup1
+            | ButtonInputs::SaveControlGroup2
+            | ButtonInputs::SaveControlGroup3
+            | ButtonInputs::SaveControlGroup4
+            | ButtonInputs::SaveControlGroup5
+            | ButtonInputs::SaveControlGroup6
+            | ButtonInputs::SaveControlGroup7
+            | ButtonInputs::SaveControlGroup8
+            | ButtonInputs::SaveControlGroup9
+            | ButtonInputs::SelectControlGroup1
+            | ButtonInputs::SelectControlGroup2
+            | ButtonInputs::SelectControlGroup3
+            | ButtonInputs::SelectControlGroup4
+            | ButtonInputs::SelectControlGroup5
+            | ButtonInputs::SelectControlGroup6
+            | ButtonInputs::SelectControlGroup7
+            | ButtonInputs::SelectControlGroup8
+            | ButtonInputs::SelectControlGroup9 => 0,
         }
     }
+
+    /// If `control` for `self` and `other_control` for `other` can't be told apart,
+    /// since they clash but neither has a higher priority or more modifiers than the other
+    pub(crate) fn ambiguous_with(
+        self,
+        control: &ButtonControl,
+        other: Self,
+        other_control: &ButtonControl,
+    ) -> bool {
+        self != other
+            && control.clashes(other_control)
+            && self.priority() == other.priority()
+            && control.is_subset(other_control)
+            && other_control.is_subset(control)
+    }
 }
 
+/// Held while pressing another key to change what it does,
+/// so they're captured as modifiers rather than the principle key when rebinding controls
+pub(crate) const MODIFIER_KEYS: [KeyCode; 8] = [
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::SuperLeft,
+    KeyCode::SuperRight,
+];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum KeybindKey {
     Gamepad(GamepadButton),
@@ -150,6 +327,16 @@ impl KeybindKey {
     }
 }
 
+impl fmt::Display for KeybindKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeybindKey::Gamepad(b) => write!(f, "Gamepad {b:?}"),
+            KeybindKey::Keyboard(b) => write!(f, "{b:?}"),
+            KeybindKey::Mouse(b) => write!(f, "Mouse {b:?}"),
+        }
+    }
+}
+
 impl From<GamepadButton> for KeybindKey {
     fn from(value: GamepadButton) -> Self {
         Self::Gamepad(value)
@@ -213,11 +400,21 @@ impl ButtonControl {
                 .all(|k| other.modifiers.contains(&k))
     }
 
-    fn clashes(&self, other: &Self) -> bool {
+    pub(crate) fn clashes(&self, other: &Self) -> bool {
         self.is_subset(other) || other.is_subset(self)
     }
 }
 
+impl fmt::Display for ButtonControl {
+    /// The modifiers, then the principle key, such as `ControlLeft + KeyQ`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{modifier} + ")?;
+        }
+        write!(f, "{}", self.principle)
+    }
+}
+
 struct ButtonMap {
     controls: EnumMap<ButtonInputs, ButtonControl>,
 }
@@ -300,6 +497,10 @@ impl ActionState {
         self.buttons[action].value && !self.buttons[action].prev_value
     }
 
+    pub fn just_released(&self, action: ButtonInputs) -> bool {
+        !self.buttons[action].value && self.buttons[action].prev_value
+    }
+
     pub fn read_axis(&self, axis: AxisInputs) -> f32 {
         self.axes[axis].value
     }
@@ -334,8 +535,12 @@ fn update_action_state(
     mouse: Res<ButtonInput<MouseButton>>,
 
     hovering_ships: Query<&Hovering>,
+    fire_target_under_cursor: Res<FireTargetUnderCursor>,
+    minimaps: Query<&RelativeCursorPosition, With<Minimap>>,
 ) {
     let (_gamepad_name, gamepad) = gamepads.single().ok().unzip();
+    // Clicking the minimap recenters the camera, rather than selecting or ordering ships
+    let cursor_over_minimap = minimaps.iter().any(RelativeCursorPosition::mouse_over);
     let ctx = ControlReadCtx {
         gamepad,
         keyboard: &*keyboard,
@@ -403,16 +608,18 @@ fn update_action_state(
             let principle_pressed = actions.button_map.controls[button]
                 .principle
                 .read_pressed(ctx);
-            let principle_just_pressed = actions.button_map.controls[button]
-                .principle
-                .read_just_pressed(ctx);
+            let principle = actions.button_map.controls[button].principle;
+            let principle_just_pressed = principle.read_just_pressed(ctx)
+                && !(cursor_over_minimap && matches!(principle, KeybindKey::Mouse(_)));
 
             let special_conditions_fulfilled =
                 button
                     .special_conditions()
                     .into_iter()
                     .all(|condition| match condition {
-                        SpecialCondition::HoveringOverEnemyShip => hovering_ships.single().is_ok(),
+                        SpecialCondition::HoveringOverEnemyShip => {
+                            hovering_ships.single().is_ok() || fire_target_under_cursor.0.is_some()
+                        }
                     });
 
             let state = &mut actions.buttons[button];
@@ -449,7 +656,24 @@ fn update_cursor_world_pos(
     }
 }
 
-fn update_map_zoom(mut mouse_scroll: EventReader<MouseWheel>, mut zoom: ResMut<MapZoom>) {
+/// The room left around the map when zoomed out to frame it, as a fraction of its size
+const MAP_FRAMING_MARGIN: f32 = 0.1;
+
+/// The zoom at which the whole map fits in a window of `window_size` logical pixels
+///
+/// Reads the map bounds each time, so follows them if they change
+fn zoom_to_frame_map(window_size: Vec2) -> f32 {
+    let (lower, upper) = wrts_match_shared::map_bounds();
+    let framed_size = (upper - lower) * (1. + MAP_FRAMING_MARGIN);
+    (framed_size / window_size.max(Vec2::ONE)).max_element()
+}
+
+fn update_map_zoom(
+    mut mouse_scroll: EventReader<MouseWheel>,
+    mut zoom: ResMut<MapZoom>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<PlayerSettings>,
+) {
     let scroll_speed = 0.2;
     for scroll in mouse_scroll.read() {
         // We want it so that scrolling by 10 once is equivalent to scrolling
@@ -477,7 +701,75 @@ fn update_map_zoom(mut mouse_scroll: EventReader<MouseWheel>, mut zoom: ResMut<M
         // This isn't really necessary
         zoom.0 = zoom.0 * f32::exp(-scroll.y * scroll_speed);
     }
-    zoom.0 = zoom.0.clamp(0.5, 50.);
+    let max_zoom = settings.max_zoom.unwrap_or_else(|| {
+        windows
+            .single()
+            .map_or(f32::INFINITY, |window| zoom_to_frame_map(window.size()))
+    });
+    zoom.0 = zoom
+        .0
+        .clamp(settings.min_zoom, max_zoom.max(settings.min_zoom));
+}
+
+/// With full aim assist, fire target clicks reach this many times further than [crate::SHIP_SELECTION_SIZE]
+const AIM_ASSIST_MAX_REACH: f32 = 4.;
+
+/// The enemy a fire target click would land on, including through aim assist
+#[derive(Resource, Debug, Default)]
+struct FireTargetUnderCursor(Option<Entity>);
+
+/// The enemy ship directly under the cursor, or with aim assist, the closest currently detected
+/// enemy within the widened reach. Ships which have been detected before but aren't now
+/// can still be clicked on directly, but are never snapped to
+///
+/// * `reach` - how far from the cursor counts as directly under it
+/// * `aim_assist` - clamped between 0 (off) and 1
+fn find_fire_target_near(
+    enemies: impl IntoIterator<Item = (Entity, Vec2, DetectionStatus)>,
+    cursor: Vec2,
+    reach: f32,
+    aim_assist: f32,
+) -> Option<Entity> {
+    let assisted_reach = reach * (1. + aim_assist.clamp(0., 1.) * (AIM_ASSIST_MAX_REACH - 1.));
+    let mut closest_detected = None;
+    for (enemy, pos, detection) in enemies {
+        let dist = pos.distance(cursor);
+        if detection != DetectionStatus::Never && dist <= reach {
+            return Some(enemy);
+        }
+        if detection == DetectionStatus::Detected
+            && dist <= assisted_reach
+            && closest_detected.is_none_or(|(_, closest_dist)| dist < closest_dist)
+        {
+            closest_detected = Some((enemy, dist));
+        }
+    }
+    closest_detected.map(|(enemy, _)| enemy)
+}
+
+fn update_fire_target_under_cursor(
+    ships: Query<(Entity, &Team, &Transform, &DetectionStatus), With<Ship>>,
+    cursor_pos: Res<CursorWorldPos>,
+    zoom: Res<MapZoom>,
+    this_client: Res<ThisClient>,
+    actions: Res<ActionState>,
+    settings: Res<PlayerSettings>,
+    mut under_cursor: ResMut<FireTargetUnderCursor>,
+) {
+    let aim_assist = match actions.button_map.controls[ButtonInputs::SetFireTarg].principle {
+        KeybindKey::Gamepad(_) => 1.,
+        KeybindKey::Keyboard(_) | KeybindKey::Mouse(_) => settings.aim_assist,
+    };
+    let enemies = ships
+        .iter()
+        .filter(|(_, team, _, _)| !team.is_this_client(*this_client))
+        .map(|(ship, _, trans, &detection)| (ship, trans.translation.truncate(), detection));
+    under_cursor.0 = find_fire_target_near(
+        enemies,
+        cursor_pos.0,
+        crate::SHIP_SELECTION_SIZE * zoom.0,
+        aim_assist,
+    );
 }
 
 fn update_hovering(
@@ -503,8 +795,10 @@ fn update_hovering(
 
 fn update_camera(
     mut camera: Query<(&mut Projection, &mut Transform), With<MainCamera>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
     actions: Res<ActionState>,
     zoom: Res<MapZoom>,
+    cursor_pos: Res<CursorWorldPos>,
     time: Res<Time>,
 ) {
     let mut camera = camera.single_mut().unwrap();
@@ -512,13 +806,29 @@ fn update_camera(
         panic!()
     };
 
+    // Zoom around the cursor, so the world point under it stays there.
+    // `CursorWorldPos` was found with the previous scale
+    let cursor_in_window = q_window
+        .single()
+        .is_ok_and(|window| window.cursor_position().is_some());
+    if cursor_in_window && proj.scale != zoom.0 {
+        let center = camera.1.translation.truncate();
+        let new_center = cursor_pos.0 - (cursor_pos.0 - center) * (zoom.0 / proj.scale);
+        camera.1.translation = new_center.extend(camera.1.translation.z);
+    }
     proj.scale = zoom.0;
+
     let dir = vec2(
         actions.read_axis(AxisInputs::MoveCameraX),
         actions.read_axis(AxisInputs::MoveCameraY),
     )
     .normalize_or_zero();
     camera.1.translation += (dir * 200. * zoom.0 * time.delta_secs()).extend(0.);
+
+    // Keep the center of the screen over the map
+    let (lower, upper) = wrts_match_shared::map_bounds();
+    let center = camera.1.translation.truncate().clamp(lower, upper);
+    camera.1.translation = center.extend(camera.1.translation.z);
 }
 
 fn update_selection(
@@ -557,104 +867,482 @@ fn update_selection(
     }
 }
 
-fn update_selected_ship_orders(
+fn reset_control_groups(mut commands: Commands) {
+    commands.insert_resource(ControlGroups::default());
+}
+
+fn update_control_groups(
     mut commands: Commands,
+    mut control_groups: ResMut<ControlGroups>,
+    ships: Query<(Entity, Has<Selected>), With<Ship>>,
     actions: Res<ActionState>,
-    mouse_pos: Res<CursorWorldPos>,
-    all_ships: Query<(Entity, &Transform, &Team, &DetectionStatus), With<Ship>>,
-    mut ships_selected: Query<(
-        Entity,
-        &Transform,
-        &Selected,
-        &Ship,
-        Option<&FireTarget>,
-        Option<&mut MoveOrder>,
-    )>,
-    this_client: Res<ThisClient>,
-    zoom: Res<MapZoom>,
-    shared_entities: Res<SharedEntityTracking>,
-    mut server: ResMut<ServerConnection>,
 ) {
-    for ship in &mut ships_selected {
-        let mut new_move_order = None;
-        let mut new_fire_target = None;
-
-        if actions.just_pressed(ButtonInputs::SetFireTarg) {
-            if let Some(new_targ) = all_ships.iter().find(|maybe_targ| {
-                !maybe_targ.2.is_this_client(*this_client)
-                    && *maybe_targ.3 != DetectionStatus::Never
-                    && maybe_targ.1.translation.truncate().distance(mouse_pos.0)
-                        <= crate::SHIP_SELECTION_SIZE * zoom.0
-            }) {
-                new_fire_target = Some(Some(FireTarget { ship: new_targ.0 }));
+    // Forget ships which have been destroyed
+    for group in &mut control_groups.groups {
+        group.retain(|&ship| ships.contains(ship));
+    }
+
+    for (group_idx, (save, select)) in CONTROL_GROUP_INPUTS.into_iter().enumerate() {
+        let group = &mut control_groups.groups[group_idx];
+        if actions.just_pressed(save) {
+            *group = ships
+                .iter()
+                .filter_map(|(ship, selected)| selected.then_some(ship))
+                .collect();
+        } else if actions.just_pressed(select) && !group.is_empty() {
+            for (ship, selected) in &ships {
+                match (group.contains(&ship), selected) {
+                    (true, false) => {
+                        commands.entity(ship).insert(Selected);
+                    }
+                    (false, true) => {
+                        commands.entity(ship).remove::<Selected>();
+                    }
+                    _ => (),
+                }
             }
         }
+    }
+}
+
+/// An order for one ship, as issued by the player
+#[derive(Debug, Clone, PartialEq)]
+enum ShipOrder {
+    SetWaypoints(Vec<Vec2>),
+    PushWaypoint(Vec2),
+    SetFireTarget(Option<Entity>),
+}
+
+/// What a [ShipOrder] resolves to once applied on top of the orders before it,
+/// each sent to the match as its own message
+#[derive(Debug, Clone, PartialEq)]
+enum ResolvedOrder {
+    MoveOrder(MoveOrder),
+    FireTarget(Option<FireTarget>),
+}
+
+/// Orders issued this frame, in the order they were issued
+///
+/// Sent together by `send_queued_orders`, so that several orders
+/// given to the same ship in one frame are all kept
+#[derive(Resource, Debug, Default)]
+struct QueuedOrders(Vec<(Entity, ShipOrder)>);
+
+/// Applies each order on top of the orders before it for the same ship,
+/// starting from each ship's current waypoints
+fn resolve_orders(
+    orders: impl IntoIterator<Item = (Entity, ShipOrder)>,
+    current_waypoints: impl Fn(Entity) -> Option<Vec<Vec2>>,
+) -> Vec<(Entity, ResolvedOrder)> {
+    let mut waypoints: HashMap<Entity, Vec<Vec2>> = HashMap::new();
+    orders
+        .into_iter()
+        .map(|(ship, order)| {
+            let resolved = match order {
+                ShipOrder::SetWaypoints(new_waypoints) => {
+                    waypoints.insert(ship, new_waypoints.clone());
+                    ResolvedOrder::MoveOrder(MoveOrder {
+                        waypoints: new_waypoints,
+                    })
+                }
+                ShipOrder::PushWaypoint(waypoint) => {
+                    let ship_waypoints = waypoints
+                        .entry(ship)
+                        .or_insert_with(|| current_waypoints(ship).unwrap_or_default());
+                    ship_waypoints.push(waypoint);
+                    ResolvedOrder::MoveOrder(MoveOrder {
+                        waypoints: ship_waypoints.clone(),
+                    })
+                }
+                ShipOrder::SetFireTarget(targ) => {
+                    ResolvedOrder::FireTarget(targ.map(|ship| FireTarget { ship }))
+                }
+            };
+            (ship, resolved)
+        })
+        .collect()
+}
+
+fn update_selected_ship_orders(
+    actions: Res<ActionState>,
+    mouse_pos: Res<CursorWorldPos>,
+    ships_selected: Query<Entity, (With<Ship>, With<Selected>)>,
+    fire_target_under_cursor: Res<FireTargetUnderCursor>,
+    mut queued: ResMut<QueuedOrders>,
+) {
+    // Found once so that every selected ship focuses the same target
+    let clicked_enemy = actions
+        .just_pressed(ButtonInputs::SetFireTarg)
+        .then_some(fire_target_under_cursor.0)
+        .flatten();
+
+    for ship in &ships_selected {
+        let mut push = |order| queued.0.push((ship, order));
+
+        if let Some(new_targ) = clicked_enemy {
+            push(ShipOrder::SetFireTarget(Some(new_targ)));
+        }
         if actions.just_pressed(ButtonInputs::ClearFireTarg) {
-            new_fire_target = Some(None);
+            push(ShipOrder::SetFireTarget(None));
         }
 
         if actions.just_pressed(ButtonInputs::SetWaypoint) {
-            new_move_order = Some(MoveOrder {
-                waypoints: vec![mouse_pos.0],
-            });
+            push(ShipOrder::SetWaypoints(vec![mouse_pos.0]));
         }
         if actions.just_pressed(ButtonInputs::PushWaypoint) {
-            if let Some(mut move_order) = ship.5 {
-                move_order.waypoints.push(mouse_pos.0);
-                new_move_order = Some(move_order.clone());
-            } else {
-                new_move_order = Some(MoveOrder {
-                    waypoints: vec![mouse_pos.0],
-                });
-            }
+            push(ShipOrder::PushWaypoint(mouse_pos.0));
         }
         if actions.just_pressed(ButtonInputs::ClearWaypoints) {
-            new_move_order = Some(MoveOrder { waypoints: vec![] });
+            push(ShipOrder::SetWaypoints(vec![]));
+        }
+        if actions.just_pressed(ButtonInputs::StopShip) {
+            // With no waypoints left, the ship will decelerate to a stop
+            push(ShipOrder::SetWaypoints(vec![]));
+            push(ShipOrder::SetFireTarget(None));
         }
+    }
+}
 
-        if let Some(move_order) = new_move_order {
-            let _ = server.send(Message::Client2Match(Client2Match::SetMoveOrder {
-                id: shared_entities[ship.0],
-                waypoints: move_order.waypoints.clone(),
-            }));
-            commands.entity(ship.0).insert(move_order);
+/// How far away from the nearest enemy a disengaging ship is sent
+const DISENGAGE_RETREAT_DIST: f32 = 5_000.;
+
+/// Which actions `ButtonInputs::Disengage` takes for each selected ship
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisengageActions {
+    /// Clears the fire target, so the main battery stops firing and giving away the ship
+    pub hold_fire: bool,
+    /// Deploys smoke, if the ship has any and isn't already deploying it
+    pub deploy_smoke: bool,
+    /// Sails directly away from the nearest detected enemy
+    pub retreat: bool,
+}
+
+impl Default for DisengageActions {
+    fn default() -> Self {
+        Self {
+            hold_fire: true,
+            deploy_smoke: true,
+            retreat: true,
         }
+    }
+}
 
-        if let Some(fire_target) = new_fire_target {
-            let _ = server.send(Message::Client2Match(Client2Match::SetFireTarg {
-                id: shared_entities[ship.0],
-                targ: fire_target.clone().map(|targ| shared_entities[targ.ship]),
+/// Where a ship at `ship_pos` should sail to get away from the nearest of `enemy_positions`,
+/// kept inside the map
+fn retreat_waypoint(
+    ship_pos: Vec2,
+    enemy_positions: impl IntoIterator<Item = Vec2>,
+) -> Option<Vec2> {
+    let nearest_enemy = enemy_positions
+        .into_iter()
+        .min_by_key(|enemy_pos| OrderedFloat(enemy_pos.distance_squared(ship_pos)))?;
+    // An enemy right on top of the ship gives no direction, so it just sails on ahead
+    let away = (ship_pos - nearest_enemy)
+        .try_normalize()
+        .unwrap_or(Vec2::X);
+    let (lower, upper) = wrts_match_shared::map_bounds();
+    Some((ship_pos + away * DISENGAGE_RETREAT_DIST).clamp(lower, upper))
+}
+
+fn disengage_selected_ships(
+    selected_ships: Query<
+        (Entity, &Ship, &Transform, Option<&SmokeConsumableState>),
+        With<Selected>,
+    >,
+    ships: Query<(&Team, &Transform, &DetectionStatus), With<Ship>>,
+    actions: Res<ActionState>,
+    settings: Res<PlayerSettings>,
+    this_client: Res<ThisClient>,
+    mut queued: ResMut<QueuedOrders>,
+    mut server: ResMut<ServerConnection>,
+    shared_entities: Res<SharedEntityTracking>,
+) {
+    if !actions.just_pressed(ButtonInputs::Disengage) {
+        return;
+    }
+    let disengage = settings.disengage;
+    let enemy_positions = ships
+        .iter()
+        .filter(|(team, _, detection)| {
+            !team.is_this_client(*this_client) && **detection == DetectionStatus::Detected
+        })
+        .map(|(_, trans, _)| trans.translation.truncate())
+        .collect_vec();
+
+    for (ship, selected_ship, trans, smoke_state) in &selected_ships {
+        if disengage.hold_fire {
+            queued.0.push((ship, ShipOrder::SetFireTarget(None)));
+        }
+        if disengage.retreat
+            && let Some(waypoint) = retreat_waypoint(
+                trans.translation.truncate(),
+                enemy_positions.iter().copied(),
+            )
+        {
+            queued
+                .0
+                .push((ship, ShipOrder::SetWaypoints(vec![waypoint])));
+        }
+        let is_deploying_smoke = smoke_state.is_some_and(|state| {
+            matches!(
+                state.action_state,
+                SmokeConsumableActionState::Deploying { .. }
+            )
+        });
+        if disengage.deploy_smoke
+            && selected_ship.template.consumables.smoke().is_some()
+            && !is_deploying_smoke
+            && let Some(shared) = shared_entities.get_by_local(ship)
+        {
+            let _ = server.send(Message::Client2Match(Client2Match::UseConsumableSmoke {
+                ship: shared,
             }));
-            match fire_target {
-                Some(fire_target) => {
-                    commands.entity(ship.0).insert(fire_target);
-                }
-                None => {
-                    commands.entity(ship.0).remove::<FireTarget>();
+        }
+    }
+}
+
+fn send_queued_orders(
+    mut commands: Commands,
+    mut queued: ResMut<QueuedOrders>,
+    move_orders: Query<&MoveOrder>,
+    shared_entities: Res<SharedEntityTracking>,
+    mut server: ResMut<ServerConnection>,
+    mut pending_move_orders: ResMut<PendingMoveOrders>,
+    time: Res<Time>,
+) {
+    let resolved = resolve_orders(queued.0.drain(..), |ship| {
+        move_orders
+            .get(ship)
+            .ok()
+            .map(|move_order| move_order.waypoints.clone())
+    });
+    for (ship, order) in resolved {
+        let Some(shared) = shared_entities.get_by_local(ship) else {
+            continue;
+        };
+        match order {
+            ResolvedOrder::MoveOrder(move_order) => {
+                let _ = server.send(Message::Client2Match(Client2Match::SetMoveOrder {
+                    id: shared,
+                    waypoints: move_order.waypoints.clone(),
+                }));
+                pending_move_orders.0.insert(
+                    ship,
+                    PendingMoveOrder {
+                        waypoints: move_order.waypoints.clone(),
+                        sent_at: time.elapsed(),
+                    },
+                );
+                commands
+                    .entity(ship)
+                    .insert(move_order)
+                    .remove::<HeadingOrder>();
+            }
+            ResolvedOrder::FireTarget(fire_target) => {
+                // A target the match can't be told about is cleared there,
+                // so it's cleared here too rather than shown as set
+                let fire_target = fire_target.and_then(|targ| {
+                    shared_entities
+                        .get_by_local(targ.ship)
+                        .map(|shared_targ| (targ, shared_targ))
+                });
+                let _ = server.send(Message::Client2Match(Client2Match::SetFireTarg {
+                    id: shared,
+                    targ: fire_target.as_ref().map(|(_, shared_targ)| *shared_targ),
+                }));
+                match fire_target {
+                    Some((fire_target, _)) => {
+                        commands.entity(ship).insert(fire_target);
+                    }
+                    None => {
+                        commands.entity(ship).remove::<FireTarget>();
+                    }
                 }
             }
         }
     }
 }
 
+fn set_heading(
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+    selected_ships: Query<(Entity, &Transform), (With<Ship>, With<Selected>)>,
+    actions: Res<ActionState>,
+    mouse_pos: Res<CursorWorldPos>,
+    zoom: Res<MapZoom>,
+    mut server: ResMut<ServerConnection>,
+    shared_entities: Res<SharedEntityTracking>,
+) {
+    let previewing = actions.pressed(ButtonInputs::SetHeading);
+    if !previewing && !actions.just_released(ButtonInputs::SetHeading) {
+        return;
+    }
+    for (selected_entity, trans) in &selected_ships {
+        let pos = trans.translation.truncate();
+        let Ok(heading) = Dir2::new(mouse_pos.0 - pos) else {
+            continue;
+        };
+        if previewing {
+            gizmos.arrow_2d(
+                pos,
+                pos + heading * 60. * zoom.0,
+                Color::linear_rgb(0.9, 0.8, 0.2),
+            );
+        } else {
+            let _ = server.send(Message::Client2Match(Client2Match::SetHeading {
+                id: shared_entities[selected_entity],
+                heading: heading.to_angle(),
+            }));
+            // The match clears the ship's waypoints too
+            commands.entity(selected_entity).insert((
+                MoveOrder { waypoints: vec![] },
+                HeadingOrder {
+                    heading: heading.to_angle(),
+                },
+            ));
+        }
+    }
+}
+
 fn use_consumables(
-    selected_ships: Query<(Entity, &Ship), With<Selected>>,
+    selected_ships: Query<(Entity, &Ship, Option<&SmokeConsumableState>), With<Selected>>,
     actions: Res<ActionState>,
     mut server: ResMut<ServerConnection>,
     shared_entities: Res<SharedEntityTracking>,
 ) {
-    let Ok((selected_entity, selected_ship)) = selected_ships.single() else {
+    let Ok((selected_entity, selected_ship, smoke_state)) = selected_ships.single() else {
         return;
     };
     let consumables = &selected_ship.template.consumables;
-    // Smoke
+    // Smoke, which is canceled by pressing the same key again while deploying
     if actions.just_pressed(ButtonInputs::UseConsumableSmoke) {
-        if consumables.smoke().is_some() {
+        let is_deploying = smoke_state.is_some_and(|state| {
+            matches!(
+                state.action_state,
+                SmokeConsumableActionState::Deploying { .. }
+            )
+        });
+        if is_deploying {
+            let _ = server.send(Message::Client2Match(Client2Match::CancelConsumableSmoke {
+                ship: shared_entities[selected_entity],
+            }));
+        } else if consumables.smoke().is_some() {
             let _ = server.send(Message::Client2Match(Client2Match::UseConsumableSmoke {
                 ship: shared_entities[selected_entity],
             }));
         }
     }
+    // Torpedo reload booster
+    if actions.just_pressed(ButtonInputs::UseConsumableTorpReloadBooster) {
+        if consumables.torp_reload_booster().is_some() {
+            let _ = server.send(Message::Client2Match(
+                Client2Match::UseConsumableTorpReloadBooster {
+                    ship: shared_entities[selected_entity],
+                },
+            ));
+        }
+    }
+    // Spotter plane
+    if actions.just_pressed(ButtonInputs::UseConsumableSpotterPlane) {
+        if consumables.spotter_plane().is_some() {
+            let _ = server.send(Message::Client2Match(
+                Client2Match::UseConsumableSpotterPlane {
+                    ship: shared_entities[selected_entity],
+                },
+            ));
+        }
+    }
+    // Repair party
+    if actions.just_pressed(ButtonInputs::UseConsumableRepairParty) {
+        if consumables.repair_party().is_some() {
+            let _ = server.send(Message::Client2Match(
+                Client2Match::UseConsumableRepairParty {
+                    ship: shared_entities[selected_entity],
+                },
+            ));
+        }
+    }
+}
+
+fn toggle_enemy_ship_uis(actions: Res<ActionState>, mut settings: ResMut<PlayerSettings>) {
+    if actions.just_pressed(ButtonInputs::ToggleEnemyShipUIs) {
+        settings.hide_enemy_ship_uis = !settings.hide_enemy_ship_uis;
+    }
+}
+
+fn toggle_rings(actions: Res<ActionState>, mut settings: ResMut<PlayerSettings>) {
+    if actions.just_pressed(ButtonInputs::ToggleRings) {
+        settings.show_rings = !settings.show_rings;
+    }
+}
+
+fn toggle_debug_overlay(actions: Res<ActionState>, mut settings: ResMut<PlayerSettings>) {
+    if actions.just_pressed(ButtonInputs::ToggleDebugOverlay) {
+        settings.show_debug_overlay = !settings.show_debug_overlay;
+    }
+}
+
+fn toggle_pause(
+    actions: Res<ActionState>,
+    paused: Res<MatchPaused>,
+    mut server: ResMut<ServerConnection>,
+) {
+    if actions.just_pressed(ButtonInputs::TogglePause) {
+        let _ = server.send(Message::Client2Match(Client2Match::SetPaused {
+            paused: !paused.0,
+        }));
+    }
+}
+
+fn copy_match_seed(actions: Res<ActionState>, seed: Option<Res<MatchSeed>>) {
+    if !actions.just_pressed(ButtonInputs::CopyMatchSeed) {
+        return;
+    }
+    let Some(seed) = seed else {
+        return;
+    };
+    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(seed.0.to_string()))
+    {
+        Ok(()) => info!("Copied match seed {} to the clipboard", seed.0),
+        Err(err) => warn!(
+            "Couldn't copy match seed {} to the clipboard: {err}",
+            seed.0
+        ),
+    }
+}
+
+/// How much `PlayerSettings::ui_scale` changes with each press
+const UI_SCALE_STEP: f32 = 0.1;
+
+fn adjust_ui_scale(actions: Res<ActionState>, mut settings: ResMut<PlayerSettings>) {
+    let step = if actions.just_pressed(ButtonInputs::IncreaseUIScale) {
+        UI_SCALE_STEP
+    } else if actions.just_pressed(ButtonInputs::DecreaseUIScale) {
+        -UI_SCALE_STEP
+    } else {
+        return;
+    };
+    settings.ui_scale =
+        (settings.ui_scale + step).clamp(crate::UI_SCALE_LIMITS.0, crate::UI_SCALE_LIMITS.1);
+}
+
+fn toggle_shell_arc(
+    mut selected_ships: Query<(Entity, &mut Ship), With<Selected>>,
+    actions: Res<ActionState>,
+    mut server: ResMut<ServerConnection>,
+    shared_entities: Res<SharedEntityTracking>,
+) {
+    if !actions.just_pressed(ButtonInputs::ToggleShellArc) {
+        return;
+    }
+    for (selected_entity, mut selected_ship) in &mut selected_ships {
+        selected_ship.shell_arc = selected_ship.shell_arc.toggled();
+        let _ = server.send(Message::Client2Match(Client2Match::SetShellArc {
+            id: shared_entities[selected_entity],
+            arc: selected_ship.shell_arc,
+        }));
+    }
 }
 
 fn fire_torpedoes(
@@ -682,7 +1370,13 @@ fn fire_torpedoes(
         });
 
     let ship_pos = selected_trans.translation.truncate();
-    let angles_color = Color::linear_rgb(0.1, 0.4, 0.8);
+    let is_armed = selected_ship.reloaded_torp_volleys > 0;
+    // Dimmed while every volley is reloading
+    let angles_color = if is_armed {
+        Color::linear_rgb(0.1, 0.4, 0.8)
+    } else {
+        Color::linear_rgba(0.1, 0.4, 0.8, 0.25)
+    };
     let min_dist = 100.;
     let max_dist = torps.range;
 
@@ -733,6 +1427,9 @@ fn fire_torpedoes(
         );
     }
 
+    if !is_armed {
+        return;
+    }
     if let Some(fire_dir) = (cursor_pos.0 - ship_pos).try_normalize() {
         let is_valid_angle = firing_angles
             .into_iter()
@@ -753,3 +1450,197 @@ fn fire_torpedoes(
         }
     };
 }
+
+/// Shows the flight time of the shells previewed by [preview_shell_impact], next to the cursor
+#[derive(Component, Debug, Clone, Copy)]
+struct ShellFlightTimeText;
+
+fn spawn_shell_flight_time_text(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        ShellFlightTimeText,
+        Text::new(""),
+        TextFont {
+            font_size: 16.,
+            ..default()
+        },
+        TextColor(SHELL_IMPACT_PREVIEW_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        Visibility::Hidden,
+    ));
+}
+
+const SHELL_IMPACT_PREVIEW_COLOR: Color = Color::linear_rgb(1., 0.8, 0.2);
+
+/// Previews where the selected ship's main battery would land shells on the enemy under the cursor,
+/// and how long they'd be in the air, like [fire_torpedoes] does for torpedoes.
+/// Nothing is shown if every turret is out of range or can't turn to face the enemy
+fn preview_shell_impact(
+    mut gizmos: Gizmos,
+    selected: Query<(&Ship, &Transform), With<Selected>>,
+    targets: Query<(&Transform, &Velocity, &DetectionStatus), With<Ship>>,
+    under_cursor: Res<FireTargetUnderCursor>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut flight_time_text: Query<(&mut Text, &mut Node, &mut Visibility), With<ShellFlightTimeText>>,
+    ui_scale: Res<UiScale>,
+    zoom: Res<MapZoom>,
+) {
+    let Ok((mut text, mut node, mut visibility)) = flight_time_text.single_mut() else {
+        return;
+    };
+    *visibility = Visibility::Hidden;
+
+    let Ok((ship, ship_trans)) = selected.single() else {
+        return;
+    };
+    let Some((targ_trans, targ_vel, _)) = under_cursor
+        .0
+        .and_then(|target| targets.get(target).ok())
+        .filter(|&(_, _, &detection)| detection == DetectionStatus::Detected)
+    else {
+        return;
+    };
+    let targ_pos = targ_trans.translation.truncate();
+    let shots = ship.main_battery_shots(ship_trans, targ_pos, targ_vel.0);
+    if shots.is_empty() {
+        return;
+    }
+
+    let impact_point = shots
+        .iter()
+        .map(|(_, bp)| bp.intersection_point)
+        .sum::<Vec2>()
+        / shots.len() as f32;
+    let flight_time = shots
+        .iter()
+        .map(|(_, bp)| bp.intersection_time)
+        .sum::<f32>()
+        / shots.len() as f32;
+    gizmos.circle_2d(
+        Isometry2d::from_translation(impact_point),
+        8. * zoom.0,
+        SHELL_IMPACT_PREVIEW_COLOR,
+    );
+    gizmos.line_2d(targ_pos, impact_point, SHELL_IMPACT_PREVIEW_COLOR);
+
+    let Some(cursor) = q_window.single().ok().and_then(Window::cursor_position) else {
+        return;
+    };
+    text.0 = format!("{flight_time:.1}s");
+    node.left = Val::Px(cursor.x / ui_scale.0 + 16.);
+    node.top = Val::Px(cursor.y / ui_scale.0 + 16.);
+    *visibility = Visibility::Inherited;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::{
+        DetectionStatus, FireTarget, MoveOrder,
+        input_handling::{
+            DISENGAGE_RETREAT_DIST, ResolvedOrder, ShipOrder, find_fire_target_near,
+            resolve_orders, retreat_waypoint, zoom_to_frame_map,
+        },
+    };
+
+    #[test]
+    fn test_orders_in_one_frame_are_all_sent() {
+        let ship = Entity::from_raw(1);
+        let target = Entity::from_raw(2);
+        let resolved = resolve_orders(
+            [
+                (ship, ShipOrder::SetWaypoints(vec![vec2(1., 0.)])),
+                (ship, ShipOrder::PushWaypoint(vec2(2., 0.))),
+                (ship, ShipOrder::SetFireTarget(Some(target))),
+            ],
+            |_| Some(vec![vec2(-1., 0.)]),
+        );
+        assert_eq!(
+            resolved,
+            vec![
+                (
+                    ship,
+                    ResolvedOrder::MoveOrder(MoveOrder {
+                        waypoints: vec![vec2(1., 0.)]
+                    })
+                ),
+                (
+                    ship,
+                    ResolvedOrder::MoveOrder(MoveOrder {
+                        waypoints: vec![vec2(1., 0.), vec2(2., 0.)]
+                    })
+                ),
+                (
+                    ship,
+                    ResolvedOrder::FireTarget(Some(FireTarget { ship: target }))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pushed_waypoints_start_from_current_order() {
+        let ship = Entity::from_raw(1);
+        let resolved = resolve_orders(
+            [
+                (ship, ShipOrder::PushWaypoint(vec2(2., 0.))),
+                (ship, ShipOrder::PushWaypoint(vec2(3., 0.))),
+            ],
+            |_| Some(vec![vec2(1., 0.)]),
+        );
+        assert_eq!(
+            resolved.last().unwrap().1,
+            ResolvedOrder::MoveOrder(MoveOrder {
+                waypoints: vec![vec2(1., 0.), vec2(2., 0.), vec2(3., 0.)]
+            })
+        );
+    }
+
+    #[test]
+    fn test_aim_assist_only_snaps_to_detected_enemies() {
+        let [detected, undetected] = [Entity::from_raw(1), Entity::from_raw(2)];
+        let enemies = [
+            (detected, vec2(60., 0.), DetectionStatus::Detected),
+            (undetected, vec2(30., 0.), DetectionStatus::UnDetected),
+        ];
+
+        assert_eq!(find_fire_target_near(enemies, Vec2::ZERO, 20., 0.), None);
+        assert_eq!(
+            find_fire_target_near(enemies, Vec2::ZERO, 20., 1.),
+            Some(detected)
+        );
+        // Clicking right on a ship which isn't currently detected still targets it
+        assert_eq!(
+            find_fire_target_near(enemies, vec2(25., 0.), 20., 1.),
+            Some(undetected)
+        );
+    }
+
+    #[test]
+    fn test_max_zoom_frames_whole_map() {
+        let (lower, upper) = wrts_match_shared::map_bounds();
+        for window_size in [vec2(1280., 720.), vec2(720., 1280.), vec2(3840., 2160.)] {
+            let zoom = zoom_to_frame_map(window_size);
+            let visible = window_size * zoom;
+            assert!(visible.cmpge(upper - lower).all());
+        }
+    }
+
+    #[test]
+    fn test_retreat_waypoint_heads_away_from_nearest_enemy() {
+        let ship_pos = vec2(0., 0.);
+        let waypoint = retreat_waypoint(ship_pos, [vec2(0., 3_000.), vec2(8_000., 0.)]).unwrap();
+        assert!(waypoint.distance(vec2(0., -DISENGAGE_RETREAT_DIST)) < 0.01);
+
+        assert_eq!(retreat_waypoint(ship_pos, []), None);
+
+        // Kept inside the map, even when backed up against its edge
+        let (lower, _) = wrts_match_shared::map_bounds();
+        let waypoint = retreat_waypoint(lower + 10., [lower + 1_000.]).unwrap();
+        assert!(waypoint.cmpge(lower).all());
+    }
+}
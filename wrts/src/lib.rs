@@ -5,23 +5,31 @@ mod networking;
 mod ship;
 mod ui;
 
-use std::{collections::HashMap, iter};
+use std::{collections::HashMap, f32::consts::FRAC_PI_2, iter, path::PathBuf, time::Duration};
 
 use bevy::prelude::*;
+use clap::Parser;
 use enum_map::{EnumMap, enum_map};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use wrts_match_shared::{
+    formulas::ballistics,
+    ship_path::ShipPathCatmull,
+    ship_template::{
+        BulletType, Caliber, DEFAULT_SHIP_MODS_DIR, HullSection, ShipTemplateRegistry,
+    },
+};
 use wrts_messaging::ClientId;
 
 use crate::{
-    in_match::InMatchPlugin,
+    in_match::{InMatchPlugin, PendingMoveOrders},
     input_handling::{
-        AxisControl, AxisInputs, ButtonControl, ButtonInputs, InputHandlingPlugin,
-        InputHandlingSystem,
+        AxisControl, AxisInputs, ButtonControl, ButtonInputs, DisengageActions, Hovering,
+        InputHandlingPlugin, InputHandlingSystem,
     },
     networking::{NetworkingPlugin, ThisClient},
     ship::{Ship, ShipDisplayPlugin},
-    ui::{in_game::InGameUIPlugin, lobby::LobbyUiPlugin},
+    ui::{debug_overlay::DebugOverlayPlugin, in_game::InGameUIPlugin, lobby::LobbyUiPlugin},
 };
 
 #[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -41,11 +49,34 @@ struct TeamColors {
 }
 
 #[derive(Serialize, Deserialize)]
+#[serde(from = "SavedPlayerControls")]
 struct PlayerControls {
     axis_controls: EnumMap<AxisInputs, AxisControl>,
     button_controls: EnumMap<ButtonInputs, ButtonControl>,
 }
 
+/// [PlayerControls] as read from the settings file, where any controls missing,
+/// such as ones added since it was written, keep their defaults
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SavedPlayerControls {
+    axis_controls: HashMap<AxisInputs, AxisControl>,
+    button_controls: HashMap<ButtonInputs, ButtonControl>,
+}
+
+impl From<SavedPlayerControls> for PlayerControls {
+    fn from(saved: SavedPlayerControls) -> Self {
+        let mut controls = Self::default();
+        for (axis, control) in saved.axis_controls {
+            controls.axis_controls[axis] = control;
+        }
+        for (button, control) in saved.button_controls {
+            controls.button_controls[button] = control;
+        }
+        controls
+    }
+}
+
 impl Default for PlayerControls {
     fn default() -> Self {
         use AxisInputs::*;
@@ -66,22 +97,78 @@ impl Default for PlayerControls {
                 SetWaypoint => ButtonControl::new(MouseButton::Right),
                 PushWaypoint => ButtonControl::new_with(MouseButton::Right, [ShiftLeft]),
                 ClearWaypoints => ButtonControl::new_with(KeyQ, [AltLeft]),
+                SetHeading => ButtonControl::new_with(MouseButton::Right, [AltLeft]),
+                StopShip => ButtonControl::new(KeyX),
+                Disengage => ButtonControl::new(KeyZ),
+                ToggleShellArc => ButtonControl::new(KeyV),
+                ToggleEnemyShipUIs => ButtonControl::new(KeyL),
+                ToggleRings => ButtonControl::new(KeyG),
+                ToggleDebugOverlay => ButtonControl::new(F3),
+                TogglePause => ButtonControl::new(Pause),
+                CopyMatchSeed => ButtonControl::new_with(KeyC, [ControlLeft]),
+                IncreaseUIScale => ButtonControl::new_with(Equal, [ControlLeft]),
+                DecreaseUIScale => ButtonControl::new_with(Minus, [ControlLeft]),
 
                 FireTorpVolley => ButtonControl::new_with(MouseButton::Left, [ControlLeft]),
 
-                UseConsumableSmoke => ButtonControl::new(Digit1),
+                UseConsumableSmoke => ButtonControl::new(KeyR),
+                UseConsumableTorpReloadBooster => ButtonControl::new(KeyT),
+                UseConsumableSpotterPlane => ButtonControl::new(KeyY),
+                UseConsumableRepairParty => ButtonControl::new(KeyF),
+
+                SaveControlGroup1 => ButtonControl::new_with(Digit1, [ControlLeft]),
+                SaveControlGroup2 => ButtonControl::new_with(Digit2, [ControlLeft]),
+                SaveControlGroup3 => ButtonControl::new_with(Digit3, [ControlLeft]),
+                SaveControlGroup4 => ButtonControl::new_with(Digit4, [ControlLeft]),
+                SaveControlGroup5 => ButtonControl::new_with(Digit5, [ControlLeft]),
+                SaveControlGroup6 => ButtonControl::new_with(Digit6, [ControlLeft]),
+                SaveControlGroup7 => ButtonControl::new_with(Digit7, [ControlLeft]),
+                SaveControlGroup8 => ButtonControl::new_with(Digit8, [ControlLeft]),
+                SaveControlGroup9 => ButtonControl::new_with(Digit9, [ControlLeft]),
+                SelectControlGroup1 => ButtonControl::new(Digit1),
+                SelectControlGroup2 => ButtonControl::new(Digit2),
+                SelectControlGroup3 => ButtonControl::new(Digit3),
+                SelectControlGroup4 => ButtonControl::new(Digit4),
+                SelectControlGroup5 => ButtonControl::new(Digit5),
+                SelectControlGroup6 => ButtonControl::new(Digit6),
+                SelectControlGroup7 => ButtonControl::new(Digit7),
+                SelectControlGroup8 => ButtonControl::new(Digit8),
+                SelectControlGroup9 => ButtonControl::new(Digit9),
             },
         }
     }
 }
 
 #[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
 struct PlayerSettings {
     username: String,
+    /// Pre-filled into the server address field,
+    /// set whenever a connection to a server succeeds
+    last_server_address: Option<String>,
     ship_icon_scale: f32,
     bullet_icon_scale: f32,
     team_friend_colors: TeamColors,
     team_enemy_colors: TeamColors,
+    /// Nudges overlapping ship UIs apart, rather than drawing them over each other
+    declutter_ship_uis: bool,
+    hide_enemy_ship_uis: bool,
+    disengage: DisengageActions,
+    /// Draws every visible ship's gun range and detection rings
+    show_rings: bool,
+    show_debug_overlay: bool,
+    /// From 0 (off) to 1, how far beyond the ship under the cursor fire target clicks
+    /// snap to the nearest detected enemy. Always at full strength while
+    /// setting fire targets is bound to a gamepad button
+    aim_assist: f32,
+    /// The closest the camera can zoom in, in world units per logical pixel
+    min_zoom: f32,
+    /// The furthest the camera can zoom out, in world units per logical pixel.
+    /// If `None`, just far enough to frame the whole map
+    max_zoom: Option<f32>,
+    /// Multiplies the size of every UI element,
+    /// on top of the window's own scale factor
+    ui_scale: f32,
     controls: PlayerControls,
 }
 
@@ -89,6 +176,7 @@ impl Default for PlayerSettings {
     fn default() -> Self {
         Self {
             username: "Username".into(),
+            last_server_address: None,
             ship_icon_scale: 20.,
             bullet_icon_scale: 5.,
             team_friend_colors: TeamColors {
@@ -99,6 +187,15 @@ impl Default for PlayerSettings {
                 ship_color: Color::linear_rgb(0.7, 0.2, 0.),
                 gun_range_ring_color: Color::linear_rgb(0.8, 0.2, 0.2),
             },
+            declutter_ship_uis: true,
+            hide_enemy_ship_uis: false,
+            disengage: DisengageActions::default(),
+            show_rings: true,
+            show_debug_overlay: false,
+            aim_assist: 0.,
+            min_zoom: 0.5,
+            max_zoom: None,
+            ui_scale: 1.,
             controls: Default::default(),
         }
     }
@@ -122,18 +219,36 @@ struct MainCamera;
 #[derive(Component, Debug, Default, Clone)]
 struct Velocity(pub Vec2);
 
-#[derive(Component, Debug, Default, Clone)]
+#[derive(Component, Debug, Default, Clone, PartialEq)]
 struct MoveOrder {
     pub waypoints: Vec<Vec2>,
 }
 
+/// The heading last ordered through `Client2Match::SetHeading`,
+/// kept until the ship is given waypoints again
+#[derive(Component, Debug, Clone, Copy)]
+struct HeadingOrder {
+    /// Radians, as from `Vec2::to_angle`
+    pub heading: f32,
+}
+
 #[derive(Component, Debug, Default, Clone)]
 #[require(Transform)]
 struct SmokePuff {
     pub radius: f32,
 }
 
-#[derive(Component, Debug, Clone)]
+/// An aircraft circling `owning_ship`
+#[derive(Component, Debug, Clone, Copy)]
+#[require(Team, Transform, Sprite)]
+struct Aircraft {
+    owning_ship: Entity,
+}
+
+/// The size of aircraft icons, as a fraction of `PlayerSettings::ship_icon_scale`
+const AIRCRAFT_ICON_SIZE: Vec2 = vec2(0.5, 0.35);
+
+#[derive(Component, Debug, Clone, PartialEq)]
 struct FireTarget {
     ship: Entity,
 }
@@ -141,6 +256,16 @@ struct FireTarget {
 #[derive(Component, Debug, Default, Clone)]
 struct Health(pub f64);
 
+/// The fraction of each [HullSection]'s health left, in the order of [HullSection::ALL]
+#[derive(Component, Debug, Clone, Copy)]
+struct SectionHealth(pub [f32; HullSection::COUNT]);
+
+impl Default for SectionHealth {
+    fn default() -> Self {
+        Self([1.; HullSection::COUNT])
+    }
+}
+
 #[derive(Component, Debug, Default, Clone, Copy)]
 struct Selected;
 
@@ -190,8 +315,16 @@ struct Torpedo {
     owning_ship: Entity,
     damage: f64,
     speed: f32,
+    /// When the torpedo was spawned on this client, in [Time::elapsed]
+    launched_at: Duration,
 }
 
+/// A torpedo's wake grows to its full length over this long after launch
+const TORPEDO_WAKE_BUILDUP_SECS: f32 = 4.;
+/// A torpedo's wake fades over this long after launch,
+/// long enough for it to still be visible at the end of most torpedoes' range
+const TORPEDO_WAKE_FADE_SECS: f32 = 120.;
+
 #[derive(Component, Debug, Clone, Copy)]
 struct TorpedoReloadText;
 
@@ -201,6 +334,7 @@ fn update_torpedo_displays(
     this_client: Res<ThisClient>,
     zoom: Res<MapZoom>,
     settings: Res<PlayerSettings>,
+    time: Res<Time>,
 ) {
     for (torp, torp_team, torp_trans, mut torp_sprite, torp_detection) in torps {
         let is_visible =
@@ -214,11 +348,19 @@ fn update_torpedo_displays(
                 );
                 let torp_dir = Vec2::from_angle(torp_trans.rotation.to_euler(EulerRot::ZXY).0);
                 let torp_pos = torp_trans.translation.truncate();
+                // The wake's length shows the torpedo's speed, and its brightness its age
+                let age = time
+                    .elapsed()
+                    .saturating_sub(torp.launched_at)
+                    .as_secs_f32();
+                let buildup = (age / TORPEDO_WAKE_BUILDUP_SECS).min(1.);
+                let brightness = 1. - 0.7 * (age / TORPEDO_WAKE_FADE_SECS).min(1.);
+                let wake_len = (torp.speed / 75. * buildup).max(1.);
                 gizmos.line_gradient_2d(
                     torp_pos - torp_dir * 10. * zoom.0,
-                    torp_pos - torp.speed / 75. * torp_dir * 10. * zoom.0,
-                    Color::WHITE,
-                    Color::linear_rgb(0.5, 0.5, 0.5),
+                    torp_pos - wake_len * torp_dir * 10. * zoom.0,
+                    Color::WHITE.with_alpha(brightness),
+                    Color::linear_rgba(0.5, 0.5, 0.5, 0.5 * brightness),
                 );
             }
             false => {
@@ -240,6 +382,29 @@ fn update_smoke_puff_displays(mut gizmos: Gizmos, smoke_puffs: Query<(&SmokePuff
     }
 }
 
+/// Aircraft are only shown while their ship is, as the match stops updating them otherwise
+fn update_aircraft_displays(
+    aircraft: Query<(&Aircraft, &Team, &mut Sprite)>,
+    ships: Query<&DetectionStatus, With<Ship>>,
+    settings: Res<PlayerSettings>,
+    zoom: Res<MapZoom>,
+    this_client: Res<ThisClient>,
+) {
+    for (plane, &team, mut sprite) in aircraft {
+        let is_visible = team.is_this_client(*this_client)
+            || ships
+                .get(plane.owning_ship)
+                .is_ok_and(|detection| *detection == DetectionStatus::Detected);
+        *sprite = match is_visible {
+            true => Sprite::from_color(
+                settings.team_colors(team, *this_client).ship_color,
+                AIRCRAFT_ICON_SIZE * settings.ship_icon_scale * zoom.0,
+            ),
+            false => Sprite::default(),
+        };
+    }
+}
+
 fn update_ship_ghosts(
     mut commands: Commands,
     changed_ships: Query<
@@ -305,27 +470,41 @@ fn update_ship_ghosts_display(
 struct Bullet {
     owning_ship: Entity,
     damage: f64,
+    caliber: Caliber,
+    ty: BulletType,
 }
 
+/// Tracers of this caliber are drawn at `PlayerSettings::bullet_icon_scale`,
+/// with larger shells drawn bigger
+const TRACER_REFERENCE_CALIBER_MM: f32 = 300.;
+
 fn update_bullet_displays(
-    bullets: Query<(&Transform, &mut Sprite, &Team), With<Bullet>>,
+    bullets: Query<(&Bullet, &Transform, &mut Sprite, &Team)>,
     settings: Res<PlayerSettings>,
     zoom: Res<MapZoom>,
     this_client: Res<ThisClient>,
 ) {
-    for (trans, mut sprite, &team) in bullets {
+    for (bullet, trans, mut sprite, &team) in bullets {
         if trans.translation.z <= 0. {
             *sprite = Sprite::from_color(
                 Color::linear_rgb(0., 0., 0.),
                 sprite.custom_size.unwrap_or_default(),
             );
         } else {
-            sprite.color = settings.team_colors(team, *this_client).ship_color;
+            sprite.color = match bullet.ty {
+                BulletType::AP | BulletType::HE => {
+                    settings.team_colors(team, *this_client).ship_color
+                }
+            };
         }
         let double_height = 1000.;
         let height_scaling = 1. + trans.translation.z.clamp(0., 20_000.) / double_height;
-        sprite.custom_size =
-            Some(vec2(2., 0.5) * height_scaling * settings.bullet_icon_scale * zoom.0);
+        let caliber_scaling = (bullet.caliber.mm() / TRACER_REFERENCE_CALIBER_MM)
+            .sqrt()
+            .clamp(0.5, 1.5);
+        sprite.custom_size = Some(
+            vec2(2., 0.5) * height_scaling * caliber_scaling * settings.bullet_icon_scale * zoom.0,
+        );
     }
 }
 
@@ -339,18 +518,50 @@ fn make_camera(mut commands: Commands) {
     ));
 }
 
+/// Draws the segments between `points` as dashes `dash_len` long
+fn dashed_linestrip_2d(
+    gizmos: &mut Gizmos,
+    points: impl IntoIterator<Item = Vec2>,
+    dash_len: f32,
+    color: Color,
+) {
+    for (start, end) in points.into_iter().tuple_windows() {
+        let len = start.distance(end);
+        let dir = (end - start).normalize_or_zero();
+        let mut dist = 0.;
+        while dist < len {
+            let dash_end = (dist + dash_len).min(len);
+            gizmos.line_2d(start + dir * dist, start + dir * dash_end, color);
+            dist += 2. * dash_len;
+        }
+    }
+}
+
 fn update_selected_ship_orders_display(
     mut gizmos: Gizmos,
     ships_selected: Query<
-        (&Ship, &Transform, Option<&FireTarget>, Option<&MoveOrder>),
+        (
+            Entity,
+            &Ship,
+            &Transform,
+            Option<&FireTarget>,
+            Option<&MoveOrder>,
+        ),
         With<Selected>,
     >,
     transforms: Query<&Transform>,
+    pending_move_orders: Res<PendingMoveOrders>,
     settings: Res<PlayerSettings>,
     zoom: Res<MapZoom>,
+    time: Res<Time>,
 ) {
-    for (_selected_ship, selected_trans, selected_fire_target, selected_move_order) in
-        &ships_selected
+    for (
+        selected_entity,
+        selected_ship,
+        selected_trans,
+        selected_fire_target,
+        selected_move_order,
+    ) in &ships_selected
     {
         let circle_size = zoom.0 * settings.ship_icon_scale * 0.5 * 1.4;
         gizmos
@@ -374,15 +585,137 @@ fn update_selected_ship_orders_display(
         if let Some(move_order) = selected_move_order
             && !move_order.waypoints.is_empty()
         {
-            gizmos.linestrip_2d(
-                iter::once(selected_trans.translation.truncate())
-                    .chain(move_order.waypoints.iter().copied()),
-                Color::linear_rgb(1., 0.2, 0.2),
-            );
+            let waypoints_line = iter::once(selected_trans.translation.truncate())
+                .chain(move_order.waypoints.iter().copied());
+            // Dashed until the match confirms the order, and greyed out if it never did
+            match pending_move_orders.0.get(&selected_entity) {
+                None => gizmos.linestrip_2d(waypoints_line, Color::linear_rgb(1., 0.2, 0.2)),
+                Some(pending) => {
+                    let color = match pending.is_overdue(time.elapsed()) {
+                        true => Color::linear_rgb(0.5, 0.5, 0.5),
+                        false => Color::linear_rgb(1., 0.2, 0.2),
+                    };
+                    dashed_linestrip_2d(&mut gizmos, waypoints_line, 8. * zoom.0, color);
+                }
+            }
+
+            // The smoothed track the ship will actually follow
+            let heading = Vec2::from_angle(selected_trans.rotation.to_euler(EulerRot::ZXY).0);
+            let Some(path) =
+                ShipPathCatmull::new(selected_trans.translation.truncate(), &move_order.waypoints)
+            else {
+                continue;
+            };
+            let path = path.with_start_dir(heading);
+            gizmos.linestrip_2d(path.sample_points(16), Color::linear_rgb(1., 0.6, 0.6));
+
+            let turning_radius = selected_ship
+                .template
+                .turning_radius(selected_ship.template.max_speed);
+            for waypoint_idx in path.unfollowable_waypoints(turning_radius) {
+                gizmos.cross_2d(
+                    Isometry2d::from_translation(move_order.waypoints[waypoint_idx]),
+                    10. * zoom.0,
+                    Color::linear_rgb(1., 0.6, 0.),
+                );
+            }
         }
     }
 }
 
+/// While several ships are selected, shows which of them would focus fire
+/// on the enemy ship under the cursor
+fn update_focus_fire_preview_display(
+    mut gizmos: Gizmos,
+    ships_selected: Query<(&Transform, Option<&FireTarget>), With<Selected>>,
+    hovered_enemies: Query<(Entity, &Team, &Transform), (With<Ship>, With<Hovering>)>,
+    this_client: Res<ThisClient>,
+    settings: Res<PlayerSettings>,
+    zoom: Res<MapZoom>,
+) {
+    if ships_selected.iter().count() < 2 {
+        return;
+    }
+    let Some((enemy, _, enemy_trans)) = hovered_enemies
+        .iter()
+        .find(|(_, team, _)| !team.is_this_client(*this_client))
+    else {
+        return;
+    };
+
+    let enemy_pos = enemy_trans.translation.truncate();
+    let circle_size = zoom.0 * settings.ship_icon_scale * 0.5 * 1.4;
+    for (selected_trans, selected_fire_target) in &ships_selected {
+        let already_targeted = selected_fire_target.is_some_and(|targ| targ.ship == enemy);
+        let color = match already_targeted {
+            true => Color::linear_rgba(0.8, 0.3, 0.3, 0.6),
+            false => Color::linear_rgba(1., 1., 1., 0.3),
+        };
+        let selected_pos = selected_trans.translation.truncate();
+        let dir = (enemy_pos - selected_pos).normalize_or_zero();
+        gizmos.line_2d(
+            selected_pos + dir * circle_size,
+            enemy_pos - dir * circle_size,
+            color,
+        );
+    }
+}
+
+/// Shows where the selected ship's next main battery salvo will land,
+/// at its fire target's predicted position
+fn update_impact_prediction_display(
+    mut gizmos: Gizmos,
+    ships_selected: Query<(&Ship, &Team, &Transform, &FireTarget), With<Selected>>,
+    targets: Query<(&Transform, &Velocity, &DetectionStatus)>,
+    this_client: Res<ThisClient>,
+) {
+    for (ship, team, trans, fire_target) in &ships_selected {
+        if !team.is_this_client(*this_client) {
+            continue;
+        }
+        let Ok((targ_trans, targ_vel, targ_detection)) = targets.get(fire_target.ship) else {
+            continue;
+        };
+        if *targ_detection != DetectionStatus::Detected {
+            continue;
+        }
+
+        let ship_pos = trans.translation.truncate();
+        let mut impact_points = vec![];
+        let mut footprint = Vec2::ZERO;
+        for (turret_template, bp) in
+            ship.main_battery_shots(trans, targ_trans.translation.truncate(), targ_vel.0)
+        {
+            footprint = footprint.max(ballistics::dispersion_footprint(
+                &turret_template.dispersion,
+                bp.intersection_dist,
+                bp.projectile_elevation,
+                turret_template.muzzle_vel,
+                ballistics::GRAVITY,
+            ));
+            impact_points.push(bp.intersection_point);
+        }
+        if impact_points.is_empty() {
+            continue;
+        }
+
+        let center = impact_points.iter().sum::<Vec2>() / impact_points.len() as f32;
+        let line_of_fire = (center - ship_pos).to_angle();
+        let color = Color::linear_rgb(1., 0.8, 0.2);
+        gizmos.ellipse_2d(
+            // The footprint's x axis is across the line of fire
+            Isometry2d::new(center, Rot2::radians(line_of_fire + FRAC_PI_2)),
+            footprint,
+            color,
+        );
+        gizmos.cross_2d(
+            Isometry2d::from_translation(center),
+            0.3 * footprint.x,
+            color,
+        );
+    }
+}
+
 fn draw_background(
     mut gizmos: Gizmos,
     camera: Query<&Transform, With<MainCamera>>,
@@ -413,49 +746,142 @@ fn draw_background(
     );
 }
 
+/// The range `PlayerSettings::ui_scale` can be adjusted within
+const UI_SCALE_LIMITS: (f32, f32) = (0.5, 3.);
+
+fn apply_ui_scale(settings: Res<PlayerSettings>, mut ui_scale: ResMut<UiScale>) {
+    ui_scale.0 = settings
+        .ui_scale
+        .clamp(UI_SCALE_LIMITS.0, UI_SCALE_LIMITS.1);
+}
+
+const PLAYER_SETTINGS_PATH: &str = "player_settings/settings.json";
+
+/// Falls back to the default settings if the file is missing or can't be read
+fn read_settings_from_file() -> PlayerSettings {
+    let Ok(contents) = std::fs::read_to_string(PLAYER_SETTINGS_PATH) else {
+        return default();
+    };
+    serde_json::from_str(&contents).unwrap_or_else(|err| {
+        warn!("Couldn't read `{PLAYER_SETTINGS_PATH}`, using the default settings: {err}");
+        default()
+    })
+}
+
 fn write_settings_to_file(settings: Res<PlayerSettings>) {
     std::fs::create_dir_all("player_settings").unwrap();
     std::fs::write(
-        "player_settings/settings.json",
+        PLAYER_SETTINGS_PATH,
         serde_json::to_string_pretty(&*settings).unwrap(),
     )
     .unwrap();
 }
 
+#[derive(Parser, Debug)]
+struct Args {
+    /// The directory to load modded ship templates from,
+    /// which must hold the same ships as the lobby's `--ship-mods-dir`
+    #[arg(long, default_value = DEFAULT_SHIP_MODS_DIR)]
+    ship_mods_dir: PathBuf,
+}
+
 pub fn run() {
+    let args = Args::parse();
+    let mod_errors = ShipTemplateRegistry::init(&args.ship_mods_dir);
+
     // Note: if system A depends on system B or if system A is run in a later schedule (i.e. `Update` after `PreUpdate`),
     // then the `Commands` buffer will be flushed between system A and B
     App::new()
         .add_plugins(DefaultPlugins)
+        .add_systems(Startup, move || {
+            for err in &mod_errors {
+                warn!("{err:#}");
+            }
+        })
         .add_plugins(bevy::diagnostic::FrameTimeDiagnosticsPlugin::default())
         //
         .add_plugins(InGameUIPlugin)
+        .add_plugins(DebugOverlayPlugin)
         .add_plugins(LobbyUiPlugin)
         .add_plugins(NetworkingPlugin)
         .add_plugins(InMatchPlugin)
         .add_plugins(ShipDisplayPlugin)
         .add_plugins(InputHandlingPlugin)
         //
-        .init_resource::<PlayerSettings>()
+        .insert_resource(read_settings_from_file())
         .init_resource::<CursorWorldPos>()
         .init_resource::<MapZoom>()
         //
         .insert_state(AppState::ConnectingToServer)
         //
-        .add_systems(Startup, write_settings_to_file)
+        .add_systems(
+            Update,
+            write_settings_to_file.run_if(resource_changed::<PlayerSettings>),
+        )
         .add_systems(Startup, make_camera)
+        .add_systems(
+            Update,
+            apply_ui_scale.run_if(resource_changed::<PlayerSettings>),
+        )
         .add_systems(
             Update,
             (
                 update_selected_ship_orders_display.after(InputHandlingSystem),
+                update_impact_prediction_display.after(InputHandlingSystem),
+                update_focus_fire_preview_display.after(InputHandlingSystem),
                 update_ship_ghosts,
                 update_ship_ghosts_display.after(update_ship_ghosts),
                 draw_background,
                 update_bullet_displays,
                 update_torpedo_displays,
                 update_smoke_puff_displays,
+                update_aircraft_displays,
             )
                 .run_if(in_state(AppState::InMatch)),
         )
         .run();
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::{
+        PlayerControls, PlayerSettings,
+        input_handling::{AxisInputs, ButtonControl, ButtonInputs},
+    };
+
+    #[test]
+    fn test_settings_missing_a_control_keep_its_default() {
+        let mut saved = serde_json::to_value(PlayerSettings::default()).unwrap();
+        let controls = saved["controls"].as_object_mut().unwrap();
+        controls["button_controls"]
+            .as_object_mut()
+            .unwrap()
+            .remove("StopShip")
+            .unwrap();
+        controls["button_controls"]["ClearSelectedShips"] =
+            serde_json::to_value(ButtonControl::new(KeyCode::KeyP)).unwrap();
+        controls["axis_controls"]
+            .as_object_mut()
+            .unwrap()
+            .remove("MoveCameraX")
+            .unwrap();
+
+        let settings: PlayerSettings = serde_json::from_value(saved).unwrap();
+
+        let defaults = PlayerControls::default();
+        assert_eq!(
+            settings.controls.button_controls[ButtonInputs::StopShip],
+            defaults.button_controls[ButtonInputs::StopShip]
+        );
+        assert_eq!(
+            settings.controls.axis_controls[AxisInputs::MoveCameraX],
+            defaults.axis_controls[AxisInputs::MoveCameraX]
+        );
+        assert_ne!(
+            settings.controls.button_controls[ButtonInputs::ClearSelectedShips],
+            defaults.button_controls[ButtonInputs::ClearSelectedShips]
+        );
+    }
+}
@@ -66,19 +66,31 @@ pub fn torpedo_problem(
     ship_vel: Vec2,
     muzzle_vel: f64,
 ) -> Option<TorpedoProblemRes> {
-    // Wolfram alpha black box
+    // Solves |p + v*t| = s*t, i.e. a*t^2 + b*t + c = 0
     let p = ship_start.as_dvec2() - projectile_start.as_dvec2();
     let v = ship_vel.as_dvec2();
     let s = muzzle_vel;
-    let disc = f64::powi(2. * p.x * v.x + 2. * p.y * v.y, 2)
-        - 4. * (p.x * p.x + p.y * p.y) * (-s * s + v.x * v.x + v.y * v.y);
-    if disc < 0. {
+    let a = v.length_squared() - s * s;
+    let b = 2. * p.dot(v);
+    let c = p.length_squared();
+    let t = if a.abs() < 1e-9 {
+        // The ship is exactly as fast as the projectile, so the equation is linear
+        -c / b
+    } else {
+        let disc = b * b - 4. * a * c;
+        if disc < 0. {
+            return None;
+        }
+        // When the ship is faster, both roots have the same sign and the first is the earlier.
+        // Otherwise only one is positive, which is the first unless the ship is exactly as fast
+        let first = (-b - disc.sqrt()) / (2. * a);
+        let other = (-b + disc.sqrt()) / (2. * a);
+        if first >= 0. { first } else { other }
+    };
+    // A negative time would mean launching the projectile in the past
+    if !t.is_finite() || t < 0. {
         return None;
     }
-    let t_num = -f64::sqrt(disc) - 2. * p.x * v.x - 2. * p.y * v.y;
-    let t_den = 2. * (-s * s + v.x * v.x + v.y * v.y);
-    let t = t_num / t_den;
-    // ...
 
     let intersection_point = ship_start + ship_vel * t as f32;
     let projectile_dir = (intersection_point - projectile_start).normalize();
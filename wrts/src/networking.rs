@@ -8,12 +8,26 @@ use bevy_simple_text_input::{
     TextInputValue,
 };
 use tokio::sync::mpsc;
-use wrts_messaging::{Client2Lobby, ClientId, Lobby2Client, Message, RecvFromStream, SendToStream};
+use wrts_match_shared::ship_template::ShipTemplateRegistry;
+use wrts_messaging::{
+    Client2Lobby, ClientId, Lobby2Client, Message, PROTOCOL_VERSION, RecvFromStream, SendToStream,
+    check_protocol_version, check_ship_templates,
+};
 use wtransport::{ClientConfig, Endpoint};
 
 #[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ThisClient(pub ClientId);
 
+/// Set when the connection drops mid-match, so that the next time
+/// a server is joined, it's asked to put this client back in that match
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RejoinableMatch {
+    /// The id this client had in the match
+    pub client: ClientId,
+    /// Proves to the lobby that this is the client which dropped out
+    pub rejoin_token: u64,
+}
+
 /// Note that all `ClientInfo`s are cleared when leaving [AppState::LobbyMenu] or [AppState::InMatch]
 #[derive(Component, Debug, Clone)]
 pub struct ClientInfo {
@@ -29,6 +43,7 @@ pub enum RecvNextErr {
 #[derive(Resource, Debug)]
 pub struct ServerConnection {
     this_client: ClientId,
+    rejoin_token: u64,
     tx: mpsc::Sender<Message>,
     rx: mpsc::Receiver<Message>,
     disconnection: mpsc::Receiver<()>,
@@ -40,6 +55,11 @@ impl ServerConnection {
         self.this_client
     }
 
+    /// The token the lobby requires to rejoin a match this connection drops out of
+    pub fn rejoin_token(&self) -> u64 {
+        self.rejoin_token
+    }
+
     pub fn disconnected(&mut self) -> bool {
         if self.disconnection_triggered {
             return true;
@@ -106,7 +126,8 @@ impl Plugin for NetworkingPlugin {
             )
             .add_systems(
                 Update,
-                (update_join_server_button,).run_if(in_state(AppState::ConnectingToServer)),
+                (validate_ip_address_field, update_join_server_button)
+                    .run_if(in_state(AppState::ConnectingToServer)),
             );
     }
 }
@@ -118,10 +139,21 @@ fn clear_server_connection(mut commands: Commands) {
 #[derive(Component, Debug, Clone, Copy)]
 struct IPAddressField;
 
+const IP_ADDRESS_FIELD_BORDER_COLOR: Color = Color::linear_rgb(0.75, 0.52, 0.99);
+const IP_ADDRESS_FIELD_INVALID_BORDER_COLOR: Color = Color::linear_rgb(0.9, 0.2, 0.2);
+
 #[derive(Component, Debug, Clone, Copy)]
 struct JoinServerButton;
 
-fn setup_connecting_to_network_ui(mut commands: Commands) {
+/// Explains why the last attempt to join a server failed
+#[derive(Component, Debug, Clone, Copy)]
+struct JoinServerError;
+
+fn setup_connecting_to_network_ui(
+    mut commands: Commands,
+    settings: Res<PlayerSettings>,
+    rejoinable: Option<Res<RejoinableMatch>>,
+) {
     let text_color = Color::linear_rgb(0.2, 0.4, 0.4);
 
     commands.spawn((
@@ -145,9 +177,10 @@ fn setup_connecting_to_network_ui(mut commands: Commands) {
                     IPAddressField,
                     Node {
                         margin: UiRect::all(Val::Px(50.0)),
+                        border: UiRect::all(Val::Px(3.0)),
                         ..default()
                     },
-                    BorderColor(Color::linear_rgb(0.75, 0.52, 0.99)),
+                    BorderColor(IP_ADDRESS_FIELD_BORDER_COLOR),
                     BackgroundColor(Color::linear_rgb(0.15, 0.15, 0.15)),
                     TextInput,
                     TextInputTextFont(TextFont {
@@ -155,7 +188,15 @@ fn setup_connecting_to_network_ui(mut commands: Commands) {
                         ..default()
                     }),
                     TextInputTextColor(TextColor(Color::linear_rgb(0.9, 0.9, 0.9))),
-                    TextInputValue(format!("127.0.0.1:{}", wrts_messaging::DEFAULT_PORT)),
+                    TextInputValue(
+                        settings
+                            .last_server_address
+                            .clone()
+                            .unwrap_or_else(|| format!(
+                                "127.0.0.1:{}",
+                                wrts_messaging::DEFAULT_PORT
+                            ))
+                    ),
                     TextInputSettings {
                         retain_on_submit: true,
                         ..default()
@@ -169,7 +210,10 @@ fn setup_connecting_to_network_ui(mut commands: Commands) {
                     },
                     BorderColor(Color::linear_rgb(0.75, 0.52, 0.99)),
                     BackgroundColor(Color::linear_rgb(0.15, 0.15, 0.15)),
-                    Text::new("Join Server!"),
+                    Text::new(match rejoinable {
+                        Some(_) => "Rejoin Match!",
+                        None => "Join Server!",
+                    }),
                     TextFont {
                         font_size: 60.0,
                         ..default()
@@ -178,16 +222,42 @@ fn setup_connecting_to_network_ui(mut commands: Commands) {
                     ImageNode::solid_color(Color::WHITE),
                     Button,
                 ),
+                (
+                    JoinServerError,
+                    Text::new(""),
+                    TextFont {
+                        font_size: 30.0,
+                        ..default()
+                    },
+                    TextColor(Color::linear_rgb(0.9, 0.2, 0.2)),
+                ),
             ]
         ),],
     ));
 }
 
+/// Outlines the address field in red while it can't be parsed as an address
+fn validate_ip_address_field(
+    fields: Query<
+        (&TextInputValue, &mut BorderColor),
+        (With<IPAddressField>, Changed<TextInputValue>),
+    >,
+) {
+    for (value, mut border_color) in fields {
+        border_color.0 = match SocketAddr::from_str(&value.0) {
+            Ok(_) => IP_ADDRESS_FIELD_BORDER_COLOR,
+            Err(_) => IP_ADDRESS_FIELD_INVALID_BORDER_COLOR,
+        };
+    }
+}
+
 fn update_join_server_button(
     mut commands: Commands,
     button: Query<&Interaction, (With<JoinServerButton>, Changed<Interaction>)>,
     ip_address: Query<&TextInputValue, With<IPAddressField>>,
-    settings: Res<PlayerSettings>,
+    mut join_error: Query<&mut Text, With<JoinServerError>>,
+    mut settings: ResMut<PlayerSettings>,
+    rejoinable: Option<Res<RejoinableMatch>>,
     mut next_app_state: ResMut<NextState<AppState>>,
 ) {
     let Ok(&button) = button.single() else {
@@ -198,8 +268,13 @@ fn update_join_server_button(
     match button {
         Interaction::Pressed => {
             info!("JoinServerButton pressed, starting handshake");
-            let Ok(ip) = SocketAddr::from_str(ip_address.as_str()) else {
-                return;
+            let ip = match SocketAddr::from_str(ip_address.as_str()) {
+                Ok(ip) => ip,
+                Err(err) => {
+                    join_error.single_mut().unwrap().0 =
+                        format!("`{ip_address}` isn't a valid address: {err}");
+                    return;
+                }
             };
             let (to_bevy, mut rx) = mpsc::channel(4096);
             let (tx, from_bevy) = mpsc::channel(1024);
@@ -214,22 +289,58 @@ fn update_join_server_button(
 
             let Message::Lobby2Client(Lobby2Client::InitA {
                 client_id: this_client,
+                protocol_version,
+                rejoin_token,
+                ship_templates,
             }) = rx.blocking_recv().unwrap()
             else {
                 todo!()
             };
+            // A ship this client doesn't have couldn't be spawned once in a match
+            let handshake = check_protocol_version(protocol_version)
+                .and_then(|()| check_ship_templates(ship_templates));
+            if let Err(err) = handshake {
+                error!("{err}");
+                // Dropping the channels disconnects from the lobby
+                join_error.single_mut().unwrap().0 = err.to_string();
+                return;
+            }
 
-            commands.insert_resource(ThisClient(this_client));
-
-            info!("Client ID assigned: {this_client}");
+            if settings.last_server_address.as_ref() != Some(ip_address) {
+                settings.last_server_address = Some(ip_address.clone());
+            }
 
-            tx.blocking_send(Message::Client2Lobby(Client2Lobby::InitB {
-                username: settings.username.clone(),
-            }))
-            .unwrap();
+            let this_client = match rejoinable {
+                // Only one attempt is made, so if the lobby refuses,
+                // joining again goes back to the lobby as usual
+                Some(rejoinable) => {
+                    commands.remove_resource::<RejoinableMatch>();
+                    info!("Rejoining match as {}", rejoinable.client);
+                    tx.blocking_send(Message::Client2Lobby(Client2Lobby::RejoinMatch {
+                        client_id: rejoinable.client,
+                        rejoin_token: rejoinable.rejoin_token,
+                        protocol_version: PROTOCOL_VERSION,
+                        ship_templates: ShipTemplateRegistry::get().fingerprint(),
+                    }))
+                    .unwrap();
+                    rejoinable.client
+                }
+                None => {
+                    info!("Client ID assigned: {this_client}");
+                    tx.blocking_send(Message::Client2Lobby(Client2Lobby::InitB {
+                        username: settings.username.clone(),
+                        protocol_version: PROTOCOL_VERSION,
+                        ship_templates: ShipTemplateRegistry::get().fingerprint(),
+                    }))
+                    .unwrap();
+                    this_client
+                }
+            };
+            commands.insert_resource(ThisClient(this_client));
 
             commands.insert_resource(ServerConnection {
                 this_client,
+                rejoin_token,
                 tx,
                 rx,
                 disconnection: recv_network_failure,
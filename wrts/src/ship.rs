@@ -3,12 +3,19 @@ use std::{cell::Cell, time::Duration};
 use bevy::{prelude::*, window::PrimaryWindow};
 use itertools::{Itertools, iproduct};
 use ordered_float::OrderedFloat;
-use wrts_match_shared::ship_template::{ShipClass, ShipTemplate, TargetingMode};
+use wrts_match_shared::{
+    formulas::{
+        GunRangeCalc, GunRangeModifiers,
+        ballistics::{self, BulletProblemRes, ShellArc},
+        turn_turret_towards,
+    },
+    ship_template::{ShipClass, ShipTemplate, TargetingMode, TurretTemplate},
+};
 use wrts_messaging::ClientId;
 
 use crate::{
-    AppState, DetectionStatus, Health, MainCamera, MapZoom, PlayerSettings, Selected, Team,
-    networking::ThisClient,
+    AppState, DetectionStatus, Health, MainCamera, MapZoom, PlayerSettings, SectionHealth,
+    Selected, Team, in_match::match_running, networking::ThisClient,
 };
 
 const CONSUMABLE_CHARGING_COLOR: Color = Color::linear_rgb(0.6, 0.1, 0.1);
@@ -29,18 +36,27 @@ impl Plugin for ShipDisplayPlugin {
             Update,
             (
                 destroy_dead_ship_uis,
+                tick_torpedo_reloads
+                    .run_if(match_running)
+                    .before(update_torpedo_reload_display),
                 // UI element updaters
                 (
                     update_torpedo_reload_display,
                     update_smoke_consumable_display,
+                    update_torp_reload_booster_display,
+                    update_spotter_plane_display,
                 )
                     .after(destroy_dead_ship_uis)
                     .before(sort_ship_modifiers_display),
                 // ...
                 sort_ship_modifiers_display,
                 update_ship_ui_position,
+                turn_turrets_towards_networked_dirs
+                    .run_if(match_running)
+                    .before(update_ship_sprites),
                 update_ship_sprites,
                 update_detection_indicator_display,
+                update_guns_idle_indicators,
                 update_shaded_progress_bars.after(sort_ship_modifiers_display),
             )
                 .in_set(ShipDisplaySystem),
@@ -50,19 +66,124 @@ impl Plugin for ShipDisplayPlugin {
 
 #[derive(Debug)]
 pub struct TurretState {
-    /// Relative to ship-space
+    /// The direction displayed, relative to ship-space
     pub dir: f32,
+    /// The latest direction sent by the match, which `dir` turns towards
+    /// so that turrets don't jump between updates
+    pub networked_dir: f32,
 }
 
 #[derive(Component, Debug)]
-#[require(DetectionStatus, Health, Sprite, Transform, Team)]
+#[require(DetectionStatus, Health, SectionHealth, Sprite, Transform, Team)]
 pub struct Ship {
     pub template: &'static ShipTemplate,
+    /// The template's name, numbered when its team has more than one of that template
+    pub name: String,
     pub turret_states: Vec<TurretState>,
     pub reloaded_torp_volleys: usize,
     /// Remaining time until each reloading volley is reading,
     /// in ascending order
+    ///
+    /// Counted down locally by [Ship::tick_torpedo_reloads] between the match's updates,
+    /// so it's only an estimate until the next one arrives
     pub reloading_torp_volleys_remaining_time: Vec<Duration>,
+    /// How long a volley takes to reload from empty, which the match
+    /// may have scaled away from the template's reload
+    pub torp_volley_reload: Duration,
+    /// The trajectory the main battery is set to fire along,
+    /// kept in sync with the match through `Client2Match::SetShellArc`
+    pub shell_arc: ShellArc,
+    /// Whether the main battery has no valid target, only networked for this client's ships
+    pub guns_idle: bool,
+    /// Passed to `GunRangeCalc` for every turret on the ship, the same as the match does
+    pub gun_range_modifiers: GunRangeModifiers,
+}
+
+impl Ship {
+    /// How far `turret_template`'s guns reach on this ship right now
+    pub fn gun_range(&self, turret_template: &TurretTemplate) -> f32 {
+        GunRangeCalc {
+            base_range: turret_template.max_range,
+        }
+        .run(self.gun_range_modifiers)
+    }
+
+    /// How each main battery turret would fire on a target at `target_pos` moving at `target_vel`,
+    /// along the ship's [ShellArc] or its [ShellArc::with_fallbacks]. Turrets which the target is out of range or arc of are left out
+    pub fn main_battery_shots(
+        &self,
+        trans: &Transform,
+        target_pos: Vec2,
+        target_vel: Vec2,
+    ) -> Vec<(&'static TurretTemplate, BulletProblemRes)> {
+        let ship_pos = trans.translation.truncate();
+        let ship_dir = trans.rotation.to_euler(EulerRot::ZYX).0;
+        let mut shots = vec![];
+        for turret in &self.template.turret_instances {
+            let turret_template = turret.turret_template();
+            if turret_template.targeting_mode != TargetingMode::Primary {
+                continue;
+            }
+            let Some(bp) = self.shell_arc.with_fallbacks().iter().find_map(|&arc| {
+                ballistics::bullet_problem(
+                    turret.absolute_pos(ship_pos, trans.rotation),
+                    target_pos,
+                    target_vel,
+                    turret_template.muzzle_vel as f64,
+                    ballistics::GRAVITY as f64,
+                    arc,
+                )
+                .filter(|bp| {
+                    bp.intersection_dist < self.gun_range(turret_template)
+                        && turret_template.can_elevate_to(bp.projectile_elevation)
+                })
+            }) else {
+                continue;
+            };
+            let in_arc = turret
+                .firing_angle
+                .or(turret.movement_angle)
+                .is_none_or(|angle| {
+                    angle.contains(Vec2::from_angle(bp.projectile_azimuth - ship_dir))
+                });
+            if in_arc {
+                shots.push((turret_template, bp));
+            }
+        }
+        shots
+    }
+
+    /// Counts every reloading torpedo volley down by `delta`,
+    /// moving those which finish to the reloaded volleys
+    pub fn tick_torpedo_reloads(&mut self, delta: Duration) {
+        for remaining in &mut self.reloading_torp_volleys_remaining_time {
+            *remaining = remaining.saturating_sub(delta);
+        }
+        // Still in ascending order, so the finished volleys are all at the start
+        let finished = self
+            .reloading_torp_volleys_remaining_time
+            .iter()
+            .take_while(|remaining| remaining.is_zero())
+            .count();
+        self.reloading_torp_volleys_remaining_time.drain(..finished);
+        self.reloaded_torp_volleys += finished;
+    }
+
+    /// Replaces the local estimate of the torpedo reloads with the match's,
+    /// from `Match2Client::SetReloadedTorps`
+    ///
+    /// The match is authoritative either way, so a volley it says is ready is ready straight away,
+    /// even if the estimate still had it reloading, such as while a reload booster was running
+    pub fn reconcile_torpedo_reloads(
+        &mut self,
+        ready_to_fire: usize,
+        still_reloading: Vec<Duration>,
+        full_reload: Duration,
+    ) {
+        self.reloaded_torp_volleys = ready_to_fire;
+        self.reloading_torp_volleys_remaining_time = still_reloading;
+        self.torp_volley_reload = full_reload;
+    }
 }
 
 /// Attached to `ShipUI` and its children
@@ -106,10 +227,43 @@ pub enum SmokeConsumableActionState {
 #[require(Node)]
 struct SmokeConsumableDisplay;
 
+#[derive(Component, Debug, Clone, Copy)]
+pub struct TorpReloadBoosterState {
+    pub charges_unused: Option<u16>,
+    /// `None` once recharged
+    pub recharge_time_remaining: Option<Duration>,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+#[require(Node)]
+struct TorpReloadBoosterDisplay;
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct SpotterPlaneState {
+    pub charges_unused: Option<u16>,
+    pub action_state: SpotterPlaneActionState,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SpotterPlaneActionState {
+    Flying { time_remaining: Duration },
+    Recharging { time_remaining: Duration },
+    Recharged,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+#[require(Node)]
+struct SpotterPlaneDisplay;
+
 #[derive(Component, Debug, Clone, Copy)]
 #[require(Node, ImageNode)]
 pub struct DetectionIndicatorDisplay;
 
+/// Reminds the player to pick a target while one of their ships' main battery is idle
+#[derive(Component, Debug, Clone, Copy)]
+#[require(Node)]
+pub struct GunsIdleIndicator;
+
 fn make_shaded_progress_bar(
     mut commands: Commands,
     parent: Option<Entity>,
@@ -182,6 +336,13 @@ fn update_shaded_progress_bars(
     }
 }
 
+/// Keeps the torpedo reload bars moving between the match's `SetReloadedTorps` updates
+fn tick_torpedo_reloads(ships: Query<&mut Ship>, time: Res<Time>) {
+    for mut ship in ships {
+        ship.tick_torpedo_reloads(time.delta());
+    }
+}
+
 fn update_torpedo_reload_display(
     mut commands: Commands,
     ships: Query<(Entity, &Ship)>,
@@ -208,53 +369,53 @@ fn update_torpedo_reload_display(
         else {
             continue;
         };
+        // Ships without torpedoes never get a display, rather than an empty one
+        let Some(torpedoes) = &ship.template.torpedoes else {
+            continue;
+        };
         let Some(torpedo_reload_display) = disp_children.and_then(|disp_children| {
             disp_children
                 .iter()
                 .find(|e| torpedo_reload_displays.contains(*e))
         }) else {
-            if let Some(torps) = &ship.template.torpedoes {
-                let id = commands
-                    .spawn((ShipUITrackedShip(ship_entity), TorpedoReloadDisplay))
-                    .id();
-                let c = (0..torps.volleys)
-                    .map(|_| {
-                        let torp_status_disp = commands
-                            .spawn((
-                                ShipUITrackedShip(ship_entity),
-                                Node {
-                                    width: Val::Px(total_sprite_size.x),
-                                    height: Val::Px(total_sprite_size.y),
-                                    margin: UiRect::all(Val::Px(3.)),
-                                    ..default()
-                                },
-                                TorpedoReloadDisplayTorpedoStatus,
-                            ))
-                            .id();
-                        make_shaded_progress_bar(
-                            commands.reborrow(),
-                            Some(torp_status_disp),
+            let id = commands
+                .spawn((ShipUITrackedShip(ship_entity), TorpedoReloadDisplay))
+                .id();
+            let c = (0..torpedoes.volleys)
+                .map(|_| {
+                    let torp_status_disp = commands
+                        .spawn((
+                            ShipUITrackedShip(ship_entity),
                             Node {
-                                width: Val::Percent(100.),
-                                height: Val::Percent(100.),
+                                width: Val::Px(total_sprite_size.x),
+                                height: Val::Px(total_sprite_size.y),
+                                margin: UiRect::all(Val::Px(3.)),
                                 ..default()
                             },
-                            ImageNode::solid_color(CONSUMABLE_READY_COLOR),
-                            ImageNode::solid_color(bar_grey_color),
-                            ImageNode::solid_color(CONSUMABLE_CHARGING_COLOR),
-                        );
-
-                        torp_status_disp
-                    })
-                    .collect_vec();
-                commands.entity(disp_entity).add_child(id);
-                commands.entity(id).add_children(&c);
-            }
+                            TorpedoReloadDisplayTorpedoStatus,
+                        ))
+                        .id();
+                    make_shaded_progress_bar(
+                        commands.reborrow(),
+                        Some(torp_status_disp),
+                        Node {
+                            width: Val::Percent(100.),
+                            height: Val::Percent(100.),
+                            ..default()
+                        },
+                        ImageNode::solid_color(CONSUMABLE_READY_COLOR),
+                        ImageNode::solid_color(bar_grey_color),
+                        ImageNode::solid_color(CONSUMABLE_CHARGING_COLOR),
+                    );
+
+                    torp_status_disp
+                })
+                .collect_vec();
+            commands.entity(disp_entity).add_child(id);
+            commands.entity(id).add_children(&c);
             continue;
         };
 
-        let torpedoes = ship.template.torpedoes.as_ref().unwrap();
-
         let (_torpedo_reload_display, torpedo_reload_display_children) = torpedo_reload_displays
             .get_mut(torpedo_reload_display)
             .expect("unreachable");
@@ -282,7 +443,7 @@ fn update_torpedo_reload_display(
                     let cutoff_lerp = ship.reloading_torp_volleys_remaining_time
                         [i - ship.reloaded_torp_volleys]
                         .as_secs_f32()
-                        / torpedoes.reload.as_secs_f32();
+                        / ship.torp_volley_reload.as_secs_f32();
                     progress_bar.progress = cutoff_lerp;
                 }
             }
@@ -409,6 +570,202 @@ fn update_smoke_consumable_display(
     }
 }
 
+fn update_torp_reload_booster_display(
+    mut commands: Commands,
+    ships: Query<(Entity, &Ship, &TorpReloadBoosterState)>,
+    ship_modifiers_displays: Query<(
+        Entity,
+        &ShipUITrackedShip,
+        &ShipModifiersDisplay,
+        Option<&Children>,
+    )>,
+    booster_displays: Query<&Children, With<TorpReloadBoosterDisplay>>,
+    mut text_query: Query<&mut Text>,
+    mut progress_bars: Query<&mut ShadedProgressBar>,
+) {
+    let total_sprite_size = vec2(15., 20.);
+
+    for (ship_entity, ship, booster_state) in ships {
+        let Some((disp_entity, _, _, disp_children)) = ship_modifiers_displays
+            .iter()
+            .find(|(_, disp_tracked_ship, _, _)| disp_tracked_ship.0 == ship_entity)
+        else {
+            continue;
+        };
+        let Some(booster) = ship.template.consumables.torp_reload_booster() else {
+            continue;
+        };
+        let Some(booster_display) = disp_children
+            .and_then(|disp_children| disp_children.iter().find(|e| booster_displays.contains(*e)))
+        else {
+            let booster_icon_id = make_shaded_progress_bar(
+                commands.reborrow(),
+                None,
+                Node {
+                    width: Val::Px(total_sprite_size.x),
+                    height: Val::Px(total_sprite_size.y),
+                    margin: UiRect::all(Val::Px(3.)),
+                    ..default()
+                },
+                ImageNode::solid_color(CONSUMABLE_READY_COLOR),
+                ImageNode::solid_color(Color::linear_rgb(0., 0., 0.)),
+                ImageNode::solid_color(CONSUMABLE_CHARGING_COLOR),
+            );
+
+            let id = commands
+                .spawn((
+                    ShipUITrackedShip(ship_entity),
+                    TorpReloadBoosterDisplay,
+                    Node { ..default() },
+                    children![
+                        // Charge count
+                        (
+                            ShipUITrackedShip(ship_entity),
+                            Node {
+                                width: Val::Auto,
+                                height: Val::Px(total_sprite_size.y),
+                                margin: UiRect::all(Val::Px(3.)),
+                                ..default()
+                            },
+                            Text("".into())
+                        ),
+                        // Booster icon (added outside of this scope)
+                    ],
+                ))
+                .id();
+            commands.entity(disp_entity).add_child(id);
+            commands.entity(id).add_child(booster_icon_id);
+            continue;
+        };
+
+        let booster_display_children = booster_displays.get(booster_display).unwrap();
+        let mut charge_count_text = text_query.get_mut(booster_display_children[0]).unwrap();
+        let mut booster_icon = progress_bars.get_mut(booster_display_children[1]).unwrap();
+
+        charge_count_text.0 = booster_state
+            .charges_unused
+            .map_or("".into(), |n| format!("{}", n));
+        booster_icon.progress = match booster_state.recharge_time_remaining {
+            Some(time_remaining) => time_remaining.as_secs_f32() / booster.cooldown.as_secs_f32(),
+            None => 2.,
+        };
+    }
+}
+
+fn update_spotter_plane_display(
+    mut commands: Commands,
+    ships: Query<(Entity, &Ship, &SpotterPlaneState)>,
+    ship_modifiers_displays: Query<(
+        Entity,
+        &ShipUITrackedShip,
+        &ShipModifiersDisplay,
+        Option<&Children>,
+    )>,
+    plane_displays: Query<&Children, With<SpotterPlaneDisplay>>,
+    mut text_query: Query<&mut Text>,
+    mut progress_bars: Query<&mut ShadedProgressBar>,
+) {
+    let total_sprite_size = vec2(15., 20.);
+
+    for (ship_entity, ship, plane_state) in ships {
+        let Some((disp_entity, _, _, disp_children)) = ship_modifiers_displays
+            .iter()
+            .find(|(_, disp_tracked_ship, _, _)| disp_tracked_ship.0 == ship_entity)
+        else {
+            continue;
+        };
+        let Some(spotter_plane) = ship.template.consumables.spotter_plane() else {
+            continue;
+        };
+        let Some(plane_display) = disp_children
+            .and_then(|disp_children| disp_children.iter().find(|e| plane_displays.contains(*e)))
+        else {
+            let plane_icon_id = make_shaded_progress_bar(
+                commands.reborrow(),
+                None,
+                Node {
+                    width: Val::Px(total_sprite_size.x),
+                    height: Val::Px(total_sprite_size.y),
+                    margin: UiRect::all(Val::Px(3.)),
+                    ..default()
+                },
+                ImageNode::solid_color(CONSUMABLE_READY_COLOR),
+                ImageNode::solid_color(Color::linear_rgb(0., 0., 0.)),
+                ImageNode::solid_color(CONSUMABLE_CHARGING_COLOR),
+            );
+
+            let id = commands
+                .spawn((
+                    ShipUITrackedShip(ship_entity),
+                    SpotterPlaneDisplay,
+                    Node { ..default() },
+                    children![
+                        // Charge count
+                        (
+                            ShipUITrackedShip(ship_entity),
+                            Node {
+                                width: Val::Auto,
+                                height: Val::Px(total_sprite_size.y),
+                                margin: UiRect::all(Val::Px(3.)),
+                                ..default()
+                            },
+                            Text("".into())
+                        ),
+                        // Plane icon (added outside of this scope)
+                    ],
+                ))
+                .id();
+            commands.entity(disp_entity).add_child(id);
+            commands.entity(id).add_child(plane_icon_id);
+            continue;
+        };
+
+        let plane_display_children = plane_displays.get(plane_display).unwrap();
+        let mut charge_count_text = text_query.get_mut(plane_display_children[0]).unwrap();
+        let mut plane_icon = progress_bars.get_mut(plane_display_children[1]).unwrap();
+
+        charge_count_text.0 = plane_state
+            .charges_unused
+            .map_or("".into(), |n| format!("{}", n));
+
+        // Same colors as smoke, so that a consumable in use always looks the same
+        match plane_state.action_state {
+            SpotterPlaneActionState::Flying { time_remaining } => {
+                plane_icon.progress =
+                    time_remaining.as_secs_f32() / spotter_plane.action_time.as_secs_f32();
+                plane_icon.top_image = ImageNode::solid_color(Color::linear_rgb(0.3, 0.7, 0.7));
+                plane_icon.loaded_image = plane_icon.top_image.clone();
+                plane_icon.base_image = ImageNode::solid_color(Color::linear_rgb(0.3, 0.3, 0.3));
+            }
+            SpotterPlaneActionState::Recharging { time_remaining } => {
+                plane_icon.progress =
+                    time_remaining.as_secs_f32() / spotter_plane.cooldown.as_secs_f32();
+                plane_icon.top_image = ImageNode::solid_color(Color::linear_rgb(0., 0., 0.));
+                plane_icon.loaded_image = plane_icon.top_image.clone();
+                plane_icon.base_image = ImageNode::solid_color(CONSUMABLE_CHARGING_COLOR);
+            }
+            SpotterPlaneActionState::Recharged => {
+                plane_icon.progress = 2.;
+                plane_icon.loaded_image = ImageNode::solid_color(CONSUMABLE_READY_COLOR);
+            }
+        }
+    }
+}
+
+fn update_guns_idle_indicators(
+    ships: Query<&Ship>,
+    indicators: Query<(&ShipUITrackedShip, &mut Visibility), With<GunsIdleIndicator>>,
+) {
+    for (tracked_ship, mut visibility) in indicators {
+        let guns_idle = ships.get(tracked_ship.0).is_ok_and(|ship| ship.guns_idle);
+        visibility.set_if_neq(if guns_idle {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
 fn update_detection_indicator_display(
     ships: Query<(&Ship, &Team, &DetectionStatus)>,
     detection_indicator_displays: Query<(
@@ -447,27 +804,85 @@ fn update_detection_indicator_display(
     }
 }
 
+/// The vertical gap left between ship UIs nudged apart so they don't overlap
+const SHIP_UI_SPACING: f32 = 2.;
+/// The gap between the bottom of a ship's icon and the top of its UI, in logical pixels
+const SHIP_UI_ICON_GAP: f32 = 10.;
+
 fn update_ship_ui_position(
     camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-    ships: Query<&Transform>,
-    ship_uis: Query<(&ShipUI, &ShipUITrackedShip, &mut Node, &ComputedNode)>,
+    ships: Query<(&Transform, &Team)>,
+    mut ship_uis: Query<(Entity, &ShipUITrackedShip, &mut Node, &ComputedNode), With<ShipUI>>,
+    settings: Res<PlayerSettings>,
+    ui_scale: Res<UiScale>,
+    this_client: Res<ThisClient>,
 ) {
     let Ok((camera, camera_trans)) = camera.single() else {
         return;
     };
-    for (_disp, disp_tracked, mut disp_node, disp_computed_node) in ship_uis {
-        let Ok(ship_trans) = ships.get(disp_tracked.0) else {
+
+    // (ship ui, top left, size) of every visible ship ui, in the units of `Val::Px`,
+    // which are logical pixels divided by `UiScale`
+    let mut placements = vec![];
+    for (disp_entity, disp_tracked, mut disp_node, disp_computed_node) in &mut ship_uis {
+        let Ok((ship_trans, ship_team)) = ships.get(disp_tracked.0) else {
             continue;
         };
+        if settings.hide_enemy_ship_uis && !ship_team.is_this_client(*this_client) {
+            disp_node.display = Display::None;
+            continue;
+        }
+        // Recomputed every frame from the camera's current viewport,
+        // so UIs follow their ships through window resizes
         let Ok(pos) = camera.world_to_viewport(camera_trans, ship_trans.translation) else {
+            // Such as when the ship is behind the camera
+            disp_node.display = Display::None;
             continue;
         };
+        disp_node.display = Display::Flex;
+
+        // Computed sizes are in physical pixels, so they follow changes to the window's scale factor
+        let inverse_scale = disp_computed_node.inverse_scale_factor();
+        let content_size = disp_computed_node.content_size() * inverse_scale;
+        let size = disp_computed_node.size() * inverse_scale;
+        // The ship's icon is drawn in the world, so isn't scaled by `UiScale`
+        let pos = (pos + vec2(0., settings.ship_icon_scale / 2. + SHIP_UI_ICON_GAP)) / ui_scale.0;
+        placements.push((disp_entity, vec2(pos.x - content_size.x / 2., pos.y), size));
+    }
 
-        let content_size =
-            disp_computed_node.content_size() * camera.target_scaling_factor().unwrap_or(1.);
+    if settings.declutter_ship_uis {
+        declutter_ship_uis(&mut placements);
+    }
+
+    for (disp_entity, top_left, _size) in placements {
+        let (_, _, mut disp_node, _) = ship_uis.get_mut(disp_entity).unwrap();
+        disp_node.left = Val::Px(top_left.x);
+        disp_node.top = Val::Px(top_left.y);
+    }
+}
 
-        disp_node.left = Val::Px(pos.x - content_size.x / 2.);
-        disp_node.top = Val::Px(pos.y + 20.);
+/// Nudges overlapping ship UIs downwards, in order from the top of the screen,
+/// until none of them overlap.
+/// Only ever moves UIs down, so each one stays close to its ship
+fn declutter_ship_uis(placements: &mut [(Entity, Vec2, Vec2)]) {
+    placements.sort_by(|a, b| a.1.y.total_cmp(&b.1.y).then(a.1.x.total_cmp(&b.1.x)));
+    for idx in 1..placements.len() {
+        let (placed, rest) = placements.split_at_mut(idx);
+        let (_, top_left, size) = &mut rest[0];
+        // Every move is downwards, past an already placed UI,
+        // so this is bounded by the number of placed UIs
+        while let Some(bottom) = placed
+            .iter()
+            .filter(|(_, other_top_left, other_size)| {
+                let overlap = (*top_left + *size).min(*other_top_left + *other_size)
+                    - top_left.max(*other_top_left);
+                overlap.x > 0. && overlap.y > 0.
+            })
+            .map(|(_, other_top_left, other_size)| other_top_left.y + other_size.y)
+            .reduce(f32::max)
+        {
+            top_left.y = bottom + SHIP_UI_SPACING;
+        }
     }
 }
 
@@ -490,6 +905,8 @@ fn sort_ship_modifiers_display(
     ship_modifiers_displays: Query<(Entity, &ShipUITrackedShip, &ShipModifiersDisplay, &Children)>,
     torpedo_reload_displays: Query<(), With<TorpedoReloadDisplay>>,
     smoke_consumable_displays: Query<(), With<SmokeConsumableDisplay>>,
+    torp_reload_booster_displays: Query<(), With<TorpReloadBoosterDisplay>>,
+    spotter_plane_displays: Query<(), With<SpotterPlaneDisplay>>,
     this_client: Res<ThisClient>,
 ) {
     for (ship_entity, ship_team) in ships {
@@ -509,6 +926,10 @@ fn sort_ship_modifiers_display(
                     0
                 } else if smoke_consumable_displays.contains(entity) {
                     1
+                } else if torp_reload_booster_displays.contains(entity) {
+                    2
+                } else if spotter_plane_displays.contains(entity) {
+                    3
                 } else {
                     u32::MAX
                 }
@@ -520,6 +941,29 @@ fn sort_ship_modifiers_display(
     }
 }
 
+/// Turns each turret at its template's `turn_rate` towards the direction last sent by the match,
+/// the same way the match turns it
+fn turn_turrets_towards_networked_dirs(ships: Query<&mut Ship>, time: Res<Time>) {
+    for mut ship in ships {
+        let template = ship.template;
+        for (turret_instance, state) in template
+            .turret_instances
+            .iter()
+            .zip(&mut ship.turret_states)
+        {
+            let curr_dir = Vec2::from_angle(state.dir);
+            let targ_dir = Vec2::from_angle(state.networked_dir);
+            let max_turn = turret_instance.turret_template().turn_rate.radps() * time.delta_secs();
+            state.dir = if curr_dir.angle_to(targ_dir).abs() <= max_turn {
+                state.networked_dir
+            } else {
+                turn_turret_towards(curr_dir, targ_dir, turret_instance.movement_angle, max_turn)
+                    .to_angle()
+            };
+        }
+    }
+}
+
 fn update_ship_sprites(
     mut gizmos: Gizmos,
     ships: Query<(
@@ -589,7 +1033,9 @@ fn update_ship_sprites(
                 };
                 let pos =
                     turrets[turret_idx].absolute_pos(trans.translation.truncate(), trans.rotation);
-                let &TurretState { dir: dir_relative } = &ship.turret_states[turret_idx];
+                let &TurretState {
+                    dir: dir_relative, ..
+                } = &ship.turret_states[turret_idx];
                 let dir_absolute = trans.rotation.to_euler(EulerRot::ZXY).0 + dir_relative;
                 let delta = Vec2::from_angle(dir_absolute) * length;
                 gizmos.arrow_2d(pos, pos + delta, color);
@@ -641,28 +1087,35 @@ fn update_ship_sprites(
                         ShipClass::CruiserLight => "cruiser",
                         ShipClass::Destroyer => "destroyer",
                     };
-                    let icon = asset_server.load(format!("icons/{icon_name}.png"));
-                    let mut s = Sprite::from_image(icon);
-                    s.image_mode = SpriteImageMode::Scale(ScalingMode::FillCenter);
-                    s.custom_size = Some(sprite_size * 3.4);
-                    s.color = ship_color;
-                    s
+                    let icon: Handle<Image> = asset_server.load(format!("icons/{icon_name}.png"));
+                    // Fall back to a plain rectangle while the icon is loading,
+                    // or if it failed to load entirely
+                    if images.contains(&icon) {
+                        let mut s = Sprite::from_image(icon);
+                        s.image_mode = SpriteImageMode::Scale(ScalingMode::FillCenter);
+                        s.custom_size = Some(sprite_size * 3.4);
+                        s.color = ship_color;
+                        s
+                    } else {
+                        Sprite::from_color(ship_color, sprite_size)
+                    }
                 }
             };
         }
 
-        if is_visible {
+        if is_visible && settings.show_rings {
             // Gun range circle
-            if let Some(t) = ship
+            if let Some(gun_range) = ship
                 .template
                 .turret_templates
                 .values()
-                .max_by_key(|t| OrderedFloat(t.max_range))
+                .map(|t| ship.gun_range(t))
+                .max_by_key(|&range| OrderedFloat(range))
             {
                 gizmos
                     .circle_2d(
                         Isometry2d::from_translation(trans.translation.truncate()),
-                        t.max_range,
+                        gun_range,
                         settings
                             .team_colors(*team, *this_client)
                             .gun_range_ring_color,
@@ -681,3 +1134,120 @@ fn update_ship_sprites(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::prelude::*;
+    use wrts_match_shared::{
+        formulas::ballistics::ShellArc,
+        ship_template::{ShipClass, ShipTemplate, ShipTemplateId},
+    };
+    use wrts_messaging::ClientId;
+
+    use crate::{
+        Team,
+        networking::ThisClient,
+        ship::{
+            Ship, ShipModifiersDisplay, ShipUITrackedShip, SmokeConsumableActionState,
+            SmokeConsumableDisplay, SmokeConsumableState, TorpedoReloadDisplay,
+            TorpedoReloadDisplayTorpedoStatus, sort_ship_modifiers_display,
+            update_smoke_consumable_display, update_torp_reload_booster_display,
+            update_torpedo_reload_display,
+        },
+    };
+
+    #[test]
+    fn test_torpedo_reload_estimate_ticks_until_reconciled() {
+        let secs = Duration::from_secs_f32;
+        let mut ship = Ship {
+            template: ShipTemplateId::nagato().to_template(),
+            name: "Nagato".into(),
+            turret_states: vec![],
+            reloaded_torp_volleys: 1,
+            reloading_torp_volleys_remaining_time: vec![secs(1.), secs(3.)],
+            torp_volley_reload: secs(4.),
+            shell_arc: ShellArc::Low,
+            guns_idle: false,
+            gun_range_modifiers: default(),
+        };
+
+        ship.tick_torpedo_reloads(secs(1.5));
+        assert_eq!(ship.reloaded_torp_volleys, 2);
+        assert_eq!(ship.reloading_torp_volleys_remaining_time, vec![secs(1.5)]);
+
+        // The match says the last volley is ready while it's still reloading here
+        ship.reconcile_torpedo_reloads(3, vec![], secs(4.));
+        assert_eq!(ship.reloaded_torp_volleys, 3);
+        assert!(ship.reloading_torp_volleys_remaining_time.is_empty());
+        ship.tick_torpedo_reloads(secs(1.));
+        assert_eq!(ship.reloaded_torp_volleys, 3);
+    }
+
+    #[test]
+    fn test_ship_without_torpedoes_gets_no_torpedo_display() {
+        // Nagato has no torpedoes, so is given smoke to check the other displays still appear
+        let id = ShipTemplateId::nagato();
+        let mut template = ShipTemplate::from_ron(id, &id.to_template().to_ron()).unwrap();
+        template.consumables = ShipClass::Destroyer.default_consumables();
+        let template: &'static ShipTemplate = Box::leak(Box::new(template));
+        assert!(template.torpedoes.is_none());
+
+        let mut app = App::new();
+        app.insert_resource(ThisClient(ClientId(0))).add_systems(
+            Update,
+            (
+                (
+                    update_torpedo_reload_display,
+                    update_smoke_consumable_display,
+                    update_torp_reload_booster_display,
+                    update_spotter_plane_display,
+                ),
+                sort_ship_modifiers_display,
+            )
+                .chain(),
+        );
+        let ship = app
+            .world_mut()
+            .spawn((
+                Ship {
+                    template,
+                    name: "Nagato".into(),
+                    turret_states: vec![],
+                    reloaded_torp_volleys: 0,
+                    reloading_torp_volleys_remaining_time: vec![],
+                    torp_volley_reload: Duration::ZERO,
+                    shell_arc: ShellArc::Low,
+                    guns_idle: false,
+                    gun_range_modifiers: default(),
+                },
+                Team(ClientId(0)),
+                SmokeConsumableState {
+                    charges_unused: Some(3),
+                    action_state: SmokeConsumableActionState::Recharged,
+                },
+            ))
+            .id();
+        app.world_mut()
+            .spawn((ShipUITrackedShip(ship), ShipModifiersDisplay));
+        app.update();
+        app.update();
+
+        let world = app.world_mut();
+        let torpedo_displays = world
+            .query_filtered::<(), With<TorpedoReloadDisplay>>()
+            .iter(world)
+            .count();
+        let torpedo_statuses = world
+            .query_filtered::<(), With<TorpedoReloadDisplayTorpedoStatus>>()
+            .iter(world)
+            .count();
+        let smoke_displays = world
+            .query_filtered::<(), With<SmokeConsumableDisplay>>()
+            .iter(world)
+            .count();
+        assert_eq!((torpedo_displays, torpedo_statuses), (0, 0));
+        assert_eq!(smoke_displays, 1);
+    }
+}
@@ -0,0 +1,113 @@
+//! Frame rate, entity counts, network traffic and the match seed, shown while
+//! `PlayerSettings::show_debug_overlay` is set
+
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::{
+    AppState, Bullet, MapZoom, PlayerSettings, Torpedo,
+    in_match::{MatchSeed, ReceivedMessageCount},
+    ship::Ship,
+};
+
+/// How often the network message rate is sampled
+const MESSAGE_RATE_SAMPLE_SECS: f32 = 1.;
+
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InMatch), setup_debug_overlay)
+            .add_systems(
+                Update,
+                update_debug_overlay.run_if(in_state(AppState::InMatch)),
+            );
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+struct DebugOverlayText;
+
+#[derive(Debug)]
+struct MessageRate {
+    sample_timer: Timer,
+    count_at_last_sample: u64,
+    per_sec: f32,
+}
+
+impl Default for MessageRate {
+    fn default() -> Self {
+        Self {
+            sample_timer: Timer::from_seconds(MESSAGE_RATE_SAMPLE_SECS, TimerMode::Repeating),
+            count_at_last_sample: 0,
+            per_sec: 0.,
+        }
+    }
+}
+
+fn setup_debug_overlay(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        DebugOverlayText,
+        Text::default(),
+        TextFont {
+            font_size: 16.,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            right: Val::Px(10.),
+            padding: UiRect::all(Val::Px(4.)),
+            ..default()
+        },
+        Visibility::Hidden,
+    ));
+}
+
+fn update_debug_overlay(
+    overlays: Query<(&mut Text, &mut Visibility), With<DebugOverlayText>>,
+    ships: Query<(), With<Ship>>,
+    bullets: Query<(), With<Bullet>>,
+    torpedoes: Query<(), With<Torpedo>>,
+    settings: Res<PlayerSettings>,
+    diagnostics: Res<DiagnosticsStore>,
+    received: Res<ReceivedMessageCount>,
+    seed: Option<Res<MatchSeed>>,
+    zoom: Res<MapZoom>,
+    time: Res<Time>,
+    mut message_rate: Local<MessageRate>,
+) {
+    // Sampled even while hidden, so the rate is accurate as soon as it's shown
+    if message_rate.sample_timer.tick(time.delta()).just_finished() {
+        let elapsed = message_rate.sample_timer.duration().as_secs_f32();
+        message_rate.per_sec = (received.0 - message_rate.count_at_last_sample) as f32 / elapsed;
+        message_rate.count_at_last_sample = received.0;
+    }
+
+    for (mut text, mut visibility) in overlays {
+        if !settings.show_debug_overlay {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+
+        let fps = diagnostics
+            .get(&FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps| fps.smoothed())
+            .map_or("-".to_string(), |fps| format!("{fps:.0}"));
+        let seed = seed.map_or("-".to_string(), |seed| seed.0.to_string());
+        text.0 = format!(
+            "FPS: {fps}\nShips: {}\nBullets: {}\nTorpedoes: {}\nMessages/s: {:.0}\nZoom: {:.2}\nSeed: {seed}",
+            ships.iter().count(),
+            bullets.iter().count(),
+            torpedoes.iter().count(),
+            message_rate.per_sec,
+            zoom.0,
+        );
+    }
+}
@@ -1,13 +1,71 @@
-use bevy::prelude::*;
+use std::{
+    collections::HashMap,
+    f32::consts::{FRAC_PI_2, PI},
+};
 
-use crate::AppState;
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+use itertools::Itertools;
+use wrts_match_shared::ship_template::HullSection;
+use wrts_messaging::{Client2Match, Message};
+
+use crate::{
+    AppState, DetectionStatus, FireTarget, HeadingOrder, MainCamera, MoveOrder, PlayerSettings,
+    SectionHealth, Selected, SmokePuff, Team, Torpedo,
+    in_match::{MatchPaused, MatchTimeRemaining, ShipSunk},
+    networking::{ClientInfo, ServerConnection, ThisClient},
+    ship::Ship,
+};
+
+/// How long each entry stays in the detection log
+const DETECTION_LOG_ENTRY_SECS: f32 = 8.;
+/// The oldest entries are removed early to keep the log at most this long
+const DETECTION_LOG_MAX_ENTRIES: usize = 6;
+/// In logical pixels, so the compass grows with `PlayerSettings::ui_scale`
+const COMPASS_RADIUS: f32 = 55.;
+/// How long each entry stays in the kill feed, including fading out
+const KILL_FEED_ENTRY_SECS: f32 = 10.;
+/// Entries fade out over the end of their time in the kill feed
+const KILL_FEED_FADE_SECS: f32 = 2.;
+const KILL_FEED_MAX_ENTRIES: usize = 5;
+/// The width and height of the minimap, in logical pixels
+const MINIMAP_SIZE: f32 = 200.;
 
 pub struct InGameUIPlugin;
 
 impl Plugin for InGameUIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_sub_state::<InGameUIState>();
-        // .add_systems(OnEnter(AppState::InGame), ());
+        app.add_sub_state::<InGameUIState>()
+            .add_systems(
+                OnEnter(AppState::InMatch),
+                (
+                    setup_detection_ui,
+                    setup_selected_ship_info,
+                    setup_surrender_ui,
+                    setup_paused_overlay,
+                    setup_match_clock,
+                    setup_minimap,
+                    setup_kill_feed,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_selected_ship_info,
+                    update_heading_compass,
+                    update_being_detected_indicator,
+                    log_detection_changes,
+                    expire_detection_log_entries,
+                    update_surrender_ui,
+                    update_paused_overlay,
+                    update_match_clock,
+                    update_minimap_markers,
+                    update_minimap_viewport,
+                    recenter_camera_from_minimap,
+                    add_kill_feed_entries,
+                    fade_kill_feed_entries,
+                )
+                    .run_if(in_state(AppState::InMatch)),
+            );
     }
 }
 
@@ -18,3 +76,883 @@ pub enum InGameUIState {
     #[default]
     BasicUI,
 }
+
+/// Shown while any of this client's ships are detected by the enemy
+#[derive(Component, Debug, Clone, Copy)]
+struct BeingDetectedIndicator;
+
+/// Lists recent detection changes, newest at the bottom
+#[derive(Component, Debug, Clone, Copy)]
+struct DetectionLog;
+
+#[derive(Component, Debug, Clone)]
+struct DetectionLogEntry {
+    expiry_timer: Timer,
+}
+
+/// Stats of the single selected ship, hidden otherwise
+#[derive(Component, Debug, Clone, Copy)]
+struct SelectedShipInfo;
+
+/// Shown across the screen while the match is paused
+#[derive(Component, Debug, Clone, Copy)]
+struct PausedOverlay;
+
+/// Opens the [SurrenderDialog]
+#[derive(Component, Debug, Clone, Copy)]
+struct SurrenderButton;
+
+/// Asks for confirmation before surrendering, hidden until [SurrenderButton] is pressed
+#[derive(Component, Debug, Clone, Copy)]
+struct SurrenderDialog;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum SurrenderDialogButton {
+    Confirm,
+    Cancel,
+}
+
+fn setup_surrender_ui(mut commands: Commands) {
+    let button_color = BackgroundColor(Color::linear_rgba(0.4, 0.4, 0.6, 0.8));
+    let button_node = Node {
+        padding: UiRect::all(Val::Px(6.)),
+        ..default()
+    };
+    let button_font = TextFont {
+        font_size: 18.,
+        ..default()
+    };
+
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        SurrenderButton,
+        Button,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            left: Val::Px(10.),
+            ..button_node.clone()
+        },
+        button_color,
+        BorderRadius::all(Val::Px(5.)),
+        children![(Text::new("Surrender"), button_font.clone())],
+    ));
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        SurrenderDialog,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Visibility::Hidden,
+        children![(
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(10.),
+                padding: UiRect::all(Val::Px(12.)),
+                ..default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.8)),
+            BorderRadius::all(Val::Px(5.)),
+            children![
+                (
+                    Text::new("Surrender this match?"),
+                    TextFont {
+                        font_size: 24.,
+                        ..default()
+                    },
+                ),
+                (
+                    Node {
+                        column_gap: Val::Px(10.),
+                        ..default()
+                    },
+                    children![
+                        (
+                            SurrenderDialogButton::Confirm,
+                            Button,
+                            button_node.clone(),
+                            button_color,
+                            BorderRadius::all(Val::Px(5.)),
+                            children![(Text::new("Surrender"), button_font.clone())],
+                        ),
+                        (
+                            SurrenderDialogButton::Cancel,
+                            Button,
+                            button_node,
+                            button_color,
+                            BorderRadius::all(Val::Px(5.)),
+                            children![(Text::new("Keep fighting"), button_font)],
+                        ),
+                    ]
+                ),
+            ]
+        )],
+    ));
+}
+
+/// The match ends as soon as it receives the surrender,
+/// and `Match2Client::MatchEnded` returns this client to the lobby
+fn update_surrender_ui(
+    surrender_buttons: Query<&Interaction, (With<SurrenderButton>, Changed<Interaction>)>,
+    dialog_buttons: Query<(&Interaction, &SurrenderDialogButton), Changed<Interaction>>,
+    dialogs: Query<&mut Visibility, With<SurrenderDialog>>,
+    mut server: ResMut<ServerConnection>,
+) {
+    let mut show_dialog = None;
+    if surrender_buttons
+        .iter()
+        .any(|&interaction| interaction == Interaction::Pressed)
+    {
+        show_dialog = Some(true);
+    }
+    for (&interaction, &button) in dialog_buttons {
+        if interaction != Interaction::Pressed {
+            continue;
+        }
+        if button == SurrenderDialogButton::Confirm {
+            let _ = server.send(Message::Client2Match(Client2Match::Surrender));
+        }
+        show_dialog = Some(false);
+    }
+
+    let Some(show_dialog) = show_dialog else {
+        return;
+    };
+    for mut visibility in dialogs {
+        *visibility = match show_dialog {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+    }
+}
+
+fn setup_paused_overlay(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        PausedOverlay,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.3)),
+        Visibility::Hidden,
+        children![(
+            Text::new("Paused"),
+            TextFont {
+                font_size: 48.,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        )],
+    ));
+}
+
+fn update_paused_overlay(
+    paused: Res<MatchPaused>,
+    overlays: Query<&mut Visibility, With<PausedOverlay>>,
+) {
+    if !paused.is_changed() {
+        return;
+    }
+    for mut visibility in overlays {
+        *visibility = match paused.0 {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+    }
+}
+
+/// The time left in the match, at the top of the screen
+#[derive(Component, Debug, Clone, Copy)]
+struct MatchClock;
+
+fn setup_match_clock(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            top: Val::Px(10.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        children![(
+            MatchClock,
+            Text::new(""),
+            TextFont {
+                font_size: 24.,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        )],
+    ));
+}
+
+fn update_match_clock(
+    time_remaining: Res<MatchTimeRemaining>,
+    clocks: Query<&mut Text, With<MatchClock>>,
+) {
+    if !time_remaining.is_changed() {
+        return;
+    }
+    let text = match time_remaining.0 {
+        Some(remaining) => {
+            let secs = remaining.as_secs();
+            format!("{}:{:02}", secs / 60, secs % 60)
+        }
+        None => String::new(),
+    };
+    for mut clock in clocks {
+        clock.0.clone_from(&text);
+    }
+}
+
+/// Shows the single selected ship's heading, ordered heading
+/// and the bearing to its fire target, hidden otherwise
+#[derive(Component, Debug, Clone, Copy)]
+struct HeadingCompass;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum CompassMarker {
+    Heading,
+    OrderedHeading,
+    FireTargetBearing,
+}
+
+impl CompassMarker {
+    fn size(self) -> f32 {
+        match self {
+            CompassMarker::Heading => 12.,
+            CompassMarker::OrderedHeading => 8.,
+            CompassMarker::FireTargetBearing => 10.,
+        }
+    }
+
+    /// How far out from the compass's center the marker is drawn,
+    /// as a fraction of its radius
+    fn radius_frac(self) -> f32 {
+        match self {
+            CompassMarker::Heading | CompassMarker::OrderedHeading => 0.7,
+            CompassMarker::FireTargetBearing => 0.45,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            CompassMarker::Heading => Color::WHITE,
+            CompassMarker::OrderedHeading => Color::linear_rgb(0.9, 0.8, 0.2),
+            CompassMarker::FireTargetBearing => Color::linear_rgb(0.9, 0.3, 0.3),
+        }
+    }
+}
+
+/// Degrees clockwise from north (+y), for an angle as from `Vec2::to_angle`
+fn compass_bearing(angle: f32) -> f32 {
+    (90. - angle.to_degrees()).rem_euclid(360.)
+}
+
+/// Where to place something of `size` centered `radius` out from the compass's center
+/// in the direction of `angle`, relative to the compass's top left corner
+fn compass_offset(angle: f32, radius: f32, size: f32) -> (Val, Val) {
+    let offset = Vec2::from_angle(angle) * radius;
+    (
+        Val::Px(COMPASS_RADIUS + offset.x - size / 2.),
+        // UI space is y-down
+        Val::Px(COMPASS_RADIUS - offset.y - size / 2.),
+    )
+}
+
+fn compass_cardinal(label: &'static str, angle: f32) -> impl Bundle {
+    const SIZE: f32 = 16.;
+    let (left, top) = compass_offset(angle, COMPASS_RADIUS - 10., SIZE);
+    (
+        Node {
+            position_type: PositionType::Absolute,
+            left,
+            top,
+            width: Val::Px(SIZE),
+            height: Val::Px(SIZE),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size: 14.,
+                ..default()
+            },
+            TextColor(Color::linear_rgb(0.8, 0.8, 0.8)),
+        )],
+    )
+}
+
+fn compass_marker(marker: CompassMarker) -> impl Bundle {
+    (
+        marker,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Px(marker.size()),
+            height: Val::Px(marker.size()),
+            ..default()
+        },
+        BackgroundColor(marker.color()),
+        BorderRadius::MAX,
+        Visibility::Hidden,
+    )
+}
+
+fn setup_selected_ship_info(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.),
+            bottom: Val::Px(10.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::FlexEnd,
+            row_gap: Val::Px(6.),
+            ..default()
+        },
+        children![
+            (
+                HeadingCompass,
+                Node {
+                    width: Val::Px(COMPASS_RADIUS * 2.),
+                    height: Val::Px(COMPASS_RADIUS * 2.),
+                    ..default()
+                },
+                BackgroundColor(Color::BLACK.with_alpha(0.5)),
+                BorderRadius::MAX,
+                Visibility::Hidden,
+                children![
+                    compass_cardinal("N", FRAC_PI_2),
+                    compass_cardinal("E", 0.),
+                    compass_cardinal("S", -FRAC_PI_2),
+                    compass_cardinal("W", PI),
+                    compass_marker(CompassMarker::FireTargetBearing),
+                    compass_marker(CompassMarker::Heading),
+                    compass_marker(CompassMarker::OrderedHeading),
+                ],
+            ),
+            (
+                SelectedShipInfo,
+                Text::default(),
+                TextFont {
+                    font_size: 18.,
+                    ..default()
+                },
+                BackgroundColor(Color::linear_rgba(0.4, 0.4, 0.6, 0.6)),
+                BorderRadius::all(Val::Px(5.)),
+                Node {
+                    padding: UiRect::all(Val::Px(6.)),
+                    ..default()
+                },
+                Visibility::Hidden,
+            ),
+        ],
+    ));
+}
+
+fn update_heading_compass(
+    selected: Query<
+        (
+            &Transform,
+            Option<&HeadingOrder>,
+            Option<&MoveOrder>,
+            Option<&FireTarget>,
+        ),
+        (With<Ship>, With<Selected>),
+    >,
+    targets: Query<&Transform, With<Ship>>,
+    compasses: Query<&mut Visibility, (With<HeadingCompass>, Without<CompassMarker>)>,
+    markers: Query<(&CompassMarker, &mut Node, &mut Visibility), Without<HeadingCompass>>,
+) {
+    let selected = selected.single().ok();
+    for mut visibility in compasses {
+        *visibility = match selected {
+            Some(_) => Visibility::Inherited,
+            None => Visibility::Hidden,
+        };
+    }
+    let Some((trans, heading_order, move_order, fire_target)) = selected else {
+        return;
+    };
+
+    let pos = trans.translation.truncate();
+    let angle_towards = |towards: Vec2| Dir2::new(towards - pos).ok().map(Dir2::to_angle);
+    let heading = trans.rotation.to_euler(EulerRot::ZYX).0;
+    // A heading order replaces any waypoints, otherwise the ship is steering for its next waypoint
+    let ordered_heading = heading_order.map(|order| order.heading).or_else(|| {
+        move_order
+            .and_then(|order| order.waypoints.first())
+            .and_then(|&waypoint| angle_towards(waypoint))
+    });
+    let fire_target_bearing = fire_target
+        .and_then(|targ| targets.get(targ.ship).ok())
+        .and_then(|targ_trans| angle_towards(targ_trans.translation.truncate()));
+
+    for (&marker, mut node, mut visibility) in markers {
+        let angle = match marker {
+            CompassMarker::Heading => Some(heading),
+            CompassMarker::OrderedHeading => ordered_heading,
+            CompassMarker::FireTargetBearing => fire_target_bearing,
+        };
+        let Some(angle) = angle else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Inherited;
+        let (left, top) =
+            compass_offset(angle, COMPASS_RADIUS * marker.radius_frac(), marker.size());
+        node.left = left;
+        node.top = top;
+    }
+}
+
+fn update_selected_ship_info(
+    selected: Query<(&Ship, &Transform, &SectionHealth), With<Selected>>,
+    infos: Query<(&mut Text, &mut Visibility), With<SelectedShipInfo>>,
+) {
+    let selected = selected.single().ok();
+    for (mut text, mut visibility) in infos {
+        let Some((ship, trans, section_health)) = selected else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Inherited;
+
+        let mut info = format!(
+            "{}\nHeading: {:03.0}°",
+            ship.name,
+            compass_bearing(trans.rotation.to_euler(EulerRot::ZYX).0),
+        );
+        if let Some(torps) = &ship.template.torpedoes {
+            info += &format!(
+                "\nTorpedo range: {:.1} km\nTorpedo speed: {:.0} kts",
+                torps.range / 1_000.,
+                torps.speed.kts(),
+            );
+        }
+        let damaged = HullSection::ALL
+            .into_iter()
+            .zip(section_health.0)
+            .filter(|&(_, frac)| frac < 1.)
+            .map(|(section, frac)| format!("{} {:.0}%", section.name(), frac * 100.))
+            .join(", ");
+        if !damaged.is_empty() {
+            info += &format!("\nDamaged: {damaged}");
+        }
+        if text.0 != info {
+            text.0 = info;
+        }
+    }
+}
+
+fn setup_detection_ui(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        children![(
+            BeingDetectedIndicator,
+            Text::new("Detected!"),
+            TextFont {
+                font_size: 30.,
+                ..default()
+            },
+            TextColor(Color::srgb_u8(240, 208, 41)),
+            Visibility::Hidden,
+        )],
+    ));
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        DetectionLog,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.),
+            bottom: Val::Px(10.),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+    ));
+}
+
+fn update_being_detected_indicator(
+    ships: Query<(&Team, &DetectionStatus), With<Ship>>,
+    indicators: Query<&mut Visibility, With<BeingDetectedIndicator>>,
+    this_client: Res<ThisClient>,
+) {
+    let is_detected = ships.iter().any(|(team, detection)| {
+        team.is_this_client(*this_client) && *detection == DetectionStatus::Detected
+    });
+    for mut visibility in indicators {
+        *visibility = if is_detected {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Both sides' detection comes from `Match2Client::SetDetection`,
+/// which the match sends to every client including the ship's owner
+fn log_detection_changes(
+    mut commands: Commands,
+    changed_ships: Query<(&Ship, &Team, &DetectionStatus), Changed<DetectionStatus>>,
+    logs: Query<(Entity, Option<&Children>), With<DetectionLog>>,
+    this_client: Res<ThisClient>,
+) {
+    let Ok((log, entries)) = logs.single() else {
+        return;
+    };
+    let mut entry_count = entries.map_or(0, |entries| entries.len());
+    let mut oldest_entries = entries.into_iter().flat_map(|entries| entries.iter());
+
+    for (ship, team, detection) in changed_ships {
+        let name = &ship.name;
+        let (text, color) = match (team.is_this_client(*this_client), detection) {
+            (_, DetectionStatus::Never) => continue,
+            (true, DetectionStatus::Detected) => (
+                format!("Your {name} was detected!"),
+                Color::srgb_u8(240, 208, 41),
+            ),
+            (true, DetectionStatus::UnDetected) => (
+                format!("Your {name} is no longer detected"),
+                Color::linear_rgb(0.8, 0.8, 0.8),
+            ),
+            (false, DetectionStatus::Detected) => (
+                format!("Enemy {name} spotted"),
+                Color::linear_rgb(0.9, 0.3, 0.3),
+            ),
+            (false, DetectionStatus::UnDetected) => (
+                format!("Lost sight of enemy {name}"),
+                Color::linear_rgb(0.8, 0.8, 0.8),
+            ),
+        };
+        info!("{text}");
+
+        if entry_count < DETECTION_LOG_MAX_ENTRIES {
+            entry_count += 1;
+        } else if let Some(oldest) = oldest_entries.next() {
+            commands.entity(oldest).try_despawn();
+        }
+        commands.entity(log).with_child((
+            DetectionLogEntry {
+                expiry_timer: Timer::from_seconds(DETECTION_LOG_ENTRY_SECS, TimerMode::Once),
+            },
+            Text::new(text),
+            TextFont {
+                font_size: 18.,
+                ..default()
+            },
+            TextColor(color),
+        ));
+    }
+}
+
+fn expire_detection_log_entries(
+    mut commands: Commands,
+    entries: Query<(Entity, &mut DetectionLogEntry)>,
+    time: Res<Time>,
+) {
+    for (entity, mut entry) in entries {
+        if entry.expiry_timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// An overview of the whole map in the top right corner,
+/// which recenters the camera where it's clicked.
+/// Clicks on it don't reach the world behind it, see `update_action_state`
+#[derive(Component, Debug, Clone, Copy)]
+pub(crate) struct Minimap;
+
+/// Outlines the part of the map the camera can see
+#[derive(Component, Debug, Clone, Copy)]
+struct MinimapViewport;
+
+/// A dot on the [Minimap] following a ship, torpedo or smoke puff
+#[derive(Component, Debug, Clone, Copy)]
+struct MinimapMarker {
+    tracking: Entity,
+}
+
+/// Where `pos` is on the minimap, relative to its top left corner
+fn minimap_offset(pos: Vec2) -> Vec2 {
+    let (lower, upper) = wrts_match_shared::map_bounds();
+    let frac = (pos - lower) / (upper - lower);
+    // UI space is y-down
+    vec2(frac.x, 1. - frac.y) * MINIMAP_SIZE
+}
+
+/// The world position under a point on the minimap,
+/// as a fraction of its size from its top left corner
+fn minimap_world_pos(normalized: Vec2) -> Vec2 {
+    let (lower, upper) = wrts_match_shared::map_bounds();
+    lower + vec2(normalized.x, 1. - normalized.y) * (upper - lower)
+}
+
+fn setup_minimap(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        Minimap,
+        Button,
+        RelativeCursorPosition::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            right: Val::Px(10.),
+            width: Val::Px(MINIMAP_SIZE),
+            height: Val::Px(MINIMAP_SIZE),
+            overflow: Overflow::clip(),
+            ..default()
+        },
+        BackgroundColor(Color::linear_rgba(0.02, 0.05, 0.12, 0.8)),
+        children![(
+            MinimapViewport,
+            Node {
+                position_type: PositionType::Absolute,
+                border: UiRect::all(Val::Px(1.)),
+                ..default()
+            },
+            BorderColor(Color::WHITE),
+            ZIndex(3),
+        )],
+    ));
+}
+
+/// Enemy ships and torpedoes only appear while detected, like their sprites
+fn update_minimap_markers(
+    mut commands: Commands,
+    minimaps: Query<Entity, With<Minimap>>,
+    ships_and_torps: Query<
+        (Entity, &Team, &DetectionStatus, &Transform, Has<Torpedo>),
+        Or<(With<Ship>, With<Torpedo>)>,
+    >,
+    smoke_puffs: Query<(Entity, &SmokePuff, &Transform)>,
+    markers: Query<(Entity, &MinimapMarker, &mut Node, &mut BackgroundColor)>,
+    this_client: Res<ThisClient>,
+    settings: Res<PlayerSettings>,
+) {
+    let Ok(minimap) = minimaps.single() else {
+        return;
+    };
+    let is_visible = |team: &Team, detection: &DetectionStatus| {
+        team.is_this_client(*this_client) || *detection == DetectionStatus::Detected
+    };
+    let team_color = |team: &Team| settings.team_colors(*team, *this_client).ship_color;
+
+    // Each tracked entity's position, marker size in logical pixels, color and draw order
+    let mut wanted = HashMap::new();
+    for (entity, team, detection, trans, is_torp) in ships_and_torps {
+        if !is_visible(team, detection) {
+            continue;
+        }
+        let (size, z_index) = match is_torp {
+            true => (2., 1),
+            false => (6., 2),
+        };
+        wanted.insert(entity, (trans.translation, size, team_color(team), z_index));
+    }
+    let (lower, upper) = wrts_match_shared::map_bounds();
+    for (entity, puff, trans) in smoke_puffs {
+        let size = (puff.radius * 2. / (upper.x - lower.x) * MINIMAP_SIZE).max(3.);
+        wanted.insert(
+            entity,
+            (trans.translation, size, Color::WHITE.with_alpha(0.4), 0),
+        );
+    }
+
+    let marker_node = |pos: Vec3, size: f32| {
+        let offset = minimap_offset(pos.truncate()) - size / 2.;
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(offset.x),
+            top: Val::Px(offset.y),
+            width: Val::Px(size),
+            height: Val::Px(size),
+            ..default()
+        }
+    };
+    for (marker, &MinimapMarker { tracking }, mut node, mut background) in markers {
+        let Some((pos, size, color, _)) = wanted.remove(&tracking) else {
+            commands.entity(marker).despawn();
+            continue;
+        };
+        let new_node = marker_node(pos, size);
+        if *node != new_node {
+            *node = new_node;
+        }
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+    for (tracking, (pos, size, color, z_index)) in wanted {
+        commands.entity(minimap).with_child((
+            MinimapMarker { tracking },
+            marker_node(pos, size),
+            BackgroundColor(color),
+            BorderRadius::MAX,
+            ZIndex(z_index),
+        ));
+    }
+}
+
+fn update_minimap_viewport(
+    camera: Query<(&Camera, &Projection, &Transform), With<MainCamera>>,
+    viewports: Query<&mut Node, With<MinimapViewport>>,
+) {
+    let Ok((camera, Projection::Orthographic(proj), trans)) = camera.single() else {
+        return;
+    };
+    let Some(viewport_size) = camera.logical_viewport_size() else {
+        return;
+    };
+    let half_size = viewport_size * proj.scale / 2.;
+    let center = trans.translation.truncate();
+    // The top left and bottom right corners, as UI space is y-down
+    let top_left = minimap_offset(center + vec2(-half_size.x, half_size.y));
+    let bottom_right = minimap_offset(center + vec2(half_size.x, -half_size.y));
+    for mut node in viewports {
+        node.left = Val::Px(top_left.x);
+        node.top = Val::Px(top_left.y);
+        node.width = Val::Px(bottom_right.x - top_left.x);
+        node.height = Val::Px(bottom_right.y - top_left.y);
+    }
+}
+
+/// Holding the mouse down over the minimap keeps recentering,
+/// so the view can be dragged around it
+fn recenter_camera_from_minimap(
+    minimaps: Query<(&Interaction, &RelativeCursorPosition), With<Minimap>>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let Ok(mut camera) = camera.single_mut() else {
+        return;
+    };
+    for (&interaction, cursor) in minimaps {
+        if interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(normalized) = cursor.normalized else {
+            continue;
+        };
+        let (lower, upper) = wrts_match_shared::map_bounds();
+        let center = minimap_world_pos(normalized).clamp(lower, upper);
+        camera.translation = center.extend(camera.translation.z);
+    }
+}
+
+/// Lists recently sunk ships under the minimap, newest at the bottom
+#[derive(Component, Debug, Clone, Copy)]
+struct KillFeed;
+
+#[derive(Component, Debug, Clone)]
+struct KillFeedEntry {
+    expiry_timer: Timer,
+    color: Color,
+}
+
+fn setup_kill_feed(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(AppState::InMatch),
+        KillFeed,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(MINIMAP_SIZE + 20.),
+            right: Val::Px(10.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::FlexEnd,
+            ..default()
+        },
+    ));
+}
+
+fn add_kill_feed_entries(
+    mut commands: Commands,
+    mut sunk: EventReader<ShipSunk>,
+    feeds: Query<(Entity, Option<&Children>), With<KillFeed>>,
+    clients: Query<&ClientInfo>,
+    this_client: Res<ThisClient>,
+) {
+    let Ok((feed, entries)) = feeds.single() else {
+        sunk.clear();
+        return;
+    };
+    let mut entry_count = entries.map_or(0, |entries| entries.len());
+    let mut oldest_entries = entries.into_iter().flat_map(|entries| entries.iter());
+
+    for ShipSunk {
+        victim,
+        victim_team,
+        killer_team,
+        killer,
+    } in sunk.read()
+    {
+        // The match only names the killing ship to clients which can see it,
+        // otherwise its owner is named instead
+        let text = match killer {
+            Some(killer) => format!("{killer} sank {victim}"),
+            None => match clients.iter().find(|cl| cl.id == killer_team.0) {
+                Some(cl) => format!("{} sank {victim}", cl.user),
+                None => format!("An undetected ship sank {victim}"),
+            },
+        };
+        let color = match victim_team.is_this_client(*this_client) {
+            true => Color::linear_rgb(0.9, 0.3, 0.3),
+            false => Color::WHITE,
+        };
+        info!("{text}");
+
+        if entry_count < KILL_FEED_MAX_ENTRIES {
+            entry_count += 1;
+        } else if let Some(oldest) = oldest_entries.next() {
+            commands.entity(oldest).try_despawn();
+        }
+        commands.entity(feed).with_child((
+            KillFeedEntry {
+                expiry_timer: Timer::from_seconds(KILL_FEED_ENTRY_SECS, TimerMode::Once),
+                color,
+            },
+            Text::new(text),
+            TextFont {
+                font_size: 18.,
+                ..default()
+            },
+            TextColor(color),
+        ));
+    }
+}
+
+fn fade_kill_feed_entries(
+    mut commands: Commands,
+    entries: Query<(Entity, &mut KillFeedEntry, &mut TextColor)>,
+    time: Res<Time>,
+) {
+    for (entity, mut entry, mut text_color) in entries {
+        if entry.expiry_timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let remaining = entry.expiry_timer.remaining_secs();
+        let alpha = (remaining / KILL_FEED_FADE_SECS).min(1.);
+        text_color.0 = entry.color.with_alpha(entry.color.alpha() * alpha);
+    }
+}
@@ -2,18 +2,31 @@ use std::collections::{HashMap, HashSet};
 
 use bevy::prelude::*;
 use itertools::Itertools;
-use wrts_messaging::{Client2Lobby, ClientId, Lobby2Client, Message};
+use strum::IntoEnumIterator;
+use wrts_messaging::{Client2Lobby, ClientId, Lobby2Client, MatchListing, Message, SharedMatchId};
 
 use crate::{
-    AppState,
-    networking::{ClientInfo, RecvNextErr, ServerConnection},
+    AppState, PlayerSettings,
+    in_match::{LastMatchResult, LastMatchScoreboard},
+    input_handling::{
+        AxisControl, AxisInputs, ButtonControl, ButtonInputs, KeybindKey, MODIFIER_KEYS,
+    },
+    networking::{ClientInfo, RecvNextErr, ServerConnection, ThisClient},
 };
 
 pub struct LobbyUiPlugin;
 
 impl Plugin for LobbyUiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(AppState::LobbyMenu), (setup_lobby_ui))
+        app.init_resource::<Rebinding>()
+            .add_systems(
+                OnEnter(AppState::LobbyMenu),
+                (
+                    setup_lobby_ui,
+                    setup_controls_menu,
+                    ready_for_match.pipe(lobby_networking_none_handler),
+                ),
+            )
             .add_systems(
                 FixedUpdate,
                 (lobby_networking.pipe(lobby_networking_none_handler))
@@ -21,8 +34,19 @@ impl Plugin for LobbyUiPlugin {
             )
             .add_systems(
                 Update,
-                (update_lobby_clients_list,).run_if(in_state(AppState::LobbyMenu)),
-            );
+                (
+                    update_lobby_clients_list,
+                    spectate_match_buttons,
+                    toggle_controls_menu,
+                    start_rebinding,
+                    capture_rebinding
+                        .after(start_rebinding)
+                        .after(toggle_controls_menu),
+                    update_controls_entries.after(capture_rebinding),
+                )
+                    .run_if(in_state(AppState::LobbyMenu)),
+            )
+            .add_systems(OnExit(AppState::LobbyMenu), cancel_rebinding);
     }
 }
 
@@ -34,7 +58,72 @@ struct LobbyClientsListEntry {
     tracking_client: ClientId,
 }
 
-pub fn setup_lobby_ui(mut commands: Commands) {
+/// Lists the running matches, rebuilt whenever the lobby sends a new [Lobby2Client::MatchList]
+#[derive(Component, Debug, Clone, Copy)]
+struct MatchList;
+
+#[derive(Component, Debug, Clone, Copy)]
+struct SpectateMatchButton {
+    match_id: SharedMatchId,
+}
+
+pub fn setup_lobby_ui(
+    mut commands: Commands,
+    last_match: Option<Res<LastMatchResult>>,
+    scoreboard: Option<Res<LastMatchScoreboard>>,
+    this_client: Option<Res<ThisClient>>,
+) {
+    if let Some(last_match) = last_match {
+        let is_this_client = |client| this_client.as_ref().is_some_and(|cl| cl.0 == client);
+        let result = match last_match.winner {
+            None => "Draw",
+            Some(winner) if is_this_client(winner) => "Victory",
+            Some(_) => "Defeat",
+        };
+        let mut last_match_info = commands.spawn((
+            StateScoped(AppState::LobbyMenu),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.),
+                left: Val::Px(10.),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.),
+                ..default()
+            },
+            children![(
+                Text::new(format!("Last match: {result}")),
+                TextFont {
+                    font_size: 30.,
+                    ..default()
+                },
+            )],
+        ));
+        // Highest damage first
+        let entries = scoreboard
+            .iter()
+            .flat_map(|scoreboard| &scoreboard.0)
+            .sorted_by(|a, b| b.damage.total_cmp(&a.damage));
+        for entry in entries {
+            let color = match is_this_client(entry.client) {
+                true => Color::srgb_u8(240, 208, 41),
+                false => Color::WHITE,
+            };
+            last_match_info.with_child((
+                Text::new(format!(
+                    "{}: {:.0} damage, {} {}",
+                    entry.user,
+                    entry.damage,
+                    entry.kills,
+                    if entry.kills == 1 { "kill" } else { "kills" },
+                )),
+                TextFont {
+                    font_size: 18.,
+                    ..default()
+                },
+                TextColor(color),
+            ));
+        }
+    }
     commands.spawn((
         StateScoped(AppState::LobbyMenu),
         Node {
@@ -54,25 +143,37 @@ pub fn setup_lobby_ui(mut commands: Commands) {
             BackgroundColor(Color::BLACK),
         )],
     ));
+    commands.spawn((
+        StateScoped(AppState::LobbyMenu),
+        MatchList,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            right: Val::Px(10.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::End,
+            ..default()
+        },
+    ));
+}
+
+/// Sent each time the lobby is entered, including when returning from a match
+fn ready_for_match(mut server: ResMut<ServerConnection>) -> Option<()> {
+    server.send(Message::Client2Lobby(Client2Lobby::SetReadyForMatch {
+        is_ready: true,
+    }))
 }
 
 fn lobby_networking(
     mut commands: Commands,
     clients: Query<(Entity, &ClientInfo)>,
+    match_lists: Query<Entity, With<MatchList>>,
     mut server: ResMut<ServerConnection>,
-    mut has_readied: Local<bool>,
     mut next_state: ResMut<NextState<AppState>>,
 ) -> Option<()> {
     let mut clients_by_id: HashMap<ClientId, Entity> =
         clients.into_iter().map(|(e, c)| (c.id, e)).collect();
 
-    if !*has_readied {
-        server.send(Message::Client2Lobby(Client2Lobby::SetReadyForMatch {
-            is_ready: true,
-        }))?;
-        *has_readied = true;
-    }
-
     loop {
         let msg = match server.recv_next() {
             Ok(x) => x,
@@ -80,6 +181,10 @@ fn lobby_networking(
             Err(RecvNextErr::Disconnected) => return None,
         };
 
+        if let Message::Match2Client(msg) = msg {
+            debug!("Ignoring message left over from the last match: {msg:?}");
+            continue;
+        }
         let Message::Lobby2Client(msg) = msg else {
             error!("Received non-lobby2client message: {msg:?}");
             return None;
@@ -112,6 +217,15 @@ fn lobby_networking(
                 next_state.set(AppState::InMatch);
                 return Some(());
             }
+            Lobby2Client::MatchList { matches } => {
+                for list in &match_lists {
+                    commands.entity(list).despawn_related::<Children>();
+                    for listing in &matches {
+                        let button = spawn_spectate_match_button(commands.reborrow(), listing);
+                        commands.entity(list).add_child(button);
+                    }
+                }
+            }
             Lobby2Client::InitA { .. } => {
                 error!("Unexpected message: {msg:?}");
                 return None;
@@ -120,6 +234,43 @@ fn lobby_networking(
     }
 }
 
+fn spawn_spectate_match_button(mut commands: Commands, listing: &MatchListing) -> Entity {
+    let players = listing.clients.iter().join(" vs ");
+    commands
+        .spawn((
+            SpectateMatchButton {
+                match_id: listing.id,
+            },
+            Node {
+                margin: UiRect::all(Val::Px(5.)),
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgb(0.15, 0.15, 0.15)),
+            Text::new(format!("Spectate: {players}")),
+            TextFont {
+                font_size: 20.,
+                ..default()
+            },
+            Button,
+        ))
+        .id()
+}
+
+fn spectate_match_buttons(
+    buttons: Query<(&Interaction, &SpectateMatchButton), Changed<Interaction>>,
+    mut server: ResMut<ServerConnection>,
+) {
+    for (&interaction, button) in &buttons {
+        if interaction != Interaction::Pressed {
+            continue;
+        }
+        info!("Spectating match {:?}", button.match_id);
+        server.send(Message::Client2Lobby(Client2Lobby::SpectateMatch {
+            match_id: button.match_id,
+        }));
+    }
+}
+
 fn lobby_networking_none_handler(
     In(input): In<Option<()>>,
     mut next_state: ResMut<NextState<AppState>>,
@@ -196,3 +347,332 @@ fn update_lobby_clients_list(
         }
     }
 }
+
+/// Opens and closes the [ControlsMenu]
+#[derive(Component, Debug, Clone, Copy)]
+struct ControlsMenuButton;
+
+/// Lists every control, hidden until [ControlsMenuButton] is pressed
+#[derive(Component, Debug, Clone, Copy)]
+struct ControlsMenu;
+
+/// Explains the result of the last rebinding, such as which controls it clashes with
+#[derive(Component, Debug, Clone, Copy)]
+struct ControlsMenuStatus;
+
+/// A control which can be rebound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RebindTarget {
+    Button(ButtonInputs),
+    /// The key which moves the axis towards 1
+    AxisHi(AxisInputs),
+    /// The key which moves the axis towards -1
+    AxisLo(AxisInputs),
+}
+
+impl RebindTarget {
+    fn label(self) -> String {
+        match self {
+            RebindTarget::Button(button) => format!("{button:?}"),
+            RebindTarget::AxisHi(axis) => format!("{axis:?} +"),
+            RebindTarget::AxisLo(axis) => format!("{axis:?} -"),
+        }
+    }
+
+    fn binding(self, settings: &PlayerSettings) -> String {
+        let axis_side = |axis: AxisInputs, hi: bool| match settings.controls.axis_controls[axis] {
+            AxisControl::Gamepad(gamepad_axis) => format!("Gamepad {gamepad_axis:?}"),
+            AxisControl::Virtual { hi: key, .. } if hi => key.to_string(),
+            AxisControl::Virtual { lo: key, .. } => key.to_string(),
+        };
+        match self {
+            RebindTarget::Button(button) => settings.controls.button_controls[button].to_string(),
+            RebindTarget::AxisHi(axis) => axis_side(axis, true),
+            RebindTarget::AxisLo(axis) => axis_side(axis, false),
+        }
+    }
+}
+
+/// A button in the [ControlsMenu], which rebinds `target` when pressed
+#[derive(Component, Debug, Clone, Copy)]
+struct ControlsEntry {
+    target: RebindTarget,
+}
+
+/// The control waiting for a new key to be pressed, if any
+#[derive(Resource, Debug, Default)]
+struct Rebinding(Option<RebindTarget>);
+
+fn setup_controls_menu(mut commands: Commands) {
+    let button_color = BackgroundColor(Color::linear_rgb(0.15, 0.15, 0.15));
+    let font = TextFont {
+        font_size: 16.,
+        ..default()
+    };
+
+    commands.spawn((
+        StateScoped(AppState::LobbyMenu),
+        ControlsMenuButton,
+        Button,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.),
+            left: Val::Px(10.),
+            padding: UiRect::all(Val::Px(6.)),
+            ..default()
+        },
+        button_color,
+        BorderRadius::all(Val::Px(5.)),
+        children![(
+            Text::new("Controls"),
+            TextFont {
+                font_size: 20.,
+                ..default()
+            },
+        )],
+    ));
+
+    let targets = ButtonInputs::iter().map(RebindTarget::Button).chain(
+        AxisInputs::iter()
+            .flat_map(|axis| [RebindTarget::AxisHi(axis), RebindTarget::AxisLo(axis)]),
+    );
+    let menu = commands
+        .spawn((
+            StateScoped(AppState::LobbyMenu),
+            ControlsMenu,
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(50.),
+                left: Val::Px(10.),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.),
+                padding: UiRect::all(Val::Px(10.)),
+                ..default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.9)),
+            BorderRadius::all(Val::Px(5.)),
+            Visibility::Hidden,
+            children![
+                (
+                    Text::new(
+                        "Click a control, then press its new key while holding any modifiers, \
+                         or press and release a modifier alone. Escape cancels"
+                    ),
+                    font.clone(),
+                ),
+                (ControlsMenuStatus, Text::default(), font.clone()),
+            ],
+        ))
+        .id();
+    let entries = commands
+        .spawn(Node {
+            flex_direction: FlexDirection::Column,
+            flex_wrap: FlexWrap::Wrap,
+            max_height: Val::Vh(70.),
+            column_gap: Val::Px(10.),
+            row_gap: Val::Px(2.),
+            ..default()
+        })
+        .with_children(|entries| {
+            for target in targets {
+                entries.spawn((
+                    ControlsEntry { target },
+                    Button,
+                    Node {
+                        padding: UiRect::axes(Val::Px(6.), Val::Px(2.)),
+                        ..default()
+                    },
+                    button_color,
+                    Text::new(target.label()),
+                    font.clone(),
+                ));
+            }
+        })
+        .id();
+    commands.entity(menu).add_child(entries);
+}
+
+fn toggle_controls_menu(
+    buttons: Query<&Interaction, (With<ControlsMenuButton>, Changed<Interaction>)>,
+    menus: Query<&mut Visibility, With<ControlsMenu>>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    if !buttons
+        .iter()
+        .any(|&interaction| interaction == Interaction::Pressed)
+    {
+        return;
+    }
+    rebinding.0 = None;
+    for mut visibility in menus {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+fn start_rebinding(
+    entries: Query<(&Interaction, &ControlsEntry), Changed<Interaction>>,
+    mut rebinding: ResMut<Rebinding>,
+    settings: Res<PlayerSettings>,
+    mut status: Query<&mut Text, With<ControlsMenuStatus>>,
+) {
+    // Clicking another entry while rebinding binds the mouse button to the first instead
+    if rebinding.0.is_some() {
+        return;
+    }
+    for (&interaction, entry) in entries {
+        if interaction != Interaction::Pressed {
+            continue;
+        }
+        let message = match entry.target {
+            RebindTarget::AxisHi(axis) | RebindTarget::AxisLo(axis)
+                if matches!(
+                    settings.controls.axis_controls[axis],
+                    AxisControl::Gamepad(_)
+                ) =>
+            {
+                "Gamepad axes can only be rebound in the settings file".to_string()
+            }
+            target => {
+                rebinding.0 = Some(target);
+                format!("Press a new key for {}", target.label())
+            }
+        };
+        for mut text in &mut status {
+            text.0 = message.clone();
+        }
+    }
+}
+
+/// Binds the first key pressed to the control being rebound, along with
+/// any [MODIFIER_KEYS] held at the time, or a modifier on its own if it's released
+/// without another key being pressed. Rebinding a button is refused if it would
+/// be [ButtonInputs::ambiguous_with] another, and any other clashes are listed
+fn capture_rebinding(
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<PlayerSettings>,
+    mut status: Query<&mut Text, With<ControlsMenuStatus>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+) {
+    // Skip the frame rebinding started on, so the click which started it isn't captured
+    let Some(target) = rebinding.0 else {
+        return;
+    };
+    if rebinding.is_changed() {
+        return;
+    }
+    let mut set_status = |message: String| {
+        for mut text in &mut status {
+            text.0 = message.clone();
+        }
+    };
+    if keyboard.just_pressed(KeyCode::Escape) {
+        rebinding.0 = None;
+        set_status("Rebinding cancelled".to_string());
+        return;
+    }
+
+    let is_modifier = |key: &&KeyCode| MODIFIER_KEYS.contains(key);
+    let principle: Option<KeybindKey> = match target {
+        // Axes are bound to a single key each, so modifiers are bound like any other key
+        RebindTarget::AxisHi(_) | RebindTarget::AxisLo(_) => {
+            keyboard.get_just_pressed().next().copied().map(Into::into)
+        }
+        // A modifier is only bound on its own once it's released without any other key
+        // having been pressed, since until then it might be held for the key after it
+        RebindTarget::Button(_) => keyboard
+            .get_just_pressed()
+            .find(|key| !is_modifier(key))
+            .or_else(|| {
+                keyboard
+                    .get_just_released()
+                    .filter(is_modifier)
+                    .find(|_| keyboard.get_pressed().next().is_none())
+            })
+            .copied()
+            .map(Into::into),
+    }
+    .or_else(|| mouse.get_just_pressed().next().copied().map(Into::into))
+    .or_else(|| {
+        gamepads
+            .iter()
+            .find_map(|gamepad| gamepad.get_just_pressed().next().copied())
+            .map(Into::into)
+    });
+    let Some(principle) = principle else {
+        return;
+    };
+    rebinding.0 = None;
+
+    let button = match target {
+        RebindTarget::Button(button) => button,
+        RebindTarget::AxisHi(axis) | RebindTarget::AxisLo(axis) => {
+            if let AxisControl::Virtual { hi, lo } = &mut settings.controls.axis_controls[axis] {
+                match target {
+                    RebindTarget::AxisHi(_) => *hi = principle,
+                    _ => *lo = principle,
+                }
+            }
+            set_status(format!("{} bound to {principle}", target.label()));
+            return;
+        }
+    };
+
+    let control = ButtonControl::new_with(
+        principle,
+        keyboard.get_pressed().filter(is_modifier).copied(),
+    );
+    let controls = &settings.controls.button_controls;
+    if let Some(other) =
+        ButtonInputs::iter().find(|&other| button.ambiguous_with(&control, other, &controls[other]))
+    {
+        set_status(format!(
+            "{control} is already bound to {other:?}, so {button:?} wasn't changed"
+        ));
+        return;
+    }
+    let clashes = ButtonInputs::iter()
+        .filter(|&other| other != button && control.clashes(&controls[other]))
+        .map(|other| format!("{other:?}"))
+        .join(", ");
+    set_status(match clashes.is_empty() {
+        true => format!("{button:?} bound to {control}"),
+        false => format!(
+            "{button:?} bound to {control}, which overlaps with {clashes}. \
+             The control with more modifiers or a higher priority takes precedence"
+        ),
+    });
+    // Saved by `write_settings_to_file`, and used from the next match on
+    settings.controls.button_controls[button] = control;
+}
+
+fn update_controls_entries(
+    entries: Query<(Ref<ControlsEntry>, &mut Text, &mut BackgroundColor)>,
+    rebinding: Res<Rebinding>,
+    settings: Res<PlayerSettings>,
+) {
+    let any_changed = rebinding.is_changed() || settings.is_changed();
+    for (entry, mut text, mut background) in entries {
+        if !any_changed && !entry.is_added() {
+            continue;
+        }
+        let is_rebinding = rebinding.0 == Some(entry.target);
+        let binding = match is_rebinding {
+            true => "...".to_string(),
+            false => entry.target.binding(&settings),
+        };
+        text.0 = format!("{}: {binding}", entry.target.label());
+        background.0 = match is_rebinding {
+            true => Color::linear_rgb(0.3, 0.3, 0.5),
+            false => Color::linear_rgb(0.15, 0.15, 0.15),
+        };
+    }
+}
+
+fn cancel_rebinding(mut rebinding: ResMut<Rebinding>) {
+    rebinding.0 = None;
+}
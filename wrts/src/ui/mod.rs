@@ -1,2 +1,3 @@
+pub mod debug_overlay;
 pub mod in_game;
 pub mod lobby;
@@ -1,4 +1,7 @@
-use std::{collections::HashMap, sync::LazyLock};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
 
 use tokio::sync::{Mutex, broadcast};
 use wrts_messaging::{ClientId, ClientSharedInfo};
@@ -23,10 +26,15 @@ pub enum ClientsEvent {
 #[derive(Debug, Clone)]
 pub struct ClientData {
     pub info: ClientSharedInfo,
+    /// Sent to the client in its `Lobby2Client::InitA`, and required to rejoin its match
+    pub rejoin_token: u64,
 }
 
 pub struct Clients {
     pub id2info: HashMap<ClientId, ClientData>,
+    /// Clients which disconnected mid-match, kept until they rejoin it
+    /// or the matchmaker stops waiting for them
+    pub rejoinable: HashMap<ClientId, ClientData>,
     events: broadcast::Sender<ClientsEvent>,
 }
 
@@ -44,10 +52,47 @@ impl Clients {
         let _ = self.events.send(event);
     }
 
+    /// `requested`, or if another client already has that name,
+    /// `requested` with the lowest free numbered suffix such as `Username (2)`
+    pub fn unique_username(&self, requested: &str) -> String {
+        unique_username(
+            requested,
+            self.id2info.values().map(|data| data.info.user.as_str()),
+        )
+    }
+
     fn new() -> Self {
         Self {
             id2info: HashMap::new(),
+            rejoinable: HashMap::new(),
             events: broadcast::channel(1024).0,
         }
     }
 }
+
+fn unique_username<'a>(requested: &str, taken: impl IntoIterator<Item = &'a str>) -> String {
+    let taken = taken.into_iter().collect::<HashSet<_>>();
+    if !taken.contains(requested) {
+        return requested.to_string();
+    }
+    (2..)
+        .map(|suffix| format!("{requested} ({suffix})"))
+        .find(|name| !taken.contains(name.as_str()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clients::unique_username;
+
+    #[test]
+    fn test_duplicate_usernames_get_distinct_suffixes() {
+        let first = unique_username("Username", []);
+        let second = unique_username("Username", [first.as_str()]);
+        let third = unique_username("Username", [first.as_str(), second.as_str()]);
+        assert_eq!(first, "Username");
+        assert_eq!(second, "Username (2)");
+        assert_eq!(third, "Username (3)");
+        assert_eq!(unique_username("Other", [first.as_str()]), "Other");
+    }
+}
@@ -1,21 +1,22 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use anyhow::{Result, anyhow};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use tracing::{Instrument, debug, info, info_span, level_filters::LevelFilter, warn};
 use tracing_subscriber::EnvFilter;
+use wrts_match_shared::ship_template::{DEFAULT_SHIP_MODS_DIR, ShipTemplateRegistry};
 use wrts_messaging::{
-    Client2Lobby, ClientId, ClientSharedInfo, Lobby2Client, Message, RecvFromStream, SendToStream,
+    Client2Lobby, Client2Match, ClientId, ClientSharedInfo, Lobby2Client, Match2Client, Message,
+    PROTOCOL_VERSION, RecvFromStream, SendToStream, check_protocol_version, check_ship_templates,
 };
 use wtransport::{Endpoint, Identity, ServerConfig, endpoint::IncomingSession};
 
 use crate::{
     clients::{ClientData, Clients, ClientsEvent},
     match_handler::{
-        ClientHandler2Matchmaker, ClientHandlerMatchmakerSubscription, Matchmaker,
-        Matchmaker2ClientHandler,
+        ClientHandler2Matchmaker, Matchmaker, Matchmaker2ClientHandler, MatchmakerSubscriber,
     },
     temp_dir::TempDirBuilder,
 };
@@ -40,23 +41,39 @@ fn init_logging() {
 struct NewConnectionInfo {
     incoming_session: IncomingSession,
     client_id: ClientId,
-    mm_subscription: ClientHandlerMatchmakerSubscription,
+    mm: MatchmakerSubscriber,
+}
+
+/// What `handle_connection` needs to clean up after a client's connection ends
+struct ClientConnectionExit {
+    /// The id the client ended up with, which is its old one if it rejoined a match
+    client_id: ClientId,
+    /// Whether the client was in a match, so can rejoin it
+    in_match: bool,
 }
 
 async fn handle_connection(info: NewConnectionInfo) {
     info!("Handling new client connection");
-    let client_id = info.client_id;
+    let mut exit = ClientConnectionExit {
+        client_id: info.client_id,
+        in_match: false,
+    };
     let abort_token = CancellationToken::new();
-    let exit = handle_connection_inner(info, abort_token.clone()).await;
-    match exit {
+    let res = handle_connection_inner(info, abort_token.clone(), &mut exit).await;
+    let client_id = exit.client_id;
+    match res {
         Ok(()) => info!("{client_id} Exited successfully"),
         Err(err) => info!("{client_id} Exited with error: `{err}`"),
     }
     {
         let mut clients = Clients::lock().await;
-        let client_info = clients.id2info.remove(&client_id);
-        assert!(client_info.is_some());
-        clients.send(ClientsEvent::ClientLeft { id: client_id });
+        // Clients turned away during the handshake were never added
+        if let Some(client_data) = clients.id2info.remove(&client_id) {
+            if exit.in_match {
+                clients.rejoinable.insert(client_id, client_data);
+            }
+            clients.send(ClientsEvent::ClientLeft { id: client_id });
+        }
     }
     abort_token.cancel();
 }
@@ -65,9 +82,10 @@ async fn handle_connection_inner(
     NewConnectionInfo {
         incoming_session,
         client_id,
-        mut mm_subscription,
+        mm,
     }: NewConnectionInfo,
     abort_token: CancellationToken,
+    exit: &mut ClientConnectionExit,
 ) -> Result<()> {
     debug!("Waiting for session request...");
 
@@ -88,21 +106,71 @@ async fn handle_connection_inner(
 
     debug!("Sending client initial information");
 
-    Message::Lobby2Client(Lobby2Client::InitA { client_id })
-        .send(&mut tx)
-        .await?;
-
-    let Message::Client2Lobby(Client2Lobby::InitB { username }) = Message::recv(&mut rx).await?
-    else {
-        return Err(anyhow!(
-            "Expected network message: `Client2Lobby::InitialInformationResponse`"
-        ));
+    let rejoin_token = rand::random();
+    Message::Lobby2Client(Lobby2Client::InitA {
+        client_id,
+        protocol_version: PROTOCOL_VERSION,
+        rejoin_token,
+        ship_templates: ShipTemplateRegistry::get().fingerprint(),
+    })
+    .send(&mut tx)
+    .await?;
+
+    let (client_id, username, mut mm_subscription) = match Message::recv(&mut rx).await? {
+        Message::Client2Lobby(Client2Lobby::InitB {
+            username,
+            protocol_version,
+            ship_templates,
+        }) => {
+            // Clients check the version in `InitA` as well, but ones from before versioning don't
+            check_protocol_version(protocol_version)
+                .and_then(|()| check_ship_templates(ship_templates))
+                .map_err(|err| err.context(format!("Rejected client {client_id}")))?;
+            (client_id, username, mm.subscribe(client_id).await)
+        }
+        Message::Client2Lobby(Client2Lobby::RejoinMatch {
+            client_id: rejoin_id,
+            rejoin_token: sent_token,
+            protocol_version,
+            ship_templates,
+        }) => {
+            check_protocol_version(protocol_version)
+                .and_then(|()| check_ship_templates(ship_templates))
+                .map_err(|err| err.context(format!("Rejected client {client_id}")))?;
+            let client_data = {
+                let mut clients = Clients::lock().await;
+                match clients.rejoinable.get(&rejoin_id) {
+                    Some(client_data) if client_data.rejoin_token == sent_token => {}
+                    Some(_) => return Err(anyhow!("Wrong rejoin token for {rejoin_id}")),
+                    None => return Err(anyhow!("{rejoin_id} has no match to rejoin")),
+                }
+                clients.rejoinable.remove(&rejoin_id).unwrap()
+            };
+            let mm_subscription = mm
+                .rejoin(rejoin_id)
+                .await
+                .ok_or_else(|| anyhow!("The match of {rejoin_id} can no longer be rejoined"))?;
+            info!("Rejoining match as {rejoin_id}");
+            exit.client_id = rejoin_id;
+            (rejoin_id, client_data.info.user, mm_subscription)
+        }
+        _ => {
+            return Err(anyhow!(
+                "Expected network message: `Client2Lobby::InitB` or `Client2Lobby::RejoinMatch`"
+            ));
+        }
     };
 
     debug!("username selected: `{username}`");
 
     let mut clients_events = {
         let mut clients = Clients::lock().await;
+        // `ClientId` stays the authoritative identity, this only keeps names readable
+        let requested_username = username;
+        let username = clients.unique_username(&requested_username);
+        if username != requested_username {
+            info!("Username `{requested_username}` was taken, renamed to `{username}`");
+        }
         clients.id2info.insert(
             client_id,
             ClientData {
@@ -110,6 +178,8 @@ async fn handle_connection_inner(
                     id: client_id,
                     user: username,
                 },
+                // A rejoining client was sent a new token in this connection's `InitA`
+                rejoin_token,
             },
         );
         clients.send(ClientsEvent::ClientJoined { id: client_id });
@@ -189,7 +259,8 @@ async fn handle_connection_inner(
     enum ClientState {
         InLobby,
         InMatch {
-            match_instance_tx: mpsc::Sender<Message>,
+            /// `None` while only spectating the match
+            match_instance_tx: Option<mpsc::Sender<Message>>,
             match_instance_rx: mpsc::Receiver<Message>,
         },
     }
@@ -228,7 +299,13 @@ async fn handle_connection_inner(
                 .send(ClientHandler2Matchmaker::SetReadyForMatch { is_ready })
                 .await
                 .map_err(|_| anyhow!("Matchmaker disconnnected"))?,
+            Message::Client2Lobby(Client2Lobby::SpectateMatch { match_id }) => mm_subscription
+                .tx
+                .send(ClientHandler2Matchmaker::SpectateMatch { match_id })
+                .await
+                .map_err(|_| anyhow!("Matchmaker disconnnected"))?,
             Message::Client2Lobby(Client2Lobby::InitB { .. })
+            | Message::Client2Lobby(Client2Lobby::RejoinMatch { .. })
             | Message::Lobby2Client(_)
             | Message::Client2Match(_)
             | Message::Match2Client(_) => warn!(
@@ -254,9 +331,17 @@ async fn handle_connection_inner(
                         let mm_msg = mm_msg.ok_or(anyhow!("Matchmaker disconnected"))?;
                         match mm_msg {
                             Matchmaker2ClientHandler::MatchJoined { match_id: _, match_instance_tx, match_instance_rx } => {
-                                state = ClientState::InMatch { match_instance_tx, match_instance_rx };
+                                state = ClientState::InMatch { match_instance_tx: Some(match_instance_tx), match_instance_rx };
+                                exit.in_match = true;
+                                let _ = client_tx.send(Message::Lobby2Client(Lobby2Client::MatchJoined {  })).await;
+                            },
+                            Matchmaker2ClientHandler::MatchSpectated { match_id: _, match_instance_rx } => {
+                                state = ClientState::InMatch { match_instance_tx: None, match_instance_rx };
                                 let _ = client_tx.send(Message::Lobby2Client(Lobby2Client::MatchJoined {  })).await;
                             },
+                            Matchmaker2ClientHandler::MatchList(matches) => {
+                                client_tx.send(Message::Lobby2Client(Lobby2Client::MatchList { matches })).await?;
+                            },
                         }
                     }
                     _ = abort_token.cancelled() => {
@@ -268,32 +353,96 @@ async fn handle_connection_inner(
                 match_instance_tx,
                 match_instance_rx,
             } => {
-                tokio::select! {
+                let match_closed = tokio::select! {
                     cl_msg = client_rx.recv() => {
                         let cl_msg = cl_msg.ok_or(anyhow!("Client disconnected"))?;
-                        match_instance_tx.send(cl_msg).await.map_err(|_| anyhow!("Match instance disconnected"))?;
+                        // The lobby's record of the client is authoritative, including its deduplicated username
+                        let cl_msg = match cl_msg {
+                            Message::Client2Match(Client2Match::InitB { .. }) => {
+                                let info = Clients::lock().await.id2info[&client_id].info.clone();
+                                Message::Client2Match(Client2Match::InitB { info })
+                            }
+                            cl_msg => cl_msg,
+                        };
+                        match match_instance_tx {
+                            _ if is_lobby_only(&cl_msg) => warn!("Dropped lobby-only message from client: {cl_msg:?}"),
+                            Some(match_instance_tx) => match_instance_tx.send(cl_msg).await.map_err(|_| anyhow!("Match instance disconnected"))?,
+                            None => debug!("Dropped message from spectator: {cl_msg:?}"),
+                        }
+                        false
                     }
                     match_msg = match_instance_rx.recv() => {
-                        let match_msg = match_msg.ok_or(anyhow!("Match instance disconnected"))?;
-                        client_tx.send(match_msg).await.map_err(|_| anyhow!("Client disconnected"))?;
+                        let closed = match_route_closed(match_msg.as_ref());
+                        if let Some(match_msg) = match_msg {
+                            client_tx.send(match_msg).await.map_err(|_| anyhow!("Client disconnected"))?;
+                        }
+                        closed
                     }
                     mm_msg = mm_subscription.rx.recv() => {
                         let mm_msg = mm_msg.ok_or(anyhow!("Matchmaker disconnected"))?;
                         match mm_msg {
-                            Matchmaker2ClientHandler::MatchJoined { .. } => {
+                            Matchmaker2ClientHandler::MatchJoined { .. } | Matchmaker2ClientHandler::MatchSpectated { .. } => {
                                 return Err(anyhow!("Matchmaker sent `MatchJoined` message when client already in match"))
                             },
+                            // Sent again when the client is back in the lobby
+                            Matchmaker2ClientHandler::MatchList(_) => {}
                         }
+                        false
                     }
                     _ = abort_token.cancelled() => {
                         return Err(anyhow!("Client disconnected"));
                     }
+                };
+                if match_closed {
+                    info!("Match ended, returning to the lobby");
+                    mm_subscription
+                        .tx
+                        .send(ClientHandler2Matchmaker::LeftMatch)
+                        .await
+                        .map_err(|_| anyhow!("Matchmaker disconnnected"))?;
+                    state = ClientState::InLobby;
+                    exit.in_match = false;
+
+                    // The client's list of clients was cleared when it joined the match,
+                    // so it's sent in full rather than catching up on the events since then
+                    let clients = Clients::lock().await;
+                    clients_events = clients.subscribe();
+                    for cl_data in clients.id2info.values() {
+                        client_tx
+                            .send(Message::Lobby2Client(Lobby2Client::ClientJoined {
+                                info: cl_data.info.clone(),
+                            }))
+                            .await?;
+                    }
                 }
             }
         }
     }
 }
 
+/// Whether a client's route to its match is closed once `match_msg` is forwarded to it,
+/// where `None` means the match instance has exited
+///
+/// Closed as soon as the match ends rather than when its instance exits, so that the lobby
+/// messages the client sends once it's back in the lobby, like readying up again,
+/// aren't forwarded to the finished match and lost
+fn match_route_closed(match_msg: Option<&Message>) -> bool {
+    matches!(
+        match_msg,
+        None | Some(Message::Match2Client(Match2Client::MatchEnded { .. }))
+    )
+}
+
+/// Whether `cl_msg` is one only the lobby may send a match, so is dropped when a client sends it.
+/// `Rejoined` would otherwise have the match resend everything to any client which asked,
+/// and `SpectatorJoined` would let a player see every enemy ship
+fn is_lobby_only(cl_msg: &Message) -> bool {
+    matches!(
+        cl_msg,
+        Message::Client2Match(Client2Match::Rejoined | Client2Match::SpectatorJoined { .. })
+    )
+}
+
 async fn trace_client_events() {
     let mut events = {
         let clients = Clients::lock().await;
@@ -305,8 +454,31 @@ async fn trace_client_events() {
 }
 
 #[derive(Parser, Debug)]
-enum Args {
-    Lobby,
+struct Args {
+    #[command(subcommand)]
+    mode: Mode,
+    /// The directory to load modded ship templates from
+    #[arg(long, global = true, default_value = DEFAULT_SHIP_MODS_DIR)]
+    ship_mods_dir: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+enum Mode {
+    Lobby {
+        /// Starts every match with this seed instead of a random one,
+        /// for reproducing a match that was reported with its seed
+        #[arg(long)]
+        match_seed: Option<u64>,
+        /// Lets shells and torpedoes damage ships on their own team in every match
+        #[arg(long)]
+        friendly_fire: bool,
+        /// How many clients each match waits for, each playing as its own team
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u64).range(2..))]
+        team_count: u64,
+        /// How many seconds a client which disconnects mid-match has to rejoin it
+        #[arg(long, default_value_t = 60)]
+        rejoin_grace_secs: u64,
+    },
     Match,
 }
 
@@ -314,11 +486,20 @@ enum Args {
 async fn main() -> Result<()> {
     let args = Args::parse();
 
-    match args {
-        Args::Lobby => {
+    match args.mode {
+        Mode::Lobby {
+            match_seed,
+            friendly_fire,
+            team_count,
+            rejoin_grace_secs,
+        } => {
             let _tmp_dir = TempDirBuilder::build();
             init_logging();
 
+            for err in ShipTemplateRegistry::init(&args.ship_mods_dir) {
+                warn!("{err:#}");
+            }
+
             tokio::spawn(trace_client_events().instrument(info_span!("Trace Clients Events")));
 
             let config = ServerConfig::builder()
@@ -331,28 +512,67 @@ async fn main() -> Result<()> {
 
             info!("Endpoint created");
 
-            let mm = Matchmaker::spawn();
+            let mm = Matchmaker::spawn(
+                match_seed,
+                friendly_fire,
+                team_count as usize,
+                Duration::from_secs(rejoin_grace_secs),
+            );
 
             for id in 0.. {
                 let client_id = ClientId(id);
                 info!("Open sessions: {}", ep.open_connections());
                 info!("Awaiting session {client_id}");
                 let session = ep.accept().await;
-                let mm_subscription = mm.subscribe(client_id).await;
                 tokio::spawn(
                     handle_connection(NewConnectionInfo {
                         incoming_session: session,
                         client_id,
-                        mm_subscription,
+                        mm: mm.clone(),
                     })
                     .instrument(info_span!("Client Connection", %client_id)),
                 );
             }
         }
-        Args::Match => {
-            wrts_match::start_match().expect("Couldn't start match");
+        Mode::Match => {
+            wrts_match::start_match(&args.ship_mods_dir).expect("Couldn't start match");
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use wrts_messaging::{Client2Match, ClientId, ClientSharedInfo, Match2Client, Message};
+
+    use crate::{is_lobby_only, match_route_closed};
+
+    #[test]
+    fn test_match_route_closes_when_match_ends() {
+        let print = Message::Match2Client(Match2Client::PrintMsg("hello".into()));
+        let ended = Message::Match2Client(Match2Client::MatchEnded {
+            winner: Some(ClientId(0)),
+        });
+        assert!(!match_route_closed(Some(&print)));
+        assert!(match_route_closed(Some(&ended)));
+        assert!(match_route_closed(None));
+    }
+
+    #[test]
+    fn test_clients_cant_send_lobby_only_messages() {
+        let info = ClientSharedInfo {
+            id: ClientId(0),
+            user: "player".into(),
+        };
+        assert!(is_lobby_only(&Message::Client2Match(
+            Client2Match::Rejoined
+        )));
+        assert!(is_lobby_only(&Message::Client2Match(
+            Client2Match::SpectatorJoined { info }
+        )));
+        assert!(!is_lobby_only(&Message::Client2Match(
+            Client2Match::Surrender
+        )));
+    }
+}
@@ -1,30 +1,62 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use itertools::Itertools;
-use slotmap::SlotMap;
+use slotmap::{Key, KeyData, SlotMap};
 use tokio::sync::{Mutex, mpsc, oneshot};
 use tokio_util::sync::CancellationToken;
-use tracing::{Instrument, info_span, warn};
+use tracing::{Instrument, info, info_span, warn};
 use wrts_messaging::{
-    ClientId, Message, RecvFromStream, SendToStream, WrtsMatchInitMessage, WrtsMatchMessage,
+    Client2Match, ClientId, ClientSharedInfo, MatchListing, Message, RecvFromStream, SendToStream,
+    SharedMatchId, WrtsMatchInitMessage, WrtsMatchMessage,
 };
 
-use crate::temp_dir::WrtsMatchProcess;
+use crate::{clients::Clients, temp_dir::WrtsMatchProcess};
 
 slotmap::new_key_type! {
     pub struct MatchId;
 }
 
+impl From<MatchId> for SharedMatchId {
+    fn from(id: MatchId) -> Self {
+        Self(id.data().as_ffi())
+    }
+}
+
+impl From<SharedMatchId> for MatchId {
+    fn from(id: SharedMatchId) -> Self {
+        KeyData::from_ffi(id.0).into()
+    }
+}
+
 pub enum Matchmaker2ClientHandler {
     MatchJoined {
         match_id: MatchId,
         match_instance_tx: mpsc::Sender<Message>,
         match_instance_rx: mpsc::Receiver<Message>,
     },
+    /// Like `MatchJoined`, but the client can only watch, so has no channel to the match
+    MatchSpectated {
+        match_id: MatchId,
+        match_instance_rx: mpsc::Receiver<Message>,
+    },
+    /// Every running match, sent to clients in the lobby whenever it changes
+    MatchList(Vec<MatchListing>),
 }
 
 pub enum ClientHandler2Matchmaker {
-    SetReadyForMatch { is_ready: bool },
+    SetReadyForMatch {
+        is_ready: bool,
+    },
+    SpectateMatch {
+        match_id: SharedMatchId,
+    },
+    /// The client's match instance closed down, such as after a surrender,
+    /// and the client is back in the lobby. Also sent once a spectated match closes
+    LeftMatch,
 }
 
 pub struct ClientHandlerMatchmakerSubscription {
@@ -36,63 +68,153 @@ enum ClientState {
     InLobby,
     ReadyForMatch,
     InMatch(MatchId),
+    Spectating(MatchId),
 }
 
 struct MatchmakerClientData {
     disconnected: CancellationToken,
     tx: mpsc::Sender<Matchmaker2ClientHandler>,
     state: ClientState,
+    /// When the client disconnected while in a match,
+    /// after which it has [Matchmaker::rejoin_grace] to rejoin
+    disconnected_at: Option<Instant>,
+}
+
+/// The match instance's ends of the channels to and from one client's handler
+type ClientChannels = (mpsc::Sender<Message>, mpsc::Receiver<Message>);
+
+/// A client joining a match instance which is already running
+#[derive(Debug)]
+enum MatchInstanceJoin {
+    /// A client which reconnected, with new channels to take its place back in the match
+    Rejoin {
+        client: ClientId,
+        channels: ClientChannels,
+    },
+    /// A client which only watches, so is only sent to
+    Spectate {
+        info: ClientSharedInfo,
+        tx: mpsc::Sender<Message>,
+    },
 }
 
 #[derive(Debug, Clone)]
 struct ActiveMatch {
     id: MatchId,
-    clients: [ClientId; 2],
+    clients: Vec<ClientId>,
+    seed: u64,
+    friendly_fire: bool,
+    /// Hands the match instance clients which join it after it started
+    joins: mpsc::Sender<MatchInstanceJoin>,
 }
 
+/// Forwards messages between the match instance and its clients' handlers
+///
+/// A client whose handler disconnects has `rejoin_grace` to rejoin through `joins`,
+/// and the match is shut down if it doesn't
 async fn match_instance_router(
     match_instance: ActiveMatch,
-    client_channels: HashMap<ClientId, (mpsc::Sender<Message>, mpsc::Receiver<Message>)>,
+    client_channels: HashMap<ClientId, ClientChannels>,
+    mut joins: mpsc::Receiver<MatchInstanceJoin>,
+    rejoin_grace: Duration,
 ) {
     let mut process = WrtsMatchProcess::spawn().await.unwrap();
 
     WrtsMatchInitMessage {
-        clients: match_instance.clients,
+        clients: match_instance.clients.clone(),
+        seed: match_instance.seed,
+        friendly_fire: match_instance.friendly_fire,
     }
     .send(&mut process.stdin)
     .await
     .unwrap();
 
-    let (client_tx, mut client_rx): (HashMap<_, _>, Vec<_>) = client_channels
+    let (client_tx, mut client_rx): (HashMap<_, _>, HashMap<_, _>) = client_channels
         .into_iter()
         .map(|(cl, (tx, rx))| ((cl, tx), (cl, rx)))
         .unzip();
+    // Shared so that rejoining clients and spectators can be added
+    let client_tx = Arc::new(Mutex::new(client_tx));
+    let match_closed = CancellationToken::new();
 
     tokio::spawn({
+        let client_tx = client_tx.clone();
+        let match_closed = match_closed.clone();
         async move {
             loop {
                 let Ok(msg) = WrtsMatchMessage::recv(&mut process.stdout).await else {
                     warn!("Match instance closed down");
-                    return;
+                    break;
                 };
 
-                if let Err(_) = client_tx[&msg.client].send(msg.msg).await {
-                    warn!("Client closed down");
-                    return;
+                let tx = client_tx.lock().await.get(&msg.client).cloned();
+                // A disconnected client is sent everything it can see when it rejoins,
+                // so nothing is lost by dropping messages to it in the meantime
+                if let Some(tx) = tx
+                    && tx.send(msg.msg).await.is_err()
+                {
+                    client_tx.lock().await.remove(&msg.client);
                 }
             }
+            // Closing the channels tells the clients' handlers that the match is over
+            client_tx.lock().await.clear();
+            match_closed.cancel();
         }
     });
 
+    let mut disconnected_at = HashMap::new();
     'main_loop: loop {
         // Without yielding, this task wouldn't await until a client sends a message
         tokio::task::yield_now().await;
 
+        if match_closed.is_cancelled() {
+            break;
+        }
+
+        while let Ok(join) = joins.try_recv() {
+            let msg = match join {
+                MatchInstanceJoin::Rejoin {
+                    client,
+                    channels: (tx, rx),
+                } => {
+                    info!("{client} rejoined match {:?}", match_instance.id);
+                    client_tx.lock().await.insert(client, tx);
+                    client_rx.insert(client, rx);
+                    disconnected_at.remove(&client);
+                    WrtsMatchMessage {
+                        client,
+                        msg: Message::Client2Match(Client2Match::Rejoined),
+                    }
+                }
+                MatchInstanceJoin::Spectate { info, tx } => {
+                    info!("{} is spectating match {:?}", info.id, match_instance.id);
+                    client_tx.lock().await.insert(info.id, tx);
+                    WrtsMatchMessage {
+                        client: info.id,
+                        msg: Message::Client2Match(Client2Match::SpectatorJoined { info }),
+                    }
+                }
+            };
+
+            let res = msg.send(&mut process.stdin).await;
+            if let Err(e) = res {
+                warn!(
+                    "Error sending message to match instance {:?}: {e}",
+                    match_instance.id
+                );
+                break 'main_loop;
+            }
+        }
+
         for (client_id, rx) in &mut client_rx {
             let msg = match rx.try_recv() {
                 Ok(msg) => msg,
                 Err(mpsc::error::TryRecvError::Empty) => continue,
-                Err(mpsc::error::TryRecvError::Disconnected) => break 'main_loop,
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    warn!("{client_id} disconnected, waiting {rejoin_grace:?} for it to rejoin");
+                    disconnected_at.insert(*client_id, Instant::now());
+                    continue;
+                }
             };
 
             let res = WrtsMatchMessage {
@@ -110,15 +232,30 @@ async fn match_instance_router(
                 break 'main_loop;
             }
         }
+        client_rx.retain(|client_id, _| !disconnected_at.contains_key(client_id));
+
+        if let Some((client_id, _)) = disconnected_at
+            .iter()
+            .find(|(_, at)| at.elapsed() > rejoin_grace)
+        {
+            warn!(
+                "{client_id} didn't rejoin match {:?} in time, closing it",
+                match_instance.id
+            );
+            break;
+        }
     }
     let _ = process.process.kill();
 }
 
 struct MatchmakerSubscribeMsg {
     pub client_id: ClientId,
+    /// Whether the client is taking back its place in the match it disconnected from
+    pub rejoin: bool,
     pub send_subscribtion: oneshot::Sender<ClientHandlerMatchmakerSubscription>,
 }
 
+#[derive(Clone)]
 pub struct MatchmakerSubscriber {
     new_clients_tx: mpsc::Sender<MatchmakerSubscribeMsg>,
 }
@@ -132,23 +269,64 @@ impl MatchmakerSubscriber {
             .new_clients_tx
             .send(MatchmakerSubscribeMsg {
                 client_id,
+                rejoin: false,
                 send_subscribtion: tx,
             })
             .await;
         rx.await.expect("Matchmaker closed down!")
     }
+
+    /// Subscribes the handler of a client which disconnected mid-match,
+    /// which is sent `MatchJoined` for that match straight away
+    ///
+    /// `None` if the client has no match to rejoin, such as if it's over
+    /// or the client took longer than [Matchmaker::rejoin_grace] to come back
+    pub async fn rejoin(&self, client_id: ClientId) -> Option<ClientHandlerMatchmakerSubscription> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .new_clients_tx
+            .send(MatchmakerSubscribeMsg {
+                client_id,
+                rejoin: true,
+                send_subscribtion: tx,
+            })
+            .await;
+        rx.await.ok()
+    }
 }
 
 pub struct Matchmaker {
     active_matches: SlotMap<MatchId, ActiveMatch>,
     connected_clients: HashMap<ClientId, MatchmakerClientData>,
+    /// Every match is started with this seed if set, rather than a random one
+    match_seed: Option<u64>,
+    /// Whether every match lets shells and torpedoes damage ships on their own team
+    friendly_fire: bool,
+    /// How many clients are put in each match, each playing as its own team
+    team_count: usize,
+    /// How long a client which disconnects mid-match has to rejoin it.
+    /// The match keeps running meanwhile, and is closed if the client doesn't come back in time
+    rejoin_grace: Duration,
+    /// The matches last sent to clients in the lobby as a [Matchmaker2ClientHandler::MatchList]
+    listed_matches: Vec<MatchId>,
 }
 
 impl Matchmaker {
-    pub fn spawn() -> MatchmakerSubscriber {
+    pub fn spawn(
+        match_seed: Option<u64>,
+        friendly_fire: bool,
+        team_count: usize,
+        rejoin_grace: Duration,
+    ) -> MatchmakerSubscriber {
+        assert!(team_count >= 2, "A match needs at least two teams");
         let mm = Self {
             active_matches: SlotMap::default(),
             connected_clients: HashMap::default(),
+            match_seed,
+            friendly_fire,
+            team_count,
+            rejoin_grace,
+            listed_matches: vec![],
         };
         let (tx, rx) = mpsc::channel(128);
         tokio::spawn(
@@ -161,6 +339,64 @@ impl Matchmaker {
         );
         MatchmakerSubscriber { new_clients_tx: tx }
     }
+
+    /// The match `client` can rejoin, if it disconnected from one
+    /// which is still running no longer than [Self::rejoin_grace] ago
+    fn rejoinable_match(&self, client: ClientId) -> Option<MatchId> {
+        let cl_data = self.connected_clients.get(&client)?;
+        let ClientState::InMatch(match_id) = cl_data.state else {
+            return None;
+        };
+        // A client which is still connected can't be taken over
+        let disconnected_at = cl_data.disconnected_at?;
+        (disconnected_at.elapsed() <= self.rejoin_grace
+            && self.active_matches.contains_key(match_id))
+        .then_some(match_id)
+    }
+
+    /// Every running match, for clients to pick one to spectate
+    fn match_list(&self) -> Vec<MatchListing> {
+        self.active_matches
+            .iter()
+            .map(|(id, active_match)| MatchListing {
+                id: id.into(),
+                clients: active_match.clients.clone(),
+            })
+            .collect()
+    }
+
+    /// Sends the match list to every client in the lobby,
+    /// if a match started or ended since it was last sent
+    async fn broadcast_match_list_if_changed(&mut self) {
+        let matches = self.active_matches.keys().collect_vec();
+        if matches == self.listed_matches {
+            return;
+        }
+        self.listed_matches = matches;
+        let match_list = self.match_list();
+        for cl_data in self.connected_clients.values() {
+            if matches!(
+                cl_data.state,
+                ClientState::InLobby | ClientState::ReadyForMatch
+            ) {
+                let _ = cl_data
+                    .tx
+                    .send(Matchmaker2ClientHandler::MatchList(match_list.clone()))
+                    .await;
+            }
+        }
+    }
+
+    /// Forgets `match_id` once none of its clients are in it
+    fn remove_match_if_empty(&mut self, match_id: MatchId) {
+        let match_has_clients = self
+            .connected_clients
+            .values()
+            .any(|cl_data| matches!(cl_data.state, ClientState::InMatch(id) if id == match_id));
+        if !match_has_clients {
+            self.active_matches.remove(match_id);
+        }
+    }
 }
 
 enum MatchmakerMessage {
@@ -234,18 +470,38 @@ async fn matchmaker_runner(
     ));
 
     while let Some(msg) = msgs.recv().await {
-        let clients_disconnected = mm
-            .connected_clients
-            .iter()
-            .filter_map(|(cl, cl_data)| {
-                (cl_data.disconnected.is_cancelled() || cl_data.tx.is_closed()).then_some(*cl)
-            })
-            .collect_vec();
+        let rejoin_grace = mm.rejoin_grace;
+        let mut clients_disconnected = vec![];
+        for (&cl, cl_data) in &mut mm.connected_clients {
+            if !(cl_data.disconnected.is_cancelled() || cl_data.tx.is_closed()) {
+                continue;
+            }
+            match cl_data.state {
+                // Kept so the client can rejoin the match
+                ClientState::InMatch(match_id) => {
+                    let disconnected_at = *cl_data.disconnected_at.get_or_insert_with(|| {
+                        warn!("Disconnected from match {match_id:?}: {cl}");
+                        Instant::now()
+                    });
+                    if disconnected_at.elapsed() > rejoin_grace {
+                        clients_disconnected.push((cl, Some(match_id)));
+                    }
+                }
+                ClientState::InLobby | ClientState::ReadyForMatch | ClientState::Spectating(_) => {
+                    clients_disconnected.push((cl, None))
+                }
+            }
+        }
 
-        for cl in clients_disconnected {
+        for (cl, match_id) in clients_disconnected {
             warn!("Disconnected: {cl}");
             mm.connected_clients.remove(&cl);
+            if let Some(match_id) = match_id {
+                Clients::lock().await.rejoinable.remove(&cl);
+                mm.remove_match_if_empty(match_id);
+            }
         }
+        mm.broadcast_match_list_if_changed().await;
 
         match msg {
             MatchmakerMessage::Client2MM { client, msg } => match msg {
@@ -260,11 +516,99 @@ async fn matchmaker_runner(
                                 false => ClientState::InLobby,
                             };
                         }
-                        ClientState::InMatch(_) => continue,
+                        ClientState::InMatch(_) | ClientState::Spectating(_) => continue,
                     }
                 }
+                ClientHandler2Matchmaker::SpectateMatch { match_id } => {
+                    let match_id = MatchId::from(match_id);
+                    let Some(cl_data) = mm.connected_clients.get_mut(&client) else {
+                        continue;
+                    };
+                    if !matches!(
+                        cl_data.state,
+                        ClientState::InLobby | ClientState::ReadyForMatch
+                    ) {
+                        continue;
+                    }
+                    let Some(active_match) = mm.active_matches.get(match_id) else {
+                        warn!("{client} tried to spectate match {match_id:?}, which isn't running");
+                        continue;
+                    };
+                    let Some(client_data) = Clients::lock().await.id2info.get(&client).cloned()
+                    else {
+                        continue;
+                    };
+                    let (tx, match_instance_rx) = mpsc::channel(1024);
+                    let joined = active_match
+                        .joins
+                        .send(MatchInstanceJoin::Spectate {
+                            info: client_data.info,
+                            tx,
+                        })
+                        .await;
+                    if let Err(_) = joined {
+                        warn!("Match {match_id:?} closed before {client} could spectate it");
+                        continue;
+                    }
+                    cl_data.state = ClientState::Spectating(match_id);
+                    let _ = cl_data
+                        .tx
+                        .send(Matchmaker2ClientHandler::MatchSpectated {
+                            match_id,
+                            match_instance_rx,
+                        })
+                        .await;
+                }
+                ClientHandler2Matchmaker::LeftMatch => {
+                    let Some(cl_data) = mm.connected_clients.get_mut(&client) else {
+                        continue;
+                    };
+                    match cl_data.state {
+                        ClientState::InMatch(match_id) => {
+                            cl_data.state = ClientState::InLobby;
+                            mm.remove_match_if_empty(match_id);
+                        }
+                        ClientState::Spectating(_) => cl_data.state = ClientState::InLobby,
+                        ClientState::InLobby | ClientState::ReadyForMatch => continue,
+                    }
+                    let _ = mm.connected_clients[&client]
+                        .tx
+                        .send(Matchmaker2ClientHandler::MatchList(mm.match_list()))
+                        .await;
+                }
             },
             MatchmakerMessage::ClientJoined { subscribe } => {
+                let rejoined_match = if subscribe.rejoin {
+                    let Some(match_id) = mm.rejoinable_match(subscribe.client_id) else {
+                        // Dropping `send_subscribtion` refuses the rejoin
+                        warn!("{} has no match to rejoin", subscribe.client_id);
+                        continue;
+                    };
+                    let (match_instance_tx, rx) = mpsc::channel(1024);
+                    let (tx, match_instance_rx) = mpsc::channel(1024);
+                    let rejoined = mm.active_matches[match_id]
+                        .joins
+                        .send(MatchInstanceJoin::Rejoin {
+                            client: subscribe.client_id,
+                            channels: (tx, rx),
+                        })
+                        .await;
+                    if let Err(_) = rejoined {
+                        warn!(
+                            "Match {match_id:?} closed before {} could rejoin it",
+                            subscribe.client_id
+                        );
+                        continue;
+                    }
+                    Some(Matchmaker2ClientHandler::MatchJoined {
+                        match_id,
+                        match_instance_tx,
+                        match_instance_rx,
+                    })
+                } else {
+                    None
+                };
+
                 let (mmtx, clrx) = mpsc::channel(1024);
                 let (cltx, mmrx) = mpsc::channel(1024);
                 let disconnect_client = CancellationToken::new();
@@ -276,12 +620,30 @@ async fn matchmaker_runner(
                     subscribe.client_id,
                 ));
 
+                let state = match &rejoined_match {
+                    Some(Matchmaker2ClientHandler::MatchJoined { match_id, .. }) => {
+                        ClientState::InMatch(*match_id)
+                    }
+                    None => ClientState::InLobby,
+                };
+                // Buffered until the handler starts reading its subscription
+                match rejoined_match {
+                    Some(match_joined) => {
+                        let _ = mmtx.send(match_joined).await;
+                    }
+                    None => {
+                        let _ = mmtx
+                            .send(Matchmaker2ClientHandler::MatchList(mm.match_list()))
+                            .await;
+                    }
+                }
                 mm.connected_clients.insert(
                     subscribe.client_id,
                     MatchmakerClientData {
                         disconnected: disconnect_client.clone(),
                         tx: mmtx,
-                        state: ClientState::InLobby,
+                        state,
+                        disconnected_at: None,
                     },
                 );
                 let sub = ClientHandlerMatchmakerSubscription { tx: cltx, rx: clrx };
@@ -298,12 +660,18 @@ async fn matchmaker_runner(
             })
             .collect_vec();
 
-        if clients_ready_for_match.len() >= 2 {
-            let clients: [ClientId; 2] = std::array::from_fn(|i| clients_ready_for_match[i]);
+        if clients_ready_for_match.len() >= mm.team_count {
+            let clients = clients_ready_for_match[..mm.team_count].to_vec();
+            let seed = mm.match_seed.unwrap_or_else(rand::random);
+            let (joins_tx, joins) = mpsc::channel(16);
             let match_id = mm.active_matches.insert_with_key(|match_id| ActiveMatch {
                 id: match_id,
-                clients,
+                clients: clients.clone(),
+                seed,
+                friendly_fire: mm.friendly_fire,
+                joins: joins_tx,
             });
+            info!("Starting match {match_id:?} with seed {seed}");
             let mut client_channels = HashMap::new();
             for cl in clients {
                 let cl_data = mm.connected_clients.get_mut(&cl).unwrap();
@@ -325,10 +693,16 @@ async fn matchmaker_runner(
             }
 
             tokio::spawn(
-                match_instance_router(mm.active_matches[match_id].clone(), client_channels)
-                    .instrument(info_span!("match_instance_router", ?match_id)),
+                match_instance_router(
+                    mm.active_matches[match_id].clone(),
+                    client_channels,
+                    joins,
+                    mm.rejoin_grace,
+                )
+                .instrument(info_span!("match_instance_router", ?match_id)),
             );
         }
+        mm.broadcast_match_list_if_changed().await;
     }
 
     warn!("Matchmaker disconnecting!");
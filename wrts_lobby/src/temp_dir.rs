@@ -1,4 +1,5 @@
 use std::{
+    ffi::OsStr,
     io::{self, Write},
     marker::PhantomData,
     path::{Path, PathBuf},
@@ -8,6 +9,7 @@ use std::{
 
 use tokio::process::{Child, ChildStdin, ChildStdout, Command};
 use tracing::error;
+use wrts_match_shared::ship_template::ShipTemplateRegistry;
 
 fn ctrlc_handler() {
     let exit_code = 2;
@@ -121,16 +123,24 @@ impl WrtsMatchProcess {
     pub async fn spawn() -> anyhow::Result<Self> {
         let log_path = format!("wrts_log_{:x}.txt", rand::random_range(0..(1024 * 1024)));
 
-        let mut process = Command::new(std::env::current_exe().expect("Expected to be running an exe"))
-            // Disable coloring in bevy logs, since they are written to a `.txt` file
-            .env("NO_COLOR", "1")
-            // Enable verbose backtraces
-            .env("BEVY_BACKTRACE", "full")
-            .arg("match")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(log_create(&log_path).unwrap())
-            .spawn()?;
+        let mut process =
+            Command::new(std::env::current_exe().expect("Expected to be running an exe"))
+                // Disable coloring in bevy logs, since they are written to a `.txt` file
+                .env("NO_COLOR", "1")
+                // Enable verbose backtraces
+                .env("BEVY_BACKTRACE", "full")
+                .arg("match")
+                .args(
+                    ShipTemplateRegistry::get()
+                        .mods_dir()
+                        .map(|dir| [OsStr::new("--ship-mods-dir"), dir.as_os_str()])
+                        .into_iter()
+                        .flatten(),
+                )
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(log_create(&log_path).unwrap())
+                .spawn()?;
 
         let stdin = process.stdin.take().unwrap();
         let stdout = process.stdout.take().unwrap();
@@ -0,0 +1,6 @@
+extern crate wrts_match;
+
+fn main() {
+    // Run registered benchmarks.
+    divan::main();
+}
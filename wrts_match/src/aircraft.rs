@@ -0,0 +1,296 @@
+//! Aircraft launched by ships, which circle their ship until they land,
+//! and the AA guns which shoot down enemy aircraft
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use wrts_match_shared::ship_template::Speed;
+use wrts_messaging::ClientId;
+
+use crate::{
+    GAME_SCALE, Health, MATCH_EVENTS, Team, Velocity,
+    networking::SharedEntityTracking,
+    ship::{Ship, TurretStates},
+    spawn_entity::DespawnNetworkedEntityCommand,
+};
+
+/// How far from their ship aircraft circle once they've climbed out to it
+const ORBIT_RADIUS: f32 = 1_500.;
+/// How fast aircraft fly, before [GAME_SCALE]
+const AIRCRAFT_SPEED: Speed = Speed::from_kts(250.);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AircraftKind {
+    /// Launched by the `SpotterPlane` consumable, which ends early if it's shot down
+    Spotter,
+}
+
+impl AircraftKind {
+    fn max_health(self) -> f32 {
+        match self {
+            AircraftKind::Spotter => 1_200.,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+#[require(Team, Velocity, AircraftSpawnSent)]
+pub struct Aircraft {
+    pub owning_ship: Entity,
+    pub kind: AircraftKind,
+    pub health: f32,
+    /// How far the aircraft is from its ship,
+    /// which grows to [ORBIT_RADIUS] after it's launched
+    orbit_radius: f32,
+    /// The direction from the ship to the aircraft, in radians
+    orbit_angle: f32,
+}
+
+impl Aircraft {
+    /// An aircraft taking off from `owning_ship`, heading out in the ship's direction
+    pub fn launched_from(owning_ship: Entity, ship_trans: &Transform, kind: AircraftKind) -> Self {
+        Self {
+            owning_ship,
+            kind,
+            health: kind.max_health(),
+            orbit_radius: 0.,
+            orbit_angle: ship_trans.rotation.to_euler(EulerRot::ZXY).0,
+        }
+    }
+}
+
+/// The clients which have been sent a `Match2Client::SpawnAircraft` for this aircraft.
+/// Aircraft are only sent to clients which can see their ship, so the rest are sent it
+/// once they spot the ship
+#[derive(Component, Debug, Clone, Default)]
+pub struct AircraftSpawnSent(pub HashSet<ClientId>);
+
+/// Flies aircraft out from their ship, then around it counterclockwise.
+/// Aircraft whose ship has been destroyed have nowhere to land, so are lost with it
+pub(crate) fn fly_aircraft(
+    mut commands: Commands,
+    aircraft: Query<(Entity, &mut Aircraft, &mut Transform, &mut Velocity), Without<Ship>>,
+    ships: Query<(&Transform, &Velocity), With<Ship>>,
+    time: Res<Time>,
+) {
+    let speed = AIRCRAFT_SPEED.mps() * GAME_SCALE as f32;
+    for (entity, mut plane, mut trans, mut vel) in aircraft {
+        let Ok((ship_trans, ship_vel)) = ships.get(plane.owning_ship) else {
+            commands.queue(DespawnNetworkedEntityCommand { entity });
+            continue;
+        };
+
+        let flight_dir = if plane.orbit_radius < ORBIT_RADIUS {
+            plane.orbit_radius = (plane.orbit_radius + speed * time.delta_secs()).min(ORBIT_RADIUS);
+            Vec2::from_angle(plane.orbit_angle)
+        } else {
+            plane.orbit_angle += speed / ORBIT_RADIUS * time.delta_secs();
+            Vec2::from_angle(plane.orbit_angle).perp()
+        };
+        let pos = ship_trans.translation.truncate()
+            + Vec2::from_angle(plane.orbit_angle) * plane.orbit_radius;
+        trans.translation = pos.extend(trans.translation.z);
+        trans.rotation = Quat::from_rotation_z(flight_dir.to_angle());
+        vel.0 = ship_vel.0 + (flight_dir * speed).extend(0.);
+    }
+}
+
+/// Every AA turret damages each enemy aircraft within its `aa_range`,
+/// shooting down any left without health
+pub(crate) fn defend_against_aircraft(
+    mut commands: Commands,
+    ships: Query<(&TurretStates, &Team, &Health)>,
+    aircraft: Query<(Entity, &mut Aircraft, &Team, &Transform)>,
+    shared_entities: Res<SharedEntityTracking>,
+    time: Res<Time>,
+) {
+    for (entity, mut plane, plane_team, plane_trans) in aircraft {
+        if plane.health <= 0. {
+            continue;
+        }
+        let plane_pos = plane_trans.translation.truncate();
+        let dps = ships
+            .iter()
+            .filter(|(_, team, health)| *team != plane_team && !health.0.is_destroyed())
+            .flat_map(|(turret_states, ..)| &turret_states.states)
+            .filter_map(|state| {
+                let aa = state.template.aa?;
+                (state.absolute_pos.distance(plane_pos) <= aa.aa_range).then_some(aa.aa_dps)
+            })
+            .sum::<f32>();
+        plane.health -= dps * GAME_SCALE as f32 * time.delta_secs();
+
+        if plane.health <= 0. {
+            debug!(
+                target: MATCH_EVENTS,
+                event = "aircraft_shot_down",
+                time = time.elapsed_secs(),
+                client = %plane_team.0,
+                aircraft = ?shared_entities.get_by_local(entity),
+                kind = ?plane.kind,
+            );
+            commands.queue(DespawnNetworkedEntityCommand { entity });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use wrts_match_shared::ship_template::ShipTemplateId;
+    use wrts_messaging::{Client2Match, Match2Client, Message};
+
+    use crate::{
+        aircraft::{Aircraft, ORBIT_RADIUS},
+        ship::{Ship, SpotterPlaneFlying, SpotterPlaneState},
+        test_harness::TestMatch,
+    };
+
+    #[test]
+    fn test_spotter_plane_circles_its_ship_until_shot_down() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let ship = harness
+            .ships_of(client)
+            .into_iter()
+            .find(|&ship| harness.get::<Ship>(ship).template.id == ShipTemplateId::bismarck())
+            .unwrap();
+        let local = harness.local(ship);
+        let mut plane_state = harness
+            .app
+            .world_mut()
+            .get_mut::<SpotterPlaneState>(local)
+            .unwrap();
+        let cooldown = plane_state.cooldown_timer.duration();
+        plane_state.cooldown_timer.tick(cooldown);
+
+        harness.send(client, Client2Match::UseConsumableSpotterPlane { ship });
+        harness.tick();
+        let plane = harness
+            .sent
+            .iter()
+            .find_map(|msg| match msg.msg {
+                Message::Match2Client(Match2Client::SpawnAircraft {
+                    id, owning_ship, ..
+                }) if msg.client == other && owning_ship == ship => Some(id),
+                _ => None,
+            })
+            .unwrap();
+        let plane_local = harness.local(plane);
+
+        // Long enough to climb out to its orbit, but not to land
+        harness.ticks(64 * 25);
+        let orbit_offset = |harness: &TestMatch| {
+            harness.get::<Transform>(plane).translation.truncate()
+                - harness.get::<Transform>(ship).translation.truncate()
+        };
+        let offset_before = orbit_offset(&harness);
+        assert!((offset_before.length() - ORBIT_RADIUS).abs() < 1.);
+        harness.ticks(64);
+        let offset_after = orbit_offset(&harness);
+        assert!((offset_after.length() - ORBIT_RADIUS).abs() < 1.);
+        assert!(offset_before.angle_to(offset_after) > 0.);
+
+        // An enemy ship with AA guns sails under it
+        let enemy = harness
+            .ships_of(other)
+            .into_iter()
+            .find(|&ship| {
+                harness
+                    .get::<Ship>(ship)
+                    .template
+                    .turret_instances
+                    .iter()
+                    .any(|instance| instance.turret_template().aa.is_some())
+            })
+            .unwrap();
+        let enemy_local = harness.local(enemy);
+        let plane_pos = harness.get::<Transform>(plane).translation;
+        harness
+            .app
+            .world_mut()
+            .get_mut::<Transform>(enemy_local)
+            .unwrap()
+            .translation = plane_pos;
+        harness.ticks(64 * 20);
+
+        assert!(harness.app.world().get::<Aircraft>(plane_local).is_none());
+        assert!(harness.sent.iter().any(|msg| matches!(
+            &msg.msg,
+            Message::Match2Client(Match2Client::DestroyEntities(ids)) if ids.contains(&plane)
+        )));
+        assert!(
+            harness
+                .app
+                .world()
+                .get::<SpotterPlaneFlying>(local)
+                .is_none()
+        );
+        assert_eq!(
+            harness
+                .get::<Ship>(ship)
+                .gun_range_modifiers
+                .spotter_plane_bonus,
+            None
+        );
+    }
+
+    #[test]
+    fn test_aircraft_of_hidden_ships_arent_sent_to_enemies() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let ship = harness
+            .ships_of(client)
+            .into_iter()
+            .find(|&ship| harness.get::<Ship>(ship).template.id == ShipTemplateId::bismarck())
+            .unwrap();
+        let local = harness.local(ship);
+        let world = harness.app.world_mut();
+        // Out of sight of every ship on `other`'s team
+        world.get_mut::<Transform>(local).unwrap().translation = vec3(-20_000., -20_000., 0.);
+        let mut plane_state = world.get_mut::<SpotterPlaneState>(local).unwrap();
+        let cooldown = plane_state.cooldown_timer.duration();
+        plane_state.cooldown_timer.tick(cooldown);
+
+        harness.send(client, Client2Match::UseConsumableSpotterPlane { ship });
+        harness.tick();
+        let spawns_sent_to = |harness: &TestMatch, to| {
+            harness
+                .sent
+                .iter()
+                .filter_map(|msg| match msg.msg {
+                    Message::Match2Client(Match2Client::SpawnAircraft { id, .. })
+                        if msg.client == to =>
+                    {
+                        Some(id)
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let [plane] = spawns_sent_to(&harness, client)[..] else {
+            panic!("The owner should be sent its own aircraft");
+        };
+        harness.ticks(8);
+        assert!(spawns_sent_to(&harness, other).is_empty());
+        assert!(!harness.sent.iter().any(|msg| msg.client == other
+            && matches!(
+                &msg.msg,
+                Message::Match2Client(Match2Client::WorldSnapshot { entities })
+                    if entities.iter().any(|update| update.id == plane)
+            )));
+
+        // Sailing into sight of an enemy shows its aircraft too, just the once
+        let enemy_pos = harness
+            .get::<Transform>(harness.ships_of(other)[0])
+            .translation;
+        harness
+            .app
+            .world_mut()
+            .get_mut::<Transform>(local)
+            .unwrap()
+            .translation = enemy_pos + vec3(1_000., 0., 0.);
+        harness.ticks(8);
+        assert_eq!(spawns_sent_to(&harness, other), vec![plane]);
+    }
+}
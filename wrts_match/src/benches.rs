@@ -0,0 +1,193 @@
+//! Benchmarks for the per-frame match systems
+//!
+//! Registered with `divan` and run through `benches/bench.rs`
+
+use std::{sync::mpsc, time::Duration};
+
+use bevy::prelude::*;
+use itertools::Itertools;
+use wrts_match_shared::{
+    formulas::ballistics::ShellArc,
+    ship_template::{ShipTemplate, ShipTemplateId},
+};
+use wrts_messaging::{ClientId, ClientSharedInfo};
+
+use crate::{
+    Bullet, FireTarget, GameRules, MatchRng, Team, Velocity, aim_turrets,
+    detection::DetectionStatus,
+    fire_bullets,
+    networking::{ClientInfo, MessagesSend, SharedEntityTracking},
+    ship::{Ship, TurretAimInfo, TurretState, TurretStates},
+};
+
+/// Builds a world with `ships_per_team` copies of `ship_base` on each team,
+/// all detected and with each ship's `FireTarget` set to an enemy ship
+fn make_battle_world(ship_base: ShipTemplateId, ships_per_team: usize) -> World {
+    let mut world = World::new();
+    world.init_resource::<GameRules>();
+    let mut time = Time::<()>::default();
+    time.advance_by(Duration::from_secs_f32(1. / 64.));
+    world.insert_resource(time);
+
+    let teams = [ClientId(0), ClientId(1)].map(|id| {
+        world.spawn(ClientInfo {
+            info: ClientSharedInfo {
+                id,
+                user: format!("{id}"),
+            },
+        });
+        Team(id)
+    });
+
+    let template: &'static ShipTemplate = ship_base.to_template();
+    let ships_by_team = teams.map(|team| {
+        let x = if team == teams[0] { 6_000. } else { -6_000. };
+        let enemy = if team == teams[0] { teams[1] } else { teams[0] };
+        (0..ships_per_team)
+            .map(|ship_idx| {
+                let pos = vec2(x, 1_000. * ship_idx as f32);
+                world
+                    .spawn((
+                        Ship {
+                            template,
+                            curr_speed: 0.,
+                            torpedo_reloads: vec![],
+                            shell_arc: ShellArc::Low,
+                            gun_range_modifiers: default(),
+                        },
+                        TurretStates {
+                            states: template
+                                .turret_instances
+                                .iter()
+                                .map(|t| TurretState {
+                                    template: t.turret_template(),
+                                    dir: t.default_dir,
+                                    reload_timer: Timer::from_seconds(
+                                        t.turret_template().reload_secs,
+                                        TimerMode::Once,
+                                    ),
+                                    absolute_pos: t.absolute_pos(pos, Quat::IDENTITY),
+                                    aim_info: TurretAimInfo::NoValidTarget {},
+                                    accuracy: default(),
+                                    secondary_target: None,
+                                })
+                                .collect_vec(),
+                        },
+                        DetectionStatus {
+                            is_detected: true,
+                            detected_by: vec![enemy],
+                            detection_increased_by_firing: Timer::new(
+                                Duration::ZERO,
+                                TimerMode::Once,
+                            ),
+                            detection_increased_by_firing_at_range: 0.,
+                        },
+                        team,
+                        Velocity(Vec3::X * 10.),
+                        Transform::from_translation(pos.extend(0.)),
+                    ))
+                    .id()
+            })
+            .collect_vec()
+    });
+
+    for (team_idx, ships) in ships_by_team.iter().enumerate() {
+        let enemies = &ships_by_team[team_idx ^ 1];
+        for (ship_idx, &ship) in ships.iter().enumerate() {
+            world.entity_mut(ship).insert(FireTarget {
+                ship: enemies[ship_idx % enemies.len()],
+            });
+        }
+    }
+
+    world
+}
+
+#[divan::bench(args = [1, 4, 16])]
+fn aim_turrets_nagato(bencher: divan::Bencher, ships_per_team: usize) {
+    let mut world = make_battle_world(ShipTemplateId::nagato(), ships_per_team);
+    let mut system = IntoSystem::into_system(aim_turrets);
+    system.initialize(&mut world);
+    bencher.bench_local(|| {
+        system.run((), &mut world);
+    });
+}
+
+/// Every Nagato firing each turret as soon as it's aimed, which should stop
+/// spawning bullets at `GameRules::max_bullets_in_flight` rather than flooding the world
+#[divan::bench(args = [4, 16])]
+fn fire_bullets_nagato(bencher: divan::Bencher, ships_per_team: usize) {
+    let mut world = make_battle_world(ShipTemplateId::nagato(), ships_per_team);
+
+    // Spawned bullets are sent to the clients, so the messages need to go somewhere
+    let (msgs_tx, msgs_rx) = mpsc::sync_channel(1024);
+    std::thread::spawn(move || for _ in msgs_rx {});
+    world.insert_resource(MessagesSend::new(msgs_tx));
+    world.insert_resource(MatchRng::from_seed(0));
+    let mut shared_entities = SharedEntityTracking::default();
+    let ships = world
+        .query_filtered::<Entity, With<Ship>>()
+        .iter(&world)
+        .collect_vec();
+    for ship in ships {
+        shared_entities.insert(ship);
+    }
+    world.insert_resource(shared_entities);
+
+    let mut aim_system = IntoSystem::into_system(aim_turrets);
+    aim_system.initialize(&mut world);
+    let mut fire_system = IntoSystem::into_system(fire_bullets);
+    fire_system.initialize(&mut world);
+
+    // Give the turrets time to turn onto their targets
+    for _ in 0..1_000 {
+        aim_system.run((), &mut world);
+    }
+
+    bencher.bench_local(|| {
+        for mut turret_states in world.query::<&mut TurretStates>().iter_mut(&mut world) {
+            for state in &mut turret_states.states {
+                let reload = state.reload_timer.duration();
+                state.reload_timer.reset();
+                state.reload_timer.tick(reload);
+            }
+        }
+        aim_system.run((), &mut world);
+        fire_system.run((), &mut world);
+        fire_system.apply_deferred(&mut world);
+    });
+
+    let bullets = world
+        .query_filtered::<(), With<Bullet>>()
+        .iter(&world)
+        .count();
+    assert!(bullets <= world.resource::<GameRules>().max_bullets_in_flight);
+}
+
+/// [aim_turrets_nagato], but with every turret's template resolved through
+/// [TurretInstance::turret_template] before each frame, as `aim_turrets` did
+/// before caching it on [TurretState]
+///
+/// [TurretInstance::turret_template]: wrts_match_shared::ship_template::TurretInstance::turret_template
+#[divan::bench(args = [1, 4, 16])]
+fn aim_turrets_nagato_uncached_templates(bencher: divan::Bencher, ships_per_team: usize) {
+    let mut world = make_battle_world(ShipTemplateId::nagato(), ships_per_team);
+    let mut system = IntoSystem::into_system(aim_turrets);
+    system.initialize(&mut world);
+    bencher.bench_local(|| {
+        for (ship, mut turret_states) in world
+            .query::<(&Ship, &mut TurretStates)>()
+            .iter_mut(&mut world)
+        {
+            for (turret, state) in ship
+                .template
+                .turret_instances
+                .iter()
+                .zip(&mut turret_states.states)
+            {
+                state.template = divan::black_box(turret.turret_template());
+            }
+        }
+        system.run((), &mut world);
+    });
+}
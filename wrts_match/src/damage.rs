@@ -0,0 +1,118 @@
+//! Attribution of damage to the ship that dealt it,
+//! for the kill feed and per-player stats
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use wrts_messaging::{ClientId, DamageSource, Match2Client, Message, WrtsMatchMessage};
+
+use crate::{
+    MATCH_EVENTS, Team,
+    detection::DetectionStatus,
+    networking::{ClientInfo, MessagesSend, SharedEntityTracking, Spectator},
+};
+
+/// The damage each team has dealt to enemy ships so far, for the end of match scoreboard
+#[derive(Resource, Debug, Default)]
+pub struct DamageDealt(pub HashMap<ClientId, TeamDamage>);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TeamDamage {
+    pub damage: f64,
+    pub kills: u32,
+}
+
+/// Notifies the clients which can see `target` that it took damage, and whether it was destroyed
+/// by it. Clients which can't see `attacker` aren't told which ship it was
+///
+/// Must be queued *before* `target` is despawned, so that its shared id can still be resolved.
+/// `attacker` may have been destroyed already, in which case only `attacker_team` is credited.
+/// `damage` should only be what the target had health left for, so overkill isn't counted
+pub struct ReportDamageCommand {
+    pub target: Entity,
+    pub attacker: Entity,
+    pub attacker_team: Team,
+    pub source: DamageSource,
+    pub damage: f64,
+    pub destroyed_target: bool,
+}
+
+impl Command for ReportDamageCommand {
+    fn apply(self, world: &mut World) -> () {
+        let shared_entities = world.resource::<SharedEntityTracking>();
+        let Some(target) = shared_entities.get_by_local(self.target) else {
+            return;
+        };
+        let attacker = shared_entities.get_by_local(self.attacker);
+        let time = world.resource::<Time>().elapsed_secs();
+        debug!(
+            target: MATCH_EVENTS,
+            event = "hit",
+            time,
+            client = %self.attacker_team.0,
+            attacker = ?attacker,
+            ship = ?target,
+            source = ?self.source,
+            damage = self.damage,
+        );
+        if self.destroyed_target {
+            debug!(
+                target: MATCH_EVENTS,
+                event = "ship_destroyed",
+                time,
+                client = %self.attacker_team.0,
+                killer = ?attacker,
+                ship = ?target,
+                source = ?self.source,
+            );
+        }
+
+        // Friendly fire isn't credited
+        if world.get::<Team>(self.target) != Some(&self.attacker_team) {
+            let mut dealt = world.resource_mut::<DamageDealt>();
+            let team_damage = dealt.0.entry(self.attacker_team.0).or_default();
+            team_damage.damage += self.damage;
+            team_damage.kills += self.destroyed_target as u32;
+        }
+
+        let mut clients = world.query::<(&ClientInfo, Has<Spectator>)>();
+        let Some(&target_team) = world.get::<Team>(self.target) else {
+            return;
+        };
+        let target_detection = world.get::<DetectionStatus>(self.target);
+        let attacker_detection = world.get::<DetectionStatus>(self.attacker);
+        let msgs_tx = world.resource::<MessagesSend>();
+        for (cl, spectator) in clients.iter(world) {
+            let visible = |detection: Option<&DetectionStatus>, team: Team| {
+                spectator
+                    || detection
+                        .is_some_and(|detection| detection.is_visible_to(team, Team(cl.info.id)))
+            };
+            if !visible(target_detection, target_team) {
+                continue;
+            }
+            let attacker = attacker.filter(|_| visible(attacker_detection, self.attacker_team));
+            msgs_tx.send(WrtsMatchMessage {
+                client: cl.info.id,
+                msg: Message::Match2Client(Match2Client::DamageDealt {
+                    target,
+                    attacker_team: self.attacker_team.0,
+                    attacker,
+                    source: self.source,
+                    damage: self.damage,
+                }),
+            });
+            if self.destroyed_target {
+                msgs_tx.send(WrtsMatchMessage {
+                    client: cl.info.id,
+                    msg: Message::Match2Client(Match2Client::ShipDestroyed {
+                        ship: target,
+                        killer_team: self.attacker_team.0,
+                        killer: attacker,
+                        source: self.source,
+                    }),
+                });
+            }
+        }
+    }
+}
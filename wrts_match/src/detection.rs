@@ -2,9 +2,9 @@ use bevy::prelude::*;
 use wrts_messaging::{Match2Client, Message, WrtsMatchMessage};
 
 use crate::{
-    MoveEntitiesSystem, Team, math_utils,
-    networking::{ClientInfo, MessagesSend, SharedEntityTracking},
-    ship::{Ship, SmokePuff},
+    MoveEntitiesSystem, Team, Torpedo, math_utils,
+    networking::{ClientInfo, MessagesSend, SharedEntityTracking, Spectator, UpdateClientsSystem},
+    ship::{Ship, SmokePuff, SpotterPlaneFlying},
 };
 
 const MIN_DETECTION: f32 = 2_000.;
@@ -16,8 +16,13 @@ pub struct DetectionPlugin;
 
 impl Plugin for DetectionPlugin {
     fn build(&self, app: &mut App) {
-        app.configure_sets(FixedUpdate, DetectionSystem.after(MoveEntitiesSystem))
-            .add_systems(FixedUpdate, update_detection.in_set(DetectionSystem));
+        app.configure_sets(
+            FixedUpdate,
+            DetectionSystem
+                .after(MoveEntitiesSystem)
+                .before(UpdateClientsSystem),
+        )
+        .add_systems(FixedUpdate, update_detection.in_set(DetectionSystem));
     }
 }
 
@@ -29,11 +34,31 @@ pub struct CanDetect;
 
 #[derive(Component, Debug, Clone)]
 pub struct DetectionStatus {
+    /// Whether any other team detects this
     pub is_detected: bool,
+    /// Every other team detecting this. Each team only sees what it detects itself
+    pub detected_by: Vec<Team>,
     pub detection_increased_by_firing: Timer,
     pub detection_increased_by_firing_at_range: f32,
 }
 
+impl DetectionStatus {
+    /// Whether clients on `viewer` can see this, which they always can if it's on their team
+    pub fn is_visible_to(&self, own_team: Team, viewer: Team) -> bool {
+        own_team == viewer || self.detected_by.contains(&viewer)
+    }
+
+    /// The detection `viewer` is sent through `Match2Client::SetDetection`.
+    /// For their own team, this is whether any enemy detects it
+    pub fn detected_for(&self, own_team: Team, viewer: Team) -> bool {
+        if own_team == viewer {
+            self.is_detected
+        } else {
+            self.detected_by.contains(&viewer)
+        }
+    }
+}
+
 fn detector_detects_detectee(
     detector_pos: Vec2,
 
@@ -41,6 +66,7 @@ fn detector_detects_detectee(
     base_detection: f32,
     base_detection_when_firing_through_smoke: f32,
     detection_increased_by_firing: Option<f32>,
+    spotting_range: Option<f32>,
     smoke_puffs: Query<(&SmokePuff, &Transform)>,
 ) -> bool {
     let blocked_by_smoke = math_utils::cast_line_segment(
@@ -67,13 +93,28 @@ fn detector_detects_detectee(
         detection = MIN_DETECTION;
     }
 
+    // The detector's spotter plane can't see through smoke either
+    if !blocked_by_smoke {
+        detection = detection.max(spotting_range.unwrap_or(0.));
+    }
+
     detection = detection.max(MIN_DETECTION);
 
     detector_pos.distance(pos) <= detection
 }
 
+/// Torpedoes are spotted by the lookouts of each detecting ship,
+/// so their visibility depends on the potential victim rather than the torpedo
+fn detector_detects_torpedo(
+    detector_pos: Vec2,
+    detector_torpedo_detection: f32,
+    pos: Vec2,
+) -> bool {
+    detector_pos.distance(pos) <= detector_torpedo_detection
+}
+
 fn update_detection(
-    detectors: Query<(&Team, &Transform), With<CanDetect>>,
+    detectors: Query<(&Team, &Transform, Option<&Ship>, Has<SpotterPlaneFlying>), With<CanDetect>>,
     detectees: Query<(
         Entity,
         &Team,
@@ -81,9 +122,10 @@ fn update_detection(
         &BaseDetection,
         &mut DetectionStatus,
         Option<&Ship>,
+        Option<&Torpedo>,
     )>,
     smoke_puffs: Query<(&SmokePuff, &Transform)>,
-    clients: Query<&ClientInfo>,
+    clients: Query<(&ClientInfo, Has<Spectator>)>,
     shared_entities: Res<SharedEntityTracking>,
     msgs_tx: Res<MessagesSend>,
     time: Res<Time>,
@@ -95,6 +137,7 @@ fn update_detection(
         base_detection,
         mut detectee_status,
         detectee_is_ship,
+        detectee_is_torpedo,
     ) in detectees
     {
         let old_detectee_status = detectee_status.clone();
@@ -110,38 +153,234 @@ fn update_detection(
             .map(|ship| ship.template.detection_when_firing_through_smoke)
             .unwrap_or(f32::MAX);
 
-        detectee_status.is_detected = detectors.iter().any(|(detector_team, detector_trans)| {
-            if detector_team == detectee_team {
-                return false;
+        let mut detected_by = vec![];
+        for (detector_team, detector_trans, detector_ship, detector_plane_flying) in &detectors {
+            if detector_team == detectee_team || detected_by.contains(detector_team) {
+                continue;
             }
-            detector_detects_detectee(
-                detector_trans.translation.truncate(),
-                detectee_trans.translation.truncate(),
-                base_detection.0,
-                base_detection_when_firing_through_smoke,
-                detection_increased_by_firing
-                    .then_some(detectee_status.detection_increased_by_firing_at_range),
-                smoke_puffs,
-            )
-        });
+            let detects = if detectee_is_torpedo.is_some() {
+                detector_detects_torpedo(
+                    detector_trans.translation.truncate(),
+                    detector_ship
+                        .map(|ship| ship.template.torpedo_detection)
+                        .unwrap_or(base_detection.0),
+                    detectee_trans.translation.truncate(),
+                )
+            } else {
+                detector_detects_detectee(
+                    detector_trans.translation.truncate(),
+                    detectee_trans.translation.truncate(),
+                    base_detection.0,
+                    base_detection_when_firing_through_smoke,
+                    detection_increased_by_firing
+                        .then_some(detectee_status.detection_increased_by_firing_at_range),
+                    detector_ship
+                        .filter(|_| detector_plane_flying)
+                        .and_then(|ship| ship.template.consumables.spotter_plane())
+                        .map(|spotter_plane| spotter_plane.spotting_range),
+                    smoke_puffs,
+                )
+            };
+            if detects {
+                detected_by.push(*detector_team);
+            }
+        }
+        detectee_status.is_detected = !detected_by.is_empty();
+        detectee_status.detected_by = detected_by;
 
         if !detectee_status.is_detected {
             detectee_status.detection_increased_by_firing =
                 Timer::from_seconds(0., TimerMode::Once);
         }
 
-        if old_detectee_status.is_detected != detectee_status.is_detected {
-            if let Some(shared) = shared_entities.get_by_local(detectee) {
-                for cl in clients {
-                    msgs_tx.send(WrtsMatchMessage {
-                        client: cl.info.id,
-                        msg: Message::Match2Client(Match2Client::SetDetection {
-                            id: shared,
-                            currently_detected: detectee_status.is_detected,
-                        }),
-                    });
-                }
+        let Some(shared) = shared_entities.get_by_local(detectee) else {
+            continue;
+        };
+        for (cl, spectator) in clients {
+            let viewer = Team(cl.info.id);
+            // Spectators see everything, so only need telling once, when the entity is new
+            let (previously_detected, currently_detected) = if spectator {
+                (!detectee_status.is_added(), true)
+            } else {
+                (
+                    old_detectee_status.detected_for(*detectee_team, viewer),
+                    detectee_status.detected_for(*detectee_team, viewer),
+                )
+            };
+            if previously_detected == currently_detected {
+                continue;
             }
+            msgs_tx.send(WrtsMatchMessage {
+                client: cl.info.id,
+                msg: Message::Match2Client(Match2Client::SetDetection {
+                    id: shared,
+                    currently_detected,
+                }),
+            });
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+    use wrts_match_shared::ship_template::ShipTemplateId;
+    use wrts_messaging::{ClientId, ClientSharedInfo, Match2Client, Message};
+
+    use crate::{
+        Team, Torpedo,
+        detection::{
+            BaseDetection, CanDetect, DetectionStatus, detector_detects_torpedo, update_detection,
+        },
+        networking::{ClientInfo, MessagesSend, SharedEntityTracking},
+        ship::Ship,
+    };
+
+    #[test]
+    fn test_each_team_only_sees_what_it_detects() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<SharedEntityTracking>();
+        let (msgs_tx, msgs_rx) = mpsc::sync_channel(64);
+        world.insert_resource(MessagesSend::new(msgs_tx));
+        let [near, far, owner] = [0, 1, 2].map(|id| {
+            let id = ClientId(id);
+            world.spawn(ClientInfo {
+                info: ClientSharedInfo {
+                    id,
+                    user: format!("{id}"),
+                },
+            });
+            Team(id)
+        });
+
+        world.spawn((near, CanDetect, Transform::from_xyz(3_000., 0., 0.)));
+        world.spawn((far, CanDetect, Transform::from_xyz(-20_000., 0., 0.)));
+        let detectee = world
+            .spawn((
+                owner,
+                Transform::default(),
+                BaseDetection(5_000.),
+                DetectionStatus {
+                    is_detected: false,
+                    detected_by: vec![],
+                    detection_increased_by_firing: Timer::new(Duration::ZERO, TimerMode::Once),
+                    detection_increased_by_firing_at_range: 0.,
+                },
+            ))
+            .id();
+        let shared = world
+            .resource_mut::<SharedEntityTracking>()
+            .insert(detectee);
+
+        world.run_system_once(update_detection).unwrap();
+
+        let status = world.get::<DetectionStatus>(detectee).unwrap();
+        assert_eq!(status.detected_by, vec![near]);
+        assert!(status.is_visible_to(owner, near));
+        assert!(!status.is_visible_to(owner, far));
+        let mut sent = msgs_rx
+            .try_iter()
+            .filter_map(|msg| match msg.msg {
+                Message::Match2Client(Match2Client::SetDetection {
+                    id,
+                    currently_detected,
+                }) if id == shared => Some((msg.client, currently_detected)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        sent.sort();
+        // The far team never saw it, so isn't told anything
+        assert_eq!(sent, vec![(near.0, true), (owner.0, true)]);
+    }
+
+    #[test]
+    fn test_torpedo_detection_depends_on_detector() {
+        let destroyer = ShipTemplateId::oland().to_template();
+        let heavy_cruiser = ShipTemplateId::hipper().to_template();
+        assert!(destroyer.torpedo_detection < heavy_cruiser.torpedo_detection);
+
+        let torp_pos = vec2(
+            0.,
+            0.5 * (destroyer.torpedo_detection + heavy_cruiser.torpedo_detection),
+        );
+        assert!(!detector_detects_torpedo(
+            Vec2::ZERO,
+            destroyer.torpedo_detection,
+            torp_pos
+        ));
+        assert!(detector_detects_torpedo(
+            Vec2::ZERO,
+            heavy_cruiser.torpedo_detection,
+            torp_pos
+        ));
+    }
+
+    #[test]
+    fn test_each_ship_spots_torpedoes_within_its_own_range() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<SharedEntityTracking>();
+        let (msgs_tx, _msgs_rx) = mpsc::sync_channel(64);
+        world.insert_resource(MessagesSend::new(msgs_tx));
+
+        let destroyer = ShipTemplateId::oland().to_template();
+        let heavy_cruiser = ShipTemplateId::hipper().to_template();
+        let range = 0.5 * (destroyer.torpedo_detection + heavy_cruiser.torpedo_detection);
+
+        // Each ship has an enemy torpedo the same distance away, far from the other ship
+        let [near_destroyer, near_cruiser] =
+            [(destroyer, -20_000.), (heavy_cruiser, 20_000.)].map(|(template, x)| {
+                world.spawn((
+                    Ship {
+                        template,
+                        curr_speed: 0.,
+                        torpedo_reloads: vec![],
+                        shell_arc: default(),
+                        gun_range_modifiers: default(),
+                    },
+                    CanDetect,
+                    Team(ClientId(0)),
+                    Transform::from_xyz(x, 0., 0.),
+                ));
+                world
+                    .spawn((
+                        Torpedo {
+                            owning_ship: Entity::PLACEHOLDER,
+                            damage: 0.,
+                            inital_pos: vec2(x, range),
+                            max_range: 10_000.,
+                        },
+                        Team(ClientId(1)),
+                        Transform::from_xyz(x, range, 0.),
+                        BaseDetection(2_000.),
+                        DetectionStatus {
+                            is_detected: false,
+                            detected_by: vec![],
+                            detection_increased_by_firing: Timer::new(
+                                Duration::ZERO,
+                                TimerMode::Once,
+                            ),
+                            detection_increased_by_firing_at_range: 0.,
+                        },
+                    ))
+                    .id()
+            });
+        world.run_system_once(update_detection).unwrap();
+
+        assert!(
+            !world
+                .get::<DetectionStatus>(near_destroyer)
+                .unwrap()
+                .is_detected
+        );
+        assert!(
+            world
+                .get::<DetectionStatus>(near_cruiser)
+                .unwrap()
+                .is_detected
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Reloading modded ship templates while a match is running, for balance iteration
+//!
+//! Only built with the `hot_reload` feature. Rather than moving every ship
+//! onto reference counted templates, each reloaded template is leaked, so that
+//! [Ship::template] stays a `&'static ShipTemplate` in every build.
+//! This leaks one template per reload, which is fine for a dev build
+//!
+//! Only the match's copy is reloaded, so clients keep showing the stats they loaded.
+//! Anything looked up through the registry, such as [TurretInstance::absolute_pos],
+//! also keeps the stats from when the match started
+//!
+//! [TurretInstance::absolute_pos]: wrts_match_shared::ship_template::TurretInstance::absolute_pos
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::prelude::*;
+use wrts_match_shared::ship_template::{ShipTemplate, ShipTemplateId, ShipTemplateRegistry};
+
+use crate::ship::{Ship, TurretStates};
+
+/// How often the mods directory is checked for changed files
+const POLL_INTERVAL_SECS: f32 = 1.;
+
+pub struct HotReloadPlugin;
+
+impl Plugin for HotReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TemplateFileTimes>()
+            .add_systems(Update, reload_changed_templates);
+    }
+}
+
+/// When each template file was last modified, as of the last poll
+#[derive(Resource, Debug, Default)]
+struct TemplateFileTimes(HashMap<PathBuf, SystemTime>);
+
+fn reload_changed_templates(
+    mut commands: Commands,
+    mut file_times: ResMut<TemplateFileTimes>,
+    mut poll_timer: Local<Option<Timer>>,
+    time: Res<Time>,
+) {
+    let poll_timer = poll_timer
+        .get_or_insert_with(|| Timer::from_seconds(POLL_INTERVAL_SECS, TimerMode::Repeating));
+    if !poll_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Some(mods_dir) = ShipTemplateRegistry::get().mods_dir() else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir(mods_dir) else {
+        return;
+    };
+
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        if path.extension().is_none_or(|ext| ext != "ron") {
+            continue;
+        }
+        let Ok(modified) = path.metadata().and_then(|meta| meta.modified()) else {
+            continue;
+        };
+        // Files are only reloaded once they change after the first poll
+        let Some(prev_modified) = file_times.0.insert(path.clone(), modified) else {
+            continue;
+        };
+        if prev_modified == modified {
+            continue;
+        }
+
+        match load_changed_template(&path) {
+            Ok(template) => {
+                info!("Reloaded ship template `{}`", path.display());
+                commands.queue(ApplyReloadedTemplateCommand {
+                    template: Box::leak(Box::new(template)),
+                });
+            }
+            Err(err) => warn!("{err:#}"),
+        }
+    }
+}
+
+fn load_changed_template(path: &Path) -> anyhow::Result<ShipTemplate> {
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let id = ShipTemplateId::from_name(name).ok_or_else(|| {
+        anyhow::anyhow!(
+            "`{}` wasn't loaded when the match started, so can't be reloaded",
+            path.display()
+        )
+    })?;
+    ShipTemplate::load_file(id, path)
+}
+
+/// Points every ship using `template`'s id at `template` instead
+///
+/// A ship whose turrets no longer match the template's is skipped,
+/// since its [TurretStates] can't be carried over
+pub struct ApplyReloadedTemplateCommand {
+    pub template: &'static ShipTemplate,
+}
+
+impl Command for ApplyReloadedTemplateCommand {
+    fn apply(self, world: &mut World) -> () {
+        let template = self.template;
+        let mut ships = world.query::<(Entity, &mut Ship, &mut TurretStates)>();
+        for (entity, mut ship, mut turret_states) in ships.iter_mut(world) {
+            if ship.template.id != template.id {
+                continue;
+            }
+            if turret_states.states.len() != template.turret_instances.len() {
+                warn!(
+                    "Ship {entity} wasn't reloaded, since `{}` now has a different number of turrets",
+                    template.id.to_name()
+                );
+                continue;
+            }
+            ship.template = template;
+            for (state, instance) in turret_states
+                .states
+                .iter_mut()
+                .zip(&template.turret_instances)
+            {
+                state.template = &template.turret_templates[instance.template];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use wrts_match_shared::ship_template::{ShipTemplate, Speed};
+
+    use crate::{hot_reload::ApplyReloadedTemplateCommand, ship::Ship, test_harness::TestMatch};
+
+    #[test]
+    fn test_reloaded_template_changes_ship_stats() {
+        let mut harness = TestMatch::new();
+        let ship = harness.ships_of(harness.clients[0])[0];
+        let id = harness.get::<Ship>(ship).template.id;
+        let old_max_speed = id.to_template().max_speed.mps();
+
+        // As if the data file had been edited and loaded again
+        let mut template = ShipTemplate::from_ron(id, &id.to_template().to_ron()).unwrap();
+        template.max_speed = Speed::from_mps(old_max_speed * 2.);
+        ApplyReloadedTemplateCommand {
+            template: Box::leak(Box::new(template)),
+        }
+        .apply(harness.app.world_mut());
+
+        let reloaded = harness.get::<Ship>(ship).template;
+        assert_eq!(reloaded.max_speed.mps(), old_max_speed * 2.);
+        // The registry keeps the template the match started with
+        assert_eq!(id.to_template().max_speed.mps(), old_max_speed);
+    }
+}
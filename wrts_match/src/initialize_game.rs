@@ -1,37 +1,163 @@
+use std::f32::consts::TAU;
+
 use bevy::prelude::*;
 use itertools::Itertools;
-use wrts_match_shared::ship_template::{ShipTemplate, ShipTemplateId};
-
-use crate::{Health, Team, networking::ClientInfo, spawn_entity::SpawnShipCommand};
-
-pub fn initalize_game(mut commands: Commands, teams: Query<&ClientInfo>) {
-    let teams: [&ClientInfo; 2] = teams
-        .into_iter()
-        .collect_array()
-        .expect("There aren't two clients!!!");
-    for team_idx in 0..2 {
-        let spacing_x = 6_000.;
-        // let spacing_x = 16_000.;
-        let pos_base = match team_idx {
-            0 => vec2(spacing_x, 0.),
-            _ => vec2(-spacing_x, 0.),
-        };
-        let rot = Quat::from_rotation_z(match team_idx {
-            0 => std::f32::consts::PI,
-            _ => 0.,
-        });
-        let ships = ShipTemplateId::all_ships();
+use wrts_match_shared::ship_template::{SectionHealth, ShipTemplate, ShipTemplateId};
+
+use crate::{
+    GameRules, Health, MatchTimer, Team, networking::ClientInfo, spawn_entity::SpawnShipCommand,
+};
+
+/// Extra space kept between the hulls of ships when they spawn
+const SPAWN_CLEARANCE: f32 = 100.;
+/// How many times [resolve_spawn_overlaps] pushes ships apart before giving up
+const SPAWN_RESOLVE_ITERATIONS: usize = 256;
+
+/// How far from the center of the map each team's line of ships spawns
+const SPAWN_DISTANCE: f32 = 6_000.;
+
+pub fn initalize_game(mut commands: Commands, teams: Query<&ClientInfo>, rules: Res<GameRules>) {
+    let teams = teams.iter().collect_vec();
+    assert!(teams.len() >= 2, "There aren't at least two clients!!!");
+    commands.insert_resource(MatchTimer(Timer::new(
+        rules.match_duration,
+        TimerMode::Once,
+    )));
+    let ships = ShipTemplateId::all_ships();
+
+    let mut spawns = Vec::new();
+    for team_idx in 0..teams.len() {
+        // Teams are spread evenly around the center,
+        // so two teams face each other across it
+        let dir = Vec2::from_angle(TAU * team_idx as f32 / teams.len() as f32);
+        let pos_base = dir * SPAWN_DISTANCE;
         for ship_idx in 0..ships.len() {
             let offset_side = if ship_idx % 2 == 0 { -1. } else { 1. };
             let offset_ct = (ship_idx + 1).div_euclid(2) as f32;
-            let pos = pos_base + vec2(0., 1_000.) * offset_ct * offset_side;
-            commands.queue(SpawnShipCommand {
-                team: Team(teams[team_idx].info.id),
-                ship_base: ships[ship_idx],
-                health: Health(ShipTemplate::from_id(ships[ship_idx]).max_health),
-                pos,
-                rot,
-            });
+            let pos = pos_base + dir.perp() * 1_000. * offset_ct * offset_side;
+            spawns.push((team_idx, ships[ship_idx], pos));
+        }
+    }
+
+    let mut positions = spawns.iter().map(|&(_, _, pos)| pos).collect_vec();
+    let radii = spawns
+        .iter()
+        .map(|&(_, ship, _)| spawn_radius(ShipTemplate::from_id(ship)))
+        .collect_vec();
+    resolve_spawn_overlaps(&mut positions, &radii);
+
+    let centers = (0..teams.len())
+        .map(|team_idx| {
+            let team_positions = spawns
+                .iter()
+                .zip(&positions)
+                .filter(|((team, _, _), _)| *team == team_idx)
+                .map(|(_, &pos)| pos)
+                .collect_vec();
+            team_positions.iter().sum::<Vec2>() / team_positions.len().max(1) as f32
+        })
+        .collect_vec();
+
+    for ((team_idx, ship, _), pos) in spawns.into_iter().zip(positions) {
+        // Faces the middle of every other team
+        let enemy_center = centers
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| idx != team_idx)
+            .map(|(_, &center)| center)
+            .sum::<Vec2>()
+            / (teams.len() - 1) as f32;
+        commands.queue(SpawnShipCommand {
+            team: Team(teams[team_idx].info.id),
+            ship_base: ship,
+            health: Health(SectionHealth::full(ShipTemplate::from_id(ship).max_health)),
+            pos,
+            rot: spawn_rotation(centers[team_idx], enemy_center),
+        });
+    }
+}
+
+/// The radius of the circle a ship must have to itself when it spawns
+fn spawn_radius(template: &ShipTemplate) -> f32 {
+    template.hull.length / 2. + SPAWN_CLEARANCE
+}
+
+/// Faces a ship spawning around `own_center` towards the enemy team around `enemy_center`
+fn spawn_rotation(own_center: Vec2, enemy_center: Vec2) -> Quat {
+    let dir = (enemy_center - own_center)
+        .try_normalize()
+        .unwrap_or(Vec2::X);
+    Quat::from_rotation_z(dir.to_angle())
+}
+
+/// Nudges the ships spawning at `positions` apart, so that no two ships' circles of
+/// `radii` overlap, and keeps every ship's circle within the map bounds
+///
+/// Ships spawning at exactly the same point are split up in a
+/// deterministic direction based on their index
+pub fn resolve_spawn_overlaps(positions: &mut [Vec2], radii: &[f32]) {
+    assert_eq!(positions.len(), radii.len());
+    let (lower, upper) = wrts_match_shared::map_bounds();
+    let clamp_to_bounds = |pos: Vec2, radius: f32| pos.clamp(lower + radius, upper - radius);
+
+    for _ in 0..SPAWN_RESOLVE_ITERATIONS {
+        let mut any_overlap = false;
+        for (a, b) in (0..positions.len()).tuple_combinations() {
+            let min_dist = radii[a] + radii[b];
+            let offset = positions[b] - positions[a];
+            let dist = offset.length();
+            if dist >= min_dist {
+                continue;
+            }
+            any_overlap = true;
+            let dir = offset
+                .try_normalize()
+                .unwrap_or_else(|| Vec2::from_angle(b as f32 * std::f32::consts::FRAC_PI_3));
+            let push = dir * (min_dist - dist) / 2.;
+            positions[a] = clamp_to_bounds(positions[a] - push, radii[a]);
+            positions[b] = clamp_to_bounds(positions[b] + push, radii[b]);
+        }
+        if !any_overlap {
+            break;
         }
     }
+
+    for (pos, &radius) in positions.iter_mut().zip(radii) {
+        *pos = clamp_to_bounds(*pos, radius);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use itertools::Itertools;
+
+    use crate::initialize_game::resolve_spawn_overlaps;
+
+    #[test]
+    fn test_ships_spawning_at_one_point_are_separated() {
+        let (_, upper) = wrts_match_shared::map_bounds();
+        let radii = [150., 150., 200., 120., 180.];
+        let mut positions = [upper; 5];
+        resolve_spawn_overlaps(&mut positions, &radii);
+
+        let (lower, upper) = wrts_match_shared::map_bounds();
+        for (pos, radius) in positions.iter().zip(radii) {
+            assert!(pos.cmpge(lower + radius).all() && pos.cmple(upper - radius).all());
+        }
+        for (a, b) in (0..positions.len()).tuple_combinations() {
+            let dist = positions[a].distance(positions[b]);
+            assert!(
+                dist >= radii[a] + radii[b] - 1.,
+                "ships {a} and {b} are only {dist}m apart"
+            );
+        }
+    }
+
+    #[test]
+    fn test_separate_spawns_are_untouched() {
+        let mut positions = [vec2(0., 0.), vec2(1_000., 0.)];
+        resolve_spawn_overlaps(&mut positions, &[200., 200.]);
+        assert_eq!(positions, [vec2(0., 0.), vec2(1_000., 0.)]);
+    }
 }
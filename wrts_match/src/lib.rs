@@ -1,52 +1,168 @@
 use std::{
+    collections::{HashMap, HashSet},
     f32::consts::PI,
     ops::{Index, IndexMut},
+    path::Path,
     time::Duration,
 };
 
 use bevy::{prelude::*, window::ExitCondition};
 use itertools::Itertools;
 use ordered_float::OrderedFloat;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use wrts_match_shared::{
-    formulas::{ProjectileHitCalc, ProjectileHitRes},
-    ship_template::{AngleRange, BulletType, Caliber, Speed, TargetingMode},
+    formulas::{
+        ProjectileHitCalc, ProjectileHitRes,
+        ballistics::{GRAVITY, ShellArc},
+        turn_turret_towards,
+    },
+    ship_path::ShipPathCatmull,
+    ship_template::{
+        BulletType, Caliber, FULL_TURNING_RATE_SPEED, HullSection, SectionHealth,
+        ShipTemplateRegistry, Speed, TargetingMode, TurretInstance,
+    },
 };
-use wrts_messaging::{ClientId, Match2Client, Message, WrtsMatchMessage};
+use wrts_messaging::{ClientId, DamageSource, Match2Client, Message, WrtsMatchMessage};
 
 use crate::{
+    aircraft::{Aircraft, AircraftKind, defend_against_aircraft, fly_aircraft},
+    damage::{DamageDealt, ReportDamageCommand},
     detection::{DetectionPlugin, DetectionStatus, DetectionSystem},
     initialize_game::initalize_game,
-    math_utils::BulletProblemRes,
+    math_utils::{BulletProblemRes, Circle},
     networking::{
         ClientInfo, MessagesSend, NetworkingPlugin, ReadClientMessagesSystem, SharedEntityTracking,
-        UpdateClientsSystem,
+        Spectator, StdoutHandlerThread, UpdateClientsSystem, network_handshake,
     },
     ship::{
-        Ship, SmokeConsumableState, SmokeDeploying, SmokePuff, TurretAimInfo, TurretStates,
-        apply_dispersion,
+        Burning, RepairPartyState, RepairableDamage, RepairingState, Ship, SmokeConsumableState,
+        SmokeDeploying, SmokePuff, SpotterPlaneFlying, SpotterPlaneState, TorpReloadBoosterState,
+        TurretAccuracy, TurretAimInfo, TurretState, TurretStates, apply_dispersion,
     },
+    ship_collision::collide_ships,
     spawn_entity::{DespawnNetworkedEntityCommand, SpawnBulletCommand, SpawnSmokePuffCommand},
 };
 
+mod aircraft;
+mod benches;
+mod damage;
 mod detection;
+#[cfg(feature = "hot_reload")]
+mod hot_reload;
 mod initialize_game;
 mod math_utils;
 mod networking;
 mod ship;
+mod ship_collision;
 mod spawn_entity;
+#[cfg(test)]
+mod test_harness;
+
+/// The `tracing` target of structured events for analyzing a match afterwards,
+/// such as shots fired and ships destroyed. They're logged at the `debug` level,
+/// so are hidden in normal play and can be enabled with `RUST_LOG=match_events=debug`
+///
+/// Every event has an `event` name and the match `time` in seconds
+const MATCH_EVENTS: &str = "match_events";
 
 /// A factor applied to all mobility and final damage dealt
 /// (does NOT affect reload speed)
 pub const GAME_SCALE: f64 = 0.5;
 
+/// How close a ship has to pass to a waypoint to have reached it
+const WAYPOINT_ARRIVAL_DIST: f32 = 5.;
+/// How far ahead along its smoothed path a ship steers for, in multiples of its turning radius
+/// at its current speed. Steering for a point at least twice the turning radius away never
+/// needs a tighter turn than the ship can make, so it can't end up circling the point
+const PATH_LOOKAHEAD_TURNING_RADII: f32 = 2.;
+
+/// How long a fire burns once an HE shell starts it
+const FIRE_DURATION: Duration = Duration::from_secs(30);
+/// The fraction of a burning ship's `max_health` lost every second, before [GAME_SCALE]
+const FIRE_DAMAGE_PER_SEC: f64 = 0.003;
+
+/// The source of all of the match's randomness, such as shell dispersion,
+/// so that replaying the same orders with the same seed reproduces a match
+#[derive(Resource, Debug)]
+struct MatchRng(StdRng);
+
+impl MatchRng {
+    fn from_seed(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
 #[derive(Resource)]
 struct GameRules {
     gravity: f32,
+    /// Turrets hold their fire rather than pushing the number of bullets in flight past this,
+    /// so that rapid-firing secondaries can't flood the world and the network
+    max_bullets_in_flight: usize,
+    /// Lets shells and torpedoes damage ships on their own team,
+    /// though never the ship which fired them
+    friendly_fire: bool,
+    /// Shells which fall below this height have missed everything and are despawned,
+    /// as are any which leave the map horizontally
+    bullet_despawn_height: f32,
+    /// How secondaries pick between the enemies they can reach
+    secondary_target_distribution: SecondaryTargetDistribution,
+    /// How many times faster than their templates every gun and torpedo tube reloads,
+    /// so that test matches can exercise combat quickly.
+    /// Only applied when ships spawn, and must be positive
+    reload_speed: f32,
+    /// Lets clients pause the simulation with `Client2Match::SetPaused`, for debugging
+    allow_pause: bool,
+    /// Multiplies the damage of AP shells which penetrate into a ship's citadel
+    citadel_multiplier: f64,
+    /// The fraction of the damage a ship takes which its repair party can restore
+    repairable_fraction: f64,
+    /// How long the match lasts before it ends on time, see [check_victory]
+    match_duration: Duration,
+    /// Ships steer along a smoothed path through their waypoints,
+    /// rather than straight for each one in turn
+    smooth_ship_paths: bool,
+}
+
+/// How a ship's secondary turrets choose between the enemies in reach.
+/// Either way, a turret only falls back to these when it can't reach the ship's `FireTarget`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum SecondaryTargetDistribution {
+    /// Every turret engages the nearest enemy it can reach
+    #[default]
+    Nearest,
+    /// Each turret engages whichever reachable enemy the fewest of the ship's secondaries
+    /// aimed before it this tick are engaging, nearest first, so that secondaries
+    /// split between several nearby enemies rather than all engaging one
+    Spread,
 }
 
 impl Default for GameRules {
     fn default() -> Self {
-        Self { gravity: 10. }
+        Self {
+            gravity: GRAVITY,
+            max_bullets_in_flight: 1_024,
+            friendly_fire: false,
+            bullet_despawn_height: -100.,
+            secondary_target_distribution: SecondaryTargetDistribution::default(),
+            reload_speed: 1.,
+            allow_pause: cfg!(debug_assertions),
+            citadel_multiplier: 3.,
+            repairable_fraction: 0.5,
+            match_duration: Duration::from_secs(20 * 60),
+            smooth_ship_paths: true,
+        }
+    }
+}
+
+impl GameRules {
+    /// Checks that every rule has a value the match can run with
+    fn validate(&self) -> anyhow::Result<()> {
+        // Reload times are divided by it, so zero would never reload
+        anyhow::ensure!(
+            self.reload_speed > 0. && self.reload_speed.is_finite(),
+            "`reload_speed` must be positive"
+        );
+        Ok(())
     }
 }
 
@@ -54,8 +170,9 @@ impl Default for GameRules {
 #[require(Transform)]
 struct Velocity(pub Vec3);
 
+/// The health left in each section of a ship's hull
 #[derive(Debug, Component, Default, Clone)]
-struct Health(pub f64);
+struct Health(pub SectionHealth);
 
 #[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Team(pub ClientId);
@@ -66,29 +183,38 @@ impl Default for Team {
     }
 }
 
+/// A value for every team in the match.
+/// Matches only have a handful of teams, so this is a list rather than a hash map
 pub struct TeamMap<V> {
-    entries: [(Team, V); 2],
+    entries: Vec<(Team, V)>,
 }
 
 impl<V> TeamMap<V> {
     fn index_of_team(&self, team: Team) -> usize {
-        if self.entries[0].0 == team { 0 } else { 1 }
+        self.entries
+            .iter()
+            .position(|(entry_team, _)| *entry_team == team)
+            .unwrap_or_else(|| panic!("{team:?} isn't in this match"))
     }
 
-    pub fn get_opposite_pair(&self, team: Team) -> (Team, &V) {
-        let i = self.index_of_team(team) ^ 1;
-        (self.entries[i].0, &self.entries[i].1)
+    pub fn teams(&self) -> impl Iterator<Item = Team> + '_ {
+        self.entries.iter().map(|(team, _)| *team)
     }
 
-    pub fn get_opposite(&self, team: Team) -> &V {
-        self.get_opposite_pair(team).1
+    /// Every team other than `team`, with their values
+    pub fn enemies_of(&self, team: Team) -> impl Iterator<Item = (Team, &V)> {
+        self.entries
+            .iter()
+            .filter(move |(entry_team, _)| *entry_team != team)
+            .map(|(entry_team, v)| (*entry_team, v))
     }
 }
 
 impl<V> FromIterator<(Team, V)> for TeamMap<V> {
     fn from_iter<T: IntoIterator<Item = (Team, V)>>(iter: T) -> Self {
-        let entries = iter.into_iter().collect_array().unwrap();
-        Self { entries }
+        Self {
+            entries: iter.into_iter().collect(),
+        }
     }
 }
 
@@ -102,13 +228,115 @@ impl<V> Index<Team> for TeamMap<V> {
 
 impl<V> IndexMut<Team> for TeamMap<V> {
     fn index_mut(&mut self, team: Team) -> &mut Self::Output {
-        &mut self.entries[self.index_of_team(team)].1
+        let idx = self.index_of_team(team);
+        &mut self.entries[idx].1
     }
 }
 
+/// The waypoints a ship follows in order
+///
+/// Reached waypoints are skipped over with a cursor rather than removed,
+/// so that following a long path doesn't shift the whole list for each waypoint
 #[derive(Debug, Default, Component, Clone)]
 struct MoveOrder {
-    pub waypoints: Vec<Vec2>,
+    waypoints: Vec<Vec2>,
+    /// The index of the next waypoint to head for
+    next: usize,
+    /// The smoothed path from where the ship set off through its waypoints,
+    /// built by [MoveOrder::steering_point] the first time the ship steers along it
+    path: Option<SmoothedPath>,
+}
+
+#[derive(Debug, Clone)]
+struct SmoothedPath {
+    spline: ShipPathCatmull,
+    /// The index of the waypoint at `t = 1` along `spline`,
+    /// since earlier ones may have been reached before it was built
+    first_waypoint: usize,
+    /// How far along `spline` the ship is, as the nearest point to it
+    progress_t: f32,
+    /// How far along `spline` the point the ship is steering for is
+    steering_t: f32,
+}
+
+impl SmoothedPath {
+    /// Moves `t` on by about `dist`, going by the straight-line length of the segment it's on
+    fn step(&self, t: f32, dist: f32) -> f32 {
+        let segment = t.floor();
+        let chord = self
+            .spline
+            .sample(segment)
+            .distance(self.spline.sample(segment + 1.));
+        (t + dist / chord.max(dist)).min(self.spline.segment_count() as f32)
+    }
+}
+
+impl MoveOrder {
+    fn new(waypoints: Vec<Vec2>) -> Self {
+        Self {
+            waypoints,
+            ..default()
+        }
+    }
+
+    fn next_waypoint(&self) -> Option<Vec2> {
+        self.waypoints.get(self.next).copied()
+    }
+
+    /// The waypoints which haven't been reached yet
+    fn remaining(&self) -> &[Vec2] {
+        &self.waypoints[self.next.min(self.waypoints.len())..]
+    }
+
+    /// If the ship has gone past the next waypoint along its smoothed path,
+    /// which it may have cut the corner at rather than passing right by
+    ///
+    /// Never true of the final waypoint, which the ship has to reach to stop on
+    fn passed_next_waypoint(&self) -> bool {
+        self.path.as_ref().is_some_and(|path| {
+            let waypoint_t = (self.next + 1 - path.first_waypoint) as f32;
+            waypoint_t < path.spline.segment_count() as f32 && path.progress_t >= waypoint_t
+        })
+    }
+
+    /// The point about `lookahead` ahead of a ship at `pos` along its smoothed path,
+    /// which is built leaving in the ship's `heading` when it's first needed
+    fn steering_point(&mut self, pos: Vec2, heading: Vec2, lookahead: f32) -> Option<Vec2> {
+        if self.path.is_none() {
+            self.path = Some(SmoothedPath {
+                spline: ShipPathCatmull::new(pos, self.remaining())?.with_start_dir(heading),
+                first_waypoint: self.next,
+                progress_t: 0.,
+                steering_t: 0.,
+            });
+        }
+        let path = self.path.as_mut()?;
+        let end = path.spline.segment_count() as f32;
+        // Searched in steps of a tenth of the lookahead
+        let step = lookahead / 10.;
+        while path.steering_t < end && path.spline.sample(path.steering_t).distance(pos) < lookahead
+        {
+            path.steering_t = path.step(path.steering_t, step);
+        }
+        let mut t = path.progress_t;
+        let mut nearest = (path.spline.sample(t).distance(pos), t);
+        while t < path.steering_t {
+            t = path.step(t, step);
+            let dist = path.spline.sample(t).distance(pos);
+            if dist < nearest.0 {
+                nearest = (dist, t);
+            }
+        }
+        path.progress_t = nearest.1;
+        Some(path.spline.sample(path.steering_t))
+    }
+}
+
+/// The direction a ship with no waypoints turns in place to face,
+/// cleared by its next `MoveOrder`
+#[derive(Debug, Component, Clone, Copy)]
+struct HeadingOrder {
+    heading: f32,
 }
 
 #[derive(Debug, Component, Clone)]
@@ -147,29 +375,40 @@ fn collide_torpedoes(
     mut commands: Commands,
     mut ships: Query<(Entity, &Ship, &Team, &Transform, &mut Health)>,
     torpedoes: Query<(Entity, &Torpedo, &Team, &Transform)>,
+    rules: Res<GameRules>,
 ) {
     for (torp_entity, torp, torp_team, torp_trans) in torpedoes {
         for (ship_entity, ship, ship_team, ship_trans, mut ship_health) in &mut ships {
-            if *torp_team == *ship_team {
+            if torp.owning_ship == ship_entity {
                 continue;
             }
-            if ship_health.0 <= 0. {
+            if *torp_team == *ship_team && !rules.friendly_fire {
+                continue;
+            }
+            if ship_health.0.is_destroyed() {
                 continue;
             }
             // Calculate collisions in the local space of the ship hull
             let ship_rot_inv = Vec2::from_angle(-ship_trans.rotation.to_euler(EulerRot::ZXY).0);
-            let (ship_hull_min, ship_hull_max) = ship.template.hull.to_bounds();
             let torp_pos = ship_rot_inv
                 .rotate(torp_trans.translation.truncate() - ship_trans.translation.truncate());
-            if Vec2::cmple(ship_hull_min.truncate(), torp_pos).all()
-                && Vec2::cmple(torp_pos, ship_hull_max.truncate()).all()
-            {
-                let damage = torp.damage;
-                ship_health.0 -= damage * GAME_SCALE;
+            if ship.template.hull_contains(torp_pos) {
+                let damage = ship_health.0.damage(
+                    ship.template.hull.section_at(torp_pos),
+                    torp.damage * GAME_SCALE,
+                );
+                commands.queue(ReportDamageCommand {
+                    target: ship_entity,
+                    attacker: torp.owning_ship,
+                    attacker_team: *torp_team,
+                    source: DamageSource::Torpedo,
+                    damage,
+                    destroyed_target: ship_health.0.is_destroyed(),
+                });
                 commands.queue(DespawnNetworkedEntityCommand {
                     entity: torp_entity,
                 });
-                if ship_health.0 <= 0. {
+                if ship_health.0.is_destroyed() {
                     commands.queue(DespawnNetworkedEntityCommand {
                         entity: ship_entity,
                     });
@@ -187,25 +426,39 @@ fn update_ship_velocity(
         Option<&mut MoveOrder>,
         &Team,
         Entity,
+        Option<&HeadingOrder>,
     )>,
     time: Res<Time>,
+    rules: Res<GameRules>,
     shared_entities: Res<SharedEntityTracking>,
     msgs_tx: Res<MessagesSend>,
 ) {
     for mut ship in ships {
         if let Some(move_order) = &mut ship.3 {
-            if move_order
-                .waypoints
-                .get(0)
-                .is_some_and(|next| next.distance(ship.1.translation.truncate()) <= 5.)
+            let pos = ship.1.translation.truncate();
+            // Where this tick's move will take the ship, so that a fast ship
+            // still reaches waypoints it would otherwise pass between ticks
+            let projected_pos = pos + ship.2.0.truncate() * time.delta_secs();
+            while let Some(next) = move_order.next_waypoint()
+                && (move_order.passed_next_waypoint()
+                    || next.distance(pos) <= WAYPOINT_ARRIVAL_DIST
+                    || math_utils::cast_line_segment(
+                        pos,
+                        projected_pos,
+                        [Circle {
+                            pos: next,
+                            radius: WAYPOINT_ARRIVAL_DIST,
+                        }],
+                    )
+                    .is_some())
             {
-                move_order.waypoints.remove(0);
+                move_order.next += 1;
                 if let Some(shared) = shared_entities.get_by_local(ship.5) {
                     msgs_tx.send(WrtsMatchMessage {
                         client: ship.4.0,
-                        msg: Message::Match2Client(Match2Client::SetMoveOrder {
+                        msg: Message::Match2Client(Match2Client::WaypointReached {
                             id: shared,
-                            waypoints: move_order.waypoints.clone(),
+                            remaining: move_order.remaining().len() as u32,
                         }),
                     });
                 }
@@ -214,27 +467,58 @@ fn update_ship_velocity(
 
         let curr_dir = ship.1.rotation.to_euler(EulerRot::ZXY).0;
 
-        let (targ_speed, targ_dir) = match ship
-            .3
-            .and_then(|order| order.waypoints.get(0).copied())
-            .and_then(|next_waypoint| {
-                Some((
-                    next_waypoint,
-                    Dir2::new(next_waypoint - ship.1.translation.truncate()).ok()?,
-                ))
-            }) {
-            Some((next_waypoint, to_next_waypoint)) => {
-                let dist = ship.1.translation.truncate().distance(next_waypoint);
-                let targ_speed = ship.0.template.max_speed.mps().clamp(0., dist);
-                let targ_dir = to_next_waypoint.to_angle();
-                (targ_speed, targ_dir)
+        let pos = ship.1.translation.truncate();
+        let (template, curr_speed) = (ship.0.template, ship.0.curr_speed);
+        let (targ_speed, targ_dir, turns_in_place) = match ship.3.as_mut().and_then(|order| {
+            let next_waypoint = order.next_waypoint()?;
+            let steering_point = match rules.smooth_ship_paths {
+                true => {
+                    let turning_radius = template.turning_radius(Speed::from_mps(curr_speed));
+                    order.steering_point(
+                        pos,
+                        Vec2::from_angle(curr_dir),
+                        PATH_LOOKAHEAD_TURNING_RADII * turning_radius,
+                    )?
+                }
+                false => next_waypoint,
+            };
+            Some((
+                next_waypoint,
+                Dir2::new(steering_point - pos).ok()?,
+                order.remaining().len() == 1,
+            ))
+        }) {
+            Some((next_waypoint, to_steering_point, is_final_waypoint)) => {
+                let max_speed = ship.0.template.max_speed.mps();
+                // Ships carry their speed through waypoints along the way, but brake
+                // for the last one early enough to stop on it. Movement is scaled by
+                // `GAME_SCALE` while acceleration isn't, which lengthens the stopping distance
+                let targ_speed = match is_final_waypoint {
+                    true => {
+                        let dist = pos.distance(next_waypoint);
+                        let braking_speed = (2. * ship.0.template.engine_acceleration.mps() * dist
+                            / GAME_SCALE as f32)
+                            .sqrt();
+                        braking_speed.min(max_speed)
+                    }
+                    false => max_speed,
+                };
+                let targ_dir = to_steering_point.to_angle();
+                (targ_speed, targ_dir, false)
             }
-            None => (0., curr_dir),
+            None => match ship.6 {
+                Some(heading_order) => (0., heading_order.heading, true),
+                None => (0., curr_dir, false),
+            },
         };
 
         let (new_vel, new_dir) = {
-            let turn_rate_limiter =
-                f32::clamp(ship.0.curr_speed / Speed::from_kts(20.).mps(), 0., 1.);
+            // Turning in place isn't limited by speed, so a stationary ship can still angle itself
+            let turn_rate_limiter = if turns_in_place {
+                1.
+            } else {
+                f32::clamp(ship.0.curr_speed / FULL_TURNING_RATE_SPEED.mps(), 0., 1.)
+            };
             let new_dir = Vec2::from_angle(curr_dir).rotate_towards(
                 Vec2::from_angle(targ_dir),
                 turn_rate_limiter
@@ -277,13 +561,20 @@ fn force_ship_in_map(ships: Query<&mut Transform, With<Ship>>) {
 #[require(Team, Sprite, Transform)]
 struct Bullet {
     owning_ship: Entity,
+    /// Index into the owning ship's `TurretStates`
+    turret_idx: usize,
     targ_ship: Entity,
     caliber: Caliber,
     ty: BulletType,
+    /// The firing turret's `fire_chance`
+    fire_chance: f32,
     inital_pos: Vec3,
     inital_vel: Vec3,
     curr_vel: Vec3,
     inital_aimpoint: Vec2,
+    /// Follows the target's predicted position while the bullet's team detects it,
+    /// then stays at the last one they saw. Since every client sees where shells go,
+    /// tracking a hidden target would give its position away
     current_aimpoint: Vec2,
     expected_flight_time_total: Duration,
     current_flight_time: Duration,
@@ -292,13 +583,16 @@ struct Bullet {
 
 fn move_bullets(
     mut commands: Commands,
-    q: Query<(Entity, &mut Bullet, &mut Transform)>,
-    targets: Query<(&Transform, &Velocity), Without<Bullet>>,
+    q: Query<(Entity, &mut Bullet, &mut Transform, &Team)>,
+    targets: Query<(&Transform, &Velocity, Option<&DetectionStatus>), Without<Bullet>>,
     rules: Res<GameRules>,
     time: Res<Time>,
 ) {
-    for (entity, mut bullet, mut trans) in q {
-        if let Ok((targ_trans, targ_vel)) = targets.get(bullet.targ_ship) {
+    let (lower, upper) = wrts_match_shared::map_bounds();
+    for (entity, mut bullet, mut trans, team) in q {
+        if let Ok((targ_trans, targ_vel, targ_detection)) = targets.get(bullet.targ_ship)
+            && targ_detection.is_none_or(|detection| detection.detected_by.contains(team))
+        {
             let rem_time = bullet
                 .expected_flight_time_total
                 .saturating_sub(bullet.current_flight_time)
@@ -330,7 +624,9 @@ fn move_bullets(
         bullet.curr_vel = new_vel;
         trans.translation = new_pos;
 
-        if trans.translation.z <= -100. {
+        let pos = trans.translation.truncate();
+        let in_map = pos.cmpge(lower).all() && pos.cmple(upper).all();
+        if trans.translation.z <= rules.bullet_despawn_height || !in_map {
             commands.queue(DespawnNetworkedEntityCommand { entity });
         }
     }
@@ -339,14 +635,27 @@ fn move_bullets(
 fn collide_bullets(
     mut commands: Commands,
     bullets: Query<(Entity, &Bullet, &Transform, &Team)>,
-    mut ships: Query<(Entity, &Ship, &Transform, &Team, &mut Health)>,
+    mut ships: Query<(
+        Entity,
+        &Ship,
+        &Transform,
+        &Team,
+        &mut Health,
+        Option<&mut Burning>,
+    )>,
+    mut turret_states: Query<&mut TurretStates>,
+    rules: Res<GameRules>,
+    mut rng: ResMut<MatchRng>,
 ) {
     for (bullet_entity, bullet, bullet_trans, bullet_team) in bullets {
-        for (ship_entity, ship, ship_trans, ship_team, mut ship_health) in &mut ships {
-            if bullet_team == ship_team {
+        for (ship_entity, ship, ship_trans, ship_team, mut ship_health, burning) in &mut ships {
+            if bullet.owning_ship == ship_entity {
+                continue;
+            }
+            if bullet_team == ship_team && !rules.friendly_fire {
                 continue;
             }
-            if ship_health.0 <= 0. {
+            if ship_health.0.is_destroyed() {
                 continue;
             }
 
@@ -356,22 +665,130 @@ fn collide_bullets(
                 ship_rot: ship_trans.rotation,
                 projectile_base_damage: bullet.damage,
                 projectile_caliber: bullet.caliber,
+                projectile_type: bullet.ty,
+                projectile_fire_chance: bullet.fire_chance,
                 projectile_vel: bullet.curr_vel,
                 projectile_pos: bullet_trans.translation,
+                fire_roll: rng.0.random(),
+                citadel_multiplier: rules.citadel_multiplier,
             };
 
-            if let ProjectileHitRes::Hit { damage_dealt } = hit.run() {
-                ship_health.0 -= damage_dealt * GAME_SCALE;
-
-                commands.queue(DespawnNetworkedEntityCommand {
-                    entity: bullet_entity,
-                });
-                if ship_health.0 <= 0. {
+            let (damage_dealt, section, ignites) = match hit.run() {
+                ProjectileHitRes::Hit {
+                    damage_dealt,
+                    section,
+                    ignites,
+                    ..
+                } => (damage_dealt, section, ignites),
+                ProjectileHitRes::Overpenetration {
+                    damage_dealt,
+                    section,
+                } => (damage_dealt, section, false),
+                ProjectileHitRes::Ricochet => {
                     commands.queue(DespawnNetworkedEntityCommand {
-                        entity: ship_entity,
+                        entity: bullet_entity,
                     });
+                    continue;
+                }
+                ProjectileHitRes::Missed => continue,
+            };
+
+            let damage = ship_health.0.damage(section, damage_dealt * GAME_SCALE);
+            if ignites && !ship_health.0.is_destroyed() {
+                match burning {
+                    Some(mut burning) => burning.timer.reset(),
+                    None => {
+                        commands.entity(ship_entity).insert(Burning {
+                            section,
+                            timer: Timer::new(FIRE_DURATION, TimerMode::Once),
+                            attacker: bullet.owning_ship,
+                            attacker_team: *bullet_team,
+                            damage_dealt: 0.,
+                        });
+                    }
                 }
             }
+            // The owning ship may have been destroyed while the shell was in flight
+            if let Ok(mut owner_turrets) = turret_states.get_mut(bullet.owning_ship)
+                && let Some(turret) = owner_turrets.states.get_mut(bullet.turret_idx)
+            {
+                turret.accuracy.hits += 1;
+            }
+            commands.queue(ReportDamageCommand {
+                target: ship_entity,
+                attacker: bullet.owning_ship,
+                attacker_team: *bullet_team,
+                source: DamageSource::Shell,
+                damage,
+                destroyed_target: ship_health.0.is_destroyed(),
+            });
+
+            commands.queue(DespawnNetworkedEntityCommand {
+                entity: bullet_entity,
+            });
+            if ship_health.0.is_destroyed() {
+                commands.queue(DespawnNetworkedEntityCommand {
+                    entity: ship_entity,
+                });
+            }
+        }
+    }
+}
+
+/// Burns down each burning ship's health, reporting the fire's damage once it goes out
+/// or destroys the ship
+fn tick_fires(
+    mut commands: Commands,
+    ships: Query<(Entity, &Ship, &mut Health, &mut Burning)>,
+    time: Res<Time>,
+) {
+    for (ship_entity, ship, mut health, mut burning) in ships {
+        if health.0.is_destroyed() {
+            continue;
+        }
+        burning.timer.tick(time.delta());
+        let damage =
+            ship.template.max_health * FIRE_DAMAGE_PER_SEC * GAME_SCALE * time.delta_secs_f64();
+        burning.damage_dealt += health.0.damage(burning.section, damage);
+
+        let destroyed = health.0.is_destroyed();
+        if !destroyed && !burning.timer.finished() {
+            continue;
+        }
+        commands.queue(ReportDamageCommand {
+            target: ship_entity,
+            attacker: burning.attacker,
+            attacker_team: burning.attacker_team,
+            source: DamageSource::Fire,
+            damage: burning.damage_dealt,
+            destroyed_target: destroyed,
+        });
+        if destroyed {
+            commands.queue(DespawnNetworkedEntityCommand {
+                entity: ship_entity,
+            });
+        } else {
+            commands.entity(ship_entity).remove::<Burning>();
+        }
+    }
+}
+
+/// A debug dump of every remaining turret's [TurretAccuracy] as the match closes
+fn log_turret_accuracy_on_exit(
+    mut exits: EventReader<AppExit>,
+    ships: Query<(Entity, &Ship, &Team, &TurretStates)>,
+) {
+    if exits.read().next().is_none() {
+        return;
+    }
+    for (entity, ship, team, turret_states) in ships {
+        for (turret_idx, state) in turret_states.states.iter().enumerate() {
+            let TurretAccuracy { shots_fired, hits } = state.accuracy;
+            info!(
+                "{} {entity} ({}) turret {turret_idx}: {hits}/{shots_fired} hits",
+                ship.template.id.to_name(),
+                team.0,
+            );
         }
     }
 }
@@ -384,17 +801,60 @@ fn turret_reloading(states: Query<&mut TurretStates>, time: Res<Time>) {
     }
 }
 
-fn update_turret_absolute_pos(ships: Query<(&Ship, &mut TurretStates, &Transform)>) {
-    for (ship, mut turrets, ship_trans) in ships {
-        for (turret, turret_state) in
-            itertools::zip_eq(&ship.template.turret_instances, &mut turrets.states)
-        {
+/// * `mismatched` - ships already warned about, so each mismatch is only warned about once
+fn update_turret_absolute_pos(
+    ships: Query<(Entity, &Ship, &mut TurretStates, &Transform)>,
+    mut mismatched: Local<HashSet<Entity>>,
+) {
+    for (entity, ship, mut turrets, ship_trans) in ships {
+        let turret_instances = &ship.template.turret_instances;
+        if turret_instances.len() != turrets.states.len() {
+            if mismatched.insert(entity) {
+                warn!(
+                    "Ship {entity} has {} turret states for {} turret instances, skipping",
+                    turrets.states.len(),
+                    turret_instances.len()
+                );
+            }
+            continue;
+        }
+        mismatched.remove(&entity);
+        for (turret, turret_state) in turret_instances.iter().zip(&mut turrets.states) {
             turret_state.absolute_pos =
                 turret.absolute_pos(ship_trans.translation.truncate(), ship_trans.rotation);
         }
     }
 }
 
+/// Fraction of a turret's `turn_rate` it uses to return to its `default_dir` when it has no target
+const TURRET_IDLE_RETURN_RATE: f32 = 0.25;
+
+/// A secondary turret only switches from its last target to an enemy nearer than
+/// this fraction of the last target's distance, so it doesn't flip between
+/// enemies at about the same distance
+const SECONDARY_RETARGET_DIST_RATIO: f32 = 0.9;
+
+/// Slowly returns a turret with no valid target to its default direction,
+/// so that idle turrets look natural and are ready for the next target
+fn idle_turret(turret_state: &mut TurretState, turret_instance: &TurretInstance, delta_secs: f32) {
+    turret_state.aim_info = TurretAimInfo::NoValidTarget {};
+
+    let curr_dir = Vec2::from_angle(turret_state.dir);
+    let default_dir = Vec2::from_angle(turret_instance.default_dir);
+    let max_turn = TURRET_IDLE_RETURN_RATE * turret_state.template.turn_rate.radps() * delta_secs;
+    turret_state.dir = if curr_dir.angle_to(default_dir).abs() <= max_turn {
+        turret_instance.default_dir
+    } else {
+        turn_turret_towards(
+            curr_dir,
+            default_dir,
+            turret_instance.movement_angle,
+            max_turn,
+        )
+        .to_angle()
+    };
+}
+
 fn aim_turrets(
     ships: Query<(
         Entity,
@@ -408,13 +868,12 @@ fn aim_turrets(
     mut turret_states: Query<&mut TurretStates>,
     time: Res<Time>,
     rules: Res<GameRules>,
-    teams: Query<&ClientInfo>,
+    teams: Query<&ClientInfo, Without<Spectator>>,
 ) {
     let rules = &*rules;
 
     struct ShipQueryItem<'a> {
         entity: Entity,
-        team: Team,
         ship: &'a Ship,
         trans: Transform,
         vel: Velocity,
@@ -422,31 +881,40 @@ fn aim_turrets(
         fire_targ: Option<FireTarget>,
     }
 
-    let teams: [Team; 2] = teams
+    let mut ships_by_team: TeamMap<Vec<ShipQueryItem>> =
+        teams.iter().map(|cl| (Team(cl.info.id), vec![])).collect();
+    for (entity, team, ship, trans, vel, detection, fire_targ) in &ships {
+        ships_by_team[*team].push(ShipQueryItem {
+            entity,
+            ship,
+            trans: *trans,
+            vel: *vel,
+            detection,
+            fire_targ: fire_targ.cloned(),
+        });
+    }
+    let teams = ships_by_team.teams().collect_vec();
+    // Built once per frame so that turrets can find their fire target
+    // without scanning every enemy team
+    let ship_idx_by_entity: TeamMap<HashMap<Entity, usize>> = teams
         .iter()
-        .map(|cl| Team(cl.info.id))
-        .collect_array()
-        .unwrap();
-    let ships_by_team: TeamMap<Vec<ShipQueryItem>> = {
-        let (team0, team1) = ships
-            .into_iter()
-            .map(
-                |(entity, team, ship, trans, vel, detection, fire_targ)| ShipQueryItem {
-                    entity,
-                    team: *team,
-                    ship,
-                    trans: *trans,
-                    vel: *vel,
-                    detection,
-                    fire_targ: fire_targ.cloned(),
-                },
-            )
-            .partition::<Vec<_>, _>(|item| item.team == teams[0]);
-        TeamMap::from_iter([(teams[0], team0), (teams[1], team1)])
-    };
+        .map(|&team| {
+            let idx_by_entity = ships_by_team[team]
+                .iter()
+                .enumerate()
+                .map(|(idx, item)| (item.entity, idx))
+                .collect();
+            (team, idx_by_entity)
+        })
+        .collect();
+
+    // How many of each ship's secondaries have been aimed at each target so far this tick,
+    // for `SecondaryTargetDistribution::Spread`
+    let mut secondaries_per_target: HashMap<(Entity, Entity), usize> = HashMap::new();
 
     let turrets_iter = teams
-        .into_iter()
+        .iter()
+        .copied()
         .flat_map(|team| (0..ships_by_team[team].len()).map(move |ship_idx| (team, ship_idx)))
         .flat_map(|(team, ship_idx)| {
             (0..ships_by_team[team][ship_idx]
@@ -458,50 +926,59 @@ fn aim_turrets(
         });
 
     for (team, ship_idx, turret_idx) in turrets_iter.collect_vec() {
-        let team_opposite = if teams[0] == team { teams[1] } else { teams[0] };
         let ship_info = &ships_by_team[team][ship_idx];
         let turret_state = &mut turret_states.get_mut(ship_info.entity).unwrap().states[turret_idx];
         let turret_pos = turret_state.absolute_pos;
         let turret_instance = &ship_info.ship.template.turret_instances[turret_idx];
-        let turret_template = turret_instance.turret_template();
+        let turret_template = turret_state.template;
+
+        let shell_arc = match turret_template.targeting_mode {
+            TargetingMode::Primary => ship_info.ship.shell_arc,
+            TargetingMode::Secondary => ShellArc::Low,
+        };
+
+        let max_range = ship_info.ship.gun_range(turret_template);
 
         let (targ_info, bp) = {
             let do_bp_against_targ = move |fire_targ: &ShipQueryItem| -> Option<BulletProblemRes> {
-                if !fire_targ.detection.is_detected {
+                if !fire_targ.detection.detected_by.contains(&team) {
                     return None;
                 }
-                math_utils::bullet_problem(
-                    turret_pos,
-                    fire_targ.trans.translation.truncate(),
-                    fire_targ.vel.0.truncate(),
-                    turret_template.muzzle_vel as f64,
-                    rules.gravity as f64,
-                )
-                .filter(|bp| bp.intersection_dist < turret_template.max_range)
+                shell_arc.with_fallbacks().iter().find_map(|&arc| {
+                    math_utils::bullet_problem(
+                        turret_pos,
+                        fire_targ.trans.translation.truncate(),
+                        fire_targ.vel.0.truncate(),
+                        turret_template.muzzle_vel as f64,
+                        rules.gravity as f64,
+                        arc,
+                    )
+                    .filter(|bp| {
+                        bp.intersection_dist < max_range
+                            && turret_template.can_elevate_to(bp.projectile_elevation)
+                    })
+                })
             };
 
             let bp_is_within_firing_angle = |bp: &BulletProblemRes| -> bool {
-                turret_instance
-                    .firing_angle
-                    .or(turret_instance.movement_angle)
-                    .is_some_and(|valid_angle| {
-                        let targ_dir = Vec2::from_angle(
-                            bp.projectile_azimuth
-                                - ship_info.trans.rotation.to_euler(EulerRot::ZYX).0,
-                        );
-                        valid_angle.contains(targ_dir)
-                    })
+                turret_instance.can_fire_towards(Vec2::from_angle(
+                    bp.projectile_azimuth - ship_info.trans.rotation.to_euler(EulerRot::ZYX).0,
+                ))
             };
 
+            // Only looked up among enemies, so a `FireTarget` on an ally is ignored
             let fire_targ = ships_by_team[team][ship_idx]
                 .fire_targ
                 .clone()
                 .and_then(|targ| {
-                    ships_by_team[team_opposite]
-                        .iter()
-                        .find(|item| item.entity == targ.ship)
+                    ships_by_team
+                        .enemies_of(team)
+                        .find_map(|(enemy, enemy_ships)| {
+                            let targ_idx = *ship_idx_by_entity[enemy].get(&targ.ship)?;
+                            Some(&enemy_ships[targ_idx])
+                        })
                 })
-                .filter(|targ_info| targ_info.detection.is_detected);
+                .filter(|targ_info| targ_info.detection.detected_by.contains(&team));
 
             let primary_targ = fire_targ
                 .and_then(|fire_targ| do_bp_against_targ(fire_targ).map(|bp| (fire_targ, bp)));
@@ -510,32 +987,86 @@ fn aim_turrets(
                 // FireTarget is within range
                 (TargetingMode::Primary, Some(primary_targ)) => primary_targ,
                 (TargetingMode::Primary, None) => {
-                    turret_state.aim_info = TurretAimInfo::NoValidTarget {};
+                    idle_turret(turret_state, turret_instance, time.delta_secs());
                     continue;
                 }
 
                 (TargetingMode::Secondary, primary_targ) => {
-                    let fallback_targs = ships_by_team[team_opposite]
-                        .iter()
-                        .sorted_by_key(|targ| {
-                            OrderedFloat(
-                                targ.trans
-                                    .translation
-                                    .distance_squared(ship_info.trans.translation),
-                            )
-                        })
+                    let dist_squared = |targ: &ShipQueryItem| {
+                        targ.trans
+                            .translation
+                            .distance_squared(ship_info.trans.translation)
+                    };
+                    let fallback_targs = ships_by_team
+                        .enemies_of(team)
+                        .flat_map(|(_, enemy_ships)| enemy_ships)
+                        .sorted_by_key(|targ| OrderedFloat(dist_squared(targ)))
                         .filter_map(|potential_targ| {
                             do_bp_against_targ(potential_targ).map(|bp| (potential_targ, bp))
                         });
-                    if let Some(new_targ_found) = primary_targ
+                    let fire_targ_reachable = primary_targ
+                        .as_ref()
+                        .is_some_and(|(_, bp)| bp_is_within_firing_angle(bp));
+                    let mut reachable_targs = primary_targ
                         .into_iter()
                         .chain(fallback_targs)
-                        .filter(|(_, bp)| bp_is_within_firing_angle(bp))
-                        .next()
-                    {
+                        .filter(|(_, bp)| bp_is_within_firing_angle(bp));
+                    let new_targ_found = match rules.secondary_target_distribution {
+                        SecondaryTargetDistribution::Nearest => {
+                            let nearest = reachable_targs.next();
+                            // The last target is still in reach, so is only dropped for
+                            // the fire target or an enemy which is meaningfully nearer
+                            let locked_targ = turret_state
+                                .secondary_target
+                                .filter(|_| !fire_targ_reachable)
+                                .and_then(|locked| {
+                                    ships_by_team.enemies_of(team).find_map(
+                                        |(enemy, enemy_ships)| {
+                                            let idx = *ship_idx_by_entity[enemy].get(&locked)?;
+                                            Some(&enemy_ships[idx])
+                                        },
+                                    )
+                                })
+                                .and_then(|locked| {
+                                    do_bp_against_targ(locked)
+                                        .filter(|bp| bp_is_within_firing_angle(bp))
+                                        .map(|bp| (locked, bp))
+                                });
+                            match (nearest, locked_targ) {
+                                (Some(nearest), Some(locked))
+                                    if dist_squared(nearest.0)
+                                        > dist_squared(locked.0)
+                                            * SECONDARY_RETARGET_DIST_RATIO.powi(2) =>
+                                {
+                                    Some(locked)
+                                }
+                                (nearest, _) => nearest,
+                            }
+                        }
+                        // The fire target comes first when it's reachable
+                        SecondaryTargetDistribution::Spread if fire_targ_reachable => {
+                            reachable_targs.next()
+                        }
+                        // `min_by_key` keeps the first of equally engaged targets,
+                        // so nearer enemies still come first
+                        SecondaryTargetDistribution::Spread => {
+                            reachable_targs.min_by_key(|(targ, _)| {
+                                secondaries_per_target
+                                    .get(&(ship_info.entity, targ.entity))
+                                    .copied()
+                                    .unwrap_or(0)
+                            })
+                        }
+                    };
+                    if let Some(new_targ_found) = new_targ_found {
+                        *secondaries_per_target
+                            .entry((ship_info.entity, new_targ_found.0.entity))
+                            .or_default() += 1;
+                        turret_state.secondary_target = Some(new_targ_found.0.entity);
                         new_targ_found
                     } else {
-                        turret_state.aim_info = TurretAimInfo::NoValidTarget {};
+                        turret_state.secondary_target = None;
+                        idle_turret(turret_state, turret_instance, time.delta_secs());
                         continue;
                     }
                 }
@@ -550,58 +1081,17 @@ fn aim_turrets(
         );
         let curr_dir = Vec2::from_angle(turret_state.dir);
 
-        let rotate_dir = match turret_instance.movement_angle {
-            Some(movement_angle) => {
-                // Nudge the curr_dir so the turret doesn't get stuck at the edges of the movement angle
-                let curr_dir_nudged_ccw = Vec2::from_angle(0.001).rotate(curr_dir);
-                let curr_dir_nudged_cw = Vec2::from_angle(-0.001).rotate(curr_dir);
-                if !AngleRange::from_vectors(curr_dir_nudged_ccw, targ_dir)
-                    .overlaps(movement_angle.inverse())
-                {
-                    // If I can sweep from curr_dir to targ_dir without overlapping
-                    // the place I'm not allowed to move, sweep counter clockwise
-                    1.
-                } else if !AngleRange::from_vectors(targ_dir, curr_dir_nudged_cw)
-                    .overlaps(movement_angle.inverse())
-                {
-                    // If I can sweep from curr_dir to targ_dir *clockwise*
-                    // without overlapping the place I'm not allowed to move,
-                    // turn clockwise
-                    -1.
-                } else {
-                    // The only way that this statement can be reached is
-                    // if the target is outside our movement angle
-                    let targ_dir_clamped = movement_angle.clamp_angle(targ_dir);
-                    if targ_dir_clamped.distance_squared(movement_angle.end_dir()) <= 0.001 {
-                        // Snapped to the end angle of the `movement_angle`
-                        1.
-                    } else {
-                        // Snapped to the start angle of the `movement_angle`
-                        -1.
-                    }
-                }
-            }
-            None => curr_dir.angle_to(targ_dir).signum(),
-        };
-
-        let new_dir = {
-            let mut dir = curr_dir.rotate(Vec2::from_angle(
-                rotate_dir * turret_template.turn_rate.radps() * time.delta_secs(),
-            ));
-            if let Some(movement_angle) = turret_instance.movement_angle {
-                dir = movement_angle.clamp_angle(dir);
-            }
-            dir
-        };
+        let new_dir = turn_turret_towards(
+            curr_dir,
+            targ_dir,
+            turret_instance.movement_angle,
+            turret_template.turn_rate.radps() * time.delta_secs(),
+        );
         turret_state.dir = new_dir.to_angle();
 
         let turret_not_aimed = new_dir.angle_to(targ_dir).abs() > PI / 180.;
-        let turret_outside_firing_angle = if let Some(firing_angle) = turret_instance.firing_angle {
-            !firing_angle.contains(new_dir)
-        } else {
-            false
-        };
-        let turret_cant_fire_this_frame = turret_not_aimed || turret_outside_firing_angle;
+        let turret_cant_fire_this_frame =
+            turret_not_aimed || !turret_instance.can_fire_towards(new_dir);
 
         turret_state.aim_info = match turret_cant_fire_this_frame {
             true => TurretAimInfo::AimingToTarget {
@@ -619,22 +1109,29 @@ fn aim_turrets(
 fn fire_bullets(
     mut commands: Commands,
     ships: Query<(Entity, &Team, &mut Ship, &mut TurretStates)>,
+    bullets: Query<(), With<Bullet>>,
+    rules: Res<GameRules>,
+    mut rng: ResMut<MatchRng>,
 ) {
+    let mut bullets_in_flight = bullets.iter().count();
     let mut ships = ships.into_iter().collect_vec();
+    // Main batteries fire first, so that secondaries are the ones
+    // held back when there are too many bullets in flight
     for (ship_idx, turret_idx) in (0..ships.len())
         .flat_map(|ship_idx| {
             (0..ships[ship_idx].2.template.turret_instances.len())
                 .map(move |turret_idx| (ship_idx, turret_idx))
         })
+        .sorted_by_key(|&(ship_idx, turret_idx)| {
+            ships[ship_idx].3.states[turret_idx].template.targeting_mode != TargetingMode::Primary
+        })
         .collect_vec()
     {
         let (ship_entity, team, ship, turret_states) = &mut ships[ship_idx];
         let (ship_entity, team) = (*ship_entity, *team);
 
-        let turret_instance = &ship.template.turret_instances[turret_idx];
-        let turret_template = turret_instance.turret_template();
-
         let turret_state = &mut turret_states.states[turret_idx];
+        let turret_template = turret_state.template;
 
         let TurretAimInfo::AimedAtTarget { target, bp } = &turret_state.aim_info else {
             continue;
@@ -643,14 +1140,26 @@ fn fire_bullets(
         if !turret_state.reload_timer.finished() {
             continue;
         }
+        // The range may have shrunk since the turret aimed, such as by a spotter plane landing
+        let gun_range = ship.gun_range(turret_template);
+        if bp.intersection_dist >= gun_range {
+            continue;
+        }
+
+        let barrel_count = turret_template.barrel_count as usize;
+        if bullets_in_flight + barrel_count > rules.max_bullets_in_flight {
+            continue;
+        }
+        bullets_in_flight += barrel_count;
 
         for barrel_idx in 0..turret_template.barrel_count {
             let barrel_lateral_offset = (barrel_idx - (turret_template.barrel_count - 1) / 2)
                 as f32
                 * turret_template.barrel_spacing;
 
-            let bullet_vel = apply_dispersion(&turret_template.dispersion, bp.projectile_dir)
-                * turret_template.muzzle_vel as f32;
+            let bullet_vel =
+                apply_dispersion(&turret_template.dispersion, bp.projectile_dir, &mut rng.0)
+                    * turret_template.muzzle_vel as f32;
 
             let bullet_start = turret_state.absolute_pos
                 + Vec2::from_angle(bp.projectile_azimuth).rotate(vec2(0., barrel_lateral_offset));
@@ -661,9 +1170,11 @@ fn fire_bullets(
 
             let bullet = Bullet {
                 owning_ship: ship_entity,
+                turret_idx,
                 targ_ship: *target,
-                caliber: Caliber::from_mm(300.),
-                ty: BulletType::AP,
+                caliber: turret_template.caliber,
+                ty: turret_template.bullet_type,
+                fire_chance: turret_template.fire_chance,
                 inital_pos: bullet_start,
                 inital_vel: bullet_vel,
                 curr_vel: bullet_vel,
@@ -678,9 +1189,11 @@ fn fire_bullets(
                 team: *team,
                 bullet,
                 update_firing_detection_timer: Some(Duration::from_secs(20)),
+                // A visibility stat, so it isn't extended by whatever is extending the guns' reach
                 update_firing_detection_range: Some(turret_template.max_range),
             });
         }
+        turret_state.accuracy.shots_fired += turret_template.barrel_count as u32;
 
         turret_state.reload_timer.reset();
     }
@@ -695,6 +1208,118 @@ fn advance_smoke_cooldown(
     }
 }
 
+fn advance_torp_reload_booster_cooldown(
+    boosters: Query<&mut TorpReloadBoosterState>,
+    time: Res<Time>,
+) {
+    for mut booster in boosters {
+        booster.cooldown_timer.tick(time.delta());
+    }
+}
+
+fn advance_spotter_plane_cooldown(
+    spotters: Query<&mut SpotterPlaneState, Without<SpotterPlaneFlying>>,
+    time: Res<Time>,
+) {
+    for mut spotter in spotters {
+        spotter.cooldown_timer.tick(time.delta());
+    }
+}
+
+/// Lands spotter planes once their action time runs out or they're shot down,
+/// taking their gun range bonus with them
+fn land_spotter_planes(
+    mut commands: Commands,
+    spotters: Query<(Entity, &mut Ship, &mut SpotterPlaneFlying)>,
+    aircraft: Query<(Entity, &Aircraft)>,
+    time: Res<Time>,
+) {
+    for (spotter_entity, mut ship, mut plane) in spotters {
+        let plane_entity = aircraft
+            .iter()
+            .find(|(_, aircraft)| {
+                aircraft.owning_ship == spotter_entity && aircraft.kind == AircraftKind::Spotter
+            })
+            .map(|(entity, _)| entity);
+        if plane.action_timer.tick(time.delta()).finished() || plane_entity.is_none() {
+            ship.gun_range_modifiers.spotter_plane_bonus = None;
+            commands
+                .entity(spotter_entity)
+                .remove::<SpotterPlaneFlying>();
+            if let Some(entity) = plane_entity {
+                commands.queue(DespawnNetworkedEntityCommand { entity });
+            }
+        }
+    }
+}
+
+fn advance_repair_party_cooldown(
+    repairers: Query<&mut RepairPartyState, Without<RepairingState>>,
+    time: Res<Time>,
+) {
+    for mut repairer in repairers {
+        repairer.cooldown_timer.tick(time.delta());
+    }
+}
+
+/// Adds `GameRules::repairable_fraction` of the health each section has lost since
+/// the last tick to the ship's `RepairableDamage`, whatever dealt the damage
+fn track_repairable_damage(
+    ships: Query<(&Health, &mut RepairableDamage), Changed<Health>>,
+    rules: Res<GameRules>,
+) {
+    for (health, mut repairable) in ships {
+        repairable.track(&health.0, rules.repairable_fraction);
+    }
+}
+
+/// Restores health from each ship's `RepairableDamage` while its repair party is working,
+/// from the stern forwards
+fn repair_ships(
+    mut commands: Commands,
+    ships: Query<(
+        Entity,
+        &Ship,
+        &mut Health,
+        &mut RepairableDamage,
+        &mut RepairingState,
+    )>,
+    time: Res<Time>,
+    rules: Res<GameRules>,
+) {
+    for (ship_entity, ship, mut health, mut repairable, mut repairing) in ships {
+        let repair_party = ship.template.consumables.repair_party().unwrap();
+        if !health.0.is_destroyed() {
+            let repairable = &mut *repairable;
+            // Damage dealt since `track_repairable_damage` ran would otherwise be lost
+            // when the repairs are tracked below
+            repairable.track(&health.0, rules.repairable_fraction);
+            let mut budget = repair_party.heal_per_sec * time.delta_secs_f64();
+            for (section, pool) in HullSection::ALL.into_iter().zip(&mut repairable.pool) {
+                if budget <= 0. {
+                    break;
+                }
+                // Left untouched otherwise, so a ship with nothing to repair doesn't resend its health
+                if *pool <= 0. {
+                    continue;
+                }
+                let restored =
+                    health
+                        .0
+                        .repair(section, budget.min(*pool), ship.template.max_health);
+                *pool -= restored;
+                budget -= restored;
+            }
+            // So that `track_repairable_damage` doesn't mistake the repairs for damage taken
+            repairable.last_health = health.0;
+        }
+
+        if repairing.action_timer.tick(time.delta()).finished() {
+            commands.entity(ship_entity).remove::<RepairingState>();
+        }
+    }
+}
+
 fn deploy_smoke(
     mut commands: Commands,
     smokers: Query<(Entity, &Ship, &mut SmokeDeploying, &Transform)>,
@@ -742,33 +1367,205 @@ fn apply_velocity(q: Query<(&mut Transform, &Velocity)>, time: Res<Time>) {
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
 struct MoveEntitiesSystem;
 
-pub fn start_match() -> Result<()> {
-    let exit = App::new()
-        .init_resource::<GameRules>()
-        .add_plugins(
-            DefaultPlugins
-                .set(ImagePlugin::default_nearest())
-                .set(WindowPlugin {
-                    primary_window: None,
-                    exit_condition: ExitCondition::DontExit,
-                    ..default()
-                }),
-        )
+/// While set, nothing in the match moves, reloads or fires, but clients'
+/// messages are still read and answered. Only a client can set this,
+/// if [GameRules::allow_pause] is set
+#[derive(Resource, Debug, Default, PartialEq)]
+struct Paused(bool);
+
+fn match_running(paused: Res<Paused>) -> bool {
+    !paused.0
+}
+
+/// Clients which have surrendered in a match that's still going
+#[derive(Resource, Debug, Default)]
+struct Surrenders(Vec<ClientId>);
+
+/// The time left until the match ends on time, which only runs down while it isn't [Paused]
+#[derive(Resource, Debug)]
+struct MatchTimer(Timer);
+
+/// Counts down the [MatchTimer], telling every client how long is left
+/// each time another whole second of it passes
+fn tick_match_timer(
+    mut timer: ResMut<MatchTimer>,
+    clients: Query<&ClientInfo>,
+    msgs_tx: Res<MessagesSend>,
+    time: Res<Time>,
+) {
+    let secs_before = timer.0.remaining().as_secs();
+    timer.0.tick(time.delta());
+    let remaining = timer.0.remaining();
+    if remaining.as_secs() == secs_before {
+        return;
+    }
+    for cl in clients {
+        msgs_tx.send(WrtsMatchMessage {
+            client: cl.info.id,
+            msg: Message::Match2Client(Match2Client::MatchTime { remaining }),
+        });
+    }
+}
+
+/// Ends the match once at most one client is left in it, awarding the win to that client.
+/// Clients are out once they surrender or have no ships left afloat,
+/// and if the last clients were all knocked out during the same tick, the match is a draw
+///
+/// Once the [MatchTimer] runs out, the client with the most ships afloat wins,
+/// or the match is a draw if that's tied
+fn check_victory(
+    surrenders: Res<Surrenders>,
+    timer: Res<MatchTimer>,
+    clients: Query<(&ClientInfo, Has<Spectator>)>,
+    ships: Query<(&Team, &Health), With<Ship>>,
+    damage_dealt: Res<DamageDealt>,
+    msgs_tx: Res<MessagesSend>,
+    mut exit: EventWriter<AppExit>,
+    mut ended: Local<bool>,
+) {
+    if *ended {
+        return;
+    }
+    let remaining = clients
+        .iter()
+        .filter(|(_, spectator)| !spectator)
+        .map(|(cl, _)| cl.info.id)
+        .filter(|cl| !surrenders.0.contains(cl))
+        .map(|cl| {
+            let afloat = ships
+                .iter()
+                .filter(|(team, health)| team.0 == cl && !health.0.is_destroyed())
+                .count();
+            (cl, afloat)
+        })
+        .filter(|&(_, afloat)| afloat > 0)
+        .collect_vec();
+    let winner = match remaining.as_slice() {
+        [] => None,
+        &[(winner, _)] => Some(winner),
+        _ if timer.0.finished() => {
+            let most_afloat = remaining.iter().map(|&(_, afloat)| afloat).max();
+            let mut leaders = remaining
+                .iter()
+                .filter(|&&(_, afloat)| Some(afloat) == most_afloat);
+            match (leaders.next(), leaders.next()) {
+                (Some(&(winner, _)), None) => Some(winner),
+                _ => None,
+            }
+        }
+        // Two or more teams still afloat play on until only one is left, or time runs out
+        _ => return,
+    };
+    *ended = true;
+
+    match winner {
+        Some(winner) => info!("Match ended, client {winner} won"),
+        None => info!("Match ended as a draw"),
+    }
+    // Every player is listed, even those who never dealt damage
+    let scoreboard = clients
+        .iter()
+        .filter(|(_, spectator)| !spectator)
+        .map(|(cl, _)| {
+            let dealt = damage_dealt.0.get(&cl.info.id).copied().unwrap_or_default();
+            (cl.info.id, dealt.damage, dealt.kills)
+        })
+        .sorted_by_key(|&(cl, ..)| cl)
+        .collect_vec();
+    for (cl, _) in clients {
+        msgs_tx.send(WrtsMatchMessage {
+            client: cl.info.id,
+            msg: Message::Match2Client(Match2Client::Scoreboard {
+                entries: scoreboard.clone(),
+            }),
+        });
+        msgs_tx.send(WrtsMatchMessage {
+            client: cl.info.id,
+            msg: Message::Match2Client(Match2Client::MatchEnded { winner }),
+        });
+    }
+    exit.write(AppExit::Success);
+}
+
+pub fn start_match(ship_mods_dir: &Path) -> Result<()> {
+    let mod_errors = ShipTemplateRegistry::init(ship_mods_dir);
+
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(ImagePlugin::default_nearest())
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..default()
+            }),
+    )
+    .add_systems(PreStartup, network_handshake);
+    for err in mod_errors {
+        warn!("{err:#}");
+    }
+    add_match_systems(&mut app);
+    #[cfg(feature = "hot_reload")]
+    app.add_plugins(hot_reload::HotReloadPlugin);
+    let exit = app.run();
+
+    info!("Bevy exited: `{exit:?}`");
+
+    // Dropping the app closes `MessagesSend`, so the stdout handler returns
+    // once it's written the last messages, such as `MatchEnded`
+    let stdout_thread = app.world_mut().remove_resource::<StdoutHandlerThread>();
+    drop(app);
+    if let Some(StdoutHandlerThread(thread)) = stdout_thread {
+        let _ = thread.join();
+    }
+
+    Ok(())
+}
+
+/// Adds the simulation and messaging systems of a match,
+/// without the plugins that drive the app or the handshake with the clients
+///
+/// Everything runs in `FixedUpdate`, so each tick is ordered as:
+/// 1. [ReadClientMessagesSystem] applies the orders clients sent since the last tick
+/// 2. [MoveEntitiesSystem] moves ships, bullets, torpedoes and aircraft, and pushes apart colliding ships
+/// 3. [DetectionSystem] updates detection from the new positions,
+///    then turrets fire at whatever is detected, while the rest of the simulation
+///    runs after 1 and before 4
+/// 4. [UpdateClientsSystem] sends everything that changed during the tick,
+///    so what clients see is never a tick behind what the match decided
+///
+/// While the match is [Paused], only 1 and 4 run
+fn add_match_systems(app: &mut App) {
+    app.init_resource::<GameRules>();
+    app.world()
+        .resource::<GameRules>()
+        .validate()
+        .expect("Invalid game rules");
+    app.init_resource::<Surrenders>()
+        .init_resource::<DamageDealt>()
+        .init_resource::<Paused>()
         .add_plugins(NetworkingPlugin)
         .add_plugins(DetectionPlugin)
         .add_systems(Startup, initalize_game)
+        .add_systems(Last, log_turret_accuracy_on_exit)
         .configure_sets(
             FixedUpdate,
-            MoveEntitiesSystem
-                .after(ReadClientMessagesSystem)
-                .before(UpdateClientsSystem),
+            (
+                MoveEntitiesSystem
+                    .after(ReadClientMessagesSystem)
+                    .before(UpdateClientsSystem)
+                    .run_if(match_running),
+                DetectionSystem.run_if(match_running),
+            ),
         )
         .add_systems(
             FixedUpdate,
             (
                 update_ship_velocity,
                 apply_velocity.after(update_ship_velocity),
-                force_ship_in_map.after(apply_velocity),
+                collide_ships.after(apply_velocity),
+                force_ship_in_map.after(collide_ships),
+                fly_aircraft.after(force_ship_in_map),
                 move_bullets,
                 despawn_old_torpedoes.after(apply_velocity),
             )
@@ -779,10 +1576,12 @@ pub fn start_match() -> Result<()> {
             (
                 collide_torpedoes.after(MoveEntitiesSystem),
                 collide_bullets.after(MoveEntitiesSystem),
+                tick_fires.after(collide_bullets),
                 torpedo_reloading,
                 turret_reloading,
                 update_turret_absolute_pos,
                 aim_turrets.after(update_turret_absolute_pos),
+                defend_against_aircraft.after(update_turret_absolute_pos),
                 fire_bullets
                     .after(turret_reloading)
                     .after(aim_turrets)
@@ -790,13 +1589,1100 @@ pub fn start_match() -> Result<()> {
                 advance_smoke_cooldown,
                 deploy_smoke,
                 dissapate_smoke_puffs,
+                advance_torp_reload_booster_cooldown,
+                advance_spotter_plane_cooldown,
+                land_spotter_planes.after(defend_against_aircraft),
+                advance_repair_party_cooldown,
+                track_repairable_damage
+                    .after(collide_torpedoes)
+                    .after(collide_bullets)
+                    .after(tick_fires),
+                repair_ships.after(track_repairable_damage),
             )
+                .run_if(match_running)
                 .after(ReadClientMessagesSystem)
                 .before(UpdateClientsSystem),
         )
-        .run();
+        .add_systems(
+            FixedUpdate,
+            (
+                tick_match_timer.run_if(match_running),
+                check_victory.after(tick_match_timer),
+            )
+                .after(ReadClientMessagesSystem)
+                .before(UpdateClientsSystem),
+        );
+}
 
-    info!("Bevy exited: `{exit:?}`");
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::Duration};
+
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+    use itertools::Itertools;
+    use wrts_match_shared::{
+        formulas::ballistics::ShellArc,
+        ship_template::{
+            BulletType, Caliber, HullSection, ShipTemplateId, TargetingMode, TurretTemplate,
+        },
+    };
+    use wrts_messaging::{Client2Match, DamageSource, Match2Client, Message};
+
+    use crate::{
+        Bullet, FIRE_DAMAGE_PER_SEC, FIRE_DURATION, FireTarget, GAME_SCALE, GameRules, Health,
+        MatchRng, SecondaryTargetDistribution, TURRET_IDLE_RETURN_RATE, Team, Torpedo, Velocity,
+        detection::DetectionStatus,
+        idle_turret, repair_ships,
+        ship::{
+            Burning, RepairPartyState, RepairableDamage, RepairingState, Ship, TurretAimInfo,
+            TurretState, TurretStates,
+        },
+        test_harness::TestMatch,
+        track_repairable_damage, update_turret_absolute_pos,
+    };
 
-    Ok(())
+    #[test]
+    fn test_idle_turret_returns_to_default_dir() {
+        let turret_instance = &ShipTemplateId::nagato().to_template().turret_instances[0];
+        let turret_template = turret_instance.turret_template();
+        let start_offset = 1.;
+        let mut turret_state = TurretState {
+            template: turret_template,
+            dir: turret_instance.default_dir + start_offset,
+            reload_timer: Timer::from_seconds(turret_template.reload_secs, TimerMode::Once),
+            absolute_pos: Vec2::ZERO,
+            aim_info: TurretAimInfo::NoValidTarget {},
+            accuracy: default(),
+            secondary_target: None,
+        };
+        let offset_from_default = |state: &TurretState| {
+            Vec2::from_angle(state.dir)
+                .angle_to(Vec2::from_angle(turret_instance.default_dir))
+                .abs()
+        };
+
+        let delta_secs = 1. / 64.;
+        idle_turret(&mut turret_state, turret_instance, delta_secs);
+        let expected_turn =
+            TURRET_IDLE_RETURN_RATE * turret_template.turn_rate.radps() * delta_secs;
+        assert!((start_offset - offset_from_default(&turret_state) - expected_turn).abs() < 0.0001);
+
+        for _ in 0..100_000 {
+            idle_turret(&mut turret_state, turret_instance, delta_secs);
+        }
+        assert!(offset_from_default(&turret_state) < 0.0001);
+    }
+
+    /// Spawns a torpedo fired by `attacker` right on top of `target`,
+    /// and returns how much health `target` lost to it
+    fn torpedo_damage_to_friendly(friendly_fire: bool, self_hit: bool) -> f64 {
+        let mut harness = TestMatch::new();
+        harness
+            .app
+            .world_mut()
+            .resource_mut::<GameRules>()
+            .friendly_fire = friendly_fire;
+        let client = harness.clients[0];
+        let [target, attacker] = [0, if self_hit { 0 } else { 1 }]
+            .map(|idx| harness.local(harness.ships_of(client)[idx]));
+
+        let world = harness.app.world_mut();
+        let target_pos = world.get::<Transform>(target).unwrap().translation;
+        let health_before = world.get::<Health>(target).unwrap().0.total();
+        world.spawn((
+            Torpedo {
+                owning_ship: attacker,
+                damage: 1_000.,
+                inital_pos: target_pos.truncate(),
+                max_range: 1_000.,
+            },
+            Team(client),
+            Transform::from_translation(target_pos),
+        ));
+        harness.tick();
+
+        health_before - harness.app.world().get::<Health>(target).unwrap().0.total()
+    }
+
+    #[test]
+    fn test_fire_burns_out_and_reports_its_damage() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let attacker = harness.local(harness.ships_of(client)[0]);
+        let target_shared = harness.ships_of(other)[0];
+        let target = harness.local(target_shared);
+        let max_health = harness.get::<Ship>(target_shared).template.max_health;
+
+        let health_before = harness.get::<Health>(target_shared).0.total();
+        harness.app.world_mut().entity_mut(target).insert(Burning {
+            section: HullSection::Aft,
+            timer: Timer::new(FIRE_DURATION, TimerMode::Once),
+            attacker,
+            attacker_team: Team(client),
+            damage_dealt: 0.,
+        });
+        let sent_before = harness.sent.len();
+        harness.ticks(64);
+        let health_after_1s = harness.get::<Health>(target_shared).0.total();
+        let expected_per_sec = max_health * FIRE_DAMAGE_PER_SEC * GAME_SCALE;
+        assert!((health_before - health_after_1s - expected_per_sec).abs() < 0.01);
+        assert!(harness.app.world().get::<Burning>(target).is_some());
+
+        harness.ticks(64 * FIRE_DURATION.as_secs() as u32);
+        assert!(harness.app.world().get::<Burning>(target).is_none());
+        let fire_damage = harness.sent[sent_before..]
+            .iter()
+            .filter_map(|msg| match msg.msg {
+                Message::Match2Client(Match2Client::DamageDealt {
+                    target,
+                    source: DamageSource::Fire,
+                    damage,
+                    ..
+                }) if msg.client == client && target == target_shared => Some(damage),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(fire_damage.len(), 1);
+        let expected_total = expected_per_sec * FIRE_DURATION.as_secs_f64();
+        assert!((fire_damage[0] - expected_total).abs() < expected_per_sec / 32.);
+    }
+
+    #[test]
+    fn test_ramming_damages_both_ships_once_and_slows_them() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let rammer_shared = harness.ships_of(client)[0];
+        let rammed_shared = harness.ships_of(other)[0];
+        let [rammer, rammed] = [rammer_shared, rammed_shared].map(|id| harness.local(id));
+
+        // Put the other ship's stern just inside the rammer's bow, facing the same way
+        let world = harness.app.world_mut();
+        let rammer_trans = *world.get::<Transform>(rammer).unwrap();
+        let half_length = |entity| {
+            world
+                .get::<Ship>(entity)
+                .unwrap()
+                .template
+                .hull
+                .to_bounds()
+                .1
+                .x
+        };
+        let overlap_at = half_length(rammer) + half_length(rammed) - 2.;
+        let mut rammed_trans = rammer_trans;
+        rammed_trans.translation += rammer_trans.rotation * Vec3::X * overlap_at;
+        world.entity_mut(rammed).insert(rammed_trans);
+        let mut ship = world.get_mut::<Ship>(rammer).unwrap();
+        ship.curr_speed = ship.template.max_speed.mps();
+
+        let health_before =
+            [rammer_shared, rammed_shared].map(|id| harness.get::<Health>(id).0.total());
+        let sent_before = harness.sent.len();
+        let ramming_damage = |harness: &TestMatch, since: usize| {
+            harness.sent[since..]
+                .iter()
+                .filter(|msg| {
+                    matches!(
+                        msg.msg,
+                        Message::Match2Client(Match2Client::DamageDealt {
+                            source: DamageSource::Ramming,
+                            ..
+                        })
+                    )
+                })
+                .count()
+        };
+        harness.tick();
+
+        for (id, before) in [rammer_shared, rammed_shared]
+            .into_iter()
+            .zip(health_before)
+        {
+            assert!(harness.get::<Health>(id).0.total() < before);
+        }
+        assert!(ramming_damage(&harness, sent_before) > 0);
+        assert!(
+            harness.get::<Ship>(rammer_shared).curr_speed
+                <= crate::ship_collision::GRINDING_SPEED.mps()
+        );
+
+        // Staying in contact doesn't keep dealing damage
+        let sent_before = harness.sent.len();
+        let health_before =
+            [rammer_shared, rammed_shared].map(|id| harness.get::<Health>(id).0.total());
+        harness.ticks(16);
+        assert_eq!(ramming_damage(&harness, sent_before), 0);
+        assert_eq!(
+            [rammer_shared, rammed_shared].map(|id| harness.get::<Health>(id).0.total()),
+            health_before
+        );
+    }
+
+    #[test]
+    fn test_repair_party_only_restores_repairable_damage() {
+        let mut harness = TestMatch::new();
+        let client = harness.clients[0];
+        let ship = harness
+            .ships_of(client)
+            .into_iter()
+            .find(|&ship| harness.get::<Ship>(ship).template.id == ShipTemplateId::bismarck())
+            .unwrap();
+        let local = harness.local(ship);
+        let max_health = harness.get::<Ship>(ship).template.max_health;
+
+        let damage = 1_000.;
+        harness
+            .app
+            .world_mut()
+            .get_mut::<Health>(local)
+            .unwrap()
+            .0
+            .damage(HullSection::Fore, damage);
+        let mut repair_state = harness
+            .app
+            .world_mut()
+            .get_mut::<RepairPartyState>(local)
+            .unwrap();
+        let cooldown = repair_state.cooldown_timer.duration();
+        repair_state.cooldown_timer.tick(cooldown);
+        harness.tick();
+        let repairable = damage * GameRules::default().repairable_fraction;
+        let pool = harness
+            .get::<RepairableDamage>(ship)
+            .pool
+            .iter()
+            .sum::<f64>();
+        assert!((pool - repairable).abs() < 1e-6);
+
+        harness.send(client, Client2Match::UseConsumableRepairParty { ship });
+        harness.ticks(64 * 3);
+        assert!(harness.app.world().get::<RepairingState>(local).is_some());
+        let health = harness.get::<Health>(ship).0.total();
+        assert!((health - (max_health - damage + repairable)).abs() < 1e-6);
+        assert!(
+            harness
+                .get::<RepairableDamage>(ship)
+                .pool
+                .iter()
+                .sum::<f64>()
+                < 1e-6
+        );
+    }
+
+    #[test]
+    fn test_damage_taken_just_before_repairing_stays_repairable() {
+        let mut harness = TestMatch::new();
+        let ship = harness
+            .ships_of(harness.clients[0])
+            .into_iter()
+            .find(|&ship| harness.get::<Ship>(ship).template.id == ShipTemplateId::bismarck())
+            .unwrap();
+        let local = harness.local(ship);
+        harness.tick();
+        let health_before = harness.get::<Health>(ship).0.total();
+
+        // Dealt after `track_repairable_damage` has run this tick
+        let damage = 1_000.;
+        let world = harness.app.world_mut();
+        world.entity_mut(local).insert(RepairingState {
+            action_timer: Timer::from_seconds(10., TimerMode::Once),
+        });
+        world
+            .get_mut::<Health>(local)
+            .unwrap()
+            .0
+            .damage(HullSection::Fore, damage);
+        world.run_system_once(repair_ships).unwrap();
+        world.run_system_once(track_repairable_damage).unwrap();
+
+        let restored = world.get::<Health>(local).unwrap().0.total() - (health_before - damage);
+        let pool = world
+            .get::<RepairableDamage>(local)
+            .unwrap()
+            .pool
+            .iter()
+            .sum::<f64>();
+        let repairable = damage * GameRules::default().repairable_fraction;
+        assert!((pool + restored - repairable).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_friendly_fire_disabled() {
+        assert_eq!(torpedo_damage_to_friendly(false, false), 0.);
+    }
+
+    #[test]
+    fn test_friendly_fire_enabled() {
+        assert!(torpedo_damage_to_friendly(true, false) > 0.);
+        assert_eq!(torpedo_damage_to_friendly(true, true), 0.);
+    }
+
+    #[test]
+    fn test_sinking_the_citadel_destroys_a_ship() {
+        let mut harness = TestMatch::new();
+        let attacker = harness.local(harness.ships_of(harness.clients[0])[0]);
+        let target_shared = harness.ships_of(harness.clients[1])[0];
+        let target = harness.local(target_shared);
+
+        let world = harness.app.world_mut();
+        let target_pos = world.get::<Transform>(target).unwrap().translation;
+        let mut health = world.get_mut::<Health>(target).unwrap();
+        let citadel = health.0.get(HullSection::Citadel);
+        health.0.damage(HullSection::Citadel, citadel - 1.);
+        // The torpedo hits the middle of the hull, which is the citadel
+        world.spawn((
+            Torpedo {
+                owning_ship: attacker,
+                damage: 1_000.,
+                inital_pos: target_pos.truncate(),
+                max_range: 1_000.,
+            },
+            Team(harness.clients[0]),
+            Transform::from_translation(target_pos),
+        ));
+        let sent_before = harness.sent.len();
+        harness.tick();
+
+        assert!(harness.sent[sent_before..].iter().any(|msg| matches!(
+            msg.msg,
+            Message::Match2Client(Match2Client::ShipDestroyed { ship, .. }) if ship == target_shared
+        )));
+        assert!(harness.app.world().get_entity(target).is_err());
+    }
+
+    #[test]
+    fn test_damage_is_only_reported_to_clients_which_can_see_the_target() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let attacker = harness.local(harness.ships_of(client)[0]);
+        let target_shared = harness.ships_of(other)[0];
+        let target = harness.local(target_shared);
+        // Out of sight of every ship on `client`'s team
+        let target_pos = vec3(-20_000., -20_000., 0.);
+        harness
+            .app
+            .world_mut()
+            .get_mut::<Transform>(target)
+            .unwrap()
+            .translation = target_pos;
+        harness.ticks(4);
+
+        harness.app.world_mut().spawn((
+            Torpedo {
+                owning_ship: attacker,
+                damage: 1_000.,
+                inital_pos: target_pos.truncate(),
+                max_range: 1_000.,
+            },
+            Team(client),
+            Transform::from_translation(target_pos),
+        ));
+        let sent_before = harness.sent.len();
+        harness.tick();
+
+        let told_of_damage = |cl| {
+            harness.sent[sent_before..].iter().any(|msg| {
+                msg.client == cl
+                    && matches!(msg.msg, Message::Match2Client(Match2Client::DamageDealt { target, .. })
+                        if target == target_shared)
+            })
+        };
+        assert!(told_of_damage(other));
+        assert!(!told_of_damage(client));
+    }
+
+    #[test]
+    fn test_kill_by_an_undetected_ship_doesnt_name_it() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let attacker_shared = harness.ships_of(client)[0];
+        let attacker = harness.local(attacker_shared);
+        let target_shared = harness.ships_of(other)[0];
+        let target = harness.local(target_shared);
+        // Out of sight of every ship on `other`'s team
+        harness
+            .app
+            .world_mut()
+            .get_mut::<Transform>(attacker)
+            .unwrap()
+            .translation = vec3(-20_000., -20_000., 0.);
+        harness.ticks(4);
+
+        let world = harness.app.world_mut();
+        let target_pos = world.get::<Transform>(target).unwrap().translation;
+        let mut health = world.get_mut::<Health>(target).unwrap();
+        let citadel = health.0.get(HullSection::Citadel);
+        health.0.damage(HullSection::Citadel, citadel - 1.);
+        world.spawn((
+            Torpedo {
+                owning_ship: attacker,
+                damage: 1_000.,
+                inital_pos: target_pos.truncate(),
+                max_range: 1_000.,
+            },
+            Team(client),
+            Transform::from_translation(target_pos),
+        ));
+        let sent_before = harness.sent.len();
+        harness.tick();
+
+        let killers = harness.sent[sent_before..]
+            .iter()
+            .filter_map(|msg| match msg.msg {
+                Message::Match2Client(Match2Client::ShipDestroyed {
+                    ship,
+                    killer_team,
+                    killer,
+                    ..
+                }) if ship == target_shared => Some((msg.client, killer_team, killer)),
+                _ => None,
+            })
+            .collect_vec();
+        assert!(killers.contains(&(other, client, None)));
+        assert!(!killers.contains(&(other, client, Some(attacker_shared))));
+    }
+
+    #[test]
+    fn test_bullet_despawns_when_target_leaves_map() {
+        let mut harness = TestMatch::new();
+        let client = harness.clients[0];
+        let shooter = harness.local(harness.ships_of(client)[0]);
+
+        // Far from every ship, so the shell can't hit anything
+        let start = vec3(0., 15_000., 10.);
+        let aimpoint = vec2(5_000., 15_000.);
+        let vel = vec3(500., 0., 50.);
+        let world = harness.app.world_mut();
+        let target = world
+            .spawn((
+                Transform::from_translation(aimpoint.extend(0.)),
+                Velocity(Vec3::ZERO),
+            ))
+            .id();
+        let bullet = world
+            .spawn((
+                Bullet {
+                    owning_ship: shooter,
+                    turret_idx: 0,
+                    targ_ship: target,
+                    caliber: Caliber::from_mm(300.),
+                    ty: BulletType::AP,
+                    fire_chance: 0.,
+                    inital_pos: start,
+                    inital_vel: vel,
+                    curr_vel: vel,
+                    inital_aimpoint: aimpoint,
+                    current_aimpoint: aimpoint,
+                    expected_flight_time_total: Duration::from_secs(10),
+                    current_flight_time: Duration::ZERO,
+                    damage: 1_000.,
+                },
+                Team(client),
+                Transform::from_translation(start),
+            ))
+            .id();
+        harness.tick();
+        assert!(harness.app.world().get_entity(bullet).is_ok());
+
+        // The shell follows its target's predicted position, right off the map
+        harness
+            .app
+            .world_mut()
+            .get_mut::<Transform>(target)
+            .unwrap()
+            .translation = vec3(100_000., 15_000., 0.);
+        harness.tick();
+        assert!(harness.app.world().get_entity(bullet).is_err());
+    }
+
+    #[test]
+    fn test_bullet_keeps_last_aimpoint_once_target_is_undetected() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let shooter = harness.local(harness.ships_of(client)[0]);
+        let target = harness.local(harness.ships_of(other)[0]);
+
+        let world = harness.app.world_mut();
+        let gravity = world.resource::<GameRules>().gravity;
+        let start = world.get::<Transform>(shooter).unwrap().translation + vec3(0., 0., 10.);
+        let aimpoint = start.truncate() - vec2(5_000., 0.);
+        world.get_mut::<Transform>(target).unwrap().translation = aimpoint.extend(0.);
+        let flight_time = 10.;
+        let vel = ((aimpoint - start.truncate()) / flight_time)
+            .extend((0.5 * gravity * flight_time * flight_time - start.z) / flight_time);
+        let bullet = world
+            .spawn((
+                Bullet {
+                    owning_ship: shooter,
+                    turret_idx: 0,
+                    targ_ship: target,
+                    caliber: Caliber::from_mm(300.),
+                    ty: BulletType::AP,
+                    fire_chance: 0.,
+                    inital_pos: start,
+                    inital_vel: vel,
+                    curr_vel: vel,
+                    inital_aimpoint: aimpoint,
+                    current_aimpoint: aimpoint,
+                    expected_flight_time_total: Duration::from_secs_f32(flight_time),
+                    current_flight_time: Duration::ZERO,
+                    damage: 1_000.,
+                },
+                Team(client),
+                Transform::from_translation(start),
+            ))
+            .id();
+        harness.ticks(64);
+        assert!(
+            harness
+                .app
+                .world()
+                .get::<DetectionStatus>(target)
+                .unwrap()
+                .detected_by
+                .contains(&Team(client))
+        );
+
+        // Goes dark far away, as `DetectionSystem` would find once it ran
+        let world = harness.app.world_mut();
+        world.get_mut::<Transform>(target).unwrap().translation = vec3(-20_000., -20_000., 0.);
+        world
+            .get_mut::<DetectionStatus>(target)
+            .unwrap()
+            .detected_by
+            .clear();
+
+        let mut landed_at = None;
+        for _ in 0..64 * 20 {
+            harness.tick();
+            let Some(trans) = harness.app.world().get::<Transform>(bullet) else {
+                break;
+            };
+            if trans.translation.z <= 0. {
+                landed_at = Some(trans.translation.truncate());
+                break;
+            }
+        }
+        let landed_at = landed_at.expect("the shell should have reached the water");
+        assert!(
+            landed_at.distance(aimpoint) < 20.,
+            "landed {}m from the aimpoint",
+            landed_at.distance(aimpoint)
+        );
+    }
+
+    #[test]
+    fn test_turrets_never_aim_at_allies() {
+        let mut harness = TestMatch::new();
+        let ships = harness.ships_of(harness.clients[0]);
+        let (ship, ally) = (harness.local(ships[0]), harness.local(ships[1]));
+        // As if it had slipped past the checks in `read_messages`
+        harness
+            .app
+            .world_mut()
+            .entity_mut(ship)
+            .insert(FireTarget { ship: ally });
+        harness.ticks(64);
+
+        let turret_states = harness.app.world().get::<TurretStates>(ship).unwrap();
+        assert!(
+            turret_states
+                .states
+                .iter()
+                .all(|state| match state.aim_info {
+                    TurretAimInfo::AimedAtTarget { target, .. }
+                    | TurretAimInfo::AimingToTarget { target, .. } => target != ally,
+                    TurretAimInfo::NoValidTarget {} => true,
+                })
+        );
+    }
+
+    /// Puts two enemies off the starboard side of the first ship, slightly different distances away,
+    /// and returns the indices of the enemies its secondaries end up engaging
+    ///
+    /// * `fire_target` - the index of the enemy to set as the ship's `FireTarget`, if any
+    fn secondary_targets(
+        distribution: SecondaryTargetDistribution,
+        fire_target: Option<usize>,
+    ) -> HashSet<usize> {
+        let mut harness = TestMatch::new();
+        harness
+            .app
+            .world_mut()
+            .resource_mut::<GameRules>()
+            .secondary_target_distribution = distribution;
+        let [client, other] = harness.clients;
+        let ship = harness.local(harness.ships_of(client)[0]);
+        let enemies = [0, 1].map(|idx| harness.local(harness.ships_of(other)[idx]));
+        let world = harness.app.world_mut();
+        let ship_trans = *world.get::<Transform>(ship).unwrap();
+        for (enemy, offset) in enemies
+            .into_iter()
+            .zip([vec3(300., 4_000., 0.), vec3(-300., 4_300., 0.)])
+        {
+            world.get_mut::<Transform>(enemy).unwrap().translation =
+                ship_trans.translation + ship_trans.rotation * offset;
+        }
+        if let Some(idx) = fire_target {
+            world
+                .entity_mut(ship)
+                .insert(FireTarget { ship: enemies[idx] });
+        }
+        harness.ticks(2);
+
+        let turret_states = harness.app.world().get::<TurretStates>(ship).unwrap();
+        turret_states
+            .states
+            .iter()
+            .filter(|state| state.template.targeting_mode == TargetingMode::Secondary)
+            .filter_map(|state| match state.aim_info {
+                TurretAimInfo::AimedAtTarget { target, .. }
+                | TurretAimInfo::AimingToTarget { target, .. } => Some(target),
+                TurretAimInfo::NoValidTarget {} => None,
+            })
+            .map(|target| enemies.iter().position(|&enemy| enemy == target).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_spread_secondaries_split_between_enemies() {
+        assert_eq!(
+            secondary_targets(SecondaryTargetDistribution::Nearest, None),
+            HashSet::from([0])
+        );
+        assert_eq!(
+            secondary_targets(SecondaryTargetDistribution::Spread, None),
+            HashSet::from([0, 1])
+        );
+        // Either way, secondaries which can reach the fire target stay on it
+        for distribution in [
+            SecondaryTargetDistribution::Nearest,
+            SecondaryTargetDistribution::Spread,
+        ] {
+            assert_eq!(secondary_targets(distribution, Some(1)), HashSet::from([1]));
+        }
+    }
+
+    #[test]
+    fn test_secondaries_stick_to_their_target_until_another_is_meaningfully_nearer() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let ship = harness.local(harness.ships_of(client)[0]);
+        let enemies = [0, 1].map(|idx| harness.local(harness.ships_of(other)[idx]));
+        let place_enemies = |harness: &mut TestMatch, dists: [f32; 2]| {
+            let world = harness.app.world_mut();
+            let ship_trans = *world.get::<Transform>(ship).unwrap();
+            for ((enemy, dist), side) in enemies.into_iter().zip(dists).zip([300., -300.]) {
+                world.get_mut::<Transform>(enemy).unwrap().translation =
+                    ship_trans.translation + ship_trans.rotation * vec3(side, dist, 0.);
+            }
+        };
+        let secondary_targets = |harness: &TestMatch| {
+            harness
+                .app
+                .world()
+                .get::<TurretStates>(ship)
+                .unwrap()
+                .states
+                .iter()
+                .filter(|state| state.template.targeting_mode == TargetingMode::Secondary)
+                .map(|state| match state.aim_info {
+                    TurretAimInfo::AimedAtTarget { target, .. }
+                    | TurretAimInfo::AimingToTarget { target, .. } => Some(target),
+                    TurretAimInfo::NoValidTarget {} => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        place_enemies(&mut harness, [4_000., 4_050.]);
+        harness.ticks(2);
+        let locked = secondary_targets(&harness);
+        assert!(locked.contains(&Some(enemies[0])));
+
+        // The enemies keep trading places as the nearer one
+        for tick in 0..64 {
+            let dists = match tick % 2 {
+                0 => [4_050., 4_000.],
+                _ => [4_000., 4_050.],
+            };
+            place_enemies(&mut harness, dists);
+            harness.tick();
+            assert_eq!(secondary_targets(&harness), locked);
+        }
+
+        // Now the second enemy is much nearer
+        place_enemies(&mut harness, [4_000., 2_500.]);
+        harness.ticks(2);
+        let targets = secondary_targets(&harness);
+        assert!(targets.contains(&Some(enemies[1])));
+        assert!(!targets.contains(&Some(enemies[0])));
+    }
+
+    #[test]
+    fn test_mismatched_turret_states_dont_panic() {
+        let mut harness = TestMatch::new();
+        let ship = harness.ships_of(harness.clients[0])[0];
+        let local = harness.local(ship);
+        let world = harness.app.world_mut();
+        assert!(
+            !world
+                .get::<Ship>(local)
+                .unwrap()
+                .template
+                .turret_instances
+                .is_empty()
+        );
+        let mut turret_states = world.get_mut::<TurretStates>(local).unwrap();
+        turret_states.states.pop();
+        let before = turret_states
+            .states
+            .iter()
+            .map(|s| s.absolute_pos)
+            .collect::<Vec<_>>();
+        world.get_mut::<Transform>(local).unwrap().translation += Vec3::X * 100.;
+
+        world.run_system_once(update_turret_absolute_pos).unwrap();
+
+        let after = world.get::<TurretStates>(local).unwrap();
+        assert_eq!(
+            after
+                .states
+                .iter()
+                .map(|s| s.absolute_pos)
+                .collect::<Vec<_>>(),
+            before
+        );
+    }
+
+    #[test]
+    fn test_turret_accuracy_counts_shots_fired() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let ship = harness.ships_of(client)[0];
+        let enemy = harness.ships_of(other)[0];
+        let (ship_local, enemy_local) = (harness.local(ship), harness.local(enemy));
+        let world = harness.app.world_mut();
+        let ship_pos = world.get::<Transform>(ship_local).unwrap().translation;
+        world.get_mut::<Transform>(enemy_local).unwrap().translation =
+            ship_pos + vec3(8_000., 0., 0.);
+        world
+            .entity_mut(ship_local)
+            .insert(FireTarget { ship: enemy_local });
+        harness.ticks(64 * 60);
+
+        let spawned_bullets = harness
+            .sent
+            .iter()
+            .filter(|msg| {
+                msg.client == client
+                    && matches!(
+                        msg.msg,
+                        Message::Match2Client(Match2Client::SpawnBullet { owning_ship, .. })
+                            if owning_ship == ship
+                    )
+            })
+            .count();
+        let turret_states = harness.get::<TurretStates>(ship);
+        let shots_fired = turret_states
+            .states
+            .iter()
+            .map(|state| state.accuracy.shots_fired as usize)
+            .sum::<usize>();
+        assert!(shots_fired > 0);
+        assert_eq!(shots_fired, spawned_bullets);
+        assert!(
+            turret_states
+                .states
+                .iter()
+                .all(|state| state.accuracy.hits <= state.accuracy.shots_fired)
+        );
+    }
+
+    /// Runs a ship firing at an enemy for a minute with `reload_speed`,
+    /// and returns how many shots its turrets fired
+    fn shots_fired_with_reload_speed(reload_speed: f32) -> usize {
+        let mut harness = TestMatch::with_rules(GameRules {
+            reload_speed,
+            ..default()
+        });
+        let [client, other] = harness.clients;
+        let ship = harness.ships_of(client)[0];
+        let (ship_local, enemy) = (
+            harness.local(ship),
+            harness.local(harness.ships_of(other)[0]),
+        );
+        let world = harness.app.world_mut();
+        let ship_pos = world.get::<Transform>(ship_local).unwrap().translation;
+        world.get_mut::<Transform>(enemy).unwrap().translation = ship_pos + vec3(8_000., 0., 0.);
+        world
+            .entity_mut(ship_local)
+            .insert(FireTarget { ship: enemy });
+        harness.ticks(64 * 60);
+
+        harness
+            .get::<TurretStates>(ship)
+            .states
+            .iter()
+            .map(|state| state.accuracy.shots_fired as usize)
+            .sum()
+    }
+
+    #[test]
+    fn test_reload_speed_scales_fire_rate() {
+        let harness = TestMatch::with_rules(GameRules {
+            reload_speed: 10.,
+            ..default()
+        });
+        let ship = harness
+            .ships_of(harness.clients[0])
+            .into_iter()
+            .map(|ship| harness.get::<Ship>(ship))
+            .find(|ship| ship.template.id == ShipTemplateId::oland())
+            .unwrap();
+        let torpedoes = ship.template.torpedoes.as_ref().unwrap();
+        assert!(ship.torpedo_reloads.iter().all(|timer| {
+            (timer.duration().as_secs_f32() - torpedoes.reload.as_secs_f32() / 10.).abs() < 0.001
+        }));
+
+        let normal = shots_fired_with_reload_speed(1.);
+        assert!(normal > 0);
+        assert!(shots_fired_with_reload_speed(10.) > normal * 3);
+    }
+
+    #[test]
+    fn test_zero_reload_speed_is_rejected() {
+        for reload_speed in [0., -1., f32::INFINITY] {
+            let rules = GameRules {
+                reload_speed,
+                ..default()
+            };
+            assert!(rules.validate().is_err());
+        }
+        assert!(GameRules::default().validate().is_ok());
+    }
+
+    /// Runs a ship firing at an enemy 8km away with its main battery
+    /// limited to `max_elevation` radians, and returns the shells it fired
+    fn main_battery_shots_with_max_elevation(max_elevation: f32) -> usize {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let ship = harness.ships_of(client)[0];
+        let (ship_local, enemy) = (
+            harness.local(ship),
+            harness.local(harness.ships_of(other)[0]),
+        );
+        let world = harness.app.world_mut();
+        let ship_pos = world.get::<Transform>(ship_local).unwrap().translation;
+        world.get_mut::<Transform>(enemy).unwrap().translation = ship_pos + vec3(8_000., 0., 0.);
+        world
+            .entity_mut(ship_local)
+            .insert(FireTarget { ship: enemy });
+        let mut turret_states = world.get_mut::<TurretStates>(ship_local).unwrap();
+        for state in &mut turret_states.states {
+            if state.template.targeting_mode == TargetingMode::Primary {
+                assert!(state.template.max_range > 8_000.);
+                state.template = Box::leak(Box::new(TurretTemplate {
+                    max_elevation,
+                    ..state.template.clone()
+                }));
+            }
+        }
+        harness.ticks(64 * 40);
+
+        harness
+            .get::<TurretStates>(ship)
+            .states
+            .iter()
+            .filter(|state| state.template.targeting_mode == TargetingMode::Primary)
+            .map(|state| state.accuracy.shots_fired as usize)
+            .sum()
+    }
+
+    #[test]
+    fn test_target_above_max_elevation_is_not_engaged() {
+        // An 8km shot at ~820m/s needs about 3.4 degrees of elevation
+        assert_eq!(main_battery_shots_with_max_elevation(2_f32.to_radians()), 0);
+        assert!(main_battery_shots_with_max_elevation(30_f32.to_radians()) > 0);
+    }
+
+    /// Runs an Oland firing at a Bismarck 10% beyond the reach of its main battery,
+    /// but well within Bismarck's detection, with `spotter_plane_bonus` in its
+    /// `GunRangeModifiers`, and returns the shells it fired
+    fn main_battery_shots_with_spotter_plane_bonus(spotter_plane_bonus: Option<f32>) -> usize {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let find_ship = |harness: &TestMatch, client, id| {
+            harness
+                .ships_of(client)
+                .into_iter()
+                .find(|&ship| harness.get::<Ship>(ship).template.id == id)
+                .unwrap()
+        };
+        let ship = find_ship(&harness, client, ShipTemplateId::oland());
+        let (ship_local, enemy) = (
+            harness.local(ship),
+            harness.local(find_ship(&harness, other, ShipTemplateId::bismarck())),
+        );
+        let world = harness.app.world_mut();
+        let max_range = world
+            .get::<TurretStates>(ship_local)
+            .unwrap()
+            .states
+            .iter()
+            .filter(|state| state.template.targeting_mode == TargetingMode::Primary)
+            .map(|state| state.template.max_range)
+            .fold(0., f32::max);
+        let ship_pos = world.get::<Transform>(ship_local).unwrap().translation;
+        world.get_mut::<Transform>(enemy).unwrap().translation =
+            ship_pos + vec3(max_range * 1.1, 0., 0.);
+        world
+            .entity_mut(ship_local)
+            .insert(FireTarget { ship: enemy });
+        world
+            .get_mut::<Ship>(ship_local)
+            .unwrap()
+            .gun_range_modifiers
+            .spotter_plane_bonus = spotter_plane_bonus;
+        harness.ticks(64 * 40);
+
+        harness
+            .get::<TurretStates>(ship)
+            .states
+            .iter()
+            .filter(|state| state.template.targeting_mode == TargetingMode::Primary)
+            .map(|state| state.accuracy.shots_fired as usize)
+            .sum()
+    }
+
+    #[test]
+    fn test_gun_range_modifiers_gate_firing() {
+        assert_eq!(main_battery_shots_with_spotter_plane_bonus(None), 0);
+        assert!(main_battery_shots_with_spotter_plane_bonus(Some(0.2)) > 0);
+    }
+
+    #[test]
+    fn test_high_arc_falls_back_to_low_arc_above_max_elevation() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let ship = harness
+            .ships_of(client)
+            .into_iter()
+            .find(|&ship| harness.get::<Ship>(ship).template.id == ShipTemplateId::bismarck())
+            .unwrap();
+        let (ship_local, enemy) = (
+            harness.local(ship),
+            harness.local(harness.ships_of(other)[0]),
+        );
+        let world = harness.app.world_mut();
+        let ship_pos = world.get::<Transform>(ship_local).unwrap().translation;
+        world.get_mut::<Transform>(enemy).unwrap().translation = ship_pos + vec3(8_000., 0., 0.);
+        world
+            .entity_mut(ship_local)
+            .insert(FireTarget { ship: enemy });
+        world.get_mut::<Ship>(ship_local).unwrap().shell_arc = ShellArc::High;
+        harness.ticks(64 * 40);
+
+        // Bismarck's main battery only elevates to 30 degrees, short of any high arc
+        let main_battery = harness
+            .get::<TurretStates>(ship)
+            .states
+            .iter()
+            .filter(|state| state.template.targeting_mode == TargetingMode::Primary)
+            .collect_vec();
+        assert!(main_battery.iter().all(|state| {
+            state.template.max_elevation < 45_f32.to_radians()
+                && match &state.aim_info {
+                    TurretAimInfo::AimedAtTarget { bp, .. }
+                    | TurretAimInfo::AimingToTarget { bp, .. } => {
+                        state.template.can_elevate_to(bp.projectile_elevation)
+                    }
+                    TurretAimInfo::NoValidTarget {} => true,
+                }
+        }));
+        assert!(
+            main_battery
+                .iter()
+                .map(|state| state.accuracy.shots_fired)
+                .sum::<u32>()
+                > 0
+        );
+    }
+
+    #[test]
+    fn test_three_teams_can_engage_each_other() {
+        let mut harness = TestMatch::with_client_count(
+            GameRules {
+                reload_speed: 10.,
+                ..default()
+            },
+            3,
+        );
+        let clients = harness.all_clients();
+        assert_eq!(clients.len(), 3);
+        for &client in &clients {
+            assert!(!harness.ships_of(client).is_empty());
+        }
+
+        // Targets a ship on the last team, rather than the next one over
+        let ship = harness.ships_of(clients[0])[0];
+        let (ship_local, enemy) = (
+            harness.local(ship),
+            harness.local(harness.ships_of(clients[2])[0]),
+        );
+        let world = harness.app.world_mut();
+        let ship_pos = world.get::<Transform>(ship_local).unwrap().translation;
+        world.get_mut::<Transform>(enemy).unwrap().translation = ship_pos + vec3(8_000., 0., 0.);
+        world
+            .entity_mut(ship_local)
+            .insert(FireTarget { ship: enemy });
+        harness.ticks(64 * 10);
+
+        let main_battery_shots: u32 = harness
+            .get::<TurretStates>(ship)
+            .states
+            .iter()
+            .filter(|state| state.template.targeting_mode == TargetingMode::Primary)
+            .map(|state| state.accuracy.shots_fired)
+            .sum();
+        assert!(main_battery_shots > 0);
+    }
+
+    /// Runs a ship firing at an enemy for a while from `seed`,
+    /// and returns the bullets it fired
+    fn bullets_fired_with_seed(seed: u64) -> Vec<String> {
+        let mut harness = TestMatch::new();
+        harness
+            .app
+            .world_mut()
+            .insert_resource(MatchRng::from_seed(seed));
+        let [client, other] = harness.clients;
+        let ship = harness.local(harness.ships_of(client)[0]);
+        let enemy = harness.local(harness.ships_of(other)[0]);
+        let world = harness.app.world_mut();
+        let ship_pos = world.get::<Transform>(ship).unwrap().translation;
+        world.get_mut::<Transform>(enemy).unwrap().translation = ship_pos + vec3(8_000., 0., 0.);
+        world.entity_mut(ship).insert(FireTarget { ship: enemy });
+        harness.ticks(64 * 40);
+
+        harness
+            .sent
+            .iter()
+            .filter(|msg| {
+                msg.client == client
+                    && matches!(
+                        msg.msg,
+                        Message::Match2Client(Match2Client::SpawnBullet { .. })
+                    )
+            })
+            .map(|msg| format!("{:?}", msg.msg))
+            .collect()
+    }
+
+    #[test]
+    fn test_same_seed_reproduces_dispersion() {
+        let fired = bullets_fired_with_seed(7);
+        assert!(!fired.is_empty());
+        assert_eq!(bullets_fired_with_seed(7), fired);
+        assert_ne!(bullets_fired_with_seed(8), fired);
+    }
 }
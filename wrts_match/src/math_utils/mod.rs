@@ -1,10 +1,7 @@
 //! Important math functions
-mod generated_bullet_problem_solution;
 
-use bevy::{
-    math::{VectorSpace, dvec3},
-    prelude::*,
-};
+use bevy::{math::VectorSpace, prelude::*};
+pub use wrts_match_shared::formulas::ballistics::{BulletProblemRes, bullet_problem};
 
 /// Returns the angle, in radians from the ground, that a bullet needs to be fired from to arrive at the given distance from its origin
 pub fn gun_angle_for_distance(
@@ -69,19 +66,31 @@ pub fn torpedo_problem(
     ship_vel: Vec2,
     muzzle_vel: f64,
 ) -> Option<TorpedoProblemRes> {
-    // Wolfram alpha black box
+    // Solves |p + v*t| = s*t, i.e. a*t^2 + b*t + c = 0
     let p = ship_start.as_dvec2() - projectile_start.as_dvec2();
     let v = ship_vel.as_dvec2();
     let s = muzzle_vel;
-    let disc = f64::powi(2. * p.x * v.x + 2. * p.y * v.y, 2)
-        - 4. * (p.x * p.x + p.y * p.y) * (-s * s + v.x * v.x + v.y * v.y);
-    if disc < 0. {
+    let a = v.length_squared() - s * s;
+    let b = 2. * p.dot(v);
+    let c = p.length_squared();
+    let t = if a.abs() < 1e-9 {
+        // The ship is exactly as fast as the projectile, so the equation is linear
+        -c / b
+    } else {
+        let disc = b * b - 4. * a * c;
+        if disc < 0. {
+            return None;
+        }
+        // When the ship is faster, both roots have the same sign and the first is the earlier.
+        // Otherwise only one is positive, which is the first unless the ship is exactly as fast
+        let first = (-b - disc.sqrt()) / (2. * a);
+        let other = (-b + disc.sqrt()) / (2. * a);
+        if first >= 0. { first } else { other }
+    };
+    // A negative time would mean launching the projectile in the past
+    if !t.is_finite() || t < 0. {
         return None;
     }
-    let t_num = -f64::sqrt(disc) - 2. * p.x * v.x - 2. * p.y * v.y;
-    let t_den = 2. * (-s * s + v.x * v.x + v.y * v.y);
-    let t = t_num / t_den;
-    // ...
 
     let intersection_point = ship_start + ship_vel * t as f32;
     let projectile_dir = (intersection_point - projectile_start).normalize();
@@ -92,73 +101,6 @@ pub fn torpedo_problem(
     })
 }
 
-#[derive(Debug, Clone)]
-pub struct BulletProblemRes {
-    pub intersection_point: Vec2,
-    pub intersection_time: f32,
-    pub intersection_dist: f32,
-    pub projectile_dir: Vec3,
-    /// Rotation around the z axis
-    /// (starting at x=1,y=0 moving counter-clockwise)
-    pub projectile_azimuth: f32,
-    /// Rotation towards the z axis
-    /// (starting from the xy plane and roating towards the z axis)
-    pub projectile_elevation: f32,
-}
-
-/// Calculates the direction of firing and intersection point of a projectile launched at a ship moving at a constant velocity
-/// The projectile has constant lateral speed and is affected by gravity, so it's assumed to follow a parabola
-pub fn bullet_problem(
-    projectile_start: Vec2,
-    ship_start: Vec2,
-    ship_vel: Vec2,
-    muzzle_vel: f64,
-    gravity: f64,
-) -> Option<BulletProblemRes> {
-    let p = (ship_start - projectile_start).as_dvec2();
-    let v = ship_vel.as_dvec2();
-
-    let t =
-        generated_bullet_problem_solution::GENERATED_CODE(gravity, p.x, p.y, muzzle_vel, v.x, v.y);
-    let t = (t.is_finite() && t.im.abs() <= 0.0000001).then_some(t.re)?;
-
-    let intersection = p + v * t;
-    let azimuth = f64::atan2(intersection.y, intersection.x);
-    let elevation = f64::asin(gravity * t / (2. * muzzle_vel)); // Checked
-    let proj_dir = dvec3(
-        elevation.cos() * intersection.normalize().x,
-        elevation.cos() * intersection.normalize().y,
-        elevation.sin(),
-    );
-    let dist = intersection.length();
-
-    assert!(
-        elevation.is_finite(),
-        "If a real t was found, elevation must be a real number"
-    );
-
-    if cfg!(debug_assertions) {
-        let proj_intersection = proj_dir.truncate() * muzzle_vel * t;
-        let error = intersection.distance(proj_intersection);
-        assert!(error <= 0.001);
-        if error > 0.001 {
-            eprintln!(
-                "WARN: Large bullet problem error; error={error:.2} {{Iship={:.2},Iproj={:.2},p={:.6},v={:.2}\n    elev={:.8},azi={:.8}}}\n",
-                intersection, proj_intersection, p, v, elevation, azimuth,
-            );
-        }
-    }
-
-    Some(BulletProblemRes {
-        intersection_point: intersection.as_vec2() + projectile_start,
-        intersection_time: t as f32,
-        intersection_dist: dist as f32,
-        projectile_dir: proj_dir.as_vec3(),
-        projectile_azimuth: azimuth as f32,
-        projectile_elevation: elevation as f32,
-    })
-}
-
 pub struct Circle {
     pub pos: Vec2,
     pub radius: f32,
@@ -203,3 +145,159 @@ pub fn cast_line_segment(
     }
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use wrts_match_shared::formulas::ballistics::{GRAVITY, ShellArc};
+
+    use crate::math_utils::{
+        BulletProblemRes, TorpedoProblemRes, bullet_problem, bullet_vel_for_shot,
+        gun_angle_for_distance, max_dist_for_vel, torpedo_problem,
+    };
+
+    const MUZZLE_VEL: f64 = 800.;
+
+    fn solve(target: Vec2, target_vel: Vec2) -> Option<BulletProblemRes> {
+        bullet_problem(
+            Vec2::ZERO,
+            target,
+            target_vel,
+            MUZZLE_VEL,
+            GRAVITY as f64,
+            ShellArc::Low,
+        )
+    }
+
+    fn max_range() -> f64 {
+        max_dist_for_vel(MUZZLE_VEL, GRAVITY as f64)
+    }
+
+    #[test]
+    fn test_stationary_targets_match_closed_form() {
+        for frac in [0.01, 0.1, 0.25, 0.5, 0.75, 0.95] {
+            let dist = max_range() * frac;
+            // Spread around the compass, so the azimuth is checked too
+            let target = Vec2::from_angle(frac as f32 * 6.) * dist as f32;
+            let bp = solve(target, Vec2::ZERO).unwrap();
+
+            let angle = gun_angle_for_distance(dist, MUZZLE_VEL, GRAVITY as f64, true).unwrap();
+            assert!(
+                (bp.projectile_elevation as f64 - angle).abs() < 1e-4,
+                "{frac} {bp:?} {angle}"
+            );
+            assert!(
+                bp.intersection_point.distance(target) < 0.01,
+                "{frac} {bp:?}"
+            );
+            assert!(
+                (bp.intersection_dist as f64 - dist).abs() < 0.01,
+                "{frac} {bp:?}"
+            );
+
+            let flight_time = dist / (MUZZLE_VEL * angle.cos());
+            assert!(
+                (bp.intersection_time as f64 - flight_time).abs() < 1e-3 * flight_time,
+                "{frac} {bp:?} {flight_time}"
+            );
+
+            let vel =
+                bullet_vel_for_shot(Vec2::ZERO, target, MUZZLE_VEL, GRAVITY as f64, true).unwrap();
+            let solved_vel = bp.projectile_dir * MUZZLE_VEL as f32;
+            assert!(solved_vel.distance(vel) < 0.1, "{frac} {solved_vel} {vel}");
+        }
+    }
+
+    #[test]
+    fn test_crossing_target_is_led() {
+        let target = vec2(8_000., 0.);
+        let target_vel = vec2(0., 15.);
+        let bp = solve(target, target_vel).unwrap();
+
+        let target_at_impact = target + target_vel * bp.intersection_time;
+        assert!(
+            bp.intersection_point.distance(target_at_impact) < 0.01,
+            "{bp:?}"
+        );
+        assert!(bp.projectile_azimuth > 0., "{bp:?}");
+        // The shell's own horizontal travel reaches the lead point at the same time
+        let shell_at_impact =
+            bp.projectile_dir.truncate() * MUZZLE_VEL as f32 * bp.intersection_time;
+        assert!(
+            shell_at_impact.distance(bp.intersection_point) < 0.1,
+            "{bp:?}"
+        );
+    }
+
+    #[test]
+    fn test_target_escaping_past_max_range_is_unreachable() {
+        let target = vec2((max_range() * 0.99) as f32, 0.);
+        assert!(solve(target, Vec2::ZERO).is_some());
+        assert!(solve(target, vec2(15., 0.)).is_none());
+        // Closing in brings it back in reach
+        assert!(solve(target, vec2(-15., 0.)).is_some());
+    }
+
+    #[test]
+    fn test_targets_around_max_range() {
+        // Right around the maximum range, the two arcs meet and the discriminant nears zero
+        let just_inside = solve(vec2((max_range() * 0.9999) as f32, 0.), Vec2::ZERO).unwrap();
+        assert!(
+            (just_inside.projectile_elevation - std::f32::consts::FRAC_PI_4).abs() < 0.02,
+            "{just_inside:?}"
+        );
+        assert!(just_inside.intersection_time.is_finite());
+
+        assert!(solve(vec2((max_range() * 1.0001) as f32, 0.), Vec2::ZERO).is_none());
+        assert!(solve(vec2((max_range() * 2.) as f32, 0.), Vec2::ZERO).is_none());
+    }
+
+    /// Checks that a torpedo launched from the origin along `res` reaches the ship at the same time
+    fn assert_torpedo_reaches(res: &TorpedoProblemRes, ship: Vec2, ship_vel: Vec2, speed: f64) {
+        assert!(res.intersection_time >= 0., "{res:?}");
+        let t = res.intersection_time as f32;
+        let ship_at_impact = ship + ship_vel * t;
+        let torpedo_at_impact = res.projectile_dir * speed as f32 * t;
+        assert!(
+            ship_at_impact.distance(res.intersection_point) < 0.1,
+            "{res:?}"
+        );
+        assert!(
+            torpedo_at_impact.distance(res.intersection_point) < 0.1,
+            "{res:?}"
+        );
+    }
+
+    #[test]
+    fn test_torpedo_meets_ship_heading_straight_at_it() {
+        let (ship, ship_vel, speed) = (vec2(5_000., 0.), vec2(-20., 0.), 15.);
+        let res = torpedo_problem(Vec2::ZERO, ship, ship_vel, speed).unwrap();
+        assert!(
+            (res.intersection_time - 5_000. / 35.).abs() < 0.01,
+            "{res:?}"
+        );
+        assert_torpedo_reaches(&res, ship, ship_vel, speed);
+    }
+
+    #[test]
+    fn test_torpedo_catches_slower_fleeing_ship() {
+        let (ship, ship_vel, speed) = (vec2(5_000., 2_000.), vec2(20., 5.), 30.);
+        let res = torpedo_problem(Vec2::ZERO, ship, ship_vel, speed).unwrap();
+        assert_torpedo_reaches(&res, ship, ship_vel, speed);
+    }
+
+    #[test]
+    fn test_torpedo_cant_catch_faster_fleeing_ship() {
+        // Both roots are negative, which used to give an intercept behind the shooter
+        assert!(torpedo_problem(Vec2::ZERO, vec2(5_000., 0.), vec2(20., 0.), 15.).is_none());
+        // Exactly as fast
+        assert!(torpedo_problem(Vec2::ZERO, vec2(5_000., 0.), vec2(15., 0.), 15.).is_none());
+    }
+
+    #[test]
+    fn test_torpedo_meets_equally_fast_ship_approaching() {
+        let (ship, ship_vel, speed) = (vec2(5_000., 0.), vec2(-15., 0.), 15.);
+        let res = torpedo_problem(Vec2::ZERO, ship, ship_vel, speed).unwrap();
+        assert_torpedo_reaches(&res, ship, ship_vel, speed);
+    }
+}
@@ -1,19 +1,32 @@
 use bevy::prelude::*;
 use itertools::Itertools;
 use std::io::stdin;
-use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
 use std::time::Duration;
 use std::{collections::HashMap, io::Write, ops::Deref};
-use wrts_messaging::{Client2Match, Match2Client, Message, SharedEntityId, WrtsMatchMessage};
+use wrts_match_shared::ship_template::TargetingMode;
+use wrts_messaging::{
+    Client2Match, EntityUpdate, Match2Client, Message, SharedEntityId, WrtsMatchMessage,
+};
 
 use wrts_messaging::{
     ClientId, ClientSharedInfo, RecvFromStream, WrtsMatchInitMessage, write_to_stream_sync,
 };
 
+use crate::aircraft::{Aircraft, AircraftKind, AircraftSpawnSent};
 use crate::detection::{BaseDetection, DetectionStatus};
 pub use crate::networking::shared_entity_tracking::SharedEntityTracking;
-use crate::ship::{Ship, SmokeConsumableState, SmokeDeploying, TurretStates};
-use crate::{FireTarget, Health, MoveOrder, Team, Torpedo, Velocity};
+use crate::ship::{
+    GunsIdle, RepairPartyState, RepairingState, SentTurretDirs, Ship, SmokeConsumableState,
+    SmokeDeploying, SmokePuff, SpotterPlaneFlying, SpotterPlaneState, TorpReloadBoosterState,
+    TurretAimInfo, TurretDirsSent, TurretStates,
+};
+use crate::spawn_entity::SpawnAircraftCommand;
+use crate::{
+    Bullet, FireTarget, GameRules, HeadingOrder, Health, MATCH_EVENTS, MatchRng, MatchTimer,
+    MoveOrder, Paused, Surrenders, Team, Torpedo, Velocity,
+};
 
 pub struct NetworkingPlugin;
 
@@ -24,7 +37,7 @@ pub struct UpdateClientsSystem;
 
 impl Plugin for NetworkingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(PreStartup, network_handshake)
+        app.init_resource::<DestroyedEntities>()
             .configure_sets(FixedUpdate, ReadClientMessagesSystem)
             .add_systems(
                 FixedUpdate,
@@ -34,12 +47,16 @@ impl Plugin for NetworkingPlugin {
             .add_systems(
                 FixedUpdate,
                 (
-                    send_transform_updates,
-                    send_velocity_updates,
-                    send_turret_state_updates,
+                    send_aircraft_spawns.before(send_world_snapshots),
+                    send_world_snapshots,
+                    send_guns_idle_updates,
                     send_health_updates,
                     send_torpedo_reload_updates,
                     send_smoke_consumable_state_updates,
+                    send_torp_reload_booster_state_updates,
+                    send_spotter_plane_state_updates,
+                    send_destroyed_entities,
+                    send_pause_updates,
                 )
                     .in_set(UpdateClientsSystem),
             );
@@ -71,15 +88,10 @@ fn stdout_handler(rx: Receiver<WrtsMatchMessage>) {
     loop {
         match rx.recv() {
             Ok(msg) => {
-                match &msg.msg {
-                    Message::Match2Client(Match2Client::SetTrans { .. })
-                    | Message::Match2Client(Match2Client::SetTurretDirs { .. })
-                    | Message::Match2Client(Match2Client::SetVelocity { .. })
-                    | Message::Match2Client(Match2Client::SetSmokeConsumableState { .. })
-                    | Message::Match2Client(Match2Client::SetReloadedTorps { .. }) => {
-                        trace!("Sending: {msg:?}")
-                    }
-                    _ => info!("Sending: {msg:?}"),
+                if is_droppable_update(&msg.msg) {
+                    trace!("Sending: {msg:?}")
+                } else {
+                    info!("Sending: {msg:?}")
                 }
 
                 if let Err(e) = write_to_stream_sync(&msg, &mut stdout) {
@@ -95,27 +107,93 @@ fn stdout_handler(rx: Receiver<WrtsMatchMessage>) {
     }
 }
 
+/// How many messages each direction of the stdin/stdout bridge can buffer
+///
+/// Each client gets one snapshot of the world per tick, plus a few other state updates per ship,
+/// so this leaves room for a couple of ticks of a large match before droppable updates start being dropped
+const BRIDGE_CHANNEL_CAPACITY: usize = 1024;
+
+/// A warning is logged each time this many more updates have been dropped
+const DROPPED_UPDATES_WARN_INTERVAL: u64 = 1024;
+
+/// Whether `msg` only repeats state which is sent again on a later tick anyway,
+/// so can be dropped rather than stall the match when the stdout bridge is backed up.
+/// Snapshots resend entities which have stopped moving every [MOVEMENT_RESEND_TICKS]
+fn is_droppable_update(msg: &Message) -> bool {
+    matches!(
+        msg,
+        Message::Match2Client(
+            Match2Client::WorldSnapshot { .. }
+                | Match2Client::SetSmokeConsumableState { .. }
+                | Match2Client::SetTorpReloadBoosterState { .. }
+                | Match2Client::SetSpotterPlaneState { .. }
+                | Match2Client::SetReloadedTorps { .. }
+                | Match2Client::MatchTime { .. }
+        )
+    )
+}
+
+/// Messages to the clients, written to stdout by `stdout_handler`
+///
+/// If the lobby stops reading fast enough for the channel to fill up,
+/// droppable state updates (see `is_droppable_update`) are dropped so the match keeps running,
+/// while everything else still blocks until there's room, since losing it would desync a client
 #[derive(Debug, Resource)]
-pub struct MessagesSend(SyncSender<WrtsMatchMessage>);
+pub struct MessagesSend {
+    tx: SyncSender<WrtsMatchMessage>,
+    dropped: AtomicU64,
+}
 
 impl MessagesSend {
+    pub fn new(tx: SyncSender<WrtsMatchMessage>) -> Self {
+        Self {
+            tx,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
     pub fn send(&self, msg: WrtsMatchMessage) {
-        SyncSender::send(&self, msg)
-            .expect("`MessagesSend` should never disconnect unless the bevy app has closed down")
+        const DISCONNECTED: &str =
+            "`MessagesSend` should never disconnect unless the bevy app has closed down";
+        if !is_droppable_update(&msg.msg) {
+            self.tx.send(msg).expect(DISCONNECTED);
+            return;
+        }
+        match self.tx.try_send(msg) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped % DROPPED_UPDATES_WARN_INTERVAL == 1 {
+                    warn!("Dropped {dropped} state updates so far, since stdout is backed up");
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => panic!("{DISCONNECTED}"),
+        }
+    }
+
+    /// The number of droppable updates dropped so far because the channel was full
+    #[cfg(test)]
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
     }
 }
 
+/// The shared ids of entities despawned so far this tick,
+/// which are sent to the clients together by `send_destroyed_entities`
+#[derive(Debug, Resource, Default)]
+pub struct DestroyedEntities(pub Vec<SharedEntityId>);
+
 impl Deref for MessagesSend {
     type Target = SyncSender<WrtsMatchMessage>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.tx
     }
 }
 
 /// Non-send resource
 #[derive(Debug)]
-pub struct MessagesRecv(Receiver<WrtsMatchMessage>);
+pub struct MessagesRecv(pub(crate) Receiver<WrtsMatchMessage>);
 
 impl Deref for MessagesRecv {
     type Target = Receiver<WrtsMatchMessage>;
@@ -193,26 +271,46 @@ pub struct ClientInfo {
     pub info: ClientSharedInfo,
 }
 
-fn network_handshake(world: &mut World) {
+/// Marks a [ClientInfo] as a spectator rather than a player,
+/// which sees every ship but can't control any, and has no team of its own
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Spectator;
+
+/// The seed the match was started with, kept to resend to clients which rejoin
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct MatchSeed(pub u64);
+
+/// Writes everything sent through [MessagesSend] to stdout,
+/// returning once [MessagesSend] is dropped and every message has been written
+#[derive(Resource, Debug)]
+pub struct StdoutHandlerThread(pub std::thread::JoinHandle<()>);
+
+/// Connects to the clients over stdin/stdout
+///
+/// Must run before `NetworkingPlugin`'s systems, which expect the resources it inserts
+pub fn network_handshake(world: &mut World) {
     info!(
         "`WrtsMatchMessage` in-memory size: {}B",
         std::mem::size_of::<WrtsMatchMessage>()
     );
     let init_msg = WrtsMatchInitMessage::recv_sync(&mut stdin()).unwrap();
+    info!("Match seed: {}", init_msg.seed);
+    world.resource_mut::<GameRules>().friendly_fire = init_msg.friendly_fire;
 
-    let (handler_tx, msgs_rx) = mpsc::sync_channel::<WrtsMatchMessage>(128);
-    let (msgs_tx, handler_rx) = mpsc::sync_channel::<WrtsMatchMessage>(128);
+    let (handler_tx, msgs_rx) = mpsc::sync_channel::<WrtsMatchMessage>(BRIDGE_CHANNEL_CAPACITY);
+    let (msgs_tx, handler_rx) = mpsc::sync_channel::<WrtsMatchMessage>(BRIDGE_CHANNEL_CAPACITY);
 
     std::thread::spawn(move || {
         stdin_handler(handler_tx);
     });
-    std::thread::spawn(move || {
+    let stdout_thread = std::thread::spawn(move || {
         stdout_handler(handler_rx);
     });
+    world.insert_resource(StdoutHandlerThread(stdout_thread));
 
     let client_infos = {
         let mut infos = HashMap::new();
-        for cl in init_msg.clients {
+        for &cl in &init_msg.clients {
             let _ = msgs_tx.send(WrtsMatchMessage {
                 client: cl,
                 msg: Message::Match2Client(Match2Client::InitA { your_client: cl }),
@@ -244,12 +342,15 @@ fn network_handshake(world: &mut World) {
             client: cl_info.id,
             msg: Message::Match2Client(Match2Client::InitC {
                 all_clients: client_infos.values().cloned().collect(),
+                seed: init_msg.seed,
             }),
         });
         world.spawn(ClientInfo { info: cl_info });
     }
 
-    world.insert_resource(MessagesSend(msgs_tx));
+    world.insert_resource(MessagesSend::new(msgs_tx));
+    world.insert_resource(MatchRng::from_seed(init_msg.seed));
+    world.insert_resource(MatchSeed(init_msg.seed));
     world.init_resource::<SharedEntityTracking>();
     world.insert_non_send_resource(MessagesRecv(msgs_rx));
 }
@@ -260,9 +361,13 @@ fn read_messages(
     msgs_tx: Res<MessagesSend>,
     shared_entities: Res<SharedEntityTracking>,
     mut exit: EventWriter<AppExit>,
+    mut surrenders: ResMut<Surrenders>,
+    mut paused: ResMut<Paused>,
+    rules: Res<GameRules>,
 
-    ships: Query<(&Ship, &Transform)>,
+    mut ships: Query<(&mut Ship, &Transform)>,
     teams: Query<&Team>,
+    clients: Query<(&ClientInfo, Has<Spectator>)>,
 ) {
     loop {
         let WrtsMatchMessage {
@@ -277,6 +382,19 @@ fn read_messages(
                 return;
             }
         };
+        let is_spectator = clients
+            .iter()
+            .any(|(cl, spectator)| spectator && cl.info.id == msg_sender);
+        // Spectators can't control anything, and only the lobby speaks for them
+        if is_spectator
+            && !matches!(
+                msg,
+                Message::Client2Match(Client2Match::SpectatorJoined { .. })
+            )
+        {
+            warn!("Spectator {msg_sender} sent a message only players can send: {msg:?}");
+            continue;
+        }
         match msg {
             Message::Client2Match(Client2Match::Echo(s)) => {
                 msgs_tx.send(WrtsMatchMessage {
@@ -300,7 +418,44 @@ fn read_messages(
                     );
                     continue;
                 }
-                commands.entity(local).insert(MoveOrder { waypoints });
+                // Echoed back so the client knows the order arrived
+                msgs_tx.send(WrtsMatchMessage {
+                    client: msg_sender,
+                    msg: Message::Match2Client(Match2Client::SetMoveOrder {
+                        id,
+                        waypoints: waypoints.clone(),
+                    }),
+                });
+                commands
+                    .entity(local)
+                    .insert(MoveOrder::new(waypoints))
+                    .remove::<HeadingOrder>();
+            }
+            Message::Client2Match(Client2Match::SetHeading { id, heading }) => {
+                let Some(local) = shared_entities.get_by_shared(id) else {
+                    warn!("Client {msg_sender} sent message with bad id: {id:?}");
+                    continue;
+                };
+                if teams
+                    .get(local)
+                    .ok()
+                    .and_then(|team| (team.0 == msg_sender).then_some(()))
+                    .is_none()
+                {
+                    warn!("Client {msg_sender} tried to SetHeading on an entity not owned by them");
+                    continue;
+                }
+                if !heading.is_finite() {
+                    warn!("Client {msg_sender} tried to SetHeading to a bad heading: {heading}");
+                    continue;
+                }
+                if !ships.contains(local) {
+                    warn!("Client {msg_sender} tried to SetHeading on a non-ship");
+                    continue;
+                }
+                commands
+                    .entity(local)
+                    .insert((MoveOrder::default(), HeadingOrder { heading }));
             }
             Message::Client2Match(Client2Match::SetFireTarg { id, targ }) => {
                 let Some(local) = shared_entities.get_by_shared(id) else {
@@ -314,7 +469,7 @@ fn read_messages(
                     .is_none()
                 {
                     warn!(
-                        "Client {msg_sender} tried to SetMoveOrder on an entity not owned by them"
+                        "Client {msg_sender} tried to SetFireTarg on an entity not owned by them"
                     );
                     continue;
                 }
@@ -324,7 +479,11 @@ fn read_messages(
                             warn!("Client {msg_sender} sent message with bad id: {targ:?}");
                             continue;
                         };
-                        if ships.contains(targ_local) {
+                        if teams.get(targ_local).is_ok_and(|team| team.0 == msg_sender) {
+                            warn!(
+                                "Client {msg_sender} tried to SetFireTarg at a friendly ship: {targ:?}"
+                            );
+                        } else if ships.contains(targ_local) {
                             commands
                                 .entity(local)
                                 .insert(FireTarget { ship: targ_local });
@@ -339,6 +498,27 @@ fn read_messages(
                     }
                 }
             }
+            Message::Client2Match(Client2Match::SetShellArc { id, arc }) => {
+                let Some(local) = shared_entities.get_by_shared(id) else {
+                    warn!("Client {msg_sender} sent message with bad id: {id:?}");
+                    continue;
+                };
+                if teams
+                    .get(local)
+                    .ok()
+                    .and_then(|team| (team.0 == msg_sender).then_some(()))
+                    .is_none()
+                {
+                    warn!(
+                        "Client {msg_sender} tried to SetShellArc on an entity not owned by them"
+                    );
+                    continue;
+                }
+                match ships.get_mut(local) {
+                    Ok((mut ship, _)) => ship.shell_arc = arc,
+                    Err(_) => warn!("Client {msg_sender} tried to SetShellArc on a non-ship"),
+                }
+            }
             Message::Client2Match(Client2Match::LaunchTorpedoVolley { ship, dir }) => {
                 commands.queue(LaunchTorpedoVolleyCommand {
                     msg_sender,
@@ -352,10 +532,72 @@ fn read_messages(
                     ship_id: ship,
                 });
             }
-            Message::Client2Match(Client2Match::InitB { .. })
-            | Message::Match2Client(_)
-            | Message::Client2Lobby(_)
-            | Message::Lobby2Client(_) => {
+            Message::Client2Match(Client2Match::CancelConsumableSmoke { ship }) => {
+                commands.queue(CancelConsumableSmokeCommand {
+                    msg_sender,
+                    ship_id: ship,
+                });
+            }
+            Message::Client2Match(Client2Match::UseConsumableTorpReloadBooster { ship }) => {
+                commands.queue(UseConsumableTorpReloadBoosterCommand {
+                    msg_sender,
+                    ship_id: ship,
+                });
+            }
+            Message::Client2Match(Client2Match::UseConsumableSpotterPlane { ship }) => {
+                commands.queue(UseConsumableSpotterPlaneCommand {
+                    msg_sender,
+                    ship_id: ship,
+                });
+            }
+            Message::Client2Match(Client2Match::UseConsumableRepairParty { ship }) => {
+                commands.queue(UseConsumableRepairPartyCommand {
+                    msg_sender,
+                    ship_id: ship,
+                });
+            }
+            Message::Client2Match(Client2Match::Surrender) => {
+                info!("Client {msg_sender} surrendered");
+                if !surrenders.0.contains(&msg_sender) {
+                    surrenders.0.push(msg_sender);
+                }
+            }
+            Message::Client2Match(Client2Match::SetPaused { paused: new_paused }) => {
+                if !rules.allow_pause {
+                    warn!("Client {msg_sender} tried to pause a match which doesn't allow it");
+                    continue;
+                }
+                info!("Client {msg_sender} set paused to {new_paused}");
+                paused.set_if_neq(Paused(new_paused));
+            }
+            Message::Client2Match(Client2Match::Rejoined) => {
+                info!("Client {msg_sender} rejoined, resending the match to it");
+                commands.queue(ResyncClientCommand { client: msg_sender });
+            }
+            Message::Client2Match(Client2Match::SpectatorJoined { info }) => {
+                match clients.iter().find(|(cl, _)| cl.info.id == info.id) {
+                    Some((_, false)) => {
+                        warn!(
+                            "Client {} is playing in the match, so can't spectate it",
+                            info.id
+                        );
+                        continue;
+                    }
+                    // Spectating again after leaving
+                    Some((_, true)) => info!("Client {} resumed spectating", info.id),
+                    None => {
+                        info!("Client {} started spectating", info.id);
+                        commands.spawn((ClientInfo { info }, Spectator));
+                    }
+                }
+                commands.queue(ResyncClientCommand { client: msg_sender });
+            }
+            Message::Client2Match(Client2Match::InitB { .. }) => {
+                // The reply to the `InitA` sent by `ResyncClientCommand`,
+                // which the lobby has already checked
+                debug!("Client {msg_sender} finished rejoining");
+            }
+            Message::Match2Client(_) | Message::Client2Lobby(_) | Message::Lobby2Client(_) => {
                 error!("Received unexpected message: {msg:?}");
             }
         };
@@ -431,6 +673,14 @@ impl Command for LaunchTorpedoVolleyCommand {
 
         volley_timer.reset();
         let ship_pos = ship_trans.translation.truncate();
+        debug!(
+            target: MATCH_EVENTS,
+            event = "torpedo_volley_launched",
+            time = world.resource::<Time>().elapsed_secs(),
+            client = %msg_sender,
+            ship = ?self.owning_ship_id,
+            torpedoes = torpedoes.torps_per_volley,
+        );
 
         for torp_idx in 0..torpedoes.torps_per_volley {
             let angle_offset = {
@@ -461,6 +711,7 @@ impl Command for LaunchTorpedoVolleyCommand {
                         BaseDetection(2_000.),
                         DetectionStatus {
                             is_detected: false,
+                            detected_by: vec![],
                             detection_increased_by_firing: Timer::new(
                                 Duration::ZERO,
                                 TimerMode::Once,
@@ -552,117 +803,672 @@ impl Command for UseConsumableSmokeCommand {
                 action_timer: Timer::new(smoke.action_time, TimerMode::Once),
                 puff_timer: Timer::new(Duration::from_secs(2), TimerMode::Repeating),
             });
+            debug!(
+                target: MATCH_EVENTS,
+                event = "consumable_used",
+                time = world.resource::<Time>().elapsed_secs(),
+                client = %msg_sender,
+                ship = ?ship_id,
+                consumable = "smoke",
+            );
         }
     }
 }
 
-fn send_transform_updates(
-    transforms: Query<(Entity, &Transform, Option<(&DetectionStatus, &Team)>), Changed<Transform>>,
-    clients: Query<&ClientInfo>,
+struct CancelConsumableSmokeCommand {
+    msg_sender: ClientId,
+    ship_id: SharedEntityId,
+}
+
+impl Command for CancelConsumableSmokeCommand {
+    fn apply(self, world: &mut World) -> () {
+        let Self {
+            msg_sender,
+            ship_id,
+        } = self;
+        let Some(ship_local) = world
+            .resource::<SharedEntityTracking>()
+            .get_by_shared(self.ship_id)
+        else {
+            warn!("Client {msg_sender} sent message with bad ship id: {ship_id:?}");
+            return;
+        };
+        if world
+            .get::<Team>(ship_local)
+            .and_then(|team| (team.0 == msg_sender).then_some(()))
+            .is_none()
+        {
+            warn!(
+                "Client {msg_sender} tried to CancelConsumableSmoke on an entity not owned by them"
+            );
+            return;
+        }
+
+        if world.get::<SmokeDeploying>(ship_local).is_none() {
+            warn!(
+                "Client {msg_sender} tried to CancelConsumableSmoke on a ship not deploying smoke"
+            );
+            return;
+        }
+        // The cooldown was reset when the smoke was used,
+        // and `advance_smoke_cooldown` starts ticking it again once this is removed
+        world.entity_mut(ship_local).remove::<SmokeDeploying>();
+    }
+}
+
+struct UseConsumableTorpReloadBoosterCommand {
+    msg_sender: ClientId,
+    ship_id: SharedEntityId,
+}
+
+impl Command for UseConsumableTorpReloadBoosterCommand {
+    fn apply(self, world: &mut World) -> () {
+        let Self {
+            msg_sender,
+            ship_id,
+        } = self;
+        let Some(ship_local) = world
+            .resource::<SharedEntityTracking>()
+            .get_by_shared(self.ship_id)
+        else {
+            warn!("Client {msg_sender} sent message with bad ship id: {ship_id:?}");
+            return;
+        };
+        if world
+            .get::<Team>(ship_local)
+            .and_then(|team| (team.0 == msg_sender).then_some(()))
+            .is_none()
+        {
+            warn!(
+                "Client {msg_sender} tried to UseConsumableTorpReloadBooster on an entity not owned by them"
+            );
+            return;
+        }
+
+        let Some((mut ship, mut booster_state)) = world
+            .query::<(&mut Ship, &mut TorpReloadBoosterState)>()
+            .get_mut(world, ship_local)
+            .ok()
+        else {
+            warn!(
+                "Client {msg_sender} tried to UseConsumableTorpReloadBooster on a ship that doesn't exist anymore or doesn't have a torpedo reload booster"
+            );
+            return;
+        };
+
+        if booster_state.charges_unused.unwrap_or(usize::MAX) == 0
+            || !booster_state.cooldown_timer.finished()
+        {
+            return;
+        }
+        // Don't waste a charge when every volley is already loaded
+        if ship.torpedo_reloads.iter().all(|timer| timer.finished()) {
+            return;
+        }
+
+        if let Some(charges_unused) = &mut booster_state.charges_unused {
+            *charges_unused -= 1;
+        }
+        booster_state.cooldown_timer.reset();
+        for timer in &mut ship.torpedo_reloads {
+            let remaining = timer.remaining();
+            timer.tick(remaining);
+        }
+        debug!(
+            target: MATCH_EVENTS,
+            event = "consumable_used",
+            time = world.resource::<Time>().elapsed_secs(),
+            client = %msg_sender,
+            ship = ?ship_id,
+            consumable = "torp_reload_booster",
+        );
+    }
+}
+
+struct UseConsumableSpotterPlaneCommand {
+    msg_sender: ClientId,
+    ship_id: SharedEntityId,
+}
+
+impl Command for UseConsumableSpotterPlaneCommand {
+    fn apply(self, world: &mut World) -> () {
+        let Self {
+            msg_sender,
+            ship_id,
+        } = self;
+        let Some(ship_local) = world
+            .resource::<SharedEntityTracking>()
+            .get_by_shared(self.ship_id)
+        else {
+            warn!("Client {msg_sender} sent message with bad ship id: {ship_id:?}");
+            return;
+        };
+        if world
+            .get::<Team>(ship_local)
+            .and_then(|team| (team.0 == msg_sender).then_some(()))
+            .is_none()
+        {
+            warn!(
+                "Client {msg_sender} tried to UseConsumableSpotterPlane on an entity not owned by them"
+            );
+            return;
+        }
+
+        if world.get::<SpotterPlaneFlying>(ship_local).is_some() {
+            return;
+        }
+
+        let Some((mut ship, mut plane_state)) = world
+            .query::<(&mut Ship, &mut SpotterPlaneState)>()
+            .get_mut(world, ship_local)
+            .ok()
+        else {
+            warn!(
+                "Client {msg_sender} tried to UseConsumableSpotterPlane on a ship that doesn't exist anymore or doesn't have a spotter plane"
+            );
+            return;
+        };
+
+        if plane_state.charges_unused.unwrap_or(usize::MAX) == 0
+            || !plane_state.cooldown_timer.finished()
+        {
+            return;
+        }
+
+        if let Some(charges_unused) = &mut plane_state.charges_unused {
+            *charges_unused -= 1;
+        }
+        let spotter_plane = ship.template.consumables.spotter_plane().unwrap();
+        plane_state.cooldown_timer.reset();
+        ship.gun_range_modifiers.spotter_plane_bonus = Some(spotter_plane.range_bonus);
+        let action_timer = Timer::new(spotter_plane.action_time, TimerMode::Once);
+        world
+            .entity_mut(ship_local)
+            .insert(SpotterPlaneFlying { action_timer });
+        SpawnAircraftCommand {
+            team: Team(msg_sender),
+            owning_ship: ship_local,
+            kind: AircraftKind::Spotter,
+        }
+        .apply(world);
+        debug!(
+            target: MATCH_EVENTS,
+            event = "consumable_used",
+            time = world.resource::<Time>().elapsed_secs(),
+            client = %msg_sender,
+            ship = ?ship_id,
+            consumable = "spotter_plane",
+        );
+    }
+}
+
+struct UseConsumableRepairPartyCommand {
+    msg_sender: ClientId,
+    ship_id: SharedEntityId,
+}
+
+impl Command for UseConsumableRepairPartyCommand {
+    fn apply(self, world: &mut World) -> () {
+        let Self {
+            msg_sender,
+            ship_id,
+        } = self;
+        let Some(ship_local) = world
+            .resource::<SharedEntityTracking>()
+            .get_by_shared(self.ship_id)
+        else {
+            warn!("Client {msg_sender} sent message with bad ship id: {ship_id:?}");
+            return;
+        };
+        if world
+            .get::<Team>(ship_local)
+            .and_then(|team| (team.0 == msg_sender).then_some(()))
+            .is_none()
+        {
+            warn!(
+                "Client {msg_sender} tried to UseConsumableRepairParty on an entity not owned by them"
+            );
+            return;
+        }
+
+        if world.get::<RepairingState>(ship_local).is_some() {
+            return;
+        }
+
+        let Some((ship, mut repair_state)) = world
+            .query::<(&Ship, &mut RepairPartyState)>()
+            .get_mut(world, ship_local)
+            .ok()
+        else {
+            warn!(
+                "Client {msg_sender} tried to UseConsumableRepairParty on a ship that doesn't exist anymore or doesn't have a repair party"
+            );
+            return;
+        };
+
+        if repair_state.charges_unused.unwrap_or(usize::MAX) == 0
+            || !repair_state.cooldown_timer.finished()
+        {
+            return;
+        }
+
+        if let Some(charges_unused) = &mut repair_state.charges_unused {
+            *charges_unused -= 1;
+        }
+        let repair_party = ship.template.consumables.repair_party().unwrap();
+        repair_state.cooldown_timer.reset();
+        let action_timer = Timer::new(repair_party.action_time, TimerMode::Once);
+        world
+            .entity_mut(ship_local)
+            .insert(RepairingState { action_timer });
+        debug!(
+            target: MATCH_EVENTS,
+            event = "consumable_used",
+            time = world.resource::<Time>().elapsed_secs(),
+            client = %msg_sender,
+            ship = ?ship_id,
+            consumable = "repair_party",
+        );
+    }
+}
+
+/// Catches a client which rejoined the match, or just started spectating it, up on everything
+/// it missed, by redoing the handshake and sending every entity as if it had just spawned
+struct ResyncClientCommand {
+    client: ClientId,
+}
+
+impl Command for ResyncClientCommand {
+    fn apply(self, world: &mut World) -> () {
+        let viewer = Team(self.client);
+        let mut clients = world.query::<(&ClientInfo, Has<Spectator>)>();
+        let mut ships = world.query::<(
+            Entity,
+            &Ship,
+            &Team,
+            &Health,
+            &Transform,
+            &TurretStates,
+            &GunsIdle,
+            &DetectionStatus,
+        )>();
+        let mut torpedoes = world.query::<(
+            Entity,
+            &Torpedo,
+            &Team,
+            &Transform,
+            &Velocity,
+            &DetectionStatus,
+        )>();
+        let mut bullets = world.query::<(Entity, &Bullet, &Team, &Transform)>();
+        let mut smoke_puffs = world.query::<(Entity, &SmokePuff, &Transform)>();
+        // Aircraft are sent again by `send_aircraft_spawns`, once their ship can be seen
+        for mut sent in world.query::<&mut AircraftSpawnSent>().iter_mut(world) {
+            sent.0.remove(&self.client);
+        }
+        let mut detectees = world.query::<(Entity, &DetectionStatus, &Team)>();
+        let mut movers = world.query_filtered::<(
+            Entity,
+            &Transform,
+            Option<&Velocity>,
+            Option<&TurretStates>,
+            Option<(&DetectionStatus, &Team)>,
+        ), Without<Aircraft>>();
+        let world: &World = world;
+        let shared_entities = world.resource::<SharedEntityTracking>();
+        let spectator = clients
+            .iter(world)
+            .any(|(cl, spectator)| spectator && cl.info.id == self.client);
+        let visible = |detection: &DetectionStatus, team: Team| {
+            spectator || detection.is_visible_to(team, viewer)
+        };
+
+        let mut msgs = vec![
+            Match2Client::InitA {
+                your_client: self.client,
+            },
+            Match2Client::InitC {
+                all_clients: clients
+                    .iter(world)
+                    .filter(|(_, spectator)| !spectator)
+                    .map(|(cl, _)| cl.info.clone())
+                    .collect(),
+                seed: world.resource::<MatchSeed>().0,
+            },
+        ];
+
+        // Enemies the client can't see are still spawned, so that later updates about them
+        // have something to apply to, but without giving away where they are
+        for (local, ship, team, health, trans, turret_states, guns_idle, detection) in
+            ships.iter(world)
+        {
+            let Some(id) = shared_entities.get_by_local(local) else {
+                continue;
+            };
+            let (pos, rot, turret_rots) = if visible(detection, *team) {
+                (
+                    trans.translation.truncate(),
+                    trans.rotation,
+                    turret_states.states.iter().map(|state| state.dir).collect(),
+                )
+            } else {
+                (
+                    Vec2::ZERO,
+                    Quat::IDENTITY,
+                    ship.template
+                        .turret_instances
+                        .iter()
+                        .map(|instance| instance.default_dir)
+                        .collect(),
+                )
+            };
+            msgs.push(Match2Client::SpawnShip {
+                id,
+                team: team.0,
+                ship_base: ship.template.id,
+                health: health.0.total(),
+                pos,
+                rot,
+                turret_rots,
+            });
+            msgs.push(Match2Client::SetHealth {
+                id,
+                health: health.0.total(),
+                sections: health.0.fractions(ship.template.max_health),
+            });
+            if *team == viewer {
+                msgs.push(Match2Client::SetGunsIdle {
+                    id,
+                    idle: guns_idle.0,
+                });
+            }
+        }
+        // Shots and torpedoes are spawned by the client as belonging to a ship,
+        // so any left over from a ship which has since sunk are skipped
+        for (local, torpedo, team, trans, vel, detection) in torpedoes.iter(world) {
+            let (Some(id), Some(owning_ship)) = (
+                shared_entities.get_by_local(local),
+                shared_entities.get_by_local(torpedo.owning_ship),
+            ) else {
+                continue;
+            };
+            let vel = vel.0.truncate();
+            let (pos, vel) = if visible(detection, *team) {
+                (trans.translation.truncate(), vel)
+            } else {
+                (Vec2::ZERO, Vec2::X * vel.length())
+            };
+            msgs.push(Match2Client::SpawnTorpedo {
+                id,
+                team: team.0,
+                owning_ship,
+                damage: torpedo.damage,
+                pos,
+                vel,
+            });
+        }
+        // Shells have no detection of their own, so are only resent if their ship can be seen
+        for (local, bullet, team, trans) in bullets.iter(world) {
+            let (Some(id), Some(owning_ship)) = (
+                shared_entities.get_by_local(local),
+                shared_entities.get_by_local(bullet.owning_ship),
+            ) else {
+                continue;
+            };
+            let owner_visible = world
+                .get::<DetectionStatus>(bullet.owning_ship)
+                .is_none_or(|detection| visible(detection, *team));
+            if !owner_visible {
+                continue;
+            }
+            msgs.push(Match2Client::SpawnBullet {
+                id,
+                team: team.0,
+                owning_ship,
+                damage: bullet.damage,
+                caliber: bullet.caliber,
+                ty: bullet.ty,
+                pos: trans.translation,
+                rot: trans.rotation,
+            });
+        }
+        for (local, puff, trans) in smoke_puffs.iter(world) {
+            let Some(id) = shared_entities.get_by_local(local) else {
+                continue;
+            };
+            msgs.push(Match2Client::SpawnSmokePuff {
+                id,
+                pos: trans.translation.truncate(),
+                radius: puff.radius,
+            });
+        }
+        for (local, detection, &team) in detectees.iter(world) {
+            let Some(id) = shared_entities.get_by_local(local) else {
+                continue;
+            };
+            msgs.push(Match2Client::SetDetection {
+                id,
+                currently_detected: spectator || detection.detected_for(team, viewer),
+            });
+        }
+        let entities = movers
+            .iter(world)
+            .filter(|(.., detection)| {
+                spectator
+                    || detection
+                        .is_none_or(|(detection, &team)| detection.is_visible_to(team, viewer))
+            })
+            .filter_map(|(local, trans, vel, turret_states, _)| {
+                Some(EntityUpdate {
+                    id: shared_entities.get_by_local(local)?,
+                    trans: Some((trans.translation, trans.rotation)),
+                    vel: vel.map(|vel| vel.0.truncate()),
+                    turret_dirs: turret_states.map(|turret_states| {
+                        turret_states.states.iter().map(|state| state.dir).collect()
+                    }),
+                })
+            })
+            .collect();
+        msgs.push(Match2Client::WorldSnapshot { entities });
+        if world.resource::<Paused>().0 {
+            msgs.push(Match2Client::MatchPaused { paused: true });
+        }
+        msgs.push(Match2Client::MatchTime {
+            remaining: world.resource::<MatchTimer>().0.remaining(),
+        });
+
+        let msgs_tx = world.resource::<MessagesSend>();
+        for msg in msgs {
+            msgs_tx.send(WrtsMatchMessage {
+                client: self.client,
+                msg: Message::Match2Client(msg),
+            });
+        }
+    }
+}
+
+/// Turret directions are sent again once any turret has turned by more than this (radians)
+const TURRET_DIR_RESEND_EPSILON: f32 = 1e-3;
+/// Turret directions are sent at least this often, even if no turret has moved
+const TURRET_DIRS_RESEND_TICKS: u32 = 64;
+
+/// Moving entities have their movement sent at least this often, even once they've stopped.
+/// Snapshots can be dropped (see `is_droppable_update`), and otherwise a client which missed
+/// the last one before an entity came to rest would never be sent where it stopped
+pub(crate) const MOVEMENT_RESEND_TICKS: u32 = 64;
+
+/// Sends each client a [Match2Client::SpawnAircraft] for every aircraft whose ship it can see,
+/// the first tick it can see it. Teams which haven't spotted the ship aren't told about
+/// its aircraft, which would give away where the ship is
+fn send_aircraft_spawns(
+    aircraft: Query<(Entity, &Aircraft, &Team, &Transform, &mut AircraftSpawnSent)>,
+    ships: Query<(&DetectionStatus, &Team), With<Ship>>,
+    clients: Query<(&ClientInfo, Has<Spectator>)>,
     msgs_tx: Res<MessagesSend>,
     shared_entities: Res<SharedEntityTracking>,
 ) {
-    let clients = clients.iter().map(|cl| cl.info.id).collect_vec();
-    for (local, trans, detection) in transforms {
-        let clients_to_update: Vec<ClientId>;
-        if let Some((detection, team)) = detection
-            && !detection.is_detected
-        {
-            clients_to_update = vec![team.0];
-        } else {
-            clients_to_update = clients.clone();
-        }
-        let Some(shared) = shared_entities.get_by_local(local) else {
+    for (local, plane, team, trans, mut sent) in aircraft {
+        let (Some(id), Some(owning_ship), Ok((ship_detection, &ship_team))) = (
+            shared_entities.get_by_local(local),
+            shared_entities.get_by_local(plane.owning_ship),
+            ships.get(plane.owning_ship),
+        ) else {
             continue;
         };
-        for cl in clients_to_update {
+        for (cl, spectator) in clients {
+            let visible = spectator || ship_detection.is_visible_to(ship_team, Team(cl.info.id));
+            if !visible || !sent.0.insert(cl.info.id) {
+                continue;
+            }
             msgs_tx.send(WrtsMatchMessage {
-                client: cl,
-                msg: Message::Match2Client(Match2Client::SetTrans {
-                    id: shared,
-                    pos: trans.translation,
-                    rot: trans.rotation,
+                client: cl.info.id,
+                msg: Message::Match2Client(Match2Client::SpawnAircraft {
+                    id,
+                    team: team.0,
+                    owning_ship,
+                    pos: trans.translation.truncate(),
                 }),
             });
         }
     }
 }
 
-fn send_velocity_updates(
-    transforms: Query<(Entity, &Velocity, Option<(&DetectionStatus, &Team)>), Changed<Transform>>,
-    clients: Query<&ClientInfo>,
+/// Sends each client one [Match2Client::WorldSnapshot] per tick,
+/// holding the movement and turrets of every entity it can see which changed.
+/// Aircraft have no detection of their own, so can be seen whenever their ship can
+///
+/// * `tick` - counts the ticks so far, to spread out the resends of still entities
+fn send_world_snapshots(
+    entities: Query<(
+        Entity,
+        Ref<Transform>,
+        Option<&Velocity>,
+        Option<(&TurretStates, &mut SentTurretDirs)>,
+        Option<(&DetectionStatus, &Team)>,
+        Option<&Aircraft>,
+    )>,
+    ships: Query<(&DetectionStatus, &Team), With<Ship>>,
+    clients: Query<(&ClientInfo, Has<Spectator>)>,
     msgs_tx: Res<MessagesSend>,
     shared_entities: Res<SharedEntityTracking>,
+    mut tick: Local<u32>,
 ) {
-    let clients = clients.iter().map(|cl| cl.info.id).collect_vec();
-    for (local, vel, detection) in transforms {
-        let clients_to_update: Vec<ClientId>;
-        if let Some((detection, team)) = detection
-            && !detection.is_detected
-        {
-            clients_to_update = vec![team.0];
-        } else {
-            clients_to_update = clients.clone();
-        }
+    *tick = tick.wrapping_add(1);
+    let mut snapshots = clients
+        .iter()
+        .map(|(cl, spectator)| (cl.info.id, spectator, Vec::new()))
+        .collect_vec();
+    for (local, trans, vel, mut turrets, detection, aircraft) in entities {
         let Some(shared) = shared_entities.get_by_local(local) else {
             continue;
         };
-        for cl in clients_to_update {
-            msgs_tx.send(WrtsMatchMessage {
-                client: cl,
-                msg: Message::Match2Client(Match2Client::SetVelocity {
-                    id: shared,
-                    vel: vel.0.truncate(),
-                }),
+        let detection = detection.or_else(|| ships.get(aircraft?.owning_ship).ok());
+        let resend_due =
+            vel.is_some() && local.index().wrapping_add(*tick) % MOVEMENT_RESEND_TICKS == 0;
+        let moved = trans.is_changed() || resend_due;
+        for (cl, spectator, snapshot) in &mut snapshots {
+            let visible = *spectator
+                || detection
+                    .is_none_or(|(detection, &team)| detection.is_visible_to(team, Team(*cl)));
+            if !visible {
+                continue;
+            }
+            let turret_dirs = turrets.as_mut().and_then(|(turret_states, sent)| {
+                turret_dirs_to_send(turret_states, sent.0.entry(*cl).or_default())
             });
+            if !moved && turret_dirs.is_none() {
+                continue;
+            }
+            snapshot.push(EntityUpdate {
+                id: shared,
+                trans: moved.then_some((trans.translation, trans.rotation)),
+                vel: vel.filter(|_| moved).map(|vel| vel.0.truncate()),
+                turret_dirs,
+            });
+        }
+    }
+
+    for (cl, _, entities) in snapshots {
+        if entities.is_empty() {
+            continue;
         }
+        msgs_tx.send(WrtsMatchMessage {
+            client: cl,
+            msg: Message::Match2Client(Match2Client::WorldSnapshot { entities }),
+        });
     }
 }
 
-fn send_turret_state_updates(
-    ships: Query<(Entity, &TurretStates)>,
-    clients: Query<&ClientInfo>,
+/// The ship's turret directions, if any turret has moved enough
+/// or it's been long enough since they were last sent to the client `sent` is for
+fn turret_dirs_to_send(
+    turret_states: &TurretStates,
+    sent: &mut TurretDirsSent,
+) -> Option<Vec<f32>> {
+    sent.ticks_since_sent += 1;
+    let any_moved = sent.dirs.len() != turret_states.states.len()
+        || sent
+            .dirs
+            .iter()
+            .zip(&turret_states.states)
+            .any(|(&sent_dir, state)| (state.dir - sent_dir).abs() > TURRET_DIR_RESEND_EPSILON);
+    if !any_moved && sent.ticks_since_sent < TURRET_DIRS_RESEND_TICKS {
+        return None;
+    }
+    sent.dirs = turret_states
+        .states
+        .iter()
+        .map(|state| state.dir)
+        .collect_vec();
+    sent.ticks_since_sent = 0;
+    Some(sent.dirs.clone())
+}
+
+fn send_guns_idle_updates(
+    ships: Query<(Entity, &TurretStates, &Team, &mut GunsIdle)>,
     msgs_tx: Res<MessagesSend>,
     shared_entities: Res<SharedEntityTracking>,
 ) {
-    let clients = clients.iter().map(|cl| cl.info.id).collect_vec();
-    for (local, turret_states) in ships {
+    for (local, turret_states, team, mut guns_idle) in ships {
+        let mut main_battery = turret_states
+            .states
+            .iter()
+            .filter(|state| state.template.targeting_mode == TargetingMode::Primary)
+            .peekable();
+        let idle = main_battery.peek().is_some()
+            && main_battery.all(|state| matches!(state.aim_info, TurretAimInfo::NoValidTarget {}));
+        if !guns_idle.set_if_neq(GunsIdle(idle)) {
+            continue;
+        }
         let Some(shared) = shared_entities.get_by_local(local) else {
             continue;
         };
-        for cl in clients.clone() {
-            msgs_tx.send(WrtsMatchMessage {
-                client: cl,
-                msg: Message::Match2Client(Match2Client::SetTurretDirs {
-                    id: shared,
-                    turret_dirs: turret_states
-                        .states
-                        .iter()
-                        .map(|state| state.dir)
-                        .collect_vec(),
-                }),
-            })
-        }
+        msgs_tx.send(WrtsMatchMessage {
+            client: team.0,
+            msg: Message::Match2Client(Match2Client::SetGunsIdle { id: shared, idle }),
+        });
     }
 }
 
 fn send_health_updates(
-    healths: Query<(Entity, &Health), Changed<Health>>,
+    healths: Query<(Entity, &Ship, &Health), Changed<Health>>,
     clients: Query<&ClientInfo>,
     msgs_tx: Res<MessagesSend>,
     shared_entities: Res<SharedEntityTracking>,
 ) {
     let clients = clients.iter().map(|cl| cl.info.id).collect_vec();
-    for (local, health) in healths {
+    for (local, ship, health) in healths {
         let Some(shared) = shared_entities.get_by_local(local) else {
             continue;
         };
+        let sections = health.0.fractions(ship.template.max_health);
         for cl in clients.clone() {
             msgs_tx.send(WrtsMatchMessage {
                 client: cl,
                 msg: Message::Match2Client(Match2Client::SetHealth {
                     id: shared,
-                    health: health.0,
+                    health: health.0.total(),
+                    sections,
                 }),
             })
         }
@@ -679,9 +1485,9 @@ fn send_torpedo_reload_updates(
             continue;
         };
 
-        if ship.template.torpedoes.is_none() {
+        let Some(torpedoes) = &ship.template.torpedoes else {
             continue;
-        }
+        };
 
         let timers = &ship.torpedo_reloads;
         let ready_to_fire = timers.iter().filter(|timer| timer.finished()).count();
@@ -699,6 +1505,8 @@ fn send_torpedo_reload_updates(
                 id: shared,
                 ready_to_fire,
                 still_reloading: still_reloading.clone(),
+                // Every volley's timer is made with the same, possibly rescaled, reload
+                full_reload: timers.first().map_or(torpedoes.reload, Timer::duration),
             }),
         })
     }
@@ -744,3 +1552,182 @@ fn send_smoke_consumable_state_updates(
         }
     }
 }
+
+fn send_torp_reload_booster_state_updates(
+    boosters: Query<(Entity, &TorpReloadBoosterState, &Team)>,
+    msgs_tx: Res<MessagesSend>,
+    shared_entities: Res<SharedEntityTracking>,
+) {
+    for (local, booster_state, ship_team) in boosters {
+        let Some(shared) = shared_entities.get_by_local(local) else {
+            continue;
+        };
+
+        let charges_unused = booster_state.charges_unused.map(|x| x as u16);
+        let state = if booster_state.cooldown_timer.finished() {
+            wrts_messaging::TorpReloadBoosterState::Recharged { charges_unused }
+        } else {
+            wrts_messaging::TorpReloadBoosterState::Recharging {
+                charges_unused,
+                recharge_time_remaining: booster_state.cooldown_timer.remaining(),
+            }
+        };
+
+        msgs_tx.send(WrtsMatchMessage {
+            client: ship_team.0,
+            msg: Message::Match2Client(Match2Client::SetTorpReloadBoosterState {
+                id: shared,
+                state,
+            }),
+        })
+    }
+}
+
+fn send_spotter_plane_state_updates(
+    spotters: Query<(
+        Entity,
+        &SpotterPlaneState,
+        Option<&SpotterPlaneFlying>,
+        &Team,
+    )>,
+    msgs_tx: Res<MessagesSend>,
+    shared_entities: Res<SharedEntityTracking>,
+) {
+    for (local, plane_state, plane_flying, ship_team) in spotters {
+        let Some(shared) = shared_entities.get_by_local(local) else {
+            continue;
+        };
+
+        let charges_unused = plane_state.charges_unused.map(|x| x as u16);
+        let state = if let Some(plane_flying) = plane_flying {
+            wrts_messaging::SpotterPlaneState::Flying {
+                charges_unused,
+                action_time_remaining: plane_flying.action_timer.remaining(),
+            }
+        } else if plane_state.cooldown_timer.finished() {
+            wrts_messaging::SpotterPlaneState::Recharged { charges_unused }
+        } else {
+            wrts_messaging::SpotterPlaneState::Recharging {
+                charges_unused,
+                recharge_time_remaining: plane_state.cooldown_timer.remaining(),
+            }
+        };
+
+        msgs_tx.send(WrtsMatchMessage {
+            client: ship_team.0,
+            msg: Message::Match2Client(Match2Client::SetSpotterPlaneState { id: shared, state }),
+        })
+    }
+}
+
+/// Despawns are batched into one message per tick, since a ship being destroyed
+/// or a salvo landing can despawn many entities at once
+fn send_destroyed_entities(
+    mut destroyed: ResMut<DestroyedEntities>,
+    clients: Query<&ClientInfo>,
+    msgs_tx: Res<MessagesSend>,
+) {
+    if destroyed.0.is_empty() {
+        return;
+    }
+    let destroyed = std::mem::take(&mut destroyed.0);
+    for cl in clients {
+        msgs_tx.send(WrtsMatchMessage {
+            client: cl.info.id,
+            msg: Message::Match2Client(Match2Client::DestroyEntities(destroyed.clone())),
+        });
+    }
+}
+
+fn send_pause_updates(
+    paused: Res<Paused>,
+    clients: Query<&ClientInfo>,
+    msgs_tx: Res<MessagesSend>,
+) {
+    if !paused.is_changed() || paused.is_added() {
+        return;
+    }
+    for cl in clients {
+        msgs_tx.send(WrtsMatchMessage {
+            client: cl.info.id,
+            msg: Message::Match2Client(Match2Client::MatchPaused { paused: paused.0 }),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use bevy::prelude::*;
+    use wrts_messaging::{
+        ClientId, EntityUpdate, Match2Client, Message, SharedEntityId, WrtsMatchMessage,
+    };
+
+    use crate::networking::MessagesSend;
+
+    fn world_snapshot(id: u64) -> WrtsMatchMessage {
+        WrtsMatchMessage {
+            client: ClientId(0),
+            msg: Message::Match2Client(Match2Client::WorldSnapshot {
+                entities: vec![EntityUpdate {
+                    id: SharedEntityId(id),
+                    trans: Some((Vec3::ZERO, Quat::IDENTITY)),
+                    vel: None,
+                    turret_dirs: None,
+                }],
+            }),
+        }
+    }
+
+    fn destroy(id: u64) -> WrtsMatchMessage {
+        WrtsMatchMessage {
+            client: ClientId(0),
+            msg: Message::Match2Client(Match2Client::DestroyEntities(vec![SharedEntityId(id)])),
+        }
+    }
+
+    #[test]
+    fn test_flooding_a_stalled_bridge_drops_updates_without_blocking() {
+        let (tx, rx) = mpsc::sync_channel(8);
+        let msgs_tx = MessagesSend::new(tx);
+
+        // Nothing reads `rx` yet, so this would block forever if updates weren't dropped
+        for id in 0..10_000 {
+            msgs_tx.send(world_snapshot(id));
+        }
+        assert_eq!(msgs_tx.dropped(), 10_000 - 8);
+        assert_eq!(rx.try_iter().count(), 8);
+    }
+
+    #[test]
+    fn test_flooding_a_slow_bridge_still_delivers_every_other_message() {
+        let (tx, rx) = mpsc::sync_channel(8);
+        let msgs_tx = MessagesSend::new(tx);
+
+        let reader = thread::spawn(move || {
+            let mut destroyed = vec![];
+            for msg in rx {
+                if let Message::Match2Client(Match2Client::DestroyEntities(ids)) = msg.msg {
+                    destroyed.extend(ids);
+                }
+                thread::sleep(Duration::from_micros(10));
+            }
+            destroyed
+        });
+        for id in 0..1_000 {
+            for _ in 0..20 {
+                msgs_tx.send(world_snapshot(id));
+            }
+            msgs_tx.send(destroy(id));
+        }
+        assert!(msgs_tx.dropped() > 0);
+        drop(msgs_tx);
+
+        let destroyed = reader.join().unwrap();
+        assert_eq!(
+            destroyed,
+            (0..1_000).map(SharedEntityId).collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,6 +1,13 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
+use rand::Rng;
 use rand_distr::Distribution;
-use wrts_match_shared::ship_template::{Dispersion, ShipTemplate};
+use wrts_match_shared::{
+    formulas::{GunRangeCalc, GunRangeModifiers, ballistics::ShellArc},
+    ship_template::{Dispersion, HullSection, SectionHealth, ShipTemplate, TurretTemplate},
+};
+use wrts_messaging::ClientId;
 
 use crate::{Health, Team, Velocity, math_utils::BulletProblemRes};
 
@@ -19,18 +26,55 @@ pub enum TurretAimInfo {
 
 #[derive(Debug, Clone)]
 pub struct TurretState {
+    /// Resolved once at spawn, since [TurretInstance::turret_template]
+    /// is too slow to call per turret every frame
+    ///
+    /// [TurretInstance::turret_template]: wrts_match_shared::ship_template::TurretInstance::turret_template
+    pub template: &'static TurretTemplate,
     pub dir: f32,
     /// A `once` timer
     pub reload_timer: Timer,
     pub absolute_pos: Vec2,
     pub aim_info: TurretAimInfo,
+    pub accuracy: TurretAccuracy,
+    /// The enemy a secondary turret last engaged, which it sticks to while it's still
+    /// in reach rather than switching to whichever enemy is marginally nearer
+    pub secondary_target: Option<Entity>,
+}
+
+/// Counted over the whole match, for tuning dispersion
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TurretAccuracy {
+    /// Each barrel's shell counts as a shot
+    pub shots_fired: u32,
+    pub hits: u32,
 }
 
+/// One state per `ShipTemplate::turret_instances` of the ship, in the same order.
+/// Always built from the ship's own template when spawned,
+/// which `aim_turrets` and `fire_bullets` rely on when indexing both by turret
 #[derive(Component, Debug, Clone)]
 pub struct TurretStates {
     pub states: Vec<TurretState>,
 }
 
+/// Whether every main battery turret has no valid target,
+/// kept so that the owner is only sent `Match2Client::SetGunsIdle` when it changes
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct GunsIdle(pub bool);
+
+/// The turret directions last sent to each client in a `Match2Client::WorldSnapshot`,
+/// so that they're only sent again once a turret moves. Tracked per client,
+/// since a client which can't see the ship isn't sent them
+#[derive(Component, Debug, Clone, Default)]
+pub struct SentTurretDirs(pub HashMap<ClientId, TurretDirsSent>);
+
+#[derive(Debug, Clone, Default)]
+pub struct TurretDirsSent {
+    pub dirs: Vec<f32>,
+    pub ticks_since_sent: u32,
+}
+
 #[derive(Component, Debug, Clone)]
 pub struct SmokeConsumableState {
     /// A `once` timer
@@ -39,6 +83,83 @@ pub struct SmokeConsumableState {
     pub charges_unused: Option<usize>,
 }
 
+#[derive(Component, Debug, Clone)]
+pub struct TorpReloadBoosterState {
+    /// A `once` timer
+    pub cooldown_timer: Timer,
+    /// `None` if infinite charges
+    pub charges_unused: Option<usize>,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct SpotterPlaneState {
+    /// A `once` timer
+    pub cooldown_timer: Timer,
+    /// `None` if infinite charges
+    pub charges_unused: Option<usize>,
+}
+
+/// Inserted while the ship's spotter plane is up, which extends its gun range
+/// and lets it detect enemies within `SpotterPlane::spotting_range`
+#[derive(Component, Debug, Clone)]
+pub struct SpotterPlaneFlying {
+    /// A `once` timer
+    pub action_timer: Timer,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct RepairPartyState {
+    /// A `once` timer
+    pub cooldown_timer: Timer,
+    /// `None` if infinite charges
+    pub charges_unused: Option<usize>,
+}
+
+/// Inserted while the ship's repair party is working
+#[derive(Component, Debug, Clone)]
+pub struct RepairingState {
+    /// A `once` timer
+    pub action_timer: Timer,
+}
+
+/// How much of the health each section has lost a repair party can still restore.
+/// Only `GameRules::repairable_fraction` of each hit is added, and repairing uses it up
+#[derive(Component, Debug, Clone)]
+pub struct RepairableDamage {
+    /// In the order of `HullSection::ALL`
+    pub pool: [f64; HullSection::COUNT],
+    /// The health when damage was last tracked, to find how much has been lost since
+    pub last_health: SectionHealth,
+}
+
+impl RepairableDamage {
+    /// Adds `repairable_fraction` of the health each section has lost since the last time
+    /// this was called to the pool, then starts tracking from `health`
+    pub fn track(&mut self, health: &SectionHealth, repairable_fraction: f64) {
+        for (section, pool) in HullSection::ALL.into_iter().zip(&mut self.pool) {
+            let lost = self.last_health.get(section) - health.get(section);
+            if lost > 0. {
+                *pool += lost * repairable_fraction;
+            }
+        }
+        self.last_health = *health;
+    }
+}
+
+/// Set by an HE shell, and drains the health of the section it hit until `timer` finishes.
+/// Another ignition while burning restarts the timer, rather than adding a second fire
+#[derive(Component, Debug, Clone)]
+pub struct Burning {
+    pub section: HullSection,
+    /// A `once` timer
+    pub timer: Timer,
+    /// The ship whose shell started the fire, credited with its damage
+    pub attacker: Entity,
+    pub attacker_team: Team,
+    /// Reported once the fire goes out, rather than every tick
+    pub damage_dealt: f64,
+}
+
 #[derive(Component, Debug, Clone)]
 pub struct SmokeDeploying {
     /// A `once` timer
@@ -63,21 +184,29 @@ pub struct Ship {
     pub curr_speed: f32,
     /// A `once` timer
     pub torpedo_reloads: Vec<Timer>,
+    /// The trajectory the main battery fires along, secondaries always fire low
+    pub shell_arc: ShellArc,
+    /// Passed to `GunRangeCalc` for every turret on the ship
+    pub gun_range_modifiers: GunRangeModifiers,
 }
 
-pub fn apply_dispersion(dispersion: &Dispersion, nominal_direction: Vec3) -> Vec3 {
-    let dist = rand_distr::Normal::new(0., dispersion.sigma).unwrap();
-    let mut rng = rand::rng();
-    let h_squared = dispersion.horizontal * dispersion.horizontal;
-    let v_squared = dispersion.vertical * dispersion.vertical;
-    let ellipse_pos = loop {
-        let x = dist.sample(&mut rng);
-        let y = dist.sample(&mut rng);
-
-        if x * x / h_squared + y * y / v_squared <= 1. {
-            break vec2(x, y);
+impl Ship {
+    /// How far `turret_template`'s guns reach on this ship right now
+    pub fn gun_range(&self, turret_template: &TurretTemplate) -> f32 {
+        GunRangeCalc {
+            base_range: turret_template.max_range,
         }
-    };
+        .run(self.gun_range_modifiers)
+    }
+}
+
+/// Deflects `nominal_direction` towards a random point of the dispersion ellipse drawn at 1 km
+pub fn apply_dispersion(
+    dispersion: &Dispersion,
+    nominal_direction: Vec3,
+    rng: &mut impl Rng,
+) -> Vec3 {
+    let ellipse_pos = sample_dispersion_ellipse(dispersion, rng);
 
     let elevation = f32::atan2(ellipse_pos.y, 1000.);
     let elev_rot_axis = Vec3::cross(nominal_direction, Vec3::Z).normalize();
@@ -86,3 +215,61 @@ pub fn apply_dispersion(dispersion: &Dispersion, nominal_direction: Vec3) -> Vec
     let azimuth = f32::atan2(ellipse_pos.x, 1000.);
     Mat3::from_axis_angle(Vec3::Z, azimuth) * dir
 }
+
+/// A point in the dispersion ellipse, relative to its center, with `x` horizontal and `y` vertical
+///
+/// Points follow a normal distribution truncated at the edge of the ellipse,
+/// which is `sigma` standard deviations out along both axes,
+/// so a higher `sigma` clusters shells closer to the center
+fn sample_dispersion_ellipse(dispersion: &Dispersion, rng: &mut impl Rng) -> Vec2 {
+    // Sampled within the unit circle, then stretched out to the ellipse
+    let dist = rand_distr::Normal::new(0., 1. / dispersion.sigma).unwrap();
+    let unit_pos = loop {
+        let pos = vec2(dist.sample(rng), dist.sample(rng));
+        if pos.length_squared() <= 1. {
+            break pos;
+        }
+    };
+    unit_pos * vec2(dispersion.horizontal, dispersion.vertical)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use rand::{SeedableRng, rngs::StdRng};
+    use wrts_match_shared::ship_template::Dispersion;
+
+    use crate::ship::sample_dispersion_ellipse;
+
+    #[test]
+    fn test_higher_sigma_clusters_dispersion_closer_to_the_center() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut samples_with_sigma = |sigma: f32| {
+            let dispersion = Dispersion {
+                vertical: 15.,
+                horizontal: 30.,
+                sigma,
+            };
+            let samples = (0..10_000)
+                .map(|_| sample_dispersion_ellipse(&dispersion, &mut rng))
+                .collect::<Vec<_>>();
+            for pos in &samples {
+                let ellipse_dist =
+                    (pos / vec2(dispersion.horizontal, dispersion.vertical)).length();
+                assert!(ellipse_dist <= 1. + 1e-6, "{pos} is outside the ellipse");
+            }
+            samples.iter().map(|pos| pos * pos).sum::<Vec2>() / samples.len() as f32
+        };
+
+        let loose = samples_with_sigma(1.);
+        let tight = samples_with_sigma(3.);
+        assert!(tight.x < loose.x && tight.y < loose.y, "{tight} vs {loose}");
+
+        // Barely any samples fall outside 3 standard deviations, so truncating them hardly matters
+        let expected = vec2(30. / 3., 15. / 3.).powf(2.);
+        assert!(
+            (tight / expected - 1.).abs().max_element() < 0.1,
+            "{tight} vs {expected}"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Collisions between ships, which push them apart and damage both
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use wrts_match_shared::ship_template::{Hull, Speed};
+use wrts_messaging::DamageSource;
+
+use crate::{
+    Health, Team, Velocity, damage::ReportDamageCommand, ship::Ship,
+    spawn_entity::DespawnNetworkedEntityCommand,
+};
+
+/// The fastest ships can go while grinding against each other
+pub(crate) const GRINDING_SPEED: Speed = Speed::from_kts(2.);
+/// Damage dealt to both ships as they collide, for every m/s they were closing on each other.
+/// Ship velocities are scaled by [crate::GAME_SCALE], so the damage is as well
+const RAMMING_DAMAGE_PER_MPS: f64 = 100.;
+
+/// A ship's hull as a rectangle on the water
+#[derive(Debug, Clone, Copy)]
+struct HullRect {
+    center: Vec2,
+    /// Along the length of the hull
+    dir: Vec2,
+    half_extents: Vec2,
+}
+
+impl HullRect {
+    fn new(hull: Hull, trans: &Transform) -> Self {
+        let (_, max) = hull.to_bounds();
+        Self {
+            center: trans.translation.truncate(),
+            dir: Vec2::from_angle(trans.rotation.to_euler(EulerRot::ZXY).0),
+            half_extents: max.truncate(),
+        }
+    }
+
+    /// How far the rectangle reaches from its center along `axis`
+    fn projected_radius(self, axis: Vec2) -> f32 {
+        self.half_extents.x * self.dir.dot(axis).abs()
+            + self.half_extents.y * self.dir.perp().dot(axis).abs()
+    }
+}
+
+/// How far `b` must move to stop overlapping `a`, if their hulls overlap
+///
+/// Found with the separating axis theorem, so is along whichever
+/// of the rectangles' edges the overlap is smallest across
+fn hull_overlap(a: HullRect, b: HullRect) -> Option<Vec2> {
+    let offset = b.center - a.center;
+    let mut push: Option<Vec2> = None;
+    for axis in [a.dir, a.dir.perp(), b.dir, b.dir.perp()] {
+        let overlap = a.projected_radius(axis) + b.projected_radius(axis) - offset.dot(axis).abs();
+        if overlap <= 0. {
+            return None;
+        }
+        if push.is_none_or(|push| overlap < push.length()) {
+            let away_from_a = if offset.dot(axis) < 0. { -axis } else { axis };
+            push = Some(away_from_a * overlap);
+        }
+    }
+    push
+}
+
+/// Moves `a` and `b` apart by `push`, pointing from `a` to `b`, while keeping both in the map
+///
+/// Each normally moves half of the way, but a ship pinned against the edge of the map
+/// can't move any further out, so the other takes the rest of the push
+fn separate(a: Vec2, b: Vec2, push: Vec2) -> (Vec2, Vec2) {
+    let (lower, upper) = wrts_match_shared::map_bounds();
+    let a_target = a - push / 2.;
+    let a_moved = a_target.clamp(lower, upper);
+    let b_target = b + push / 2. + (a_moved - a_target);
+    let b_moved = b_target.clamp(lower, upper);
+    let a_moved = (a_moved + (b_moved - b_target)).clamp(lower, upper);
+    (a_moved, b_moved)
+}
+
+/// Pushes apart ships on any team whose hulls overlap, and slows them to [GRINDING_SPEED]
+/// while they stay in contact
+///
+/// Ships which have only just collided both take ramming damage,
+/// in proportion to how quickly they were closing on each other
+pub(crate) fn collide_ships(
+    mut commands: Commands,
+    mut ships: Query<(
+        Entity,
+        &mut Ship,
+        &mut Transform,
+        &Velocity,
+        &Team,
+        &mut Health,
+    )>,
+    mut in_contact: Local<HashSet<(Entity, Entity)>>,
+) {
+    let mut still_in_contact = HashSet::new();
+    let mut combinations = ships.iter_combinations_mut();
+    while let Some([a, b]) = combinations.fetch_next() {
+        let (a_entity, mut a_ship, mut a_trans, a_vel, a_team, mut a_health) = a;
+        let (b_entity, mut b_ship, mut b_trans, b_vel, b_team, mut b_health) = b;
+        if a_health.0.is_destroyed() || b_health.0.is_destroyed() {
+            continue;
+        }
+        let Some(push) = hull_overlap(
+            HullRect::new(a_ship.template.hull, &a_trans),
+            HullRect::new(b_ship.template.hull, &b_trans),
+        ) else {
+            continue;
+        };
+
+        let (a_pos, b_pos) = separate(
+            a_trans.translation.truncate(),
+            b_trans.translation.truncate(),
+            push,
+        );
+        a_trans.translation = a_pos.extend(a_trans.translation.z);
+        b_trans.translation = b_pos.extend(b_trans.translation.z);
+        for ship in [&mut a_ship, &mut b_ship] {
+            ship.curr_speed = ship.curr_speed.min(GRINDING_SPEED.mps());
+        }
+
+        let pair = (a_entity.min(b_entity), a_entity.max(b_entity));
+        still_in_contact.insert(pair);
+        if in_contact.contains(&pair) {
+            continue;
+        }
+        let closing_speed = (a_vel.0 - b_vel.0).truncate().dot(push.normalize()).max(0.);
+        let damage = RAMMING_DAMAGE_PER_MPS * closing_speed as f64;
+        if damage <= 0. {
+            continue;
+        }
+        for (
+            target,
+            target_ship,
+            target_trans,
+            target_health,
+            attacker,
+            attacker_trans,
+            attacker_team,
+        ) in [
+            (
+                a_entity,
+                &a_ship,
+                &a_trans,
+                &mut a_health,
+                b_entity,
+                &b_trans,
+                *b_team,
+            ),
+            (
+                b_entity,
+                &b_ship,
+                &b_trans,
+                &mut b_health,
+                a_entity,
+                &a_trans,
+                *a_team,
+            ),
+        ] {
+            // Where the other ship is, relative to this one, clamped onto its hull
+            let (min, max) = target_ship.template.hull.to_bounds();
+            let contact = Vec2::from_angle(-target_trans.rotation.to_euler(EulerRot::ZXY).0)
+                .rotate(attacker_trans.translation.truncate() - target_trans.translation.truncate())
+                .clamp(min.truncate(), max.truncate());
+            let dealt = target_health
+                .0
+                .damage(target_ship.template.hull.section_at(contact), damage);
+            let destroyed = target_health.0.is_destroyed();
+            commands.queue(ReportDamageCommand {
+                target,
+                attacker,
+                attacker_team,
+                source: DamageSource::Ramming,
+                damage: dealt,
+                destroyed_target: destroyed,
+            });
+            if destroyed {
+                commands.queue(DespawnNetworkedEntityCommand { entity: target });
+            }
+        }
+    }
+    *in_contact = still_in_contact;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::ship_collision::{HullRect, hull_overlap, separate};
+
+    fn rect(center: Vec2, angle: f32) -> HullRect {
+        HullRect {
+            center,
+            dir: Vec2::from_angle(angle),
+            half_extents: vec2(100., 10.),
+        }
+    }
+
+    #[test]
+    fn test_overlapping_hulls_are_pushed_apart_along_the_shallowest_axis() {
+        // Side by side, 15m apart, so overlapping by 5m across their width
+        let push = hull_overlap(rect(vec2(0., 0.), 0.), rect(vec2(0., 15.), 0.)).unwrap();
+        assert!(push.abs_diff_eq(vec2(0., 5.), 1e-4), "{push}");
+
+        // Crossing each other, with the second just reaching past the first's side
+        let push = hull_overlap(
+            rect(vec2(0., 0.), 0.),
+            rect(vec2(0., 105.), std::f32::consts::FRAC_PI_2),
+        )
+        .unwrap();
+        assert!(push.abs_diff_eq(vec2(0., 5.), 1e-4), "{push}");
+
+        assert!(hull_overlap(rect(vec2(0., 0.), 0.), rect(vec2(0., 25.), 0.)).is_none());
+        assert!(hull_overlap(rect(vec2(0., 0.), 0.), rect(vec2(150., 150.), 1.)).is_none());
+    }
+
+    #[test]
+    fn test_separation_keeps_ships_in_the_map() {
+        let (lower, upper) = wrts_match_shared::map_bounds();
+        let (a, b) = separate(vec2(0., 0.), vec2(0., 15.), vec2(0., 5.));
+        assert_eq!((a, b), (vec2(0., -2.5), vec2(0., 17.5)));
+
+        // `b` is against the top of the map, so `a` is pushed the whole way
+        let a = vec2(0., upper.y - 15.);
+        let b = vec2(0., upper.y);
+        let (a, b) = separate(a, b, vec2(0., 5.));
+        assert_eq!((a, b), (vec2(0., upper.y - 20.), vec2(0., upper.y)));
+
+        let (a, b) = separate(lower, lower + vec2(15., 0.), vec2(5., 0.));
+        assert_eq!((a, b), (lower, lower + vec2(20., 0.)));
+    }
+}
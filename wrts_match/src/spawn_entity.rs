@@ -5,14 +5,22 @@ use std::time::Duration;
 
 use bevy::prelude::*;
 use itertools::Itertools;
-use wrts_match_shared::ship_template::ShipTemplateId;
+use wrts_match_shared::{
+    formulas::ballistics::ShellArc,
+    ship_template::{HullSection, ShipTemplateId},
+};
 use wrts_messaging::{Match2Client, Message, WrtsMatchMessage};
 
 use crate::{
-    Bullet, Health, Team,
+    Bullet, GameRules, Health, MATCH_EVENTS, Team,
+    aircraft::{Aircraft, AircraftKind},
     detection::{BaseDetection, CanDetect, DetectionStatus},
-    networking::{ClientInfo, MessagesSend, SharedEntityTracking},
-    ship::{Ship, SmokeConsumableState, SmokePuff, TurretAimInfo, TurretState, TurretStates},
+    networking::{ClientInfo, DestroyedEntities, MessagesSend, SharedEntityTracking},
+    ship::{
+        GunsIdle, RepairPartyState, RepairableDamage, SentTurretDirs, Ship, SmokeConsumableState,
+        SmokePuff, SpotterPlaneState, TorpReloadBoosterState, TurretAimInfo, TurretState,
+        TurretStates,
+    },
 };
 
 pub struct DespawnNetworkedEntityCommand {
@@ -29,15 +37,7 @@ impl Command for DespawnNetworkedEntityCommand {
         else {
             return;
         };
-
-        let mut clients = world.query::<&ClientInfo>();
-        let msgs_tx = world.resource::<MessagesSend>();
-        for cl in clients.iter(world) {
-            msgs_tx.send(WrtsMatchMessage {
-                client: cl.info.id,
-                msg: Message::Match2Client(Match2Client::DestroyEntity(shared)),
-            });
-        }
+        world.resource_mut::<DestroyedEntities>().0.push(shared);
     }
 }
 
@@ -52,6 +52,7 @@ pub struct SpawnShipCommand {
 impl Command for SpawnShipCommand {
     fn apply(self, world: &mut World) -> () {
         let template = self.ship_base.to_template();
+        let reload_speed = world.resource::<GameRules>().reload_speed;
         let entity = {
             world
                 .spawn((
@@ -62,29 +63,38 @@ impl Command for SpawnShipCommand {
                             .torpedoes
                             .iter()
                             .flat_map(|torps| {
-                                (0..torps.volleys)
-                                    .map(|_idx| Timer::new(torps.reload, TimerMode::Once))
+                                (0..torps.volleys).map(|_idx| {
+                                    Timer::new(torps.reload.div_f32(reload_speed), TimerMode::Once)
+                                })
                             })
                             .collect(),
+                        shell_arc: ShellArc::Low,
+                        gun_range_modifiers: default(),
                     },
                     TurretStates {
                         states: template
                             .turret_instances
                             .iter()
                             .map(|t| TurretState {
+                                template: t.turret_template(),
                                 dir: t.default_dir,
                                 reload_timer: Timer::from_seconds(
-                                    t.turret_template().reload_secs,
+                                    t.turret_template().reload_secs / reload_speed,
                                     TimerMode::Once,
                                 ),
                                 absolute_pos: Vec2::ZERO,
                                 aim_info: TurretAimInfo::NoValidTarget {},
+                                accuracy: default(),
+                                secondary_target: None,
                             })
                             .collect_vec(),
                     },
+                    GunsIdle::default(),
+                    SentTurretDirs::default(),
                     BaseDetection(template.detection),
                     DetectionStatus {
                         is_detected: false,
+                        detected_by: vec![],
                         detection_increased_by_firing: Timer::new(Duration::ZERO, TimerMode::Once)
                             .tick(Duration::MAX)
                             .clone(),
@@ -109,9 +119,41 @@ impl Command for SpawnShipCommand {
                 charges_unused: (smoke.charges > 0).then_some(smoke.charges),
             });
         }
+        if let Some(booster) = template.consumables.torp_reload_booster() {
+            world.entity_mut(entity).insert(TorpReloadBoosterState {
+                cooldown_timer: Timer::new(booster.cooldown, TimerMode::Once),
+                charges_unused: (booster.charges > 0).then_some(booster.charges),
+            });
+        }
+        if let Some(spotter_plane) = template.consumables.spotter_plane() {
+            world.entity_mut(entity).insert(SpotterPlaneState {
+                cooldown_timer: Timer::new(spotter_plane.cooldown, TimerMode::Once),
+                charges_unused: (spotter_plane.charges > 0).then_some(spotter_plane.charges),
+            });
+        }
+        if let Some(repair_party) = template.consumables.repair_party() {
+            world.entity_mut(entity).insert((
+                RepairPartyState {
+                    cooldown_timer: Timer::new(repair_party.cooldown, TimerMode::Once),
+                    charges_unused: (repair_party.charges > 0).then_some(repair_party.charges),
+                },
+                RepairableDamage {
+                    pool: [0.; HullSection::COUNT],
+                    last_health: self.health.0,
+                },
+            ));
+        }
         // ...
 
         let shared_id = world.resource_mut::<SharedEntityTracking>().insert(entity);
+        debug!(
+            target: MATCH_EVENTS,
+            event = "ship_spawned",
+            time = world.resource::<Time>().elapsed_secs(),
+            client = %self.team.0,
+            ship = ?shared_id,
+            template = self.ship_base.to_name(),
+        );
 
         let mut clients = world.query::<&ClientInfo>();
         let msgs_tx = world.get_resource::<MessagesSend>().unwrap();
@@ -129,7 +171,7 @@ impl Command for SpawnShipCommand {
                     id: shared_id,
                     team: self.team.0,
                     ship_base: self.ship_base,
-                    health: self.health.0,
+                    health: self.health.0.total(),
                     pos: self.pos,
                     rot: self.rot,
                     turret_rots,
@@ -187,6 +229,16 @@ impl Command for SpawnBulletCommand {
             .resource::<SharedEntityTracking>()
             .get_by_local(self.bullet.owning_ship)
             .unwrap();
+        debug!(
+            target: MATCH_EVENTS,
+            event = "shot_fired",
+            time = world.resource::<Time>().elapsed_secs(),
+            client = %self.team.0,
+            ship = ?owning_ship,
+            bullet = ?shared_id,
+            caliber = ?self.bullet.caliber,
+            ty = ?self.bullet.ty,
+        );
         for cl in clients.iter(world) {
             msgs_tx.send(WrtsMatchMessage {
                 client: cl.info.id,
@@ -195,6 +247,8 @@ impl Command for SpawnBulletCommand {
                     team: self.team.0,
                     owning_ship,
                     damage: self.bullet.damage,
+                    caliber: self.bullet.caliber,
+                    ty: self.bullet.ty,
                     pos: self.bullet.inital_pos,
                     rot,
                 }),
@@ -243,3 +297,47 @@ impl Command for SpawnSmokePuffCommand {
         }
     }
 }
+
+pub struct SpawnAircraftCommand {
+    pub team: Team,
+    pub owning_ship: Entity,
+    pub kind: AircraftKind,
+}
+
+impl Command for SpawnAircraftCommand {
+    fn apply(self, world: &mut World) -> () {
+        let ship_trans = *world.get::<Transform>(self.owning_ship).unwrap();
+        let pos = ship_trans.translation.truncate();
+
+        let entity = {
+            world
+                .spawn((
+                    Aircraft::launched_from(self.owning_ship, &ship_trans, self.kind),
+                    self.team,
+                    Transform {
+                        translation: pos.extend(0.),
+                        rotation: ship_trans.rotation,
+                        ..default()
+                    },
+                ))
+                .id()
+        };
+
+        let shared_id = world.resource_mut::<SharedEntityTracking>().insert(entity);
+
+        // Clients are sent the aircraft by `send_aircraft_spawns`, once they can see its ship
+        let owning_ship = world
+            .resource::<SharedEntityTracking>()
+            .get_by_local(self.owning_ship)
+            .unwrap();
+        debug!(
+            target: MATCH_EVENTS,
+            event = "aircraft_launched",
+            time = world.resource::<Time>().elapsed_secs(),
+            client = %self.team.0,
+            ship = ?owning_ship,
+            aircraft = ?shared_id,
+            kind = ?self.kind,
+        );
+    }
+}
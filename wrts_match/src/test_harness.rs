@@ -0,0 +1,1384 @@
+//! A headless match which is advanced one `FixedUpdate` tick at a time,
+//! without any real time passing
+//!
+//! Clients are faked by feeding messages straight into the channel read by `read_messages`:
+//! ```ignore
+//! let mut harness = TestMatch::new();
+//! let ship = harness.ships_of(harness.clients[0])[0];
+//! harness.send(
+//!     harness.clients[0],
+//!     Client2Match::SetMoveOrder { id: ship, waypoints: vec![vec2(0., 0.)] },
+//! );
+//! harness.tick();
+//! ```
+//! Messages are read at the start of the next tick, and everything the match sends back
+//! is collected in `TestMatch::sent`
+
+use std::sync::mpsc::{self, Receiver, SyncSender};
+
+use bevy::{
+    app::{FixedMain, RunFixedMainLoop},
+    prelude::*,
+};
+use wrts_messaging::{
+    Client2Match, ClientId, ClientSharedInfo, Match2Client, Message, SharedEntityId,
+    WrtsMatchMessage,
+};
+
+use crate::{
+    GameRules, MatchRng, add_match_systems,
+    networking::{ClientInfo, MatchSeed, MessagesRecv, MessagesSend, SharedEntityTracking},
+};
+
+/// Large enough that a single tick never fills the channel,
+/// since the harness only drains it between updates
+const CHANNEL_CAPACITY: usize = 8192;
+
+/// The number of `FixedMain` runs requested for the next `App::update`
+#[derive(Resource, Debug, Default)]
+struct PendingTicks(u32);
+
+fn run_pending_ticks(world: &mut World) {
+    for _ in 0..std::mem::take(&mut world.resource_mut::<PendingTicks>().0) {
+        let timestep = world.resource::<Time<Fixed>>().timestep();
+        world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+        let fixed = world.resource::<Time<Fixed>>().as_generic();
+        *world.resource_mut::<Time>() = fixed;
+        world.run_schedule(FixedMain);
+    }
+}
+
+pub struct TestMatch {
+    pub app: App,
+    /// The first two clients, which every match has. See [TestMatch::all_clients] for the rest
+    pub clients: [ClientId; 2],
+    /// Every message sent by the match so far
+    pub sent: Vec<WrtsMatchMessage>,
+    to_match: SyncSender<WrtsMatchMessage>,
+    from_match: Receiver<WrtsMatchMessage>,
+}
+
+impl TestMatch {
+    /// Builds a match between two clients and runs its startup schedules,
+    /// without running any `FixedUpdate` ticks
+    pub fn new() -> Self {
+        Self::with_rules(GameRules::default())
+    }
+
+    /// Like [TestMatch::new], but with `rules` in place before any ships spawn
+    pub fn with_rules(rules: GameRules) -> Self {
+        Self::with_client_count(rules, 2)
+    }
+
+    /// Like [TestMatch::with_rules], but between `client_count` clients, each its own team
+    pub fn with_client_count(rules: GameRules, client_count: u32) -> Self {
+        assert!(client_count >= 2);
+        let (to_match, msgs_rx) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let (msgs_tx, from_match) = mpsc::sync_channel(CHANNEL_CAPACITY);
+
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<Time<Fixed>>()
+            .init_resource::<PendingTicks>()
+            .init_resource::<SharedEntityTracking>()
+            .insert_resource(MessagesSend::new(msgs_tx))
+            .insert_resource(MatchRng::from_seed(0))
+            .insert_resource(MatchSeed(0))
+            .insert_resource(rules)
+            .insert_non_send_resource(MessagesRecv(msgs_rx))
+            .add_systems(RunFixedMainLoop, run_pending_ticks);
+        for id in (0..client_count).map(ClientId) {
+            app.world_mut().spawn(ClientInfo {
+                info: ClientSharedInfo {
+                    id,
+                    user: format!("{id}"),
+                },
+            });
+        }
+        add_match_systems(&mut app);
+
+        let mut harness = Self {
+            app,
+            clients: [ClientId(0), ClientId(1)],
+            sent: vec![],
+            to_match,
+            from_match,
+        };
+        harness.update();
+        harness
+    }
+
+    /// Queues a message as if it was sent by `client`,
+    /// to be read at the start of the next tick
+    pub fn send(&self, client: ClientId, msg: Client2Match) {
+        self.to_match
+            .send(WrtsMatchMessage {
+                client,
+                msg: Message::Client2Match(msg),
+            })
+            .unwrap();
+    }
+
+    /// Advances the simulation by exactly one `FixedUpdate` tick
+    pub fn tick(&mut self) {
+        self.ticks(1);
+    }
+
+    /// Advances the simulation by `n` ticks, collecting what the match sent after each one,
+    /// so that a long run can't fill the channel and leave the match blocked on sending
+    pub fn ticks(&mut self, n: u32) {
+        for _ in 0..n {
+            self.app.world_mut().resource_mut::<PendingTicks>().0 += 1;
+            self.update();
+        }
+    }
+
+    fn update(&mut self) {
+        self.app.update();
+        self.sent.extend(self.from_match.try_iter());
+    }
+
+    /// Every client in the match, in the order they joined
+    pub fn all_clients(&self) -> Vec<ClientId> {
+        let mut clients = self
+            .app
+            .world()
+            .iter_entities()
+            .filter_map(|entity| entity.get::<ClientInfo>().map(|cl| cl.info.id))
+            .collect::<Vec<_>>();
+        clients.sort_by_key(|cl| cl.0);
+        clients
+    }
+
+    /// The ships spawned for `client`, in the order they were spawned
+    pub fn ships_of(&self, client: ClientId) -> Vec<SharedEntityId> {
+        self.sent
+            .iter()
+            .filter_map(|msg| match msg.msg {
+                Message::Match2Client(Match2Client::SpawnShip { id, team, .. })
+                    if msg.client == client && team == client =>
+                {
+                    Some(id)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn local(&self, shared: SharedEntityId) -> Entity {
+        self.app
+            .world()
+            .resource::<SharedEntityTracking>()
+            .get_by_shared(shared)
+            .expect("entity should still exist")
+    }
+
+    pub fn get<C: Component>(&self, shared: SharedEntityId) -> &C {
+        self.app.world().get::<C>(self.local(shared)).unwrap()
+    }
+}
+
+mod tests {
+    use bevy::prelude::*;
+    use itertools::Itertools;
+    use wrts_match_shared::{
+        formulas::ballistics::ShellArc,
+        ship_template::{HullSection, ShipTemplateId},
+    };
+    use wrts_messaging::{Client2Match, ClientId, ClientSharedInfo, Match2Client, Message};
+
+    use crate::{
+        FireTarget, GameRules, HeadingOrder, Health, MoveOrder, Team, Torpedo,
+        WAYPOINT_ARRIVAL_DIST,
+        detection::DetectionStatus,
+        networking::{MOVEMENT_RESEND_TICKS, SharedEntityTracking},
+        ship::{
+            Ship, SmokeConsumableState, SmokeDeploying, SpotterPlaneFlying, SpotterPlaneState,
+            TorpReloadBoosterState, TurretStates,
+        },
+        spawn_entity::DespawnNetworkedEntityCommand,
+        test_harness::TestMatch,
+    };
+
+    #[test]
+    fn test_ticks_advance_fixed_time() {
+        let mut harness = TestMatch::new();
+        let timestep = harness.app.world().resource::<Time<Fixed>>().timestep();
+        harness.tick();
+        harness.ticks(2);
+        assert_eq!(
+            harness.app.world().resource::<Time<Fixed>>().elapsed(),
+            timestep * 3
+        );
+        assert_eq!(harness.app.world().resource::<Time>().delta(), timestep);
+    }
+
+    #[test]
+    fn test_move_order_moves_ship() {
+        let mut harness = TestMatch::new();
+        let client = harness.clients[0];
+        let ship = harness.ships_of(client)[0];
+        let start = harness.get::<Transform>(ship).translation.truncate();
+
+        harness.send(
+            client,
+            Client2Match::SetMoveOrder {
+                id: ship,
+                waypoints: vec![start + vec2(0., 5_000.)],
+            },
+        );
+        harness.ticks(64);
+
+        assert!(harness.get::<Ship>(ship).curr_speed > 0.);
+        assert!(harness.get::<Transform>(ship).translation.truncate() != start);
+    }
+
+    #[test]
+    fn test_move_order_echoed_to_owner() {
+        let mut harness = TestMatch::new();
+        let client = harness.clients[0];
+        let ship = harness.ships_of(client)[0];
+        let waypoints = vec![vec2(1_000., 2_000.), vec2(3_000., 2_000.)];
+
+        harness.send(
+            client,
+            Client2Match::SetMoveOrder {
+                id: ship,
+                waypoints: waypoints.clone(),
+            },
+        );
+        let sent_before = harness.sent.len();
+        harness.tick();
+
+        assert!(harness.sent[sent_before..].iter().any(|msg| {
+            msg.client == client
+                && matches!(
+                    &msg.msg,
+                    Message::Match2Client(Match2Client::SetMoveOrder { id, waypoints: echoed })
+                        if *id == ship && *echoed == waypoints
+                )
+        }));
+    }
+
+    #[test]
+    fn test_long_path_followed_in_order_with_small_updates() {
+        let mut harness = TestMatch::new();
+        let client = harness.clients[0];
+        let ship = harness.ships_of(client)[0];
+        let start = harness.get::<Transform>(ship).translation.truncate();
+        // Straight ahead, since the ship starts facing -x
+        let waypoints = (1..=50)
+            .map(|idx| start - vec2(idx as f32 * 10., 0.))
+            .collect_vec();
+
+        harness.send(
+            client,
+            Client2Match::SetMoveOrder {
+                id: ship,
+                waypoints: waypoints.clone(),
+            },
+        );
+        harness.tick();
+        let sent_before = harness.sent.len();
+        for _ in 0..64 * 180 {
+            harness.tick();
+            if harness.get::<MoveOrder>(ship).next_waypoint().is_none() {
+                break;
+            }
+        }
+
+        assert!(harness.get::<MoveOrder>(ship).next_waypoint().is_none());
+        let mut remaining_counts = vec![];
+        for msg in &harness.sent[sent_before..] {
+            match &msg.msg {
+                Message::Match2Client(Match2Client::WaypointReached { id, remaining })
+                    if *id == ship =>
+                {
+                    remaining_counts.push(*remaining)
+                }
+                // The whole path is only sent when the order is echoed back
+                Message::Match2Client(Match2Client::SetMoveOrder { id, .. }) => {
+                    assert_ne!(*id, ship)
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(remaining_counts, (0..50).rev().collect_vec());
+    }
+
+    #[test]
+    fn test_ships_stop_on_their_last_waypoint_at_any_tick_rate() {
+        for hz in [64., 16., 4.] {
+            let mut harness = TestMatch::new();
+            harness
+                .app
+                .world_mut()
+                .resource_mut::<Time<Fixed>>()
+                .set_timestep_hz(hz);
+            let client = harness.clients[0];
+            // Each of a different class, so they're tested at a range of speeds
+            let ships = harness.ships_of(client);
+            let paths = ships
+                .iter()
+                .map(|&ship| {
+                    let trans = harness.get::<Transform>(ship);
+                    let ahead = (trans.rotation * Vec3::X).truncate();
+                    (trans.translation.truncate(), ahead)
+                })
+                .collect_vec();
+            for (&ship, &(start, ahead)) in ships.iter().zip(&paths) {
+                harness.send(
+                    client,
+                    Client2Match::SetMoveOrder {
+                        id: ship,
+                        waypoints: vec![start + ahead * 600., start + ahead * 1_200.],
+                    },
+                );
+            }
+
+            let mut furthest_along = vec![f32::MIN; ships.len()];
+            let arrived = |harness: &TestMatch| {
+                ships.iter().all(|&ship| {
+                    harness.get::<MoveOrder>(ship).next_waypoint().is_none()
+                        && harness.get::<Ship>(ship).curr_speed == 0.
+                })
+            };
+            for _ in 0..(hz * 120.) as u32 {
+                harness.tick();
+                for (idx, (&ship, &(start, ahead))) in ships.iter().zip(&paths).enumerate() {
+                    let pos = harness.get::<Transform>(ship).translation.truncate();
+                    furthest_along[idx] = furthest_along[idx].max((pos - start).dot(ahead));
+                }
+                if arrived(&harness) {
+                    break;
+                }
+            }
+            assert!(arrived(&harness), "{hz}Hz");
+
+            let positions = ships
+                .iter()
+                .map(|&ship| harness.get::<Transform>(ship).translation)
+                .collect_vec();
+            harness.ticks(hz as u32 * 5);
+            for (idx, (&ship, &(start, ahead))) in ships.iter().zip(&paths).enumerate() {
+                let name = harness.get::<Ship>(ship).template.id;
+                let pos = harness.get::<Transform>(ship).translation;
+                assert!(
+                    pos.truncate().distance(start + ahead * 1_200.) <= WAYPOINT_ARRIVAL_DIST * 2.,
+                    "{name:?} at {hz}Hz"
+                );
+                assert!(
+                    furthest_along[idx] <= 1_200. + WAYPOINT_ARRIVAL_DIST * 2.,
+                    "{name:?} at {hz}Hz overshot to {}",
+                    furthest_along[idx]
+                );
+                // Held there once it's stopped
+                assert_eq!(pos, positions[idx], "{name:?} at {hz}Hz");
+            }
+        }
+    }
+
+    /// Sends a ship around a right-angled corner, and returns how far it had turned
+    /// towards the second leg as it reached the corner, in radians
+    fn heading_change_at_corner(smooth_ship_paths: bool) -> f32 {
+        let mut harness = TestMatch::with_rules(GameRules {
+            smooth_ship_paths,
+            ..default()
+        });
+        let client = harness.clients[0];
+        let ship = harness.ships_of(client)[0];
+        let trans = *harness.get::<Transform>(ship);
+        let ahead = (trans.rotation * Vec3::X).truncate();
+        let corner = trans.translation.truncate() + ahead * 1_500.;
+        harness.send(
+            client,
+            Client2Match::SetMoveOrder {
+                id: ship,
+                waypoints: vec![corner, corner + ahead.perp() * 1_500.],
+            },
+        );
+
+        let ticks_until_waypoint = |harness: &mut TestMatch, waypoint: usize| {
+            for _ in 0..64 * 120 {
+                harness.tick();
+                if harness.get::<MoveOrder>(ship).next > waypoint {
+                    return;
+                }
+            }
+            panic!("waypoint {waypoint} wasn't reached");
+        };
+        ticks_until_waypoint(&mut harness, 0);
+        let heading = harness
+            .get::<Transform>(ship)
+            .rotation
+            .to_euler(EulerRot::ZXY)
+            .0;
+        ticks_until_waypoint(&mut harness, 1);
+        ahead.angle_to(Vec2::from_angle(heading))
+    }
+
+    #[test]
+    fn test_smoothed_paths_turn_before_reaching_corners() {
+        let straight = heading_change_at_corner(false);
+        let smooth = heading_change_at_corner(true);
+        assert!(straight.abs() < 0.01, "{straight}");
+        assert!(smooth > 0.5, "{smooth}");
+    }
+
+    #[test]
+    fn test_fire_target_rejected_on_friendly() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let ships = harness.ships_of(client);
+        let enemy = harness.ships_of(other)[0];
+
+        harness.send(
+            client,
+            Client2Match::SetFireTarg {
+                id: ships[0],
+                targ: Some(ships[1]),
+            },
+        );
+        harness.tick();
+        let local = harness.local(ships[0]);
+        assert!(harness.app.world().get::<FireTarget>(local).is_none());
+
+        harness.send(
+            client,
+            Client2Match::SetFireTarg {
+                id: ships[0],
+                targ: Some(enemy),
+            },
+        );
+        harness.tick();
+        assert_eq!(
+            harness.get::<FireTarget>(ships[0]).ship,
+            harness.local(enemy)
+        );
+    }
+
+    #[test]
+    fn test_despawns_in_one_tick_are_batched() {
+        let mut harness = TestMatch::new();
+        let world = harness.app.world_mut();
+        let entities = (0..50)
+            .map(|_| world.spawn_empty().id())
+            .collect::<Vec<_>>();
+        let ids = entities
+            .iter()
+            .map(|&entity| world.resource_mut::<SharedEntityTracking>().insert(entity))
+            .collect::<Vec<_>>();
+        for entity in entities {
+            DespawnNetworkedEntityCommand { entity }.apply(world);
+        }
+
+        let sent_before = harness.sent.len();
+        harness.tick();
+        let batches = harness.sent[sent_before..]
+            .iter()
+            .filter_map(|msg| match &msg.msg {
+                Message::Match2Client(Match2Client::DestroyEntities(destroyed)) => {
+                    Some((msg.client, destroyed.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            batches,
+            harness.clients.map(|client| (client, ids.clone())).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_guns_idle_only_sent_to_owner_on_change() {
+        let mut harness = TestMatch::new();
+        let [client, _] = harness.clients;
+        let ship = harness.ships_of(client)[0];
+        let guns_idle_msgs = |harness: &TestMatch| {
+            harness
+                .sent
+                .iter()
+                .filter_map(|msg| match msg.msg {
+                    Message::Match2Client(Match2Client::SetGunsIdle { id, idle }) if id == ship => {
+                        Some((msg.client, idle))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // No targets are assigned at the start of a match
+        harness.ticks(10);
+        assert_eq!(guns_idle_msgs(&harness), vec![(client, true)]);
+    }
+
+    #[test]
+    fn test_set_heading_turns_in_place() {
+        let mut harness = TestMatch::new();
+        let client = harness.clients[0];
+        let ship = harness.ships_of(client)[0];
+        let start = *harness.get::<Transform>(ship);
+        let start_heading = start.rotation.to_euler(EulerRot::ZXY).0;
+        let heading = start_heading + 0.3;
+
+        harness.send(
+            client,
+            Client2Match::SetHeading {
+                id: ship,
+                heading: f32::NAN,
+            },
+        );
+        harness.tick();
+        assert!(
+            harness
+                .app
+                .world()
+                .get::<HeadingOrder>(harness.local(ship))
+                .is_none()
+        );
+
+        harness.send(client, Client2Match::SetHeading { id: ship, heading });
+        harness.ticks(64 * 10);
+        let trans = harness.get::<Transform>(ship);
+        let curr_heading = trans.rotation.to_euler(EulerRot::ZXY).0;
+        assert!(
+            Vec2::from_angle(curr_heading)
+                .angle_to(Vec2::from_angle(heading))
+                .abs()
+                < 0.01
+        );
+        assert_eq!(harness.get::<Ship>(ship).curr_speed, 0.);
+        assert!(trans.translation.distance(start.translation) < 1.);
+
+        // A new move order replaces the heading
+        harness.send(
+            client,
+            Client2Match::SetMoveOrder {
+                id: ship,
+                waypoints: vec![],
+            },
+        );
+        harness.tick();
+        assert!(
+            harness
+                .app
+                .world()
+                .get::<HeadingOrder>(harness.local(ship))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_set_shell_arc_only_on_own_ships() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let ship = harness.ships_of(client)[0];
+
+        harness.send(
+            other,
+            Client2Match::SetShellArc {
+                id: ship,
+                arc: ShellArc::High,
+            },
+        );
+        harness.tick();
+        assert_eq!(harness.get::<Ship>(ship).shell_arc, ShellArc::Low);
+
+        harness.send(
+            client,
+            Client2Match::SetShellArc {
+                id: ship,
+                arc: ShellArc::High,
+            },
+        );
+        harness.tick();
+        assert_eq!(harness.get::<Ship>(ship).shell_arc, ShellArc::High);
+    }
+
+    #[test]
+    fn test_cancel_smoke_stops_deploying() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let ship = harness
+            .ships_of(client)
+            .into_iter()
+            .find(|&ship| harness.get::<Ship>(ship).template.id == ShipTemplateId::oland())
+            .unwrap();
+        let local = harness.local(ship);
+        let mut smoke_state = harness
+            .app
+            .world_mut()
+            .get_mut::<SmokeConsumableState>(local)
+            .unwrap();
+        let cooldown = smoke_state.cooldown_timer.duration();
+        smoke_state.cooldown_timer.tick(cooldown);
+        let is_deploying =
+            |harness: &TestMatch| harness.app.world().get::<SmokeDeploying>(local).is_some();
+
+        harness.send(client, Client2Match::UseConsumableSmoke { ship });
+        harness.ticks(2);
+        assert!(is_deploying(&harness));
+
+        // Only the owner can cancel
+        harness.send(other, Client2Match::CancelConsumableSmoke { ship });
+        harness.tick();
+        assert!(is_deploying(&harness));
+
+        harness.send(client, Client2Match::CancelConsumableSmoke { ship });
+        harness.tick();
+        assert!(!is_deploying(&harness));
+        let smoke_state = harness.get::<SmokeConsumableState>(ship);
+        assert!(!smoke_state.cooldown_timer.finished());
+        assert!(smoke_state.cooldown_timer.elapsed() > std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn test_torp_reload_booster_reloads_all_volleys() {
+        let mut harness = TestMatch::new();
+        let client = harness.clients[0];
+        let ship = harness
+            .ships_of(client)
+            .into_iter()
+            .find(|&ship| harness.get::<Ship>(ship).template.id == ShipTemplateId::oland())
+            .unwrap();
+        let local = harness.local(ship);
+        let mut booster_state = harness
+            .app
+            .world_mut()
+            .get_mut::<TorpReloadBoosterState>(local)
+            .unwrap();
+        let cooldown = booster_state.cooldown_timer.duration();
+        booster_state.cooldown_timer.tick(cooldown);
+        assert!(
+            harness
+                .get::<Ship>(ship)
+                .torpedo_reloads
+                .iter()
+                .any(|timer| !timer.finished())
+        );
+
+        harness.send(
+            client,
+            Client2Match::UseConsumableTorpReloadBooster { ship },
+        );
+        harness.tick();
+        assert!(
+            harness
+                .get::<Ship>(ship)
+                .torpedo_reloads
+                .iter()
+                .all(|timer| timer.finished())
+        );
+        let booster_state = harness.get::<TorpReloadBoosterState>(ship);
+        assert_eq!(booster_state.charges_unused, Some(1));
+        assert!(!booster_state.cooldown_timer.finished());
+    }
+
+    #[test]
+    fn test_spotter_plane_extends_range_until_it_lands() {
+        let mut harness = TestMatch::new();
+        let client = harness.clients[0];
+        let ship = harness
+            .ships_of(client)
+            .into_iter()
+            .find(|&ship| harness.get::<Ship>(ship).template.id == ShipTemplateId::bismarck())
+            .unwrap();
+        let local = harness.local(ship);
+        let range_bonus = harness
+            .get::<Ship>(ship)
+            .template
+            .consumables
+            .spotter_plane()
+            .unwrap()
+            .range_bonus;
+        let recharge = |harness: &mut TestMatch| {
+            let mut plane_state = harness
+                .app
+                .world_mut()
+                .get_mut::<SpotterPlaneState>(local)
+                .unwrap();
+            let cooldown = plane_state.cooldown_timer.duration();
+            plane_state.cooldown_timer.tick(cooldown);
+        };
+        recharge(&mut harness);
+        harness
+            .app
+            .world_mut()
+            .get_mut::<SpotterPlaneState>(local)
+            .unwrap()
+            .charges_unused = Some(1);
+
+        harness.send(client, Client2Match::UseConsumableSpotterPlane { ship });
+        harness.tick();
+        assert_eq!(
+            harness
+                .get::<Ship>(ship)
+                .gun_range_modifiers
+                .spotter_plane_bonus,
+            Some(range_bonus)
+        );
+        assert_eq!(
+            harness.get::<SpotterPlaneState>(ship).charges_unused,
+            Some(0)
+        );
+
+        // Skip to just before the plane's action time runs out
+        let mut plane = harness
+            .app
+            .world_mut()
+            .get_mut::<SpotterPlaneFlying>(local)
+            .unwrap();
+        let remaining = plane.action_timer.remaining();
+        plane
+            .action_timer
+            .tick(remaining.saturating_sub(std::time::Duration::from_millis(1)));
+        harness.ticks(2);
+        assert!(
+            harness
+                .app
+                .world()
+                .get::<SpotterPlaneFlying>(local)
+                .is_none()
+        );
+        assert_eq!(
+            harness
+                .get::<Ship>(ship)
+                .gun_range_modifiers
+                .spotter_plane_bonus,
+            None
+        );
+
+        // Out of charges, so it can't be launched again even once recharged
+        recharge(&mut harness);
+        harness.send(client, Client2Match::UseConsumableSpotterPlane { ship });
+        harness.tick();
+        assert!(
+            harness
+                .app
+                .world()
+                .get::<SpotterPlaneFlying>(local)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_newly_detected_ship_sends_transform_same_tick() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let own = harness.ships_of(client)[0];
+        let enemy = harness.ships_of(other)[0];
+        let enemy_local = harness.local(enemy);
+        // Out of sight of every ship on `client`'s team
+        harness
+            .app
+            .world_mut()
+            .get_mut::<Transform>(enemy_local)
+            .unwrap()
+            .translation = vec3(-20_000., -20_000., 0.);
+        harness.tick();
+        assert!(
+            !harness
+                .get::<DetectionStatus>(enemy)
+                .is_visible_to(Team(other), Team(client))
+        );
+
+        let own_pos = harness.get::<Transform>(own).translation;
+        harness
+            .app
+            .world_mut()
+            .get_mut::<Transform>(enemy_local)
+            .unwrap()
+            .translation = own_pos + vec3(1_000., 0., 0.);
+        let sent_before = harness.sent.len();
+        harness.tick();
+
+        let sent = &harness.sent[sent_before..];
+        let sent_to_client = |matches: &dyn Fn(&Match2Client) -> bool| {
+            sent.iter().any(|msg| {
+                msg.client == client
+                    && matches!(&msg.msg, Message::Match2Client(msg) if matches(msg))
+            })
+        };
+        assert!(sent_to_client(&|msg| matches!(
+            msg,
+            Match2Client::SetDetection { id, currently_detected: true } if *id == enemy
+        )));
+        assert!(sent_to_client(&|msg| matches!(
+            msg,
+            Match2Client::WorldSnapshot { entities }
+                if entities.iter().any(|update| update.id == enemy && update.trans.is_some())
+        )));
+        // Its turrets haven't moved since they were sent to `other`, but `client` has never seen them
+        assert!(sent_to_client(&|msg| matches!(
+            msg,
+            Match2Client::WorldSnapshot { entities }
+                if entities.iter().any(|update| update.id == enemy && update.turret_dirs.is_some())
+        )));
+    }
+
+    fn match_ended_messages(harness: &TestMatch) -> Vec<(ClientId, Option<ClientId>)> {
+        let mut ended = harness
+            .sent
+            .iter()
+            .filter_map(|msg| match msg.msg {
+                Message::Match2Client(Match2Client::MatchEnded { winner }) => {
+                    Some((msg.client, winner))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        ended.sort();
+        ended
+    }
+
+    #[test]
+    fn test_surrender_awards_win_to_opponent() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        harness.send(client, Client2Match::Surrender);
+        harness.tick();
+
+        assert_eq!(
+            match_ended_messages(&harness),
+            vec![(client, Some(other)), (other, Some(other))]
+        );
+        assert!(
+            harness
+                .app
+                .world()
+                .resource::<Events<AppExit>>()
+                .iter_current_update_events()
+                .any(|exit| exit.is_success())
+        );
+    }
+
+    #[test]
+    fn test_both_surrendering_is_a_draw() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        harness.send(client, Client2Match::Surrender);
+        harness.send(other, Client2Match::Surrender);
+        harness.tick();
+
+        assert_eq!(
+            match_ended_messages(&harness),
+            vec![(client, None), (other, None)]
+        );
+    }
+
+    #[test]
+    fn test_scoreboard_credits_damage_without_overkill() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let attacker = harness.local(harness.ships_of(client)[0]);
+        let target = harness.local(harness.ships_of(other)[0]);
+
+        // A torpedo far stronger than the 1 health left in the citadel it hits
+        let world = harness.app.world_mut();
+        let target_pos = world.get::<Transform>(target).unwrap().translation;
+        let mut health = world.get_mut::<Health>(target).unwrap();
+        let citadel = health.0.get(HullSection::Citadel);
+        health.0.damage(HullSection::Citadel, citadel - 1.);
+        world.spawn((
+            Torpedo {
+                owning_ship: attacker,
+                damage: 100_000.,
+                inital_pos: target_pos.truncate(),
+                max_range: 1_000.,
+            },
+            Team(client),
+            Transform::from_translation(target_pos),
+        ));
+        harness.tick();
+        harness.send(other, Client2Match::Surrender);
+        harness.tick();
+
+        let scoreboards = harness
+            .sent
+            .iter()
+            .filter_map(|msg| match &msg.msg {
+                Message::Match2Client(Match2Client::Scoreboard { entries }) => {
+                    Some((msg.client, entries.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(scoreboards.len(), 2);
+        for (_, entries) in scoreboards {
+            assert_eq!(entries.len(), 2);
+            let (cl, damage, kills) = entries[0];
+            assert_eq!(cl, client);
+            assert!((damage - 1.).abs() < 1e-6);
+            assert_eq!(kills, 1);
+            assert_eq!(entries[1], (other, 0., 0));
+        }
+    }
+
+    #[test]
+    fn test_last_client_with_ships_afloat_wins() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        for ship in harness.ships_of(other) {
+            let entity = harness.local(ship);
+            DespawnNetworkedEntityCommand { entity }.apply(harness.app.world_mut());
+        }
+        harness.tick();
+
+        assert_eq!(
+            match_ended_messages(&harness),
+            vec![(client, Some(client)), (other, Some(client))]
+        );
+    }
+
+    #[test]
+    fn test_match_clock_counts_down_then_ends_on_time() {
+        let mut harness = TestMatch::with_rules(GameRules {
+            match_duration: std::time::Duration::from_secs(3),
+            ..default()
+        });
+        let [client, other] = harness.clients;
+        harness.ticks(64 * 2);
+        let times_sent = harness
+            .sent
+            .iter()
+            .filter_map(|msg| match msg.msg {
+                Message::Match2Client(Match2Client::MatchTime { remaining })
+                    if msg.client == client =>
+                {
+                    Some(remaining.as_secs())
+                }
+                _ => None,
+            })
+            .collect_vec();
+        assert_eq!(times_sent, vec![2, 1]);
+        assert!(match_ended_messages(&harness).is_empty());
+
+        // One ship down leaves `client` ahead when time runs out
+        let entity = harness.local(harness.ships_of(other)[0]);
+        DespawnNetworkedEntityCommand { entity }.apply(harness.app.world_mut());
+        harness.ticks(64);
+        assert_eq!(
+            match_ended_messages(&harness),
+            vec![(client, Some(client)), (other, Some(client))]
+        );
+    }
+
+    #[test]
+    fn test_idle_turret_dirs_arent_resent_every_tick() {
+        let mut harness = TestMatch::new();
+        let ship = harness.ships_of(harness.clients[0])[0];
+        let turret_dirs_sent = |harness: &TestMatch| {
+            harness
+                .sent
+                .iter()
+                .filter_map(|msg| match &msg.msg {
+                    Message::Match2Client(Match2Client::WorldSnapshot { entities }) => {
+                        Some(entities)
+                    }
+                    _ => None,
+                })
+                .flatten()
+                .filter(|update| update.id == ship && update.turret_dirs.is_some())
+                .count()
+        };
+        harness.tick();
+        let sent_on_first_tick = turret_dirs_sent(&harness);
+        assert!(sent_on_first_tick > 0);
+
+        harness.ticks(10);
+        assert_eq!(turret_dirs_sent(&harness), sent_on_first_tick);
+    }
+
+    /// With 20 ships sailing, one tick sends each client a single snapshot,
+    /// in place of at least a transform and a velocity message for every ship
+    #[test]
+    fn test_world_snapshot_batches_updates_of_twenty_ships() {
+        const SHIP_COUNT: usize = 20;
+        let ships_per_team = ShipTemplateId::all_ships().len();
+        let client_count = SHIP_COUNT.div_ceil(ships_per_team).max(2);
+        let mut harness = TestMatch::with_client_count(GameRules::default(), client_count as u32);
+
+        let clients = harness.all_clients();
+        let ships = clients
+            .iter()
+            .flat_map(|&client| harness.ships_of(client))
+            .collect_vec();
+        let world = harness.app.world_mut();
+        for &extra in &ships[SHIP_COUNT..] {
+            let entity = world
+                .resource::<SharedEntityTracking>()
+                .get_by_shared(extra)
+                .unwrap();
+            DespawnNetworkedEntityCommand { entity }.apply(world);
+        }
+        for &client in &clients {
+            for ship in harness.ships_of(client) {
+                if !ships[..SHIP_COUNT].contains(&ship) {
+                    continue;
+                }
+                let start = harness.get::<Transform>(ship).translation.truncate();
+                harness.send(
+                    client,
+                    Client2Match::SetMoveOrder {
+                        id: ship,
+                        waypoints: vec![start + vec2(0., 5_000.)],
+                    },
+                );
+            }
+        }
+        harness.ticks(8);
+
+        let sent_before = harness.sent.len();
+        harness.tick();
+        let snapshots = harness.sent[sent_before..]
+            .iter()
+            .filter_map(|msg| match &msg.msg {
+                Message::Match2Client(Match2Client::WorldSnapshot { entities }) => {
+                    Some((msg.client, entities))
+                }
+                _ => None,
+            })
+            .collect_vec();
+        // Before snapshots, each transform, velocity and turret update was its own message
+        let granular_count = snapshots
+            .iter()
+            .flat_map(|(_, entities)| entities.iter())
+            .map(|update| {
+                [
+                    update.trans.is_some(),
+                    update.vel.is_some(),
+                    update.turret_dirs.is_some(),
+                ]
+                .into_iter()
+                .filter(|&sent| sent)
+                .count()
+            })
+            .sum::<usize>();
+        assert_eq!(
+            snapshots
+                .iter()
+                .map(|(client, _)| *client)
+                .sorted()
+                .collect_vec(),
+            clients
+        );
+        for (client, entities) in &snapshots {
+            let own_ships = entities
+                .iter()
+                .filter(|update| harness.ships_of(*client).contains(&update.id))
+                .filter(|update| update.trans.is_some() && update.vel.is_some())
+                .count();
+            assert_eq!(
+                own_ships,
+                harness
+                    .ships_of(*client)
+                    .iter()
+                    .filter(|ship| ships[..SHIP_COUNT].contains(ship))
+                    .count()
+            );
+        }
+        assert!(granular_count >= SHIP_COUNT * 2, "{granular_count}");
+    }
+
+    #[test]
+    fn test_rejoined_client_is_resent_the_match() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        harness.ticks(4);
+        let own_ships = harness.ships_of(client);
+        let all_ships = [own_ships.clone(), harness.ships_of(other)]
+            .concat()
+            .into_iter()
+            .sorted()
+            .collect_vec();
+
+        let sent_before = harness.sent.len();
+        harness.send(client, Client2Match::Rejoined);
+        harness.tick();
+        let resent = harness.sent[sent_before..]
+            .iter()
+            .filter(|msg| msg.client == client)
+            .filter_map(|msg| match &msg.msg {
+                Message::Match2Client(msg) => Some(msg),
+                _ => None,
+            })
+            .collect_vec();
+
+        assert!(matches!(
+            resent[..2],
+            [
+                Match2Client::InitA { your_client },
+                Match2Client::InitC { all_clients, seed: 0 },
+            ] if *your_client == client && all_clients.len() == 2
+        ));
+        let spawned = resent
+            .iter()
+            .filter_map(|msg| match msg {
+                Match2Client::SpawnShip { id, .. } => Some(*id),
+                _ => None,
+            })
+            .sorted()
+            .collect_vec();
+        assert_eq!(spawned, all_ships);
+        let snapshot = resent
+            .iter()
+            .find_map(|msg| match msg {
+                Match2Client::WorldSnapshot { entities } if entities.len() > 1 => Some(entities),
+                _ => None,
+            })
+            .unwrap();
+        for ship in own_ships {
+            assert!(
+                snapshot
+                    .iter()
+                    .any(|update| update.id == ship && update.trans.is_some())
+            );
+        }
+        assert!(
+            harness.sent[sent_before..]
+                .iter()
+                .all(|msg| msg.client == client
+                    || !matches!(msg.msg, Message::Match2Client(Match2Client::InitA { .. })))
+        );
+    }
+
+    #[test]
+    fn test_rejoined_client_isnt_told_where_hidden_enemies_are() {
+        let mut harness = TestMatch::new();
+        let [client, other] = harness.clients;
+        let enemy = harness.ships_of(other)[0];
+        let enemy_local = harness.local(enemy);
+        // Out of sight of every ship on `client`'s team
+        let hidden_pos = vec2(-20_000., -20_000.);
+        harness
+            .app
+            .world_mut()
+            .get_mut::<Transform>(enemy_local)
+            .unwrap()
+            .translation = hidden_pos.extend(0.);
+        harness.tick();
+
+        let sent_before = harness.sent.len();
+        harness.send(client, Client2Match::Rejoined);
+        harness.tick();
+        let resent = harness.sent[sent_before..]
+            .iter()
+            .filter(|msg| msg.client == client)
+            .filter_map(|msg| match &msg.msg {
+                Message::Match2Client(msg) => Some(msg),
+                _ => None,
+            })
+            .collect_vec();
+
+        let spawned_at = resent
+            .iter()
+            .find_map(|msg| match msg {
+                Match2Client::SpawnShip { id, pos, .. } if *id == enemy => Some(*pos),
+                _ => None,
+            })
+            .unwrap();
+        assert_ne!(spawned_at, hidden_pos);
+        assert!(!resent.iter().any(|msg| matches!(
+            msg,
+            Match2Client::WorldSnapshot { entities }
+                if entities.iter().any(|update| update.id == enemy && update.trans.is_some())
+        )));
+    }
+
+    #[test]
+    fn test_spectator_sees_every_ship_but_controls_nothing() {
+        let mut harness = TestMatch::with_rules(GameRules {
+            allow_pause: true,
+            ..default()
+        });
+        let [client, other] = harness.clients;
+        let spectator = ClientId(2);
+        let ship = harness.ships_of(client)[0];
+        // Out of sight of every ship on `other`'s team
+        let ship_local = harness.local(ship);
+        harness
+            .app
+            .world_mut()
+            .get_mut::<Transform>(ship_local)
+            .unwrap()
+            .translation = vec3(-20_000., -20_000., 0.);
+        harness.ticks(4);
+        let all_ships = [harness.ships_of(client), harness.ships_of(other)]
+            .concat()
+            .into_iter()
+            .sorted()
+            .collect_vec();
+
+        let sent_before = harness.sent.len();
+        harness.send(
+            spectator,
+            Client2Match::SpectatorJoined {
+                info: ClientSharedInfo {
+                    id: spectator,
+                    user: "spectator".to_string(),
+                },
+            },
+        );
+        harness.tick();
+        fn sent_to(harness: &TestMatch, client: ClientId, since: usize) -> Vec<&Match2Client> {
+            harness.sent[since..]
+                .iter()
+                .filter(|msg| msg.client == client)
+                .filter_map(|msg| match &msg.msg {
+                    Message::Match2Client(msg) => Some(msg),
+                    _ => None,
+                })
+                .collect_vec()
+        }
+        let resent = sent_to(&harness, spectator, sent_before);
+        assert!(matches!(
+            resent[..2],
+            [
+                Match2Client::InitA { your_client },
+                Match2Client::InitC { all_clients, .. },
+            ] if *your_client == spectator && all_clients.len() == 2
+        ));
+        let detected = resent
+            .iter()
+            .filter_map(|msg| match msg {
+                Match2Client::SetDetection {
+                    id,
+                    currently_detected: true,
+                } => Some(*id),
+                _ => None,
+            })
+            .filter(|id| all_ships.contains(id))
+            .sorted()
+            .collect_vec();
+        assert_eq!(detected, all_ships);
+
+        // Only the spectator should be sent where the hidden ship went
+        let start = harness.get::<Transform>(ship).translation.truncate();
+        harness.send(
+            client,
+            Client2Match::SetMoveOrder {
+                id: ship,
+                waypoints: vec![start + vec2(0., 1_000.)],
+            },
+        );
+        harness.send(
+            spectator,
+            Client2Match::SetMoveOrder {
+                id: ship,
+                waypoints: vec![start - vec2(0., 1_000.)],
+            },
+        );
+        harness.send(spectator, Client2Match::SetPaused { paused: true });
+        let sent_before = harness.sent.len();
+        harness.ticks(8);
+        assert_eq!(
+            harness.get::<MoveOrder>(ship).next_waypoint(),
+            Some(start + vec2(0., 1_000.))
+        );
+        let moved_in_snapshot = |msgs: Vec<&Match2Client>| {
+            msgs.iter().any(|msg| {
+                matches!(msg, Match2Client::WorldSnapshot { entities }
+                    if entities.iter().any(|update| update.id == ship && update.trans.is_some()))
+            })
+        };
+        assert!(moved_in_snapshot(sent_to(&harness, spectator, sent_before)));
+        assert!(!moved_in_snapshot(sent_to(&harness, other, sent_before)));
+        assert!(!harness.sent[sent_before..].iter().any(|msg| matches!(
+            msg.msg,
+            Message::Match2Client(Match2Client::MatchPaused { .. })
+        )));
+    }
+
+    #[test]
+    fn test_nothing_moves_while_paused() {
+        let mut harness = TestMatch::with_rules(GameRules {
+            allow_pause: true,
+            ..default()
+        });
+        let [client, other] = harness.clients;
+        let ship = harness.ships_of(client)[0];
+        let start = harness.get::<Transform>(ship).translation.truncate();
+        harness.send(
+            client,
+            Client2Match::SetMoveOrder {
+                id: ship,
+                waypoints: vec![start - vec2(5_000., 0.)],
+            },
+        );
+        let enemy = harness.local(harness.ships_of(other)[0]);
+        let ship_local = harness.local(ship);
+        harness
+            .app
+            .world_mut()
+            .entity_mut(ship_local)
+            .insert(FireTarget { ship: enemy });
+        harness.ticks(64 * 5);
+
+        let snapshot = |harness: &mut TestMatch| {
+            let world = harness.app.world_mut();
+            let transforms = world
+                .query::<(Entity, &Transform)>()
+                .iter(world)
+                .map(|(entity, trans)| (entity, *trans))
+                .sorted_by_key(|(entity, _)| *entity)
+                .collect_vec();
+            let reloads = world
+                .get::<TurretStates>(ship_local)
+                .unwrap()
+                .states
+                .iter()
+                .map(|state| state.reload_timer.elapsed())
+                .collect_vec();
+            (transforms, reloads)
+        };
+        let before = snapshot(&mut harness);
+        harness.send(client, Client2Match::SetPaused { paused: true });
+        let sent_before = harness.sent.len();
+        harness.ticks(64 * 5);
+        assert_eq!(snapshot(&mut harness), before);
+        let paused_msgs = harness.sent[sent_before..]
+            .iter()
+            .filter(|msg| {
+                matches!(
+                    msg.msg,
+                    Message::Match2Client(Match2Client::MatchPaused { paused: true })
+                )
+            })
+            .map(|msg| msg.client)
+            .collect_vec();
+        assert_eq!(paused_msgs.len(), 2);
+        assert!(paused_msgs.contains(&client) && paused_msgs.contains(&other));
+
+        harness.send(client, Client2Match::SetPaused { paused: false });
+        harness.ticks(64);
+        assert_ne!(snapshot(&mut harness).0, before.0);
+    }
+
+    /// A ship which has stopped still has its position resent now and then,
+    /// in case the snapshot from when it stopped was dropped
+    #[test]
+    fn test_still_ships_have_their_movement_resent() {
+        let mut harness = TestMatch::with_rules(GameRules {
+            allow_pause: true,
+            ..default()
+        });
+        let client = harness.clients[0];
+        let ship = harness.ships_of(client)[0];
+        harness.send(client, Client2Match::SetPaused { paused: true });
+        harness.tick();
+
+        let sent_before = harness.sent.len();
+        harness.ticks(MOVEMENT_RESEND_TICKS);
+        let resent = harness.sent[sent_before..]
+            .iter()
+            .filter(|msg| msg.client == client)
+            .filter_map(|msg| match &msg.msg {
+                Message::Match2Client(Match2Client::WorldSnapshot { entities }) => Some(entities),
+                _ => None,
+            })
+            .flatten()
+            .filter(|update| update.id == ship && update.trans.is_some() && update.vel.is_some())
+            .count();
+        assert_eq!(resent, 1);
+    }
+}
@@ -0,0 +1,263 @@
+//! Shell trajectories, shared so that the client can predict
+//! where the match will aim and where shells will land
+mod generated_bullet_problem_solution;
+
+use glam::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ship_template::Dispersion;
+
+/// The gravity shells are affected by, in m/s^2
+pub const GRAVITY: f32 = 10.;
+
+/// Which of the two trajectories reaching a target a shell is fired along
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShellArc {
+    /// Direct fire, below 45 degrees of elevation
+    #[default]
+    Low,
+    /// Plunging fire, above 45 degrees of elevation.
+    /// Takes longer to land, so the target has more time to move
+    High,
+}
+
+impl ShellArc {
+    pub fn toggled(self) -> Self {
+        match self {
+            ShellArc::Low => ShellArc::High,
+            ShellArc::High => ShellArc::Low,
+        }
+    }
+
+    /// The arcs to try firing along, in order, when `self` is chosen.
+    /// Turrets which can't elevate high enough for the high arc fall back to the low arc,
+    /// rather than holding their fire
+    pub fn with_fallbacks(self) -> &'static [ShellArc] {
+        match self {
+            ShellArc::Low => &[ShellArc::Low],
+            ShellArc::High => &[ShellArc::High, ShellArc::Low],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BulletProblemRes {
+    pub intersection_point: Vec2,
+    pub intersection_time: f32,
+    pub intersection_dist: f32,
+    pub projectile_dir: Vec3,
+    /// Rotation around the z axis
+    /// (starting at x=1,y=0 moving counter-clockwise)
+    pub projectile_azimuth: f32,
+    /// Rotation towards the z axis
+    /// (starting from the xy plane and roating towards the z axis)
+    pub projectile_elevation: f32,
+}
+
+/// Calculates the direction of firing and intersection point of a projectile launched at a ship moving at a constant velocity
+/// The projectile has constant lateral speed and is affected by gravity, so it's assumed to follow a parabola
+pub fn bullet_problem(
+    projectile_start: Vec2,
+    ship_start: Vec2,
+    ship_vel: Vec2,
+    muzzle_vel: f64,
+    gravity: f64,
+    arc: ShellArc,
+) -> Option<BulletProblemRes> {
+    let p = (ship_start - projectile_start).as_dvec2();
+    let v = ship_vel.as_dvec2();
+
+    let t = match arc {
+        ShellArc::Low => {
+            let t = generated_bullet_problem_solution::GENERATED_CODE(
+                gravity, p.x, p.y, muzzle_vel, v.x, v.y,
+            );
+            (t.is_finite() && t.im.abs() <= 0.0000001).then_some(t.re)?
+        }
+        ShellArc::High => high_arc_time(p, v, muzzle_vel, gravity)?,
+    };
+
+    let intersection = p + v * t;
+    let azimuth = f64::atan2(intersection.y, intersection.x);
+    let elevation = f64::asin(gravity * t / (2. * muzzle_vel)); // Checked
+    let proj_dir = dvec3(
+        elevation.cos() * intersection.normalize().x,
+        elevation.cos() * intersection.normalize().y,
+        elevation.sin(),
+    );
+    let dist = intersection.length();
+
+    assert!(
+        elevation.is_finite(),
+        "If a real t was found, elevation must be a real number"
+    );
+
+    if cfg!(debug_assertions) {
+        let proj_intersection = proj_dir.truncate() * muzzle_vel * t;
+        let error = intersection.distance(proj_intersection);
+        assert!(error <= 0.001);
+        if error > 0.001 {
+            eprintln!(
+                "WARN: Large bullet problem error; error={error:.2} {{Iship={:.2},Iproj={:.2},p={:.6},v={:.2}\n    elev={:.8},azi={:.8}}}\n",
+                intersection, proj_intersection, p, v, elevation, azimuth,
+            );
+        }
+    }
+
+    Some(BulletProblemRes {
+        intersection_point: intersection.as_vec2() + projectile_start,
+        intersection_time: t as f32,
+        intersection_dist: dist as f32,
+        projectile_dir: proj_dir.as_vec3(),
+        projectile_azimuth: azimuth as f32,
+        projectile_elevation: elevation as f32,
+    })
+}
+
+/// Finds the flight time of a high arc shell by bisection,
+/// since the generated solution only gives the low arc.
+///
+/// A shell in the air for `t` seconds covers `t * sqrt(s^2 - (g*t/2)^2)` horizontally,
+/// which peaks at a 45 degree elevation (`t = sqrt(2)*s/g`) and falls to zero
+/// when fired straight up (`t = 2*s/g`), so the high arc lands somewhere in between
+fn high_arc_time(p: DVec2, v: DVec2, muzzle_vel: f64, gravity: f64) -> Option<f64> {
+    let overshoot = |t: f64| {
+        let vertical_vel = gravity * t / 2.;
+        let horizontal_vel = (muzzle_vel * muzzle_vel - vertical_vel * vertical_vel)
+            .max(0.)
+            .sqrt();
+        t * horizontal_vel - (p + v * t).length()
+    };
+
+    let mut lo = std::f64::consts::SQRT_2 * muzzle_vel / gravity;
+    let mut hi = 2. * muzzle_vel / gravity;
+    if overshoot(lo) < 0. {
+        // Out of range
+        return None;
+    }
+    for _ in 0..64 {
+        let mid = 0.5 * (lo + hi);
+        if overshoot(mid) >= 0. {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Some(lo)
+}
+
+/// The half extents of the area shells land in around their aimpoint,
+/// as (across the line of fire, along the line of fire).
+/// This is the edge of the dispersion ellipse, most shells land well within it
+pub fn dispersion_footprint(
+    dispersion: &Dispersion,
+    dist: f32,
+    elevation: f32,
+    muzzle_vel: f32,
+    gravity: f32,
+) -> Vec2 {
+    let across = dist * f32::tan(f32::atan2(dispersion.horizontal, 1000.));
+
+    // Shells fired higher or lower than nominal land further or shorter
+    let elevation_spread = f32::atan2(dispersion.vertical, 1000.);
+    let range_at = |elevation: f32| muzzle_vel * muzzle_vel * f32::sin(2. * elevation) / gravity;
+    let along = 0.5
+        * (range_at(elevation + elevation_spread) - range_at(elevation - elevation_spread)).abs();
+
+    vec2(across, along)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::*;
+
+    use crate::{
+        formulas::ballistics::{GRAVITY, ShellArc, bullet_problem, dispersion_footprint},
+        ship_template::Dispersion,
+    };
+
+    #[test]
+    fn test_dispersion_footprint() {
+        let dispersion = Dispersion {
+            vertical: 5.,
+            horizontal: 10.,
+            sigma: 2.,
+        };
+        let muzzle_vel = 800.;
+        let footprint_at = |dist: f32| {
+            let bp = bullet_problem(
+                Vec2::ZERO,
+                vec2(dist, 0.),
+                Vec2::ZERO,
+                muzzle_vel as f64,
+                GRAVITY as f64,
+                ShellArc::Low,
+            )
+            .unwrap();
+            dispersion_footprint(
+                &dispersion,
+                bp.intersection_dist,
+                bp.projectile_elevation,
+                muzzle_vel,
+                GRAVITY,
+            )
+        };
+
+        let near = footprint_at(5_000.);
+        let far = footprint_at(10_000.);
+        assert!((near.x - 50.).abs() < 0.1, "{near}");
+        assert!((far.x - 2. * near.x).abs() < 0.1, "{near} {far}");
+        // A shell's range changes less with elevation the closer it's fired to 45 degrees,
+        // so the same vertical dispersion spreads shells slightly less along the line of fire further out
+        assert!(near.y > 0. && far.y > 0. && far.y < near.y, "{near} {far}");
+    }
+
+    #[test]
+    fn test_high_and_low_arcs_reach_same_target() {
+        let muzzle_vel = 500.;
+        let target = vec2(6_000., 8_000.);
+        let target_vel = vec2(-8., 3.);
+        let [low, high] = [ShellArc::Low, ShellArc::High].map(|arc| {
+            bullet_problem(
+                Vec2::ZERO,
+                target,
+                target_vel,
+                muzzle_vel,
+                GRAVITY as f64,
+                arc,
+            )
+            .unwrap()
+        });
+
+        for bp in [&low, &high] {
+            let target_at_impact = target + target_vel * bp.intersection_time;
+            assert!(
+                bp.intersection_point.distance(target_at_impact) < 0.5,
+                "{bp:?}"
+            );
+        }
+        assert!(low.projectile_elevation < std::f32::consts::FRAC_PI_4);
+        assert!(high.projectile_elevation > std::f32::consts::FRAC_PI_4);
+        assert!(
+            high.intersection_time > 2. * low.intersection_time,
+            "{low:?} {high:?}"
+        );
+    }
+
+    #[test]
+    fn test_high_arc_out_of_range() {
+        let muzzle_vel = 500.;
+        let max_range = muzzle_vel * muzzle_vel / GRAVITY;
+        assert!(
+            bullet_problem(
+                Vec2::ZERO,
+                vec2(max_range * 1.1, 0.),
+                Vec2::ZERO,
+                muzzle_vel as f64,
+                GRAVITY as f64,
+                ShellArc::High,
+            )
+            .is_none()
+        );
+    }
+}
@@ -1,6 +1,19 @@
+pub mod ballistics;
+
 use glam::*;
 
-use crate::ship_template::{Caliber, ShipTemplateId};
+use crate::ship_template::{AngleRange, BulletType, Caliber, HullSection, ShipTemplateId};
+
+/// HE shells of this caliber set fires at exactly their turret's `fire_chance`
+const FIRE_CHANCE_REFERENCE_CALIBER_MM: f32 = 150.;
+/// AP shells glance off armor struck at more than this angle from its normal
+const RICOCHET_ANGLE: f32 = std::f32::consts::FRAC_PI_3;
+/// The thickness of armor an AP shell can penetrate, per mm of its caliber
+const AP_PENETRATION_PER_CALIBER: f32 = 2.;
+/// AP shells pass straight through armor this many times thinner than they can penetrate
+const OVERPENETRATION_ARMOR_RATIO: f32 = 6.;
+/// The fraction of its damage an overpenetrating shell deals
+const OVERPENETRATION_DAMAGE_FRACTION: f64 = 0.1;
 
 /// Returns whether or not `v` is within the sweep from `from` to `to`,
 /// rotating clockwise
@@ -14,19 +27,114 @@ pub fn vector_is_within_swept_angle(v: Vec2, from: Vec2, to: Vec2) -> bool {
     }
 }
 
+/// Returns whether or not `pos` is inside `polygon`, which may be concave
+///
+/// https://wrfranklin.org/Research/Short_Notes/pnpoly.html
+pub fn polygon_contains(polygon: &[Vec2], pos: Vec2) -> bool {
+    let mut inside = false;
+    let mut prev = match polygon.last() {
+        Some(&last) => last,
+        None => return false,
+    };
+    for &curr in polygon {
+        if (curr.y > pos.y) != (prev.y > pos.y)
+            && pos.x < (prev.x - curr.x) * (pos.y - curr.y) / (prev.y - curr.y) + curr.x
+        {
+            inside = !inside;
+        }
+        prev = curr;
+    }
+    inside
+}
+
+/// Turns a turret by up to `max_turn` radians towards `targ_dir`,
+/// without sweeping through the directions outside of `movement_angle`.
+/// Directions are all relative to ship-space
+pub fn turn_turret_towards(
+    curr_dir: Vec2,
+    targ_dir: Vec2,
+    movement_angle: Option<AngleRange>,
+    max_turn: f32,
+) -> Vec2 {
+    let rotate_dir = match movement_angle {
+        Some(movement_angle) => {
+            // Nudge the curr_dir so the turret doesn't get stuck at the edges of the movement angle
+            let curr_dir_nudged_ccw = Vec2::from_angle(0.001).rotate(curr_dir);
+            let curr_dir_nudged_cw = Vec2::from_angle(-0.001).rotate(curr_dir);
+            if !AngleRange::from_vectors(curr_dir_nudged_ccw, targ_dir)
+                .overlaps(movement_angle.inverse())
+            {
+                // If I can sweep from curr_dir to targ_dir without overlapping
+                // the place I'm not allowed to move, sweep counter clockwise
+                1.
+            } else if !AngleRange::from_vectors(targ_dir, curr_dir_nudged_cw)
+                .overlaps(movement_angle.inverse())
+            {
+                // If I can sweep from curr_dir to targ_dir *clockwise*
+                // without overlapping the place I'm not allowed to move,
+                // turn clockwise
+                -1.
+            } else {
+                // The only way that this statement can be reached is
+                // if the target is outside our movement angle
+                let targ_dir_clamped = movement_angle.clamp_angle(targ_dir);
+                if targ_dir_clamped.distance_squared(movement_angle.end_dir()) <= 0.001 {
+                    // Snapped to the end angle of the `movement_angle`
+                    1.
+                } else {
+                    // Snapped to the start angle of the `movement_angle`
+                    -1.
+                }
+            }
+        }
+        None => curr_dir.angle_to(targ_dir).signum(),
+    };
+
+    let mut dir = curr_dir.rotate(Vec2::from_angle(rotate_dir * max_turn));
+    if let Some(movement_angle) = movement_angle {
+        dir = movement_angle.clamp_angle(dir);
+    }
+    dir
+}
+
+/// Whatever is currently extending or shortening a ship's gun range
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GunRangeModifiers {
+    /// The `SpotterPlane::range_bonus` of the ship's spotter plane, while it's up
+    pub spotter_plane_bonus: Option<f32>,
+}
+
+/// The authoritative range of a turret, used both by the match to gate firing
+/// and by the client to draw range rings
 #[derive(Debug, Clone, Copy)]
 pub struct GunRangeCalc {
+    /// The turret template's `max_range`
     pub base_range: f32,
 }
 
 impl GunRangeCalc {
-    pub fn run(self) -> f32 {
-        self.base_range
+    pub fn run(self, modifiers: GunRangeModifiers) -> f32 {
+        let spotter_plane_mult = 1. + modifiers.spotter_plane_bonus.unwrap_or(0.);
+        self.base_range * spotter_plane_mult
     }
 }
 
 pub enum ProjectileHitRes {
-    Hit { damage_dealt: f64 },
+    Hit {
+        damage_dealt: f64,
+        section: HullSection,
+        /// Whether the shell set the ship on fire
+        ignites: bool,
+        /// Whether an AP shell penetrated into the citadel, dealing extra damage
+        was_citadel: bool,
+    },
+    /// An AP shell passed straight through the ship, only dealing a fraction of its damage
+    Overpenetration {
+        damage_dealt: f64,
+        section: HullSection,
+    },
+    /// An AP shell glanced off the armor without doing any damage
+    Ricochet,
     Missed,
 }
 
@@ -37,28 +145,278 @@ pub struct ProjectileHitCalc {
     pub ship_rot: Quat,
     pub projectile_base_damage: f64,
     pub projectile_caliber: Caliber,
+    pub projectile_type: BulletType,
+    /// The firing turret's `fire_chance`
+    pub projectile_fire_chance: f32,
     pub projectile_vel: Vec3,
     pub projectile_pos: Vec3,
+    /// Uniformly random in `0..1`, the shell ignites the ship if this is below its fire chance
+    pub fire_roll: f32,
+    /// Multiplies the damage of AP shells which penetrate into the citadel
+    pub citadel_multiplier: f64,
 }
 
 impl ProjectileHitCalc {
     /// Assumes that the intersection position is on or within the ship hull
+    ///
+    /// AP shells are checked against the hull's `armor` on whichever face of its bounds
+    /// they came in through, which is thicker the more obliquely it's struck
     pub fn run(self) -> ProjectileHitRes {
         // Calculate collisions in the local space of the ship hull
         let ship_rot_inv = self.ship_rot.normalize().inverse();
         let proj_pos = ship_rot_inv * (self.projectile_pos - self.ship_pos.extend(0.));
-        let (ship_hull_min, ship_hull_max) = self.ship.to_template().hull.to_bounds();
-        if Vec3::cmple(ship_hull_min, proj_pos).all() && Vec3::cmple(proj_pos, ship_hull_max).all()
+        let template = self.ship.to_template();
+        let (ship_hull_min, ship_hull_max) = template.hull.to_bounds();
+        if !(ship_hull_min.z..=ship_hull_max.z).contains(&proj_pos.z)
+            || !template.hull_contains(proj_pos.truncate())
         {
-            let proj_vel = ship_rot_inv * self.projectile_vel;
-            let proj_alignment = proj_vel.normalize().dot(Vec3::X).abs();
-            let damage_dealt = self.projectile_base_damage * (1.5 + proj_alignment as f64);
+            return ProjectileHitRes::Missed;
+        }
+        let section = template.hull.section_at(proj_pos.truncate());
+
+        match self.projectile_type {
+            BulletType::AP => {
+                let proj_vel = ship_rot_inv * self.projectile_vel;
+                let face_normal =
+                    entry_face_normal(ship_hull_min, ship_hull_max, proj_pos, proj_vel);
+                let impact_angle = (-proj_vel).angle_between(face_normal);
+                if impact_angle > RICOCHET_ANGLE {
+                    return ProjectileHitRes::Ricochet;
+                }
+
+                let proj_alignment = proj_vel.normalize().dot(Vec3::X).abs();
+                let damage_dealt = self.projectile_base_damage * (1.5 + proj_alignment as f64);
+                let effective_armor = template.hull.armor / impact_angle.cos();
+                let penetration = self.projectile_caliber.mm() * AP_PENETRATION_PER_CALIBER;
+                if penetration > effective_armor * OVERPENETRATION_ARMOR_RATIO {
+                    return ProjectileHitRes::Overpenetration {
+                        damage_dealt: damage_dealt * OVERPENETRATION_DAMAGE_FRACTION,
+                        section,
+                    };
+                }
+                // The shell is checked along the rest of its path rather than just where it is,
+                // since it's only seen once per tick, often before it reaches the citadel
+                let (citadel_min, citadel_max) = template.hull.citadel_bounds();
+                let was_citadel = ray_hits_box(citadel_min, citadel_max, proj_pos, proj_vel);
+                ProjectileHitRes::Hit {
+                    damage_dealt: match was_citadel {
+                        true => damage_dealt * self.citadel_multiplier,
+                        false => damage_dealt,
+                    },
+                    section,
+                    ignites: false,
+                    was_citadel,
+                }
+            }
+            BulletType::HE => {
+                let fire_chance = self.projectile_fire_chance * self.projectile_caliber.mm()
+                    / FIRE_CHANCE_REFERENCE_CALIBER_MM;
+                ProjectileHitRes::Hit {
+                    damage_dealt: self.projectile_base_damage,
+                    section,
+                    ignites: self.fire_roll < fire_chance,
+                    was_citadel: false,
+                }
+            }
+        }
+    }
+}
+
+/// Whether a ray from `origin` along `dir` starts within or passes through
+/// the box from `min` to `max`
+fn ray_hits_box(min: Vec3, max: Vec3, origin: Vec3, dir: Vec3) -> bool {
+    let (mut enter, mut exit) = (0_f32, f32::INFINITY);
+    for axis in 0..3 {
+        if dir[axis].abs() < f32::EPSILON {
+            if !(min[axis]..=max[axis]).contains(&origin[axis]) {
+                return false;
+            }
+            continue;
+        }
+        let to_min = (min[axis] - origin[axis]) / dir[axis];
+        let to_max = (max[axis] - origin[axis]) / dir[axis];
+        enter = enter.max(to_min.min(to_max));
+        exit = exit.min(to_min.max(to_max));
+    }
+    enter <= exit
+}
+
+/// The outward normal of the face of the box from `min` to `max` which a projectile,
+/// now at `pos` inside it and moving along `vel`, most recently came in through
+fn entry_face_normal(min: Vec3, max: Vec3, pos: Vec3, vel: Vec3) -> Vec3 {
+    let back = -vel;
+    let mut nearest = (f32::INFINITY, Vec3::Z);
+    for axis in 0..3 {
+        if back[axis].abs() < f32::EPSILON {
+            continue;
+        }
+        let (bound, normal) = match back[axis] > 0. {
+            true => (max[axis], Vec3::AXES[axis]),
+            false => (min[axis], -Vec3::AXES[axis]),
+        };
+        let dist = (bound - pos[axis]) / back[axis];
+        if dist < nearest.0 {
+            nearest = (dist, normal);
+        }
+    }
+    nearest.1
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec2, Vec3, vec3};
+
+    use crate::{
+        formulas::{GunRangeCalc, GunRangeModifiers, ProjectileHitCalc, ProjectileHitRes},
+        ship_template::{BulletType, Caliber, ShipTemplateId},
+    };
+
+    /// A shell hitting the middle of a bismarck at the origin, travelling along `vel`
+    fn hit_amidships(projectile_type: BulletType, vel: Vec3, fire_roll: f32) -> (f64, bool) {
+        let calc = ProjectileHitCalc {
+            ship: ShipTemplateId::bismarck(),
+            ship_pos: Vec2::ZERO,
+            ship_rot: Quat::IDENTITY,
+            projectile_base_damage: 100.,
+            projectile_caliber: Caliber::from_mm(150.),
+            projectile_type,
+            projectile_fire_chance: 0.1,
+            projectile_vel: vel,
+            projectile_pos: vec3(0., 0., 1.),
+            fire_roll,
+            citadel_multiplier: 1.,
+        };
+        match calc.run() {
+            ProjectileHitRes::Hit {
+                damage_dealt,
+                ignites,
+                ..
+            } => (damage_dealt, ignites),
+            _ => panic!("the shell should have hit"),
+        }
+    }
 
-            //
+    #[test]
+    fn test_he_damage_ignores_alignment() {
+        let (broadside, _) = hit_amidships(BulletType::HE, vec3(0., -1., -0.2), 1.);
+        let (end_on, _) = hit_amidships(BulletType::HE, vec3(-1., 0., -0.2), 1.);
+        assert_eq!(broadside, 100.);
+        assert_eq!(end_on, 100.);
+
+        let (ap_broadside, _) = hit_amidships(BulletType::AP, vec3(0., -1., -0.2), 1.);
+        let (ap_end_on, _) = hit_amidships(BulletType::AP, vec3(-1., 0., -0.05), 1.);
+        assert!(ap_end_on > ap_broadside);
+    }
+
+    #[test]
+    fn test_only_he_ignites_below_fire_chance() {
+        let vel = vec3(0., -1., -0.2);
+        assert!(hit_amidships(BulletType::HE, vel, 0.05).1);
+        assert!(!hit_amidships(BulletType::HE, vel, 0.15).1);
+        assert!(!hit_amidships(BulletType::AP, vel, 0.).1);
+    }
+
+    /// A 380mm AP shell striking the middle of `ship`'s starboard side, travelling along `vel`
+    fn ap_hit_on_side(ship: ShipTemplateId, vel: Vec3) -> ProjectileHitRes {
+        let half_width = ship.to_template().hull.width / 2.;
+        ProjectileHitCalc {
+            ship,
+            ship_pos: Vec2::ZERO,
+            ship_rot: Quat::IDENTITY,
+            projectile_base_damage: 100.,
+            projectile_caliber: Caliber::from_mm(380.),
+            projectile_type: BulletType::AP,
+            projectile_fire_chance: 0.,
+            projectile_vel: vel,
+            projectile_pos: vec3(0., -half_width + 0.1, 1.),
+            fire_roll: 1.,
+            citadel_multiplier: 1.,
+        }
+        .run()
+    }
 
-            ProjectileHitRes::Hit { damage_dealt }
-        } else {
-            ProjectileHitRes::Missed
+    /// A 380mm AP shell just through the middle of a bismarck's starboard side
+    /// at `height` above the waterline, descending slightly
+    fn ap_hit_amidships_at_height(height: f32) -> ProjectileHitRes {
+        let half_width = ShipTemplateId::bismarck().to_template().hull.width / 2.;
+        ProjectileHitCalc {
+            ship: ShipTemplateId::bismarck(),
+            ship_pos: Vec2::ZERO,
+            ship_rot: Quat::IDENTITY,
+            projectile_base_damage: 100.,
+            projectile_caliber: Caliber::from_mm(380.),
+            projectile_type: BulletType::AP,
+            projectile_fire_chance: 0.,
+            projectile_vel: vec3(0., 800., -100.),
+            projectile_pos: vec3(0., -half_width + 0.5, height),
+            fire_roll: 1.,
+            citadel_multiplier: 3.,
         }
+        .run()
+    }
+
+    #[test]
+    fn test_citadel_multiplier_applies_low_amidships() {
+        let res = ap_hit_amidships_at_height(-2.);
+        assert!(matches!(
+            res,
+            ProjectileHitRes::Hit { damage_dealt, was_citadel: true, .. }
+                if (damage_dealt - 450.).abs() < 0.1
+        ));
+    }
+
+    #[test]
+    fn test_citadel_multiplier_skips_superstructure() {
+        let freeboard = ShipTemplateId::bismarck().to_template().hull.freeboard;
+        let res = ap_hit_amidships_at_height(freeboard - 0.5);
+        assert!(matches!(
+            res,
+            ProjectileHitRes::Hit { damage_dealt, was_citadel: false, .. }
+                if (damage_dealt - 150.).abs() < 0.1
+        ));
+    }
+
+    #[test]
+    fn test_ap_penetrates_armor_struck_squarely() {
+        let res = ap_hit_on_side(ShipTemplateId::bismarck(), vec3(0., 800., -50.));
+        assert!(matches!(
+            res,
+            ProjectileHitRes::Hit { damage_dealt, .. } if (damage_dealt - 150.).abs() < 0.1
+        ));
+    }
+
+    #[test]
+    fn test_ap_ricochets_off_oblique_armor() {
+        let res = ap_hit_on_side(ShipTemplateId::bismarck(), vec3(800., 240., -10.));
+        assert!(matches!(res, ProjectileHitRes::Ricochet));
+    }
+
+    #[test]
+    fn test_ap_overpenetrates_thin_armor() {
+        let res = ap_hit_on_side(ShipTemplateId::kiev(), vec3(0., 800., -50.));
+        assert!(matches!(
+            res,
+            ProjectileHitRes::Overpenetration { damage_dealt, .. }
+                if (damage_dealt - 15.).abs() < 0.1
+        ));
+    }
+
+    #[test]
+    fn test_gun_range_without_modifiers_is_base_range() {
+        let calc = GunRangeCalc {
+            base_range: 12_000.,
+        };
+        assert_eq!(calc.run(GunRangeModifiers::default()), 12_000.);
+    }
+
+    #[test]
+    fn test_spotter_plane_extends_gun_range() {
+        let calc = GunRangeCalc {
+            base_range: 12_000.,
+        };
+        let modifiers = GunRangeModifiers {
+            spotter_plane_bonus: Some(0.2),
+        };
+        assert!((calc.run(modifiers) - 14_400.).abs() < 0.01);
     }
 }
@@ -1,6 +1,8 @@
 use glam::Vec2;
 
 pub mod formulas;
+pub mod pathfinding;
+pub mod ship_path;
 pub mod ship_template;
 
 /// (lower_bound, higher_bound)
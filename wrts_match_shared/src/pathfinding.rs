@@ -0,0 +1,245 @@
+//! Routing move orders around obstacles, such as islands
+//!
+//! Obstacles are only ever tested one point at a time,
+//! so this doesn't depend on how they're represented
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use glam::*;
+
+use crate::map_bounds;
+
+/// Finds waypoints leading from `start` to `goal` without crossing any point where `is_blocked`,
+/// or `None` if `goal` can't be reached within [map_bounds]
+///
+/// Searches a grid of `cell_size` squares, so obstacles (and gaps between them)
+/// narrower than a cell may be missed. The path is then straightened,
+/// so it only turns where it has to. The returned waypoints end at `goal`,
+/// and don't include `start`
+pub fn find_path(
+    start: Vec2,
+    goal: Vec2,
+    cell_size: f32,
+    is_blocked: impl Fn(Vec2) -> bool,
+) -> Option<Vec<Vec2>> {
+    let grid = Grid::new(cell_size);
+    if !grid.contains(goal) || is_blocked(goal) {
+        return None;
+    }
+    if segment_is_clear(start, goal, cell_size, &is_blocked) {
+        return Some(vec![goal]);
+    }
+
+    let start_cell = grid.cell_of(start);
+    let goal_cell = grid.cell_of(goal);
+    let mut blocked_cells = HashMap::new();
+    let mut cell_is_blocked = |cell: IVec2| {
+        *blocked_cells
+            .entry(cell)
+            .or_insert_with(|| is_blocked(grid.center_of(cell)))
+    };
+
+    // A* over the grid, moving between the 8 neighbours of each cell
+    let mut came_from: HashMap<IVec2, IVec2> = HashMap::new();
+    let mut cost_to: HashMap<IVec2, f32> = HashMap::from([(start_cell, 0.)]);
+    let mut open = BinaryHeap::from([OpenCell {
+        cell: start_cell,
+        estimated_cost: 0.,
+    }]);
+    while let Some(OpenCell { cell, .. }) = open.pop() {
+        if cell == goal_cell {
+            break;
+        }
+        let cell_cost = cost_to[&cell];
+        for offset in [
+            ivec2(1, 0),
+            ivec2(-1, 0),
+            ivec2(0, 1),
+            ivec2(0, -1),
+            ivec2(1, 1),
+            ivec2(1, -1),
+            ivec2(-1, 1),
+            ivec2(-1, -1),
+        ] {
+            let next = cell + offset;
+            if !grid.contains_cell(next) || (next != goal_cell && cell_is_blocked(next)) {
+                continue;
+            }
+            let next_cost = cell_cost + offset.as_vec2().length();
+            if cost_to.get(&next).is_some_and(|&cost| cost <= next_cost) {
+                continue;
+            }
+            cost_to.insert(next, next_cost);
+            came_from.insert(next, cell);
+            open.push(OpenCell {
+                cell: next,
+                estimated_cost: next_cost + (goal_cell - next).as_vec2().length(),
+            });
+        }
+    }
+    if !came_from.contains_key(&goal_cell) {
+        return None;
+    }
+
+    let mut cells = vec![goal_cell];
+    while let Some(&prev) = came_from.get(cells.last().unwrap()) {
+        cells.push(prev);
+    }
+    cells.reverse();
+
+    let mut points = cells
+        .into_iter()
+        .map(|cell| grid.center_of(cell))
+        .collect::<Vec<_>>();
+    *points.first_mut().unwrap() = start;
+    *points.last_mut().unwrap() = goal;
+
+    Some(straighten(&points, cell_size, &is_blocked))
+}
+
+/// Skips every point which can be passed by heading straight for a later one,
+/// returning the points kept (apart from the first)
+fn straighten(points: &[Vec2], cell_size: f32, is_blocked: &impl Fn(Vec2) -> bool) -> Vec<Vec2> {
+    let mut waypoints = vec![];
+    let mut curr = 0;
+    while curr < points.len() - 1 {
+        let next = (curr + 1..points.len())
+            .rev()
+            .find(|&next| segment_is_clear(points[curr], points[next], cell_size, is_blocked))
+            .unwrap_or(curr + 1);
+        waypoints.push(points[next]);
+        curr = next;
+    }
+    waypoints
+}
+
+/// Checks points along the segment at intervals of half a cell
+fn segment_is_clear(
+    from: Vec2,
+    to: Vec2,
+    cell_size: f32,
+    is_blocked: &impl Fn(Vec2) -> bool,
+) -> bool {
+    let steps = (from.distance(to) / (0.5 * cell_size)).ceil().max(1.) as usize;
+    (1..=steps).all(|step| !is_blocked(from.lerp(to, step as f32 / steps as f32)))
+}
+
+struct Grid {
+    lower: Vec2,
+    cell_size: f32,
+    cell_count: IVec2,
+}
+
+impl Grid {
+    fn new(cell_size: f32) -> Self {
+        let (lower, upper) = map_bounds();
+        Self {
+            lower,
+            cell_size,
+            cell_count: ((upper - lower) / cell_size).ceil().as_ivec2(),
+        }
+    }
+
+    fn contains(&self, pos: Vec2) -> bool {
+        let (lower, upper) = map_bounds();
+        pos.cmpge(lower).all() && pos.cmple(upper).all()
+    }
+
+    fn contains_cell(&self, cell: IVec2) -> bool {
+        cell.cmpge(IVec2::ZERO).all() && cell.cmplt(self.cell_count).all()
+    }
+
+    fn cell_of(&self, pos: Vec2) -> IVec2 {
+        ((pos - self.lower) / self.cell_size)
+            .floor()
+            .as_ivec2()
+            .clamp(IVec2::ZERO, self.cell_count - 1)
+    }
+
+    fn center_of(&self, cell: IVec2) -> Vec2 {
+        self.lower + (cell.as_vec2() + 0.5) * self.cell_size
+    }
+}
+
+/// Ordered so that [BinaryHeap] pops the lowest estimated cost first
+struct OpenCell {
+    cell: IVec2,
+    estimated_cost: f32,
+}
+
+impl PartialEq for OpenCell {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenCell {}
+
+impl PartialOrd for OpenCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimated_cost.total_cmp(&self.estimated_cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::*;
+
+    use crate::{
+        map_bounds,
+        pathfinding::{find_path, segment_is_clear},
+    };
+
+    const CELL_SIZE: f32 = 250.;
+
+    fn island(pos: Vec2) -> bool {
+        pos.length() < 2_000.
+    }
+
+    #[test]
+    fn test_clear_path_is_straight() {
+        let goal = vec2(5_000., 8_000.);
+        assert_eq!(
+            find_path(vec2(-5_000., 0.), goal, CELL_SIZE, island),
+            Some(vec![goal])
+        );
+    }
+
+    #[test]
+    fn test_path_avoids_island() {
+        let start = vec2(-5_000., 0.);
+        let goal = vec2(5_000., 0.);
+        let waypoints = find_path(start, goal, CELL_SIZE, island).unwrap();
+
+        assert!(waypoints.len() > 1, "{waypoints:?}");
+        assert_eq!(*waypoints.last().unwrap(), goal);
+        let (lower, upper) = map_bounds();
+        let mut prev = start;
+        for &waypoint in &waypoints {
+            assert!(waypoint.cmpge(lower).all() && waypoint.cmple(upper).all());
+            assert!(segment_is_clear(prev, waypoint, CELL_SIZE, &island));
+            prev = waypoint;
+        }
+    }
+
+    #[test]
+    fn test_unreachable_goals() {
+        let start = vec2(-5_000., 0.);
+        // On land
+        assert_eq!(find_path(start, Vec2::ZERO, CELL_SIZE, island), None);
+        // Off the map
+        assert_eq!(find_path(start, vec2(30_000., 0.), CELL_SIZE, island), None);
+        // Surrounded by land
+        let lagoon = |pos: Vec2| (3_000. ..4_000.).contains(&pos.length());
+        assert_eq!(find_path(start, Vec2::ZERO, CELL_SIZE, lagoon), None);
+    }
+}
@@ -0,0 +1,195 @@
+//! The smoothed path a ship follows through its waypoints
+
+use glam::Vec2;
+
+/// A Catmull-Rom spline starting at a ship's position and passing through each of its waypoints
+///
+/// The first and last points are duplicated as control points,
+/// so the path starts and ends exactly at the ship and the final waypoint
+#[derive(Debug, Clone)]
+pub struct ShipPathCatmull {
+    /// The ship's position followed by its waypoints, so always at least two points
+    points: Vec<Vec2>,
+    /// The control point before the start, see [ShipPathCatmull::with_start_dir]
+    before_start: Vec2,
+}
+
+impl ShipPathCatmull {
+    /// `None` if there are no `waypoints`, such as after a ship's waypoints are cleared,
+    /// since the ship isn't going anywhere
+    pub fn new(start: Vec2, waypoints: &[Vec2]) -> Option<Self> {
+        if waypoints.is_empty() {
+            return None;
+        }
+        let mut points = Vec::with_capacity(waypoints.len() + 1);
+        points.push(start);
+        points.extend_from_slice(waypoints);
+        Some(Self {
+            points,
+            before_start: start,
+        })
+    }
+
+    /// Bends the start of the path towards `dir`, the way the ship is already heading,
+    /// rather than leaving straight for the first waypoint
+    pub fn with_start_dir(mut self, dir: Vec2) -> Self {
+        let start = self.points[0];
+        let first_leg = self.points[1].distance(start);
+        self.before_start = start - dir.normalize_or_zero() * first_leg;
+        self
+    }
+
+    /// The number of curves between adjacent points,
+    /// which is the same as the number of waypoints
+    pub fn segment_count(&self) -> usize {
+        self.points.len() - 1
+    }
+
+    fn point(&self, idx: isize) -> Vec2 {
+        if idx < 0 {
+            return self.before_start;
+        }
+        self.points[idx.min(self.points.len() as isize - 1) as usize]
+    }
+
+    /// Samples the path, where `t` ranges from `0` (the start)
+    /// to [Self::segment_count] (the final waypoint).
+    /// A `t` outside that range is clamped into it, and NaN is the start
+    pub fn sample(&self, t: f32) -> Vec2 {
+        let t = match t.is_nan() {
+            true => 0.,
+            false => t.clamp(0., self.segment_count() as f32),
+        };
+        let segment = (t.floor() as isize).min(self.segment_count() as isize - 1);
+        let u = t - segment as f32;
+
+        let p0 = self.point(segment - 1);
+        let p1 = self.point(segment);
+        let p2 = self.point(segment + 1);
+        let p3 = self.point(segment + 2);
+
+        let u2 = u * u;
+        let u3 = u2 * u;
+        0.5 * ((2. * p1)
+            + (p2 - p0) * u
+            + (2. * p0 - 5. * p1 + 4. * p2 - p3) * u2
+            + (3. * p1 - p0 - 3. * p2 + p3) * u3)
+    }
+
+    /// Returns `samples_per_segment` evenly spaced (in `t`) points along each segment,
+    /// plus the final waypoint
+    pub fn sample_points(&self, samples_per_segment: usize) -> Vec<Vec2> {
+        let samples_per_segment = samples_per_segment.max(1);
+        let total = self.segment_count() * samples_per_segment;
+        (0..=total)
+            .map(|i| self.sample(i as f32 / samples_per_segment as f32))
+            .collect()
+    }
+
+    /// Returns the indices (into the original `waypoints`) of every waypoint
+    /// whose corner is too sharp to be followed by a ship with the given turning radius
+    ///
+    /// A corner which deflects the path by `theta` needs at least
+    /// `turning_radius * tan(theta / 2)` of straight path on either side of it
+    pub fn unfollowable_waypoints(&self, turning_radius: f32) -> Vec<usize> {
+        (1..self.segment_count())
+            .filter(|&point_idx| {
+                let prev = self.points[point_idx - 1];
+                let curr = self.points[point_idx];
+                let next = self.points[point_idx + 1];
+                let (Some(dir_in), Some(dir_out)) =
+                    ((curr - prev).try_normalize(), (next - curr).try_normalize())
+                else {
+                    return false;
+                };
+                let deflection = dir_in.angle_to(dir_out).abs();
+                let required = turning_radius * f32::tan(0.5 * deflection);
+                curr.distance(prev).min(curr.distance(next)) < required
+            })
+            .map(|point_idx| point_idx - 1)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec2, vec2};
+
+    use crate::ship_path::ShipPathCatmull;
+
+    fn vec2_eq(a: Vec2, b: Vec2) -> bool {
+        a.distance_squared(b) <= 0.001
+    }
+
+    #[test]
+    fn test_catmull_passes_through_waypoints() {
+        let start = vec2(0., 0.);
+        let waypoints = [vec2(100., 0.), vec2(100., 100.), vec2(-50., 300.)];
+        let path = ShipPathCatmull::new(start, &waypoints).unwrap();
+        assert_eq!(path.segment_count(), waypoints.len());
+        assert!(vec2_eq(path.sample(0.), start));
+        for (i, &waypoint) in waypoints.iter().enumerate() {
+            assert!(vec2_eq(path.sample((i + 1) as f32), waypoint));
+        }
+        let samples = path.sample_points(8);
+        assert_eq!(samples.len(), 8 * waypoints.len() + 1);
+        assert!(vec2_eq(*samples.last().unwrap(), waypoints[2]));
+    }
+
+    #[test]
+    fn test_paths_with_few_waypoints() {
+        let start = vec2(0., 0.);
+        assert!(ShipPathCatmull::new(start, &[]).is_none());
+
+        // A single waypoint is a straight line to it
+        let end = vec2(100., 50.);
+        let path = ShipPathCatmull::new(start, &[end]).unwrap();
+        assert_eq!(path.segment_count(), 1);
+        assert!(vec2_eq(path.sample(0.5), (start + end) / 2.));
+        assert!(vec2_eq(path.sample(1.), end));
+        assert!(path.unfollowable_waypoints(1_000.).is_empty());
+
+        let waypoints = [vec2(100., 0.), vec2(100., 100.)];
+        let path = ShipPathCatmull::new(start, &waypoints).unwrap();
+        assert_eq!(path.segment_count(), 2);
+        assert!(vec2_eq(path.sample(1.), waypoints[0]));
+        assert!(vec2_eq(path.sample(2.), waypoints[1]));
+
+        // Out of range samples are clamped to either end
+        assert!(vec2_eq(path.sample(-1.), start));
+        assert!(vec2_eq(path.sample(f32::NAN), start));
+        assert!(vec2_eq(path.sample(10.), waypoints[1]));
+        assert!(vec2_eq(path.sample(f32::INFINITY), waypoints[1]));
+    }
+
+    #[test]
+    fn test_path_leaves_along_start_dir() {
+        let start = vec2(0., 0.);
+        let waypoints = [vec2(100., 100.), vec2(200., 100.)];
+        let straight = ShipPathCatmull::new(start, &waypoints).unwrap();
+        let path = straight.clone().with_start_dir(vec2(1., 0.));
+
+        let leaving_angle =
+            |path: &ShipPathCatmull| (path.sample(0.01) - start).angle_to(vec2(1., 0.)).abs();
+        assert!(leaving_angle(&path) < leaving_angle(&straight) - 0.2);
+        assert!(vec2_eq(path.sample(0.), start));
+        for (i, &waypoint) in waypoints.iter().enumerate() {
+            assert!(vec2_eq(path.sample((i + 1) as f32), waypoint));
+        }
+    }
+
+    #[test]
+    fn test_unfollowable_waypoints() {
+        let start = vec2(0., 0.);
+        // A hairpin turn with short legs, followed by a gentle corner with long legs
+        let waypoints = [
+            vec2(1_000., 0.),
+            vec2(950., 50.),
+            vec2(950., 2_050.),
+            vec2(2_950., 2_150.),
+        ];
+        let path = ShipPathCatmull::new(start, &waypoints).unwrap();
+        assert_eq!(path.unfollowable_waypoints(500.), vec![0, 1]);
+        assert!(path.unfollowable_waypoints(1.).is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+//! Loading and storing ship templates as RON data files
+//!
+//! `ShipTemplateId`s aren't stored in the data,
+//! so the id of a loaded template is given by whoever loads it
+
+use std::path::Path;
+
+use anyhow::{Context, ensure};
+
+use crate::ship_template::*;
+
+impl ShipTemplate {
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("ship templates should always be serializable")
+    }
+
+    /// Parses a template and assigns it `id`,
+    /// returning an error if the result isn't a valid template
+    pub fn from_ron(id: ShipTemplateId, s: &str) -> anyhow::Result<ShipTemplate> {
+        let mut template: ShipTemplate = ron::de::from_str(s)?;
+        template.id = id;
+        for turret in &mut template.turret_instances {
+            turret.ship_template = id;
+        }
+        template.validate()?;
+        Ok(template)
+    }
+
+    pub fn load_file(id: ShipTemplateId, path: impl AsRef<Path>) -> anyhow::Result<ShipTemplate> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read ship template `{}`", path.display()))?;
+        Self::from_ron(id, &s)
+            .with_context(|| format!("Failed to load ship template `{}`", path.display()))
+    }
+
+    /// Checks that this template is internally consistent
+    /// and has sensible values for everything the match relies on
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.hull.length > 0. && self.hull.width > 0.,
+            "Hull must have a positive length and width"
+        );
+        ensure!(self.hull.armor >= 0., "Hull `armor` can't be negative");
+        if let Some(outline) = &self.hull_outline {
+            let (min, max) = self.hull.to_bounds();
+            ensure!(
+                outline.len() >= 3,
+                "`hull_outline` must have at least 3 points"
+            );
+            ensure!(
+                outline
+                    .iter()
+                    .all(|p| min.truncate().cmple(*p).all() && p.cmple(max.truncate()).all()),
+                "`hull_outline` must fit within the hull's length and width"
+            );
+        }
+        ensure!(self.max_speed.mps() > 0., "`max_speed` must be positive");
+        ensure!(
+            self.engine_acceleration.mps() > 0.,
+            "`engine_acceleration` must be positive"
+        );
+        ensure!(
+            self.turning_rate.radps() > 0.,
+            "`turning_rate` must be positive"
+        );
+        ensure!(self.max_health > 0., "`max_health` must be positive");
+        ensure!(
+            self.detection > 0. && self.torpedo_detection > 0.,
+            "Detection ranges must be positive"
+        );
+
+        for (idx, turret) in self.turret_instances.iter().enumerate() {
+            ensure!(
+                turret.ship_template == self.id,
+                "Turret instance {idx} belongs to a different ship template"
+            );
+            ensure!(
+                self.turret_templates.contains_key(turret.template),
+                "Turret instance {idx} uses a turret template which doesn't exist"
+            );
+        }
+        for turret in self.turret_templates.values() {
+            ensure!(
+                turret.reload_secs > 0.,
+                "Turret `reload_secs` must be positive"
+            );
+            ensure!(
+                turret.muzzle_vel > 0.,
+                "Turret `muzzle_vel` must be positive"
+            );
+            ensure!(turret.max_range > 0., "Turret `max_range` must be positive");
+            ensure!(
+                turret.min_elevation < turret.max_elevation
+                    && turret.max_elevation <= std::f32::consts::FRAC_PI_2,
+                "Turret elevation limits must form a range no steeper than vertical"
+            );
+            ensure!(
+                turret.caliber.mm() > 0.,
+                "Turret `caliber` must be positive"
+            );
+            ensure!(
+                (0. ..=1.).contains(&turret.fire_chance),
+                "Turret `fire_chance` must be between 0 and 1"
+            );
+            ensure!(
+                turret.barrel_count > 0,
+                "Turrets must have at least one barrel"
+            );
+            ensure!(
+                turret.dispersion.sigma > 0.,
+                "Turret dispersion `sigma` must be positive"
+            );
+            if let Some(aa) = turret.aa {
+                ensure!(
+                    aa.aa_dps > 0. && aa.aa_range > 0.,
+                    "Turret AA must have a positive `aa_dps` and `aa_range`"
+                );
+            }
+        }
+
+        if let Some(torpedoes) = &self.torpedoes {
+            ensure!(
+                torpedoes.volleys > 0 && torpedoes.torps_per_volley > 0,
+                "Torpedoes must have at least one volley of at least one torpedo"
+            );
+            ensure!(
+                torpedoes.speed.mps() > 0. && torpedoes.range > 0.,
+                "Torpedoes must have a positive speed and range"
+            );
+        }
+        ensure!(
+            self.consumables.torp_reload_booster().is_none() || self.torpedoes.is_some(),
+            "Only ships with torpedoes can have a torpedo reload booster"
+        );
+        if let Some(spotter_plane) = self.consumables.spotter_plane() {
+            ensure!(
+                spotter_plane.range_bonus >= 0. && spotter_plane.spotting_range >= 0.,
+                "Spotter plane `range_bonus` and `spotting_range` can't be negative"
+            );
+        }
+        if let Some(repair_party) = self.consumables.repair_party() {
+            ensure!(
+                repair_party.heal_per_sec > 0.,
+                "Repair party `heal_per_sec` must be positive"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ship_template::{ShipTemplate, ShipTemplateId};
+
+    #[test]
+    fn test_built_in_templates_round_trip() {
+        for &id in ShipTemplateId::all_ships() {
+            let template = id.to_template();
+            template.validate().unwrap();
+
+            let ron = template.to_ron();
+            let loaded = ShipTemplate::from_ron(id, &ron).unwrap();
+            assert_eq!(loaded.id, id);
+            assert_eq!(
+                loaded.to_ron(),
+                ron,
+                "`{}` changed in round-trip",
+                id.to_name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_template_rejected() {
+        let id = ShipTemplateId::oland();
+        let ron = id.to_template().to_ron().replace(
+            &format!("max_health: {:?}", id.to_template().max_health),
+            "max_health: -1.0",
+        );
+        assert!(ShipTemplate::from_ron(id, &ron).is_err());
+    }
+}
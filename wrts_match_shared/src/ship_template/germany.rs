@@ -8,12 +8,18 @@ impl ShipTemplate {
         use HullLocationAxis::*;
 
         let ship_template = ShipTemplateId::bismarck();
+        let ship_class = ShipClass::Battleship;
         let mut turret_templates = SlotMap::default();
         let main_battery = turret_templates.insert(TurretTemplate {
             reload_secs: 26.,
             damage: 1000.,
             muzzle_vel: 820.,
             max_range: 21_200.,
+            min_elevation: -5.5_f32.to_radians(),
+            max_elevation: 30_f32.to_radians(),
+            caliber: Caliber::from_mm(380.),
+            bullet_type: BulletType::AP,
+            fire_chance: 0.,
             dispersion: Dispersion {
                 vertical: 6.,
                 horizontal: 12.83,
@@ -24,12 +30,18 @@ impl ShipTemplate {
             // Estimated distance
             barrel_spacing: 1.,
             targeting_mode: TargetingMode::Primary,
+            aa: None,
         });
         let secondary_battery_150mm = turret_templates.insert(TurretTemplate {
             reload_secs: 7.5,
             damage: 250.,
             muzzle_vel: 875.,
             max_range: 9_100.,
+            min_elevation: -10_f32.to_radians(),
+            max_elevation: 40_f32.to_radians(),
+            caliber: Caliber::from_mm(150.),
+            bullet_type: BulletType::HE,
+            fire_chance: 0.05,
             dispersion: Dispersion {
                 vertical: 20.,
                 horizontal: 30.,
@@ -39,12 +51,18 @@ impl ShipTemplate {
             barrel_count: 2,
             barrel_spacing: 1.8,
             targeting_mode: TargetingMode::Secondary,
+            aa: None,
         });
         let secondary_battery_105mm = turret_templates.insert(TurretTemplate {
             reload_secs: 3.4,
             damage: 170.,
             muzzle_vel: 900.,
             max_range: 9_100.,
+            min_elevation: -8_f32.to_radians(),
+            max_elevation: 80_f32.to_radians(),
+            caliber: Caliber::from_mm(105.),
+            bullet_type: BulletType::HE,
+            fire_chance: 0.05,
             dispersion: Dispersion {
                 vertical: 20.,
                 horizontal: 30.,
@@ -54,6 +72,10 @@ impl ShipTemplate {
             barrel_count: 2,
             barrel_spacing: 0.896,
             targeting_mode: TargetingMode::Secondary,
+            aa: Some(AntiAirProfile {
+                aa_dps: 45.,
+                aa_range: 5_000.,
+            }),
         });
 
         let secondary_battery_150mm_instances = [
@@ -149,19 +171,22 @@ impl ShipTemplate {
 
         ShipTemplate {
             id: ship_template,
-            ship_class: ShipClass::Battleship,
+            ship_class,
             hull: Hull {
                 length: 251.,
                 width: 36.,
                 freeboard: 8.7,
                 draft: 9.3,
+                armor: 320.,
             },
+            hull_outline: None,
             max_speed: Speed::from_kts(31. * SHIP_SPEED_SCALE),
             engine_acceleration: Speed::from_kts(3. * SHIP_SPEED_SCALE),
             turning_rate: AngularSpeed::from_radps(0.15),
             max_health: 60_000.,
             detection: 15_900.,
             detection_when_firing_through_smoke: 15_100.,
+            torpedo_detection: ship_class.default_torpedo_detection(),
             turret_templates,
             turret_instances: [
                 // Primary
@@ -203,7 +228,7 @@ impl ShipTemplate {
             .chain(secondary_battery_105mm_instances)
             .collect(),
             torpedoes: None,
-            consumables: Consumables::new(),
+            consumables: ship_class.default_consumables(),
         }
     }
     /// * https://en.wikipedia.org/wiki/German_cruiser_Admiral_Hipper
@@ -212,12 +237,18 @@ impl ShipTemplate {
     pub(super) fn hipper() -> ShipTemplate {
         use HullLocationAxis::*;
         let ship_template = ShipTemplateId::hipper();
+        let ship_class = ShipClass::CruiserHeavy;
         let mut turret_templates = SlotMap::default();
         let main_battery = turret_templates.insert(TurretTemplate {
             reload_secs: 10.5,
             damage: 400.,
             muzzle_vel: 925.,
             max_range: 17_700.,
+            min_elevation: -10_f32.to_radians(),
+            max_elevation: 37_f32.to_radians(),
+            caliber: Caliber::from_mm(203.),
+            bullet_type: BulletType::AP,
+            fire_chance: 0.,
             dispersion: Dispersion {
                 vertical: 4.,
                 horizontal: 8.75,
@@ -227,12 +258,18 @@ impl ShipTemplate {
             barrel_count: 2,
             barrel_spacing: 3.,
             targeting_mode: TargetingMode::Primary,
+            aa: None,
         });
         let secondary_battery_105mm = turret_templates.insert(TurretTemplate {
             reload_secs: 3.4,
             damage: 170.,
             muzzle_vel: 900.,
             max_range: 7_600.,
+            min_elevation: -10_f32.to_radians(),
+            max_elevation: 80_f32.to_radians(),
+            caliber: Caliber::from_mm(105.),
+            bullet_type: BulletType::HE,
+            fire_chance: 0.05,
             dispersion: Dispersion {
                 vertical: 20.,
                 horizontal: 50.,
@@ -243,6 +280,10 @@ impl ShipTemplate {
             // Estimated distance
             barrel_spacing: 0.896,
             targeting_mode: TargetingMode::Secondary,
+            aa: Some(AntiAirProfile {
+                aa_dps: 45.,
+                aa_range: 5_000.,
+            }),
         });
 
         let secondary_battery_105mm_instances = [
@@ -287,19 +328,22 @@ impl ShipTemplate {
 
         ShipTemplate {
             id: ship_template,
-            ship_class: ShipClass::CruiserHeavy,
+            ship_class,
             hull: Hull {
                 length: 202.8,
                 width: 21.3,
                 freeboard: 4.35,
                 draft: 5.4,
+                armor: 80.,
             },
+            hull_outline: None,
             max_speed: Speed::from_kts(32. * SHIP_SPEED_SCALE),
             engine_acceleration: Speed::from_kts(4. * SHIP_SPEED_SCALE),
             turning_rate: AngularSpeed::from_radps(0.20),
             max_health: 43_800.,
             detection: 13_800.,
             detection_when_firing_through_smoke: 8_500.,
+            torpedo_detection: ship_class.default_torpedo_detection(),
             turret_templates,
             turret_instances: [
                 TurretInstance {
@@ -339,7 +383,7 @@ impl ShipTemplate {
             .chain(secondary_battery_105mm_instances)
             .collect(),
             torpedoes: None,
-            consumables: Consumables::new(),
+            consumables: ship_class.default_consumables(),
         }
     }
 }
@@ -7,12 +7,18 @@ impl ShipTemplate {
     pub(super) fn nagato() -> ShipTemplate {
         use HullLocationAxis::*;
         let ship_template = ShipTemplateId::nagato();
+        let ship_class = ShipClass::Battleship;
         let mut turret_templates = SlotMap::default();
         let main_battery = turret_templates.insert(TurretTemplate {
             reload_secs: 29.,
             damage: 1200.,
             muzzle_vel: 806.,
             max_range: 21_200.,
+            min_elevation: -2_f32.to_radians(),
+            max_elevation: 43_f32.to_radians(),
+            caliber: Caliber::from_mm(410.),
+            bullet_type: BulletType::AP,
+            fire_chance: 0.,
             dispersion: Dispersion {
                 vertical: 6.,
                 horizontal: 11.3,
@@ -22,12 +28,18 @@ impl ShipTemplate {
             barrel_count: 2,
             barrel_spacing: 3.,
             targeting_mode: TargetingMode::Primary,
+            aa: None,
         });
         let secondary_battery_140mm = turret_templates.insert(TurretTemplate {
             reload_secs: 8.0,
             damage: 250.,
             muzzle_vel: 850.,
             max_range: 5_600.,
+            min_elevation: -7_f32.to_radians(),
+            max_elevation: 35_f32.to_radians(),
+            caliber: Caliber::from_mm(140.),
+            bullet_type: BulletType::HE,
+            fire_chance: 0.05,
             dispersion: Dispersion {
                 vertical: 15.,
                 horizontal: 30.,
@@ -37,12 +49,18 @@ impl ShipTemplate {
             barrel_count: 1,
             barrel_spacing: 1.,
             targeting_mode: TargetingMode::Secondary,
+            aa: None,
         });
         let secondary_battery_127mm = turret_templates.insert(TurretTemplate {
             reload_secs: 5.,
             damage: 200.,
             muzzle_vel: 725.,
             max_range: 5_600.,
+            min_elevation: -8_f32.to_radians(),
+            max_elevation: 90_f32.to_radians(),
+            caliber: Caliber::from_mm(127.),
+            bullet_type: BulletType::HE,
+            fire_chance: 0.05,
             dispersion: Dispersion {
                 vertical: 20.,
                 horizontal: 50.,
@@ -53,6 +71,10 @@ impl ShipTemplate {
             // Estimated distance
             barrel_spacing: 0.896,
             targeting_mode: TargetingMode::Secondary,
+            aa: Some(AntiAirProfile {
+                aa_dps: 50.,
+                aa_range: 5_200.,
+            }),
         });
 
         let secondary_battery_140mm_instances = [
@@ -204,19 +226,22 @@ impl ShipTemplate {
 
         ShipTemplate {
             id: ship_template,
-            ship_class: ShipClass::Battleship,
+            ship_class,
             hull: Hull {
                 length: 224.94,
                 width: 34.6,
                 freeboard: 9.,
                 draft: 9.49,
+                armor: 305.,
             },
+            hull_outline: None,
             max_speed: Speed::from_kts(26. * SHIP_SPEED_SCALE),
             engine_acceleration: Speed::from_kts(2. * SHIP_SPEED_SCALE),
             turning_rate: AngularSpeed::from_radps(0.135),
             max_health: 65_000.,
             detection: 16_600.,
             detection_when_firing_through_smoke: 16_700.,
+            torpedo_detection: ship_class.default_torpedo_detection(),
             turret_templates,
             turret_instances: [
                 TurretInstance {
@@ -257,7 +282,7 @@ impl ShipTemplate {
             .chain(secondary_battery_127mm_instances)
             .collect(),
             torpedoes: None,
-            consumables: Consumables::new(),
+            consumables: ship_class.default_consumables(),
         }
     }
 }
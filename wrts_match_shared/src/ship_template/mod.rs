@@ -1,8 +1,12 @@
+mod data;
 mod germany;
 mod japan;
+mod registry;
 mod russia;
 mod sweden;
 
+pub use registry::{DEFAULT_SHIP_MODS_DIR, ShipTemplateRegistry};
+
 use std::{f32::consts::PI, time::Duration};
 
 use glam::{EulerRot, Quat, Vec2, Vec3, vec2, vec3};
@@ -10,11 +14,14 @@ use paste::paste;
 use serde::{Deserialize, Serialize};
 use slotmap::SlotMap;
 
-use crate::{formulas::vector_is_within_swept_angle, ship_template::consumables::Consumables};
+use crate::{
+    formulas::{polygon_contains, vector_is_within_swept_angle},
+    ship_template::consumables::{Consumables, RepairParty, Smoke, SpotterPlane},
+};
 
 const SHIP_SPEED_SCALE: f32 = 5.2;
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Speed(f32);
 
 impl Speed {
@@ -29,7 +36,7 @@ impl Speed {
     }
 
     /// knots
-    pub fn from_kts(kts: f32) -> Self {
+    pub const fn from_kts(kts: f32) -> Self {
         Self(kts / 1.94384)
     }
 
@@ -40,7 +47,7 @@ impl Speed {
 }
 
 // Inner unit is millimeters
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Caliber(f32);
 
 impl Caliber {
@@ -57,7 +64,7 @@ impl Caliber {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct AngularSpeed(f32);
 
 impl AngularSpeed {
@@ -88,11 +95,17 @@ impl AngularSpeed {
 }
 
 /// Template information
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ShipTemplate {
+    /// Not stored in data files, see [ShipTemplate::from_ron]
+    #[serde(skip, default = "ShipTemplateId::unassigned")]
     pub id: ShipTemplateId,
     pub ship_class: ShipClass,
     pub hull: Hull,
+    /// The hull seen from above, in the same space as [Hull::to_bounds] with the bow towards +x,
+    /// for ships whose bow and stern taper. `None` is the rectangle from [Hull::to_outline]
+    #[serde(default)]
+    pub hull_outline: Option<Vec<Vec2>>,
     pub max_speed: Speed,
     /// Speed gained per second
     pub engine_acceleration: Speed,
@@ -100,15 +113,43 @@ pub struct ShipTemplate {
     pub max_health: f64,
     pub detection: f32,
     pub detection_when_firing_through_smoke: f32,
+    /// The range at which this ship's lookouts spot incoming torpedoes
+    pub torpedo_detection: f32,
     pub turret_templates: SlotMap<TurretTemplateId, TurretTemplate>,
     pub turret_instances: Vec<TurretInstance>,
     pub torpedoes: Option<Torpedoes>,
     pub consumables: Consumables,
 }
 
+/// Below this speed, a ship's turning rate is scaled down in proportion to its speed
+pub const FULL_TURNING_RATE_SPEED: Speed = Speed::from_kts(20.);
+
+impl ShipTemplate {
+    /// The radius of the tightest circle this ship can turn in while moving at `speed`
+    pub fn turning_radius(&self, speed: Speed) -> f32 {
+        // Below `FULL_TURNING_RATE_SPEED`, speed and turning rate
+        // shrink together so the radius stays the same
+        let speed = speed.mps().max(FULL_TURNING_RATE_SPEED.mps());
+        speed / self.turning_rate.radps()
+    }
+
+    /// Whether a point in the local space of the hull is within its outline,
+    /// ignoring height
+    pub fn hull_contains(&self, pos: Vec2) -> bool {
+        match &self.hull_outline {
+            Some(outline) => polygon_contains(outline, pos),
+            None => {
+                let (min, max) = self.hull.to_bounds();
+                min.truncate().cmple(pos).all() && pos.cmple(max.truncate()).all()
+            }
+        }
+    }
+}
+
 /// A unique numerical identifier for each ship template,
 /// used for temporary serialization/deserialization.
-/// Note that `ShipTemplateId`s can change between versions
+/// Note that `ShipTemplateId`s can change between versions,
+/// and that modded ships only have ids once [ShipTemplateRegistry::init] has loaded them
 ///
 /// For storage, use [ShipTemplateId::to_name] and [ShipTemplateId::from_name]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -132,59 +173,22 @@ macro_rules! make_ship_template_ids {
         make_ship_template_ids!(make_ids; $ship_name2 $($others)*);
     };
 
-    (make_name2id; $($ship_names:ident)*) => {
-        pub fn from_name(name: &str) -> Option<ShipTemplateId> {
-            let name: String = name.to_lowercase();
-            Some(match String::as_str(&name) {
-                $(stringify!($ship_names) => Self::$ship_names(),)*
-                _ => return None,
-            })
-        }
-    };
-
-    (make_id2name; $($ship_names:ident)*) => {
-        pub fn to_name(self) -> &'static str {
-            paste!{
-                match self {
-                    $(Self(Self::[<$ship_names:upper _ID>]) => stringify!($ship_names),)*
-                    _ => unreachable!("Impossible ShipTemplateId encountered: `{self:?}`"),
-                }
-            }
-        }
-    };
-
-    (make_id2template; $($ship_names:ident)*) => {
-        pub fn to_template(self) -> &'static ShipTemplate {
-            paste!{
-                match self {
-                    $(Self(Self::[<$ship_names:upper _ID>]) => {
-                        static ___STORE: ::std::sync::LazyLock<ShipTemplate> = ::std::sync::LazyLock::new(ShipTemplate::$ship_names);
-                        &___STORE
-                    },)*
-                    _ => unreachable!("Impossible ShipTemplateId encountered: `{self:?}`"),
-                }
-            }
-        }
-    };
-
-    (count;) => (0usize);
-    (count; $x:tt $($xs:tt)* ) => (1usize + make_ship_template_ids!(count; $($xs)*));
-
-    (make_all_ships; $($ship_names:ident)*) => {
-        pub const fn all_ships() -> &'static [ShipTemplateId; const { make_ship_template_ids!(count; $($ship_names)*) }] {
-            const {&[$(
-                Self::$ship_names()
-            ),*]}
+    (make_built_ins; $($ship_names:ident)*) => {
+        /// The ships compiled into the game, in the order they were declared
+        fn built_in_ships() -> Vec<(&'static str, ShipTemplate)> {
+            vec![$((stringify!($ship_names), ShipTemplate::$ship_names()),)*]
         }
     };
 
     ($($ship_names:ident)*) => {
         impl ShipTemplateId {
+            /// Placeholder for templates which were loaded from data and not yet given an id
+            pub(crate) const fn unassigned() -> Self {
+                Self(u32::MAX)
+            }
+
             make_ship_template_ids!(make_ids; $($ship_names)*);
-            make_ship_template_ids!(make_name2id; $($ship_names)*);
-            make_ship_template_ids!(make_id2name; $($ship_names)*);
-            make_ship_template_ids!(make_id2template; $($ship_names)*);
-            make_ship_template_ids!(make_all_ships; $($ship_names)*);
+            make_ship_template_ids!(make_built_ins; $($ship_names)*);
         }
     };
 }
@@ -202,6 +206,36 @@ make_ship_template_ids! {
     oland
 }
 
+impl ShipTemplateId {
+    pub fn from_name(name: &str) -> Option<ShipTemplateId> {
+        ShipTemplateRegistry::get()
+            .ids
+            .get(&name.to_lowercase())
+            .copied()
+    }
+
+    pub fn to_name(self) -> &'static str {
+        match ShipTemplateRegistry::get().names.get(&self) {
+            Some(name) => name,
+            None => unreachable!("Impossible ShipTemplateId encountered: `{self:?}`"),
+        }
+    }
+
+    /// Panics if no ship has this id. Ids are only sent between processes which
+    /// agree on the [ShipTemplateRegistry::fingerprint], so they're always registered
+    pub fn to_template(self) -> &'static ShipTemplate {
+        match ShipTemplateRegistry::get().templates.get(&self) {
+            Some(template) => template,
+            None => unreachable!("Impossible ShipTemplateId encountered: `{self:?}`"),
+        }
+    }
+
+    /// Every registered ship, built-in ships first
+    pub fn all_ships() -> &'static [ShipTemplateId] {
+        &ShipTemplateRegistry::get().all_ships
+    }
+}
+
 impl ShipTemplate {
     pub fn from_name(name: &str) -> Option<&'static Self> {
         ShipTemplateId::from_name(name).map(Self::from_id)
@@ -226,7 +260,7 @@ fn test_all_ship_template_ids_correct() {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ShipClass {
     Battleship,
     CruiserHeavy,
@@ -234,9 +268,52 @@ pub enum ShipClass {
     Destroyer,
 }
 
+impl ShipClass {
+    /// The range at which lookouts on ships of this class spot incoming torpedoes
+    pub fn default_torpedo_detection(self) -> f32 {
+        match self {
+            ShipClass::Battleship => 2_000.,
+            ShipClass::CruiserHeavy | ShipClass::CruiserLight => 2_500.,
+            ShipClass::Destroyer => 1_200.,
+        }
+    }
+
+    /// The consumables every ship of this class has, which templates can add to or replace
+    pub fn default_consumables(self) -> Consumables {
+        let spotter_plane = SpotterPlane {
+            action_time: Duration::from_secs(60),
+            range_bonus: 0.2,
+            spotting_range: 5_000.,
+            cooldown: Duration::from_secs(120),
+            charges: 3,
+        };
+        let repair_party = |heal_per_sec| RepairParty {
+            action_time: Duration::from_secs(20),
+            heal_per_sec,
+            cooldown: Duration::from_secs(80),
+            charges: 3,
+        };
+        match self {
+            ShipClass::Destroyer => Consumables::new().with_smoke(Smoke {
+                action_time: Duration::from_secs(10),
+                dissapation: Duration::from_secs(40),
+                radius: 450.,
+                cooldown: Duration::from_secs(60),
+                charges: 3,
+            }),
+            ShipClass::Battleship => Consumables::new()
+                .with_spotter_plane(spotter_plane)
+                .with_repair_party(repair_party(300.)),
+            ShipClass::CruiserHeavy | ShipClass::CruiserLight => Consumables::new()
+                .with_spotter_plane(spotter_plane)
+                .with_repair_party(repair_party(150.)),
+        }
+    }
+}
+
 /// * https://naval-encyclopedia.com/ww2
 /// * https://archive.org/details/ship-design-drawings
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Hull {
     /// Overall length (o/a or "length overall")
     pub length: f32,
@@ -246,8 +323,18 @@ pub struct Hull {
     pub freeboard: f32,
     /// Height of the hull below the water
     pub draft: f32,
+    /// Thickness of the main armor belt in mm, taken as the armor of every face of the hull
+    #[serde(default)]
+    pub armor: f32,
 }
 
+/// The share of the hull's width taken up by the citadel, centered on the keel
+const CITADEL_WIDTH_FRACTION: f32 = 0.6;
+/// How far above the waterline the citadel reaches, as a fraction of the freeboard
+const CITADEL_HEIGHT_FRACTION: f32 = 0.3;
+/// How far below the waterline the citadel reaches, as a fraction of the draft
+const CITADEL_DEPTH_FRACTION: f32 = 0.8;
+
 impl Hull {
     /// Returns the bounds of this hull, centered at the origin
     pub fn to_bounds(self) -> (Vec3, Vec3) {
@@ -255,9 +342,179 @@ impl Hull {
         let max = vec3(0.5 * self.length, 0.5 * self.width, self.freeboard);
         (min, max)
     }
+
+    /// Returns the bounds of the citadel, the box of machinery and magazines
+    /// sitting low in the middle of the hull, along the length of [HullSection::Citadel]
+    pub fn citadel_bounds(self) -> (Vec3, Vec3) {
+        let half_width = 0.5 * CITADEL_WIDTH_FRACTION * self.width;
+        let min = vec3(
+            (HullSection::Aft.end_frac() - 0.5) * self.length,
+            -half_width,
+            -CITADEL_DEPTH_FRACTION * self.draft,
+        );
+        let max = vec3(
+            (HullSection::Citadel.end_frac() - 0.5) * self.length,
+            half_width,
+            CITADEL_HEIGHT_FRACTION * self.freeboard,
+        );
+        (min, max)
+    }
+
+    /// The corners of [Hull::to_bounds] seen from above, going counterclockwise
+    pub fn to_outline(self) -> Vec<Vec2> {
+        let (min, max) = self.to_bounds();
+        vec![
+            vec2(min.x, min.y),
+            vec2(max.x, min.y),
+            vec2(max.x, max.y),
+            vec2(min.x, max.y),
+        ]
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// A length-wise section of the hull, which tracks its own damage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HullSection {
+    Stern,
+    Aft,
+    Citadel,
+    Fore,
+    Bow,
+}
+
+impl HullSection {
+    pub const COUNT: usize = 5;
+    /// From back to front
+    pub const ALL: [HullSection; Self::COUNT] = [
+        HullSection::Stern,
+        HullSection::Aft,
+        HullSection::Citadel,
+        HullSection::Fore,
+        HullSection::Bow,
+    ];
+
+    /// Where this section ends, as a fraction of the hull's length from the back
+    fn end_frac(self) -> f32 {
+        match self {
+            HullSection::Stern => 0.15,
+            HullSection::Aft => 0.35,
+            HullSection::Citadel => 0.65,
+            HullSection::Fore => 0.85,
+            HullSection::Bow => 1.,
+        }
+    }
+
+    /// The fraction of the ship's `max_health` held by this section
+    pub fn health_share(self) -> f64 {
+        match self {
+            HullSection::Stern | HullSection::Bow => 0.1,
+            HullSection::Aft | HullSection::Fore => 0.2,
+            HullSection::Citadel => 0.4,
+        }
+    }
+
+    /// Whether the ship is destroyed once this section has no health left
+    pub fn is_critical(self) -> bool {
+        self == HullSection::Citadel
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HullSection::Stern => "Stern",
+            HullSection::Aft => "Aft",
+            HullSection::Citadel => "Citadel",
+            HullSection::Fore => "Fore",
+            HullSection::Bow => "Bow",
+        }
+    }
+
+    fn idx(self) -> usize {
+        self as usize
+    }
+}
+
+impl Hull {
+    /// The section containing a point in the local space of the hull.
+    /// Points beyond either end are in the nearest end's section
+    pub fn section_at(self, pos: Vec2) -> HullSection {
+        let frac = pos.x / self.length + 0.5;
+        HullSection::ALL
+            .into_iter()
+            .find(|section| frac < section.end_frac())
+            .unwrap_or(HullSection::Bow)
+    }
+}
+
+/// The health left in each of a ship's [HullSection]s
+///
+/// Damage to a section with no health left spills over onto the [HullSection::Citadel],
+/// so a ship can always be sunk by hitting it anywhere
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SectionHealth([f64; HullSection::COUNT]);
+
+impl SectionHealth {
+    /// Every section at full health, splitting `max_health` by [HullSection::health_share]
+    pub fn full(max_health: f64) -> Self {
+        Self(HullSection::ALL.map(|section| max_health * section.health_share()))
+    }
+
+    pub fn get(&self, section: HullSection) -> f64 {
+        self.0[section.idx()]
+    }
+
+    /// The health left across every section, or none once [Self::is_destroyed],
+    /// even if some sections still have health left
+    pub fn total(&self) -> f64 {
+        if self.is_destroyed() {
+            return 0.;
+        }
+        self.0.iter().sum()
+    }
+
+    /// Deals `damage` to `section`, spilling over onto the citadel once it's used up.
+    /// Returns how much was dealt, which is less than `damage` once the citadel is used up too
+    pub fn damage(&mut self, section: HullSection, damage: f64) -> f64 {
+        let health = &mut self.0[section.idx()];
+        let dealt = damage.min(*health).max(0.);
+        *health -= dealt;
+        let spilled = damage - dealt;
+        if spilled > 0. && section != HullSection::Citadel {
+            return dealt + self.damage(HullSection::Citadel, spilled);
+        }
+        dealt
+    }
+
+    /// Restores up to `amount` of `section`'s health, never past its share of `max_health`.
+    /// Returns how much was restored
+    pub fn repair(&mut self, section: HullSection, amount: f64, max_health: f64) -> f64 {
+        let health = &mut self.0[section.idx()];
+        let restored = amount
+            .min(max_health * section.health_share() - *health)
+            .max(0.);
+        *health += restored;
+        restored
+    }
+
+    /// Whether a critical section has no health left
+    pub fn is_destroyed(&self) -> bool {
+        HullSection::ALL
+            .into_iter()
+            .any(|section| section.is_critical() && self.get(section) <= 0.)
+    }
+
+    /// The fraction of each section's health left, in the order of [HullSection::ALL].
+    /// Every section reads empty once [Self::is_destroyed], like [Self::total]
+    pub fn fractions(&self, max_health: f64) -> [f32; HullSection::COUNT] {
+        if self.is_destroyed() {
+            return [0.; HullSection::COUNT];
+        }
+        HullSection::ALL.map(|section| {
+            (self.get(section) / (max_health * section.health_share())).clamp(0., 1.) as f32
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum HullLocationAxis {
     Centered,
     /// Distance from the back of right of the ship,
@@ -282,7 +539,7 @@ impl HullLocationAxis {
 }
 
 /// The 2d position of an item located on a ship's hull
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct HullLocation {
     /// Along the length of the ship, from back to front
     pub l: HullLocationAxis,
@@ -323,7 +580,7 @@ impl HullLocation {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct AngleRange {
     from: Vec2,
     to: Vec2,
@@ -410,7 +667,9 @@ mod tests {
     use glam::{Vec2, vec2};
     use rand::{Rng, rng};
 
-    use crate::ship_template::AngleRange;
+    use crate::ship_template::{
+        AngleRange, HullSection, SectionHealth, ShipClass, ShipTemplate, ShipTemplateId,
+    };
 
     fn random_normalized_vector(rng: &mut impl Rng) -> Vec2 {
         loop {
@@ -467,9 +726,170 @@ mod tests {
             range.end_dir()
         ));
     }
+
+    #[test]
+    fn test_built_in_ships_use_class_defaults() {
+        for &id in ShipTemplateId::all_ships() {
+            let template = id.to_template();
+            let class = template.ship_class;
+            assert_eq!(
+                template.torpedo_detection,
+                class.default_torpedo_detection(),
+                "`{}`",
+                id.to_name()
+            );
+            assert_eq!(
+                template.consumables.smoke().is_some(),
+                class == ShipClass::Destroyer,
+                "`{}`",
+                id.to_name()
+            );
+        }
+        assert!(ShipClass::Destroyer.default_consumables().smoke().is_some());
+        assert!(
+            ShipClass::Battleship
+                .default_consumables()
+                .smoke()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_turrets_cant_fire_through_hull() {
+        let bismarck = ShipTemplateId::bismarck().to_template();
+        let [rear, _, forward, ..] = &bismarck.turret_instances[..] else {
+            panic!("Bismarck should have at least 3 turrets");
+        };
+        assert!(forward.can_fire_towards(Vec2::X));
+        assert!(!forward.can_fire_towards(Vec2::NEG_X));
+        assert!(rear.can_fire_towards(Vec2::NEG_X));
+        assert!(!rear.can_fire_towards(Vec2::X));
+    }
+
+    #[test]
+    fn test_rectangular_hull_outline_matches_bounds() {
+        let mut template = ShipTemplate::oland();
+        let (min, max) = template.hull.to_bounds();
+        let points = [
+            vec2(0., 0.),
+            vec2(max.x - 0.1, max.y - 0.1),
+            vec2(min.x + 0.1, min.y + 0.1),
+            vec2(max.x + 0.1, 0.),
+            vec2(0., min.y - 0.1),
+        ];
+        let from_bounds = points.map(|p| template.hull_contains(p));
+        assert_eq!(from_bounds, [true, true, true, false, false]);
+
+        template.hull_outline = Some(template.hull.to_outline());
+        template.validate().unwrap();
+        assert_eq!(points.map(|p| template.hull_contains(p)), from_bounds);
+    }
+
+    #[test]
+    fn test_tapered_hull_misses_beside_bow() {
+        let mut template = ShipTemplate::oland();
+        let (min, max) = template.hull.to_bounds();
+        // Narrows to a point over the front 20m
+        template.hull_outline = Some(vec![
+            vec2(min.x, min.y),
+            vec2(max.x - 20., min.y),
+            vec2(max.x, 0.),
+            vec2(max.x - 20., max.y),
+            vec2(min.x, max.y),
+        ]);
+        template.validate().unwrap();
+
+        let beside_bow = vec2(max.x - 5., 0.8 * max.y);
+        let on_bow = vec2(max.x - 5., 0.);
+        let amidships = vec2(0., 0.8 * max.y);
+        assert!(!template.hull_contains(beside_bow));
+        assert!(template.hull_contains(on_bow));
+        assert!(template.hull_contains(amidships));
+
+        template.hull_outline = None;
+        assert!(template.hull_contains(beside_bow));
+    }
+
+    #[test]
+    fn test_hull_sections_run_from_stern_to_bow() {
+        let hull = ShipTemplateId::bismarck().to_template().hull;
+        let half = 0.5 * hull.length;
+        assert_eq!(hull.section_at(vec2(-half, 0.)), HullSection::Stern);
+        assert_eq!(hull.section_at(vec2(0., 0.)), HullSection::Citadel);
+        assert_eq!(hull.section_at(vec2(0.5 * half, 0.)), HullSection::Fore);
+        assert_eq!(hull.section_at(vec2(half, 0.)), HullSection::Bow);
+        assert_eq!(hull.section_at(vec2(-2. * half, 0.)), HullSection::Stern);
+        assert_eq!(hull.section_at(vec2(2. * half, 0.)), HullSection::Bow);
+    }
+
+    #[test]
+    fn test_section_health_sums_to_total() {
+        let max_health = ShipTemplateId::bismarck().to_template().max_health;
+        let mut health = SectionHealth::full(max_health);
+        assert!((health.total() - max_health).abs() < 1e-6);
+
+        let damages = [
+            (HullSection::Bow, 1_000.),
+            (HullSection::Aft, 2_500.),
+            (HullSection::Bow, 50_000.),
+            (HullSection::Stern, 300.),
+        ];
+        let mut dealt = 0.;
+        for (section, damage) in damages {
+            health.damage(section, damage);
+            dealt += damage;
+            let sections = HullSection::ALL.map(|section| health.get(section));
+            if health.is_destroyed() {
+                assert_eq!(health.total(), 0.);
+            } else {
+                assert!((sections.iter().sum::<f64>() - health.total()).abs() < 1e-6);
+                assert!((health.total() - (max_health - dealt)).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_destroying_citadel_destroys_ship() {
+        let max_health = 10_000.;
+        let mut health = SectionHealth::full(max_health);
+
+        // The bow is used up, but the ship is still afloat
+        health.damage(HullSection::Bow, 0.1 * max_health);
+        assert_eq!(health.get(HullSection::Bow), 0.);
+        assert!(!health.is_destroyed());
+
+        // Further hits on the bow spill over onto the citadel
+        health.damage(HullSection::Bow, 0.2 * max_health);
+        assert!((health.get(HullSection::Citadel) - 0.2 * max_health).abs() < 1e-6);
+        assert!(!health.is_destroyed());
+
+        // Only the citadel's remaining health is dealt, the rest is overkill
+        let citadel = health.get(HullSection::Citadel);
+        assert_eq!(
+            health.damage(HullSection::Citadel, 0.3 * max_health),
+            citadel
+        );
+        assert!(health.is_destroyed());
+        // Sunk with health left in the other sections, which no longer counts
+        assert!(health.get(HullSection::Fore) > 0.);
+        assert_eq!(health.total(), 0.);
+        assert_eq!(health.fractions(max_health), [0.; HullSection::COUNT]);
+    }
+
+    #[test]
+    fn test_repair_stops_at_section_max() {
+        let max_health = 10_000.;
+        let mut health = SectionHealth::full(max_health);
+        health.damage(HullSection::Fore, 500.);
+
+        assert_eq!(health.repair(HullSection::Fore, 300., max_health), 300.);
+        assert_eq!(health.repair(HullSection::Fore, 300., max_health), 200.);
+        assert_eq!(health.repair(HullSection::Fore, 300., max_health), 0.);
+        assert!((health.total() - max_health).abs() < 1e-6);
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Dispersion {
     /// Vertical radius of the dispersion elliptic cone.
     /// The ellipse is drawn at 1 km
@@ -477,14 +897,25 @@ pub struct Dispersion {
     /// Horizontal radius of the dispersion elliptic cone.
     /// The ellipse is drawn at 1 km
     pub horizontal: f32,
+    /// How many standard deviations out the edge of the ellipse is.
+    /// Shells never land outside it, and higher values cluster them closer to its center
     pub sigma: f32,
 }
 
+/// How a turret defends against enemy aircraft, which it does
+/// regardless of what its guns are aiming at
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AntiAirProfile {
+    /// The damage dealt every second to each enemy aircraft in range
+    pub aa_dps: f32,
+    pub aa_range: f32,
+}
+
 slotmap::new_key_type! {
     pub struct TurretTemplateId;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TargetingMode {
     /// Only fire at the fire target
     Primary,
@@ -493,13 +924,16 @@ pub enum TargetingMode {
     Secondary,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub enum BulletType {
     /// Armor piercing
+    #[default]
     AP,
+    /// High explosive, which deals less damage but can set ships on fire
+    HE,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TurretTemplate {
     pub reload_secs: f32,
     pub damage: f64,
@@ -507,6 +941,23 @@ pub struct TurretTemplate {
     /// NOTE: a high max_range will not allow a shot to be made past
     /// the 45 degree shell distance at the given muzzle velocity
     pub max_range: f32,
+    /// The lowest angle above the horizon the barrels can be set to, in radians
+    #[serde(default)]
+    pub min_elevation: f32,
+    /// The highest angle above the horizon the barrels can be set to, in radians
+    ///
+    /// Firing solutions which need a steeper shot than this are never taken,
+    /// which can cap the turret's range well below `max_range`
+    #[serde(default = "TurretTemplate::default_max_elevation")]
+    pub max_elevation: f32,
+    #[serde(default = "TurretTemplate::default_caliber")]
+    pub caliber: Caliber,
+    #[serde(default)]
+    pub bullet_type: BulletType,
+    /// The chance of each HE shell setting the ship it hits on fire,
+    /// scaled by its caliber. Unused by other bullet types
+    #[serde(default)]
+    pub fire_chance: f32,
     /// The dispersion per km of shell distance
     pub dispersion: Dispersion,
     pub turn_rate: AngularSpeed,
@@ -515,10 +966,30 @@ pub struct TurretTemplate {
     /// The distance between adjacent barrels on the turret
     pub barrel_spacing: f32,
     pub targeting_mode: TargetingMode,
+    /// `None` if the turret can't fire at aircraft
+    #[serde(default)]
+    pub aa: Option<AntiAirProfile>,
+}
+
+impl TurretTemplate {
+    fn default_max_elevation() -> f32 {
+        std::f32::consts::FRAC_PI_2
+    }
+
+    fn default_caliber() -> Caliber {
+        Caliber::from_mm(300.)
+    }
+
+    /// Whether the barrels can be elevated to fire a shell at `elevation` radians
+    pub fn can_elevate_to(&self, elevation: f32) -> bool {
+        (self.min_elevation..=self.max_elevation).contains(&elevation)
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TurretInstance {
+    /// Not stored in data files, see [ShipTemplate::from_ron]
+    #[serde(skip, default = "ShipTemplateId::unassigned")]
     pub ship_template: ShipTemplateId,
     pub template: TurretTemplateId,
     pub location_on_ship: HullLocation,
@@ -556,6 +1027,16 @@ impl TurretInstance {
         }
     }
 
+    /// Whether this turret may fire towards `dir`, relative to ship-space
+    ///
+    /// Every way of aiming a turret on the match has to go through this,
+    /// so that turrets never fire through their own ship's hull
+    pub fn can_fire_towards(&self, dir: Vec2) -> bool {
+        self.firing_angle
+            .or(self.movement_angle)
+            .is_none_or(|valid_angle| valid_angle.contains(dir))
+    }
+
     pub fn turret_template(&self) -> &'static TurretTemplate {
         &self.ship_template.to_template().turret_templates[self.template]
     }
@@ -569,7 +1050,7 @@ impl TurretInstance {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Torpedoes {
     pub reload: Duration,
     pub volleys: usize,
@@ -592,8 +1073,9 @@ pub mod consumables {
     use std::time::Duration;
 
     use paste::paste;
+    use serde::{Deserialize, Serialize};
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Smoke {
         pub action_time: Duration,
         pub dissapation: Duration,
@@ -603,9 +1085,34 @@ pub mod consumables {
         pub charges: usize,
     }
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct SpotterPlane {
         pub action_time: Duration,
+        /// How much further the ship's guns reach while the plane is up,
+        /// as a fraction of their base range
+        pub range_bonus: f32,
+        /// Enemies within this range of the ship are detected while the plane is up,
+        /// unless they're hidden by smoke
+        pub spotting_range: f32,
+        pub cooldown: Duration,
+        /// Zero if infinite charges
+        pub charges: usize,
+    }
+
+    /// Restores some of the damage the ship has taken while it works,
+    /// though never more than the match allows to be repaired
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct RepairParty {
+        pub action_time: Duration,
+        pub heal_per_sec: f64,
+        pub cooldown: Duration,
+        /// Zero if infinite charges
+        pub charges: usize,
+    }
+
+    /// Instantly finishes reloading every torpedo volley
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct TorpReloadBooster {
         pub cooldown: Duration,
         /// Zero if infinite charges
         pub charges: usize,
@@ -615,7 +1122,7 @@ pub mod consumables {
         ($($consumable_type:ident)*) => {
             paste! {
                 /// Contains information about the base consumables a ship has access to
-                #[derive(Debug, Clone)]
+                #[derive(Debug, Clone, Serialize, Deserialize)]
                 pub struct Consumables {
                     $([<$consumable_type:snake>] : Option<$consumable_type>),*
                 }
@@ -645,5 +1152,5 @@ pub mod consumables {
         };
     }
 
-    make_consumables_struct!(Smoke SpotterPlane);
+    make_consumables_struct!(Smoke SpotterPlane TorpReloadBooster RepairParty);
 }
@@ -0,0 +1,279 @@
+//! The runtime mapping from [ShipTemplateId]s to [ShipTemplate]s
+//!
+//! Built-in ships keep the ids assigned to them at compile time,
+//! while modded ships loaded from data files are given ids derived from their names
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::ship_template::*;
+
+/// The directory searched for modded ships when no other is configured
+pub const DEFAULT_SHIP_MODS_DIR: &str = "ship_mods";
+
+static REGISTRY: OnceLock<ShipTemplateRegistry> = OnceLock::new();
+
+#[derive(Debug)]
+pub struct ShipTemplateRegistry {
+    pub(super) templates: HashMap<ShipTemplateId, ShipTemplate>,
+    pub(super) names: HashMap<ShipTemplateId, String>,
+    pub(super) ids: HashMap<String, ShipTemplateId>,
+    pub(super) all_ships: Vec<ShipTemplateId>,
+    mods_dir: Option<PathBuf>,
+}
+
+impl ShipTemplateRegistry {
+    /// Initializes the global registry with the built-in ships and
+    /// every `<ship name>.ron` file in `mods_dir`
+    ///
+    /// A file which fails to load is skipped and its error returned,
+    /// without stopping the other files from loading.
+    /// Must be called before any template is accessed,
+    /// otherwise the registry will only contain the built-in ships
+    pub fn init(mods_dir: impl Into<PathBuf>) -> Vec<anyhow::Error> {
+        let mods_dir = mods_dir.into();
+        let mut registry = Self::built_in();
+        let errors = registry.load_mods(&mods_dir);
+        registry.mods_dir = Some(mods_dir);
+
+        if REGISTRY.set(registry).is_err() {
+            return vec![anyhow!(
+                "Ship template registry was already initialized, modded ships weren't loaded"
+            )];
+        }
+        errors
+    }
+
+    pub fn get() -> &'static Self {
+        REGISTRY.get_or_init(Self::built_in)
+    }
+
+    /// A hash of every registered ship's name and template, which differs between processes that
+    /// loaded different mods. Exchanged in the lobby handshake,
+    /// since a client sent a ship it doesn't have couldn't spawn it
+    pub fn fingerprint(&self) -> u64 {
+        // FNV-1a, so that it's the same in every build
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for id in &self.all_ships {
+            let bytes = self.names[id]
+                .bytes()
+                .chain(self.templates[id].to_ron().into_bytes());
+            for byte in bytes.chain([0]) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        }
+        hash
+    }
+
+    /// The directory modded ships were loaded from,
+    /// or `None` if the registry only contains built-in ships
+    pub fn mods_dir(&self) -> Option<&Path> {
+        self.mods_dir.as_deref()
+    }
+
+    fn built_in() -> Self {
+        let mut registry = Self {
+            templates: HashMap::new(),
+            names: HashMap::new(),
+            ids: HashMap::new(),
+            all_ships: vec![],
+            mods_dir: None,
+        };
+        for (name, template) in ShipTemplateId::built_in_ships() {
+            registry.register(name.to_string(), template);
+        }
+        registry
+    }
+
+    fn register(&mut self, name: String, template: ShipTemplate) {
+        let id = template.id;
+        self.all_ships.push(id);
+        self.ids.insert(name.clone(), id);
+        self.names.insert(id, name);
+        self.templates.insert(id, template);
+    }
+
+    /// Loads files in a sorted order, so that the order of `all_ships`
+    /// is the same for every process loading the same directory
+    fn load_mods(&mut self, dir: &Path) -> Vec<anyhow::Error> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return vec![],
+            Err(err) => {
+                return vec![
+                    anyhow::Error::new(err)
+                        .context(format!("Failed to read mods directory `{}`", dir.display())),
+                ];
+            }
+        };
+
+        let mut errors = vec![];
+        let mut paths = vec![];
+        for entry in entries {
+            match entry {
+                Ok(entry) => paths.push(entry.path()),
+                Err(err) => errors.push(err.into()),
+            }
+        }
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "ron"));
+        paths.sort();
+
+        for path in paths {
+            if let Err(err) = self.load_mod(&path) {
+                errors.push(err.context(format!("Skipped modded ship `{}`", path.display())));
+            }
+        }
+        errors
+    }
+
+    fn load_mod(&mut self, path: &Path) -> anyhow::Result<()> {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .context("File name isn't valid UTF-8")?
+            .to_lowercase();
+        let id = modded_ship_id(&name);
+        if self.ids.contains_key(&name) {
+            return Err(anyhow!("A ship named `{name}` already exists"));
+        }
+        if let Some(other) = self.names.get(&id) {
+            return Err(anyhow!(
+                "Ship id collides with the ship `{other}`, rename the file"
+            ));
+        }
+
+        let template = ShipTemplate::load_file(id, path)?;
+        self.register(name, template);
+        Ok(())
+    }
+}
+
+/// Derives a modded ship's id from its name (FNV-1a),
+/// so every process loading the same files agrees on the ids regardless of load order
+fn modded_ship_id(name: &str) -> ShipTemplateId {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in name.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    // The high bit keeps modded ids clear of the built-in ones
+    let id = ShipTemplateId(hash | 0x8000_0000);
+    if id == ShipTemplateId::unassigned() {
+        ShipTemplateId(u32::MAX - 1)
+    } else {
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::ship_template::{
+        ShipTemplateId,
+        registry::{ShipTemplateRegistry, modded_ship_id},
+    };
+
+    fn temp_mods_dir(test_name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("wrts_ship_mods_{test_name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_built_in_ships_round_trip_through_registry() {
+        let registry = ShipTemplateRegistry::built_in();
+        assert_eq!(registry.all_ships.len(), registry.templates.len());
+        for (name, template) in ShipTemplateId::built_in_ships() {
+            let id = registry.ids[name];
+            assert_eq!(id, template.id);
+            assert_eq!(registry.names[&id], name);
+            assert_eq!(registry.templates[&id].id, id);
+
+            assert_eq!(ShipTemplateId::from_name(name), Some(id));
+            assert_eq!(ShipTemplateId::from_name(&name.to_uppercase()), Some(id));
+            assert_eq!(id.to_name(), name);
+            assert_eq!(id.to_template().to_ron(), template.to_ron());
+        }
+    }
+
+    /// Built-in ids are sent over the network in `Match2Client::SpawnShip`,
+    /// so they shouldn't change without a good reason
+    #[test]
+    fn test_built_in_ids_are_stable() {
+        let ids = ShipTemplateId::all_ships()
+            .iter()
+            .map(|id| (id.to_name(), id.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                ("bismarck", 4),
+                ("hipper", 3),
+                ("kiev", 2),
+                ("nagato", 1),
+                ("oland", 0)
+            ]
+        );
+        assert!(ids.iter().all(|&(_, id)| id & 0x8000_0000 == 0));
+    }
+
+    #[test]
+    fn test_load_mods() {
+        let dir = temp_mods_dir("load_mods");
+        let oland_ron = ShipTemplateId::oland().to_template().to_ron();
+        std::fs::write(dir.join("oland_mk2.ron"), &oland_ron).unwrap();
+        // Collides with a built-in
+        std::fs::write(dir.join("Oland.ron"), &oland_ron).unwrap();
+        std::fs::write(dir.join("broken.ron"), "not a ship").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let mut registry = ShipTemplateRegistry::built_in();
+        let errors = registry.load_mods(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        let id = registry.ids["oland_mk2"];
+        assert_eq!(id, modded_ship_id("oland_mk2"));
+        assert_eq!(registry.templates[&id].id, id);
+        assert_eq!(*registry.all_ships.last().unwrap(), id);
+        assert_eq!(registry.ids["oland"], ShipTemplateId::oland());
+    }
+
+    #[test]
+    fn test_fingerprint_changes_with_mods() {
+        let dir = temp_mods_dir("fingerprint");
+        let oland_ron = ShipTemplateId::oland().to_template().to_ron();
+        std::fs::write(dir.join("oland_mk2.ron"), &oland_ron).unwrap();
+
+        let built_in = ShipTemplateRegistry::built_in();
+        let mut modded = ShipTemplateRegistry::built_in();
+        let errors = modded.load_mods(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(
+            built_in.fingerprint(),
+            ShipTemplateRegistry::built_in().fingerprint()
+        );
+        assert_ne!(built_in.fingerprint(), modded.fingerprint());
+    }
+
+    #[test]
+    fn test_missing_mods_dir_is_empty() {
+        let mut registry = ShipTemplateRegistry::built_in();
+        let errors =
+            registry.load_mods(&std::env::temp_dir().join("wrts_ship_mods_does_not_exist"));
+        assert!(errors.is_empty());
+        assert_eq!(registry.all_ships, ShipTemplateId::all_ships());
+    }
+}
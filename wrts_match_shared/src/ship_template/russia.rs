@@ -1,17 +1,23 @@
 use std::f32::consts::PI;
 
-use crate::ship_template::{consumables::Smoke, *};
+use crate::ship_template::*;
 
 impl ShipTemplate {
     /// https://en.wikipedia.org/wiki/Kiev-class_destroyer
     pub(super) fn kiev() -> ShipTemplate {
         let ship_template = ShipTemplateId::kiev();
+        let ship_class = ShipClass::Destroyer;
         let mut turret_templates = SlotMap::default();
         let main_battery = turret_templates.insert(TurretTemplate {
             reload_secs: 5.,
             damage: 200.,
             muzzle_vel: 850.,
             max_range: 11_140.,
+            min_elevation: -5_f32.to_radians(),
+            max_elevation: 45_f32.to_radians(),
+            caliber: Caliber::from_mm(130.),
+            bullet_type: BulletType::HE,
+            fire_chance: 0.06,
             dispersion: Dispersion {
                 vertical: 3.5,
                 horizontal: 8.8,
@@ -22,12 +28,18 @@ impl ShipTemplate {
             // Estimated distance
             barrel_spacing: 1.,
             targeting_mode: TargetingMode::Primary,
+            aa: None,
         });
         let seccondary_battery_85mm = turret_templates.insert(TurretTemplate {
             reload_secs: 4.,
             damage: 130.,
             muzzle_vel: 792.,
             max_range: 5_000.,
+            min_elevation: -5_f32.to_radians(),
+            max_elevation: 85_f32.to_radians(),
+            caliber: Caliber::from_mm(85.),
+            bullet_type: BulletType::HE,
+            fire_chance: 0.05,
             dispersion: Dispersion {
                 vertical: 3.5,
                 horizontal: 15.,
@@ -38,24 +50,32 @@ impl ShipTemplate {
             // Estimated distance
             barrel_spacing: 1.,
             targeting_mode: TargetingMode::Secondary,
+            aa: Some(AntiAirProfile {
+                aa_dps: 35.,
+                aa_range: 4_500.,
+            }),
         });
 
         ShipTemplate {
             id: ship_template,
-            ship_class: ShipClass::Destroyer,
+            ship_class,
             hull: Hull {
                 length: 127.8,
                 width: 11.7,
                 // Estimated distance
                 freeboard: 5.,
                 draft: 4.2,
+                // Unarmored
+                armor: 0.,
             },
+            hull_outline: None,
             max_speed: Speed::from_kts(42.5 * SHIP_SPEED_SCALE),
             engine_acceleration: Speed::from_kts(8. * SHIP_SPEED_SCALE),
             turning_rate: AngularSpeed::from_radps(0.4),
             max_health: 17_500.,
             detection: 8_540.,
             detection_when_firing_through_smoke: 3_400.,
+            torpedo_detection: ship_class.default_torpedo_detection(),
             turret_templates,
             turret_instances: vec![
                 // Main battery
@@ -107,13 +127,7 @@ impl ShipTemplate {
                 range: 7_000.,
                 port_firing_angle: AngleRange::from_angles_deg(40., 140.),
             }),
-            consumables: Consumables::new().with_smoke(Smoke {
-                action_time: Duration::from_secs(10),
-                dissapation: Duration::from_secs(40),
-                radius: 450.,
-                cooldown: Duration::from_secs(60),
-                charges: 3,
-            }),
+            consumables: ship_class.default_consumables(),
         }
     }
 }
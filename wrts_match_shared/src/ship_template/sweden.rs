@@ -1,17 +1,23 @@
 use std::f32::consts::PI;
 
-use crate::ship_template::*;
+use crate::ship_template::{consumables::TorpReloadBooster, *};
 
 impl ShipTemplate {
     /// https://en.wikipedia.org/wiki/HSwMS_%C3%96land_(J16)
     pub(super) fn oland() -> ShipTemplate {
         let ship_template = ShipTemplateId::oland();
+        let ship_class = ShipClass::Destroyer;
         let mut turret_templates = SlotMap::default();
         let main_battery = turret_templates.insert(TurretTemplate {
             reload_secs: 2.3,
             damage: 150.,
             muzzle_vel: 850.,
             max_range: 10_100.,
+            min_elevation: -10_f32.to_radians(),
+            max_elevation: 85_f32.to_radians(),
+            caliber: Caliber::from_mm(120.),
+            bullet_type: BulletType::HE,
+            fire_chance: 0.06,
             dispersion: Dispersion {
                 vertical: 3.5,
                 horizontal: 9.,
@@ -22,23 +28,28 @@ impl ShipTemplate {
             // Estimated distance
             barrel_spacing: 1.,
             targeting_mode: TargetingMode::Primary,
+            aa: None,
         });
         ShipTemplate {
             id: ship_template,
-            ship_class: ShipClass::Destroyer,
+            ship_class,
             hull: Hull {
                 length: 112.,
                 width: 11.2,
                 // Estimated distance
                 freeboard: 4.,
                 draft: 3.4,
+                // Unarmored
+                armor: 0.,
             },
+            hull_outline: None,
             max_speed: Speed::from_kts(35. * SHIP_SPEED_SCALE),
             engine_acceleration: Speed::from_kts(5. * SHIP_SPEED_SCALE),
             turning_rate: AngularSpeed::from_radps(0.45),
             max_health: 14_100.,
             detection: 7_200.,
             detection_when_firing_through_smoke: 2_700.,
+            torpedo_detection: ship_class.default_torpedo_detection(),
             turret_templates,
             turret_instances: vec![
                 TurretInstance {
@@ -70,7 +81,12 @@ impl ShipTemplate {
                 range: 12_000.,
                 port_firing_angle: AngleRange::from_angles_deg(60., 120.),
             }),
-            consumables: Consumables::new(),
+            consumables: ship_class.default_consumables().with_torp_reload_booster(
+                TorpReloadBooster {
+                    cooldown: Duration::from_secs(120),
+                    charges: 2,
+                },
+            ),
         }
     }
 }
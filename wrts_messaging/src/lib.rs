@@ -1,20 +1,55 @@
 use std::{
     fmt::Display,
-    io::{self},
+    io::{self, Read, Write},
     pin::Pin,
     task::Poll,
     time::Duration,
 };
 
 use anyhow::{Result, anyhow};
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
 use glam::{Quat, Vec2, Vec3};
 use pin_project::pin_project;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
-use wrts_match_shared::ship_template::ShipTemplateId;
+use wrts_match_shared::{
+    formulas::ballistics::ShellArc,
+    ship_template::{BulletType, Caliber, HullSection, ShipTemplateId, ShipTemplateRegistry},
+};
 use wtransport::{RecvStream, SendStream};
 
 pub const DEFAULT_PORT: u16 = 4433;
 
+/// Exchanged during the lobby handshake, so that a client and server built from
+/// different versions fail fast rather than misinterpreting each other's messages.
+/// Bump whenever a message changes in a way older builds can't read,
+/// including any change to the built-in [ShipTemplateId]s
+pub const PROTOCOL_VERSION: u32 = 25;
+
+/// Checks the protocol version sent by the other end of a connection,
+/// erroring with a message fit to show the player if it's incompatible
+pub fn check_protocol_version(peer_version: u32) -> Result<()> {
+    if peer_version == PROTOCOL_VERSION {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Incompatible game versions: this game uses protocol version {PROTOCOL_VERSION}, \
+        but the other side uses version {peer_version}. \
+        Make sure the client and server are the same version"
+    ))
+}
+
+/// Checks the [ShipTemplateRegistry::fingerprint] sent by the other end of a connection,
+/// erroring with a message fit to show the player if it has different ships
+pub fn check_ship_templates(peer_fingerprint: u64) -> Result<()> {
+    if peer_fingerprint == ShipTemplateRegistry::get().fingerprint() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Incompatible ship mods: the client and server have different modded ships. \
+        Make sure both load the same ship mods"
+    ))
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct SharedEntityId(pub u64);
 
@@ -33,6 +68,18 @@ impl Display for ClientId {
     }
 }
 
+/// Identifies one of the lobby's running matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SharedMatchId(pub u64);
+
+/// A running match, as listed in [Lobby2Client::MatchList]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchListing {
+    pub id: SharedMatchId,
+    /// The clients playing in the match
+    pub clients: Vec<ClientId>,
+}
+
 /// Basic __immutable__ info associated with a client,
 /// established when first connecting
 ///
@@ -65,6 +112,47 @@ pub enum Client2Match {
     UseConsumableSmoke {
         ship: SharedEntityId,
     },
+    UseConsumableTorpReloadBooster {
+        ship: SharedEntityId,
+    },
+    UseConsumableSpotterPlane {
+        ship: SharedEntityId,
+    },
+    UseConsumableRepairParty {
+        ship: SharedEntityId,
+    },
+    SetShellArc {
+        id: SharedEntityId,
+        arc: ShellArc,
+    },
+    /// Clears the ship's waypoints, so that it stops where it is
+    /// and turns in place to face `heading` (radians, as from `Vec2::to_angle`)
+    SetHeading {
+        id: SharedEntityId,
+        heading: f32,
+    },
+    /// Stops deploying smoke before its action time ends.
+    /// Puffs already laid dissipate as usual, and the consumable starts recharging
+    CancelConsumableSmoke {
+        ship: SharedEntityId,
+    },
+    /// Drops the client out of the match, which ends once only one client hasn't surrendered
+    /// or been knocked out
+    Surrender,
+    /// Pauses or resumes the simulation, for debugging.
+    /// Ignored by matches which don't allow pausing
+    SetPaused {
+        paused: bool,
+    },
+    /// Sent by the lobby rather than the client, once a client which dropped out of
+    /// the match has reconnected. The match then redoes the handshake with it
+    /// and resends everything it can see
+    Rejoined,
+    /// Sent by the lobby rather than the client, when a client starts spectating the match.
+    /// Spectators see every ship, but can't control any
+    SpectatorJoined {
+        info: ClientSharedInfo,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -82,6 +170,53 @@ pub enum SmokeConsumableState {
     },
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum SpotterPlaneState {
+    Flying {
+        charges_unused: Option<u16>,
+        action_time_remaining: Duration,
+    },
+    Recharging {
+        charges_unused: Option<u16>,
+        recharge_time_remaining: Duration,
+    },
+    Recharged {
+        charges_unused: Option<u16>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum TorpReloadBoosterState {
+    Recharging {
+        charges_unused: Option<u16>,
+        recharge_time_remaining: Duration,
+    },
+    Recharged {
+        charges_unused: Option<u16>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DamageSource {
+    Shell,
+    Torpedo,
+    /// A fire started by an HE shell, reported once it goes out
+    Fire,
+    /// Colliding with another ship, which damages both
+    Ramming,
+}
+
+/// One entity's part of a [Match2Client::WorldSnapshot],
+/// where each field is only set if it changed since it was last sent
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntityUpdate {
+    pub id: SharedEntityId,
+    /// The position and rotation
+    pub trans: Option<(Vec3, Quat)>,
+    pub vel: Option<Vec2>,
+    pub turret_dirs: Option<Vec<f32>>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Match2Client {
     InitA {
@@ -89,9 +224,12 @@ pub enum Match2Client {
     },
     InitC {
         all_clients: Vec<ClientSharedInfo>,
+        /// The seed of all of the match's randomness, for reproducing it
+        seed: u64,
     },
     PrintMsg(String),
-    DestroyEntity(SharedEntityId),
+    /// Every entity despawned during one tick of the match
+    DestroyEntities(Vec<SharedEntityId>),
     /// FIXME? Don't send until the client
     /// should see the torp
     SpawnShip {
@@ -108,6 +246,8 @@ pub enum Match2Client {
         team: ClientId,
         owning_ship: SharedEntityId,
         damage: f64,
+        caliber: Caliber,
+        ty: BulletType,
         pos: Vec3,
         rot: Quat,
     },
@@ -126,42 +266,110 @@ pub enum Match2Client {
         pos: Vec2,
         radius: f32,
     },
+    /// An aircraft launched by `owning_ship`, which flies around it until it lands
+    /// or is shot down, and is then destroyed like any other entity
+    SpawnAircraft {
+        id: SharedEntityId,
+        team: ClientId,
+        owning_ship: SharedEntityId,
+        pos: Vec2,
+    },
     SetSmokeConsumableState {
         id: SharedEntityId,
         state: SmokeConsumableState,
     },
+    /// Only sent to the ship's owner
+    SetTorpReloadBoosterState {
+        id: SharedEntityId,
+        state: TorpReloadBoosterState,
+    },
+    /// Only sent to the ship's owner
+    SetSpotterPlaneState {
+        id: SharedEntityId,
+        state: SpotterPlaneState,
+    },
     SetReloadedTorps {
         id: SharedEntityId,
         ready_to_fire: usize,
         /// Remaining time until each volley is ready, in ascending order
         /// (the next volley to be ready is at index 0)
         still_reloading: Vec<Duration>,
+        /// How long a volley takes to reload from empty in this match,
+        /// which may differ from the template's reload
+        full_reload: Duration,
     },
-    SetTrans {
-        id: SharedEntityId,
-        pos: Vec3,
-        rot: Quat,
-    },
-    SetVelocity {
-        id: SharedEntityId,
-        vel: Vec2,
-    },
-    SetTurretDirs {
-        id: SharedEntityId,
-        turret_dirs: Vec<f32>,
+    /// The movement and turrets of every entity which changed this tick and
+    /// which the receiving client can see, batched into one message
+    WorldSnapshot {
+        entities: Vec<EntityUpdate>,
     },
     SetHealth {
         id: SharedEntityId,
         health: f64,
+        /// The fraction of each `HullSection`'s health left, in the order of `HullSection::ALL`
+        sections: [f32; HullSection::COUNT],
     },
     SetMoveOrder {
         id: SharedEntityId,
         waypoints: Vec<Vec2>,
     },
+    /// The ship reached the next waypoint of its move order,
+    /// so only the last `remaining` waypoints of the order last sent are left
+    WaypointReached {
+        id: SharedEntityId,
+        remaining: u32,
+    },
     SetDetection {
         id: SharedEntityId,
         currently_detected: bool,
     },
+    /// Only sent to clients which can see `target`
+    DamageDealt {
+        target: SharedEntityId,
+        attacker_team: ClientId,
+        /// `None` if the attacking ship was destroyed before the damage landed,
+        /// or the client can't see it
+        attacker: Option<SharedEntityId>,
+        source: DamageSource,
+        damage: f64,
+    },
+    /// Always sent after the `DamageDealt` for the killing blow,
+    /// and before the `DestroyEntities` including the ship
+    ShipDestroyed {
+        ship: SharedEntityId,
+        killer_team: ClientId,
+        /// `None` if the killing ship was destroyed before the damage landed,
+        /// or the client can't see it
+        killer: Option<SharedEntityId>,
+        source: DamageSource,
+    },
+    /// Only sent to the ship's owner, whenever every main battery turret
+    /// starts or stops having no valid target
+    SetGunsIdle {
+        id: SharedEntityId,
+        idle: bool,
+    },
+    /// Sent to every client whenever the simulation is paused or resumed.
+    /// While paused, nothing moves, reloads or fires, but orders are still accepted
+    MatchPaused {
+        paused: bool,
+    },
+    /// Sent to every client each second, and when rejoining. Once this runs out,
+    /// the match ends with whoever has the most ships left winning
+    MatchTime {
+        remaining: Duration,
+    },
+    /// Sent just before `MatchEnded`, with each player's damage dealt to enemy ships
+    /// and how many of them they sank, ordered by client
+    Scoreboard {
+        entries: Vec<(ClientId, f64, u32)>,
+    },
+    /// The last message of a match, after which the match closes
+    /// and clients return to the lobby
+    MatchEnded {
+        /// `None` if the match was a draw
+        winner: Option<ClientId>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -169,10 +377,30 @@ pub enum Client2Lobby {
     /// Handshake part B
     InitB {
         username: String,
+        /// Not sent by clients from before versioning, which are never compatible.
+        /// Their handshake fails to parse instead, since the wire encoding isn't self-describing
+        protocol_version: u32,
+        /// The client's [ShipTemplateRegistry::fingerprint]
+        ship_templates: u64,
     },
     SetReadyForMatch {
         is_ready: bool,
     },
+    /// Sent instead of `InitB` by a client whose connection dropped mid-match,
+    /// to take back its place in that match as `client_id`
+    RejoinMatch {
+        client_id: ClientId,
+        /// The `rejoin_token` from the [Lobby2Client::InitA] of the connection which dropped
+        rejoin_token: u64,
+        protocol_version: u32,
+        /// The client's [ShipTemplateRegistry::fingerprint]
+        ship_templates: u64,
+    },
+    /// Watches one of the matches from the latest [Lobby2Client::MatchList],
+    /// without playing in it
+    SpectateMatch {
+        match_id: SharedMatchId,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -180,6 +408,14 @@ pub enum Lobby2Client {
     /// Handshake part A
     InitA {
         client_id: ClientId,
+        /// Not sent by lobbies from before versioning, which are never compatible.
+        /// Their handshake fails to parse instead, since the wire encoding isn't self-describing
+        protocol_version: u32,
+        /// Kept secret by the client, and sent back in [Client2Lobby::RejoinMatch]
+        /// to prove it's the one which dropped out, since `ClientId`s are easily guessed
+        rejoin_token: u64,
+        /// The lobby's [ShipTemplateRegistry::fingerprint], since ships are sent by id
+        ship_templates: u64,
     },
     ClientJoined {
         info: ClientSharedInfo,
@@ -188,6 +424,10 @@ pub enum Lobby2Client {
         client_id: ClientId,
     },
     MatchJoined {},
+    /// Every running match, sent to clients in the lobby whenever a match starts or ends
+    MatchList {
+        matches: Vec<MatchListing>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -210,7 +450,12 @@ pub struct WrtsMatchMessage {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WrtsMatchInitMessage {
-    pub clients: [ClientId; 2],
+    /// Each client plays as its own team
+    pub clients: Vec<ClientId>,
+    /// Seeds all of the match's randomness, so that a match can be reproduced
+    pub seed: u64,
+    /// Lets shells and torpedoes damage ships on their own team
+    pub friendly_fire: bool,
 }
 
 #[pin_project]
@@ -351,6 +596,92 @@ where
     }
 }
 
+/// The largest payload accepted, both on the wire and once decompressed
+const MESSAGE_SIZE_LIMIT: u32 = 1024 * 1024;
+
+/// Messages smaller than this are sent uncompressed, since most messages
+/// are small, frequent updates which wouldn't shrink enough to be worth the CPU time
+const COMPRESSION_THRESHOLD: usize = 4 * 1024;
+
+const PAYLOAD_UNCOMPRESSED: u8 = 0;
+const PAYLOAD_DEFLATE: u8 = 1;
+
+/// How message bodies are encoded, before any compression.
+/// Every peer must be built with the same encoding, since it isn't sent on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WireEncoding {
+    /// Compact, for the many small updates streamed every tick
+    Bincode,
+    /// Human-readable, for debugging with the `json` feature
+    Json,
+}
+
+impl WireEncoding {
+    const CURRENT: Self = if cfg!(feature = "json") {
+        Self::Json
+    } else {
+        Self::Bincode
+    };
+
+    fn bincode_options() -> impl bincode::Options {
+        use bincode::Options;
+
+        bincode::DefaultOptions::new().with_limit(MESSAGE_SIZE_LIMIT as u64)
+    }
+
+    fn encode(self, msg: &impl Serialize) -> Result<Vec<u8>> {
+        use bincode::Options;
+
+        Ok(match self {
+            Self::Bincode => Self::bincode_options().serialize(msg)?,
+            Self::Json => serde_json::to_vec(msg)?,
+        })
+    }
+
+    fn decode<M: DeserializeOwned>(self, bytes: &[u8]) -> Result<M> {
+        use bincode::Options;
+
+        Ok(match self {
+            Self::Bincode => Self::bincode_options().deserialize(bytes)?,
+            Self::Json => serde_json::from_slice(bytes)?,
+        })
+    }
+}
+
+/// Serializes a message, returning the payload and the compression flag
+/// which is sent alongside it in the frame header
+fn encode_payload(msg: &impl Serialize) -> Result<(u8, Vec<u8>)> {
+    let bytes = WireEncoding::CURRENT.encode(msg)?;
+    if bytes.len() < COMPRESSION_THRESHOLD {
+        return Ok((PAYLOAD_UNCOMPRESSED, bytes));
+    }
+    let mut encoder = DeflateEncoder::new(vec![], Compression::fast());
+    encoder.write_all(&bytes)?;
+    Ok((PAYLOAD_DEFLATE, encoder.finish()?))
+}
+
+fn decode_payload<M: DeserializeOwned>(compression_flag: u8, data: &[u8]) -> Result<M> {
+    match compression_flag {
+        PAYLOAD_UNCOMPRESSED => WireEncoding::CURRENT.decode(data),
+        PAYLOAD_DEFLATE => {
+            let mut bytes = vec![];
+            // Read one byte past the limit, to tell a message that's exactly at it from one that's over
+            DeflateDecoder::new(data)
+                .take(MESSAGE_SIZE_LIMIT as u64 + 1)
+                .read_to_end(&mut bytes)?;
+            if bytes.len() > MESSAGE_SIZE_LIMIT as usize {
+                return Err(anyhow!(
+                    "A compressed message was recieved which decompresses to over {MESSAGE_SIZE_LIMIT}b"
+                ));
+            }
+            WireEncoding::CURRENT.decode(&bytes)
+        }
+        _ => Err(anyhow!(
+            "A message was recieved with an unknown compression flag: {compression_flag}"
+        )),
+    }
+}
+
 mod serialize_maybe_sync_macro {
     #[macro_export]
     macro_rules! maybe_sync_read_write {
@@ -402,15 +733,19 @@ mod serialize_maybe_sync_macro {
                     maybe_sync_read_write!(read $($async)? stream, buf);
                     u32::from_be_bytes(buf)
                 };
-                let limit = 1024 * 1024;
-                if length_prefix > limit {
+                let compression_flag = {
+                    let mut buf: [u8; 1] = [0; 1];
+                    maybe_sync_read_write!(read $($async)? stream, buf);
+                    buf[0]
+                };
+                if length_prefix > MESSAGE_SIZE_LIMIT {
                     return Err(anyhow!(
-                        "A message was recieved of length: {length_prefix}b! The limit is {limit}b"
+                        "A message was recieved of length: {length_prefix}b! The limit is {MESSAGE_SIZE_LIMIT}b"
                     ));
                 }
                 let mut data = vec![0u8; length_prefix as usize];
                 maybe_sync_read_write!(read $($async)? stream, data);
-                Ok(serde_json::from_slice(&data)?)
+                decode_payload(compression_flag, &data)
             }
         };
         {___internal; ser, $($async:ident)?, $func_name:ident, {$($stream_trait_bound:tt)*}} => {
@@ -419,9 +754,11 @@ mod serialize_maybe_sync_macro {
                 $($stream_trait_bound)*
             {
                 let mut stream = TokioWebTransportCompat::<'a, T>::from(stream);
-                let bytes = serde_json::to_vec(msg)?;
+                let (compression_flag, bytes) = encode_payload(msg)?;
                 let length_prefix: [u8; 4] = (bytes.len() as u32).to_be_bytes();
+                let compression_flag: [u8; 1] = [compression_flag];
                 maybe_sync_read_write!(write $($async)? stream, length_prefix);
+                maybe_sync_read_write!(write $($async)? stream, compression_flag);
                 maybe_sync_read_write!(write $($async)? stream, bytes);
                 Ok(())
             }
@@ -433,3 +770,307 @@ serialize_maybe_sync!(sync de read_from_stream_sync);
 serialize_maybe_sync!(async de read_from_stream_async);
 serialize_maybe_sync!(sync ser write_to_stream_sync);
 serialize_maybe_sync!(async ser write_to_stream_async);
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use glam::{Quat, vec2, vec3};
+    use wrts_match_shared::{
+        formulas::ballistics::ShellArc,
+        ship_template::{BulletType, Caliber, HullSection, ShipTemplateId},
+    };
+
+    use crate::{
+        COMPRESSION_THRESHOLD, Client2Lobby, Client2Match, ClientId, ClientSharedInfo,
+        DamageSource, EntityUpdate, Lobby2Client, Match2Client, MatchListing, Message,
+        PAYLOAD_DEFLATE, PAYLOAD_UNCOMPRESSED, SharedEntityId, SharedMatchId, SmokeConsumableState,
+        SpotterPlaneState, TorpReloadBoosterState, WireEncoding, read_from_stream_sync,
+        write_to_stream_sync,
+    };
+
+    fn move_order(waypoint_count: usize) -> Message {
+        Message::Client2Match(Client2Match::SetMoveOrder {
+            id: SharedEntityId(7),
+            waypoints: (0..waypoint_count)
+                .map(|idx| vec2(idx as f32 * 100., 2_500.))
+                .collect(),
+        })
+    }
+
+    fn round_trip(msg: &Message) -> (Vec<u8>, Message) {
+        let mut frame = vec![];
+        write_to_stream_sync(msg, &mut frame).unwrap();
+        let received = read_from_stream_sync(&mut frame.as_slice()).unwrap();
+        (frame, received)
+    }
+
+    /// One of every message variant. Add new variants here too
+    fn every_message() -> Vec<Message> {
+        let id = SharedEntityId(0x1234_5678_9abc);
+        let client = ClientId(3);
+        let info = ClientSharedInfo {
+            id: client,
+            user: "player".into(),
+        };
+        let charges_unused = Some(2);
+        let remaining = Duration::from_millis(1_500);
+        let client2match = [
+            Client2Match::InitB { info: info.clone() },
+            Client2Match::Echo("echo".into()),
+            Client2Match::SetMoveOrder {
+                id,
+                waypoints: vec![vec2(1., 2.), vec2(-3., 4.5)],
+            },
+            Client2Match::SetFireTarg { id, targ: Some(id) },
+            Client2Match::LaunchTorpedoVolley {
+                ship: id,
+                dir: vec2(0., -1.),
+            },
+            Client2Match::UseConsumableSmoke { ship: id },
+            Client2Match::UseConsumableTorpReloadBooster { ship: id },
+            Client2Match::UseConsumableSpotterPlane { ship: id },
+            Client2Match::UseConsumableRepairParty { ship: id },
+            Client2Match::SetShellArc {
+                id,
+                arc: ShellArc::High,
+            },
+            Client2Match::SetHeading { id, heading: 1.25 },
+            Client2Match::CancelConsumableSmoke { ship: id },
+            Client2Match::Surrender,
+            Client2Match::SetPaused { paused: true },
+            Client2Match::Rejoined,
+            Client2Match::SpectatorJoined { info: info.clone() },
+        ]
+        .map(Message::Client2Match);
+        let match2client = [
+            Match2Client::InitA {
+                your_client: client,
+            },
+            Match2Client::InitC {
+                all_clients: vec![info.clone()],
+                seed: u64::MAX,
+            },
+            Match2Client::PrintMsg("hello".into()),
+            Match2Client::DestroyEntities(vec![id, SharedEntityId(0)]),
+            Match2Client::SpawnShip {
+                id,
+                team: client,
+                ship_base: ShipTemplateId::bismarck(),
+                health: 60_000.,
+                pos: vec2(-6_000., 250.),
+                rot: Quat::from_rotation_z(0.5),
+                turret_rots: vec![0., 3.1],
+            },
+            Match2Client::SpawnBullet {
+                id,
+                team: client,
+                owning_ship: id,
+                damage: 1_500.,
+                caliber: Caliber::from_mm(380.),
+                ty: BulletType::HE,
+                pos: vec3(1., 2., 3.),
+                rot: Quat::IDENTITY,
+            },
+            Match2Client::SpawnTorpedo {
+                id,
+                team: client,
+                owning_ship: id,
+                damage: 10_000.,
+                pos: vec2(5., 6.),
+                vel: vec2(20., 0.),
+            },
+            Match2Client::SpawnSmokePuff {
+                id,
+                pos: vec2(7., 8.),
+                radius: 450.,
+            },
+            Match2Client::SpawnAircraft {
+                id,
+                team: client,
+                owning_ship: id,
+                pos: vec2(9., 10.),
+            },
+            Match2Client::SetSmokeConsumableState {
+                id,
+                state: SmokeConsumableState::Deploying {
+                    charges_unused,
+                    action_time_remaining: remaining,
+                },
+            },
+            Match2Client::SetTorpReloadBoosterState {
+                id,
+                state: TorpReloadBoosterState::Recharging {
+                    charges_unused: None,
+                    recharge_time_remaining: remaining,
+                },
+            },
+            Match2Client::SetSpotterPlaneState {
+                id,
+                state: SpotterPlaneState::Recharged { charges_unused },
+            },
+            Match2Client::SetReloadedTorps {
+                id,
+                ready_to_fire: 1,
+                still_reloading: vec![remaining],
+                full_reload: Duration::from_secs(60),
+            },
+            Match2Client::WorldSnapshot {
+                entities: vec![
+                    EntityUpdate {
+                        id,
+                        trans: Some((vec3(-1., -2., 0.), Quat::from_rotation_z(-2.))),
+                        vel: Some(vec2(3., -4.)),
+                        turret_dirs: Some(vec![0.25; 4]),
+                    },
+                    EntityUpdate {
+                        id: SharedEntityId(7),
+                        trans: None,
+                        vel: None,
+                        turret_dirs: Some(vec![]),
+                    },
+                ],
+            },
+            Match2Client::SetHealth {
+                id,
+                health: 12_345.5,
+                sections: [0.5; HullSection::COUNT],
+            },
+            Match2Client::SetMoveOrder {
+                id,
+                waypoints: vec![vec2(9., 10.)],
+            },
+            Match2Client::WaypointReached { id, remaining: 2 },
+            Match2Client::SetDetection {
+                id,
+                currently_detected: true,
+            },
+            Match2Client::DamageDealt {
+                target: id,
+                attacker_team: client,
+                attacker: None,
+                source: DamageSource::Fire,
+                damage: 321.,
+            },
+            Match2Client::ShipDestroyed {
+                ship: id,
+                killer_team: client,
+                killer: Some(id),
+                source: DamageSource::Torpedo,
+            },
+            Match2Client::DamageDealt {
+                target: id,
+                attacker_team: client,
+                attacker: Some(id),
+                source: DamageSource::Ramming,
+                damage: 12.5,
+            },
+            Match2Client::SetGunsIdle { id, idle: false },
+            Match2Client::MatchPaused { paused: false },
+            Match2Client::MatchTime { remaining },
+            Match2Client::Scoreboard {
+                entries: vec![(client, 1_234.5, 2)],
+            },
+            Match2Client::MatchEnded { winner: None },
+        ]
+        .map(Message::Match2Client);
+        let client2lobby = [
+            Client2Lobby::InitB {
+                username: "player".into(),
+                protocol_version: 13,
+                ship_templates: u64::MAX,
+            },
+            Client2Lobby::SetReadyForMatch { is_ready: true },
+            Client2Lobby::RejoinMatch {
+                client_id: client,
+                rejoin_token: u64::MAX,
+                protocol_version: 13,
+                ship_templates: u64::MAX,
+            },
+            Client2Lobby::SpectateMatch {
+                match_id: SharedMatchId(u64::MAX),
+            },
+        ]
+        .map(Message::Client2Lobby);
+        let lobby2client = [
+            Lobby2Client::InitA {
+                client_id: client,
+                protocol_version: 13,
+                rejoin_token: u64::MAX,
+                ship_templates: u64::MAX,
+            },
+            Lobby2Client::ClientJoined { info },
+            Lobby2Client::ClientLeft { client_id: client },
+            Lobby2Client::MatchJoined {},
+            Lobby2Client::MatchList {
+                matches: vec![MatchListing {
+                    id: SharedMatchId((1 << 32) | 7),
+                    clients: vec![client, ClientId(4)],
+                }],
+            },
+        ]
+        .map(Message::Lobby2Client);
+
+        client2match
+            .into_iter()
+            .chain(match2client)
+            .chain(client2lobby)
+            .chain(lobby2client)
+            .collect()
+    }
+
+    #[test]
+    fn test_every_message_round_trips_in_both_encodings() {
+        for encoding in [WireEncoding::Bincode, WireEncoding::Json] {
+            for msg in every_message() {
+                let bytes = encoding.encode(&msg).unwrap();
+                let decoded: Message = encoding.decode(&bytes).unwrap();
+                assert_eq!(
+                    format!("{decoded:?}"),
+                    format!("{msg:?}"),
+                    "{encoding:?} changed the message"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_bincode_is_smaller_than_json() {
+        let msg = Message::Match2Client(Match2Client::WorldSnapshot {
+            entities: vec![EntityUpdate {
+                id: SharedEntityId(42),
+                trans: Some((vec3(1_234.5, -678.25, 0.), Quat::from_rotation_z(1.))),
+                vel: Some(vec2(12.5, -3.)),
+                turret_dirs: None,
+            }],
+        });
+        let bincode_len = WireEncoding::Bincode.encode(&msg).unwrap().len();
+        let json_len = WireEncoding::Json.encode(&msg).unwrap().len();
+        assert!(bincode_len * 2 < json_len, "{bincode_len} {json_len}");
+    }
+
+    #[test]
+    fn test_large_message_is_compressed() {
+        let msg = move_order(10_000);
+        let encoded_len = WireEncoding::CURRENT.encode(&msg).unwrap().len();
+        assert!(encoded_len > COMPRESSION_THRESHOLD);
+
+        let (frame, received) = round_trip(&msg);
+        assert_eq!(frame[4], PAYLOAD_DEFLATE);
+        assert!(
+            frame.len() < encoded_len / 2,
+            "{} {encoded_len}",
+            frame.len()
+        );
+        assert_eq!(format!("{received:?}"), format!("{msg:?}"));
+    }
+
+    #[test]
+    fn test_small_message_is_not_compressed() {
+        let msg = move_order(2);
+        let (frame, received) = round_trip(&msg);
+        assert_eq!(frame[4], PAYLOAD_UNCOMPRESSED);
+        assert_eq!(&frame[5..], WireEncoding::CURRENT.encode(&msg).unwrap());
+        assert_eq!(format!("{received:?}"), format!("{msg:?}"));
+    }
+}